@@ -0,0 +1,185 @@
+use aes::cipher::generic_array::GenericArray;
+use aes::{Aes128, BlockCipher, NewBlockCipher};
+use milenage::Milenage;
+
+use crate::tuak::Tuak;
+use crate::types::{
+    Ak, Algorithm, AuthVectorConversionError, Ck, Ik, MacS, Opc, Topc, XRes, AMF_LENGTH, K,
+    OPC_LENGTH, RAND_LENGTH, SQN_LENGTH, TOPC_LENGTH,
+};
+
+/// Authentication functions f1-f5 and f1*/f5*, per TS 33.102 6.3.
+pub trait AkaAlgorithm {
+    /// Computes the network authentication code MAC-A.
+    fn f1(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> [u8; 8];
+
+    /// Computes the resync authentication code MAC-S.
+    fn f1star(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> MacS;
+
+    /// Computes (RES, CK, IK, AK).
+    fn f2345(&mut self, rand: &[u8; RAND_LENGTH]) -> (XRes, Ck, Ik, Ak);
+
+    /// Computes the resync anonymity key AK*.
+    fn f5star(&mut self, rand: &[u8; RAND_LENGTH]) -> Ak;
+}
+
+impl AkaAlgorithm for Milenage {
+    fn f1(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> [u8; 8] {
+        Milenage::f1(self, rand, sqn, amf)
+    }
+
+    fn f1star(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> MacS {
+        Milenage::f1star(self, rand, sqn, amf)
+    }
+
+    fn f2345(&mut self, rand: &[u8; RAND_LENGTH]) -> (XRes, Ck, Ik, Ak) {
+        Milenage::f2345(self, rand)
+    }
+
+    fn f5star(&mut self, rand: &[u8; RAND_LENGTH]) -> Ak {
+        Milenage::f5star(self, rand)
+    }
+}
+
+impl AkaAlgorithm for Tuak {
+    fn f1(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> [u8; 8] {
+        Tuak::f1(self, rand, sqn, amf)
+    }
+
+    fn f1star(
+        &mut self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> MacS {
+        Tuak::f1star(self, rand, sqn, amf)
+    }
+
+    fn f2345(&mut self, rand: &[u8; RAND_LENGTH]) -> (XRes, Ck, Ik, Ak) {
+        Tuak::f2345(self, rand)
+    }
+
+    fn f5star(&mut self, rand: &[u8; RAND_LENGTH]) -> Ak {
+        Tuak::f5star(self, rand)
+    }
+}
+
+/// Builds the algorithm set for a subscriber.
+/// The opc is the OPc for Milenage or the TOPc for TUAK.
+pub fn build_algorithm(
+    algorithm: Algorithm,
+    k: &K,
+    opc: &[u8],
+) -> Result<Box<dyn AkaAlgorithm>, AuthVectorConversionError> {
+    match algorithm {
+        Algorithm::Milenage => {
+            let opc: [u8; OPC_LENGTH] = opc.try_into()?;
+            Ok(Box::new(Milenage::new_with_opc(k.clone(), opc)))
+        }
+        Algorithm::Tuak => {
+            let topc: [u8; TOPC_LENGTH] = opc.try_into()?;
+            Ok(Box::new(Tuak::new_with_topc(k.clone(), topc)))
+        }
+    }
+}
+
+/// Derives the operator constant of a subscriber from the operator value.
+/// Computes OPc = E_K(OP) xor OP for Milenage, per TS 35.206 4.1,
+/// or TOPc from TOP for TUAK.
+pub fn compute_opc(
+    algorithm: Algorithm,
+    k: &K,
+    op: &[u8],
+) -> Result<Vec<u8>, AuthVectorConversionError> {
+    match algorithm {
+        Algorithm::Milenage => {
+            let op: Opc = op.try_into()?;
+
+            let cipher = Aes128::new(GenericArray::from_slice(k));
+            let mut block = GenericArray::clone_from_slice(&op);
+            cipher.encrypt_block(&mut block);
+
+            Ok(block.iter().zip(op).map(|(e, o)| e ^ o).collect())
+        }
+        Algorithm::Tuak => {
+            let top: Topc = op.try_into()?;
+
+            Ok(Tuak::compute_topc(k, &top).to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_opc_milenage() {
+        // TS 35.208 test sets 1 and 2
+        for (k, op, opc) in [
+            (
+                "465b5ce8b199b49faa5f0a2ee238a6bc",
+                "cdc202d5123e20f62b6d676ac72cb318",
+                "cd63cb71954a9f4e48a5994e37a02baf",
+            ),
+            (
+                "0396eb317b6d1c36f19c1c84cd6ffd16",
+                "ff53bade17df5d4e793073ce9d7579fa",
+                "53c15671c60a4b731c55b4a441c0bde2",
+            ),
+        ] {
+            let k: K = hex::decode(k).unwrap().try_into().unwrap();
+            let op = hex::decode(op).unwrap();
+
+            assert_eq!(
+                opc,
+                hex::encode(compute_opc(Algorithm::Milenage, &k, &op).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_compute_opc_tuak() {
+        // TS 35.232 test set 1
+        let k: K = hex::decode("abababababababababababababababab")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let top = hex::decode("5555555555555555555555555555555555555555555555555555555555555555")
+            .unwrap();
+
+        assert_eq!(
+            "bd04d9530e87513c5d837ac2ad954623a8e2330c115305a73eb45d1f40cccbff",
+            hex::encode(compute_opc(Algorithm::Tuak, &k, &top).unwrap())
+        );
+
+        // The operator value length must match the algorithm
+        assert!(compute_opc(Algorithm::Milenage, &k, &top).is_err());
+        assert!(compute_opc(Algorithm::Tuak, &k, &top[..OPC_LENGTH]).is_err());
+    }
+}
@@ -12,3 +12,13 @@ pub struct AuthVectorData {
     pub xres_hash: types::XResHash,
     pub xres: types::XRes,
 }
+
+/// Contains all EAP-AKA' vector data
+#[derive(Debug, Clone)]
+pub struct EapAkaPrimeVectorData {
+    pub autn: types::Autn,
+    pub rand: types::Rand,
+    pub xres_hash: types::XResHash,
+    pub xres: types::XRes,
+    pub keys: types::EapAkaPrimeKeys,
+}
@@ -1,52 +1,55 @@
+pub mod algorithm;
 pub mod data;
+pub mod tuak;
 pub mod types;
 
-use milenage::Milenage;
 use rand as r;
+use subtle::ConstantTimeEq;
 
-use crate::data::AuthVectorData;
+use crate::algorithm::build_algorithm;
+use crate::data::{AuthVectorData, EapAkaPrimeVectorData};
 use crate::types::AuthVectorConversionError;
 
-/// Uses provided k, opc, and rand with milenage.
-/// Returns tuple of auth vector data (xres, rand, sqn_xor_ak, mac_a)
+/// Uses provided k, opc, and a random rand with the user's algorithm.
+/// The opc is the OPc for Milenage or the TOPc for TUAK.
 pub fn generate_vector(
     mcc: &str,
     mnc: &str,
+    algorithm: types::Algorithm,
     k: &types::K,
-    opc: &types::Opc,
+    opc: &[u8],
     sqn: &types::Sqn,
-) -> AuthVectorData {
+) -> Result<AuthVectorData, AuthVectorConversionError> {
     let rand = types::Rand::new(&mut r::thread_rng());
 
-    generate_vector_with_rand(mcc, mnc, k, opc, &rand, sqn)
+    generate_vector_with_rand(mcc, mnc, algorithm, k, opc, &rand, sqn)
 }
 
 /// Generate auth vector data with a provided rand
 pub fn generate_vector_with_rand(
     mcc: &str,
     mnc: &str,
+    algorithm: types::Algorithm,
     k: &types::K,
-    opc: &types::Opc,
+    opc: &[u8],
     rand: &types::Rand,
     sqn: &types::Sqn,
-) -> AuthVectorData {
-    let mut m = Milenage::new_with_opc(k.clone(), opc.clone());
+) -> Result<AuthVectorData, AuthVectorConversionError> {
+    let mut m = build_algorithm(algorithm, k, opc)?;
 
     let (xres, ck, ik, ak) = m.f2345(&rand.as_array());
 
-    let xres_star = m
-        .compute_res_star(mcc, mnc, &rand.as_array(), &xres)
-        .unwrap();
+    let xres_star = types::gen_xres_star(mcc, mnc, &ck, &ik, rand, &xres);
 
     let xres_star_hash = types::gen_xres_star_hash(rand, &xres_star);
     let xres_hash = types::gen_xres_hash(rand, &xres);
 
-    let autn = types::build_autn(sqn, &ak, rand, &mut m);
+    let autn = types::build_autn(sqn, &ak, rand, m.as_mut());
 
     let kseaf = types::gen_kseaf(mcc, mnc, &types::gen_kausf(mcc, mnc, &ck, &ik, &autn));
     let kasme = types::Kasme::derive(mcc, mnc, &ck, &ik, &autn);
 
-    AuthVectorData {
+    Ok(AuthVectorData {
         xres_star_hash,
         xres_star,
         autn,
@@ -55,9 +58,163 @@ pub fn generate_vector_with_rand(
         kasme,
         xres_hash,
         xres,
+    })
+}
+
+/// Builds a vector again from its rand and AUTN, with the same keys.
+/// The SQN is recovered from the AUTN with the anonymity key from f5.
+pub fn regenerate_vector(
+    mcc: &str,
+    mnc: &str,
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    autn: &types::Autn,
+) -> Result<AuthVectorData, AuthVectorConversionError> {
+    let mut m = build_algorithm(algorithm, k, opc)?;
+
+    let (_, _, _, ak) = m.f2345(&rand.as_array());
+    let sqn: types::Sqn = autn[..types::SQN_LENGTH]
+        .iter()
+        .zip(ak.iter())
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<u8>>()[..]
+        .try_into()?;
+
+    generate_vector_with_rand(mcc, mnc, algorithm, k, opc, rand, &sqn)
+}
+
+/// Uses provided k, opc, and a random rand to build an EAP-AKA' vector.
+/// The network name is the access network identity bound into CK' and IK',
+/// and the identity is the one the UE used in EAP-Response/Identity.
+pub fn generate_eap_aka_prime_vector(
+    network_name: &str,
+    identity: &str,
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    sqn: &types::Sqn,
+) -> Result<EapAkaPrimeVectorData, AuthVectorConversionError> {
+    let rand = types::Rand::new(&mut r::thread_rng());
+
+    generate_eap_aka_prime_vector_with_rand(network_name, identity, algorithm, k, opc, &rand, sqn)
+}
+
+/// Generate EAP-AKA' vector data with a provided rand, per RFC 9048
+pub fn generate_eap_aka_prime_vector_with_rand(
+    network_name: &str,
+    identity: &str,
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    sqn: &types::Sqn,
+) -> Result<EapAkaPrimeVectorData, AuthVectorConversionError> {
+    let mut m = build_algorithm(algorithm, k, opc)?;
+
+    let (xres, ck, ik, ak) = m.f2345(&rand.as_array());
+
+    let xres_hash = types::gen_xres_hash(rand, &xres);
+
+    let autn = types::build_autn(sqn, &ak, rand, m.as_mut());
+
+    let (ck_prime, ik_prime) = types::gen_ck_ik_prime(network_name, &ck, &ik, &autn);
+    let keys = types::EapAkaPrimeKeys::derive(identity, &ck_prime, &ik_prime);
+
+    Ok(EapAkaPrimeVectorData {
+        autn,
+        rand: rand.to_owned(),
+        xres_hash,
+        xres,
+        keys,
+    })
+}
+
+/// Computes the resync anonymity key AK* with f5*.
+pub fn compute_ak_star(
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+) -> Result<types::Ak, AuthVectorConversionError> {
+    let mut m = build_algorithm(algorithm, k, opc)?;
+
+    Ok(m.f5star(&rand.as_array()))
+}
+
+/// Computes MAC-S with f1*.
+/// The UE always uses types::RESYNC_AMF when building an AUTS.
+pub fn compute_mac_s(
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    sqn: &types::Sqn,
+    amf: &[u8; types::AMF_LENGTH],
+) -> Result<types::MacS, AuthVectorConversionError> {
+    let mut m = build_algorithm(algorithm, k, opc)?;
+
+    Ok(m.f1star(&rand.as_array(), sqn.as_bytes(), amf))
+}
+
+/// Checks a received MAC-S against the expected f1* output.
+/// Compared in constant time, so timing does not reveal the expected MAC.
+pub fn verify_mac_s(
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    sqn: &types::Sqn,
+    amf: &[u8; types::AMF_LENGTH],
+    mac_s: &types::MacS,
+) -> Result<(), types::ResyncError> {
+    if bool::from(compute_mac_s(algorithm, k, opc, rand, sqn, amf)?[..].ct_eq(&mac_s[..])) {
+        Ok(())
+    } else {
+        Err(types::ResyncError::MacFailure())
     }
 }
 
+/// Recovers SQN_MS from an AUTS and verifies its MAC-S.
+/// The rand is the one of the challenge the UE rejected.
+pub fn recover_sqn_ms(
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    auts: &types::Auts,
+) -> Result<types::Sqn, types::ResyncError> {
+    let sqn_ms = auts.recover_sqn(&compute_ak_star(algorithm, k, opc, rand)?);
+
+    verify_mac_s(
+        algorithm,
+        k,
+        opc,
+        rand,
+        &sqn_ms,
+        &types::RESYNC_AMF,
+        auts.mac_s(),
+    )?;
+
+    Ok(sqn_ms)
+}
+
+/// Builds the AUTS a UE would send for SQN_MS, per TS 33.102 6.3.3.
+pub fn build_auts(
+    algorithm: types::Algorithm,
+    k: &types::K,
+    opc: &[u8],
+    rand: &types::Rand,
+    sqn_ms: &types::Sqn,
+) -> Result<types::Auts, AuthVectorConversionError> {
+    Ok(types::Auts::build(
+        sqn_ms,
+        &compute_ak_star(algorithm, k, opc, rand)?,
+        &compute_mac_s(algorithm, k, opc, rand, sqn_ms, &types::RESYNC_AMF)?,
+    ))
+}
+
 // Encode the PLMN for keying per TS 33.401: A.2-1
 fn get_encoded_plmn(mcc: &str, mnc: &str) -> Result<Vec<u8>, AuthVectorConversionError> {
     if mcc.len() != 3 {
@@ -101,9 +258,12 @@ mod tests {
     use hex;
     use milenage::Milenage;
 
-    use crate::generate_vector_with_rand;
     use crate::get_encoded_plmn;
     use crate::types;
+    use crate::types::Algorithm;
+    use crate::{build_auts, compute_ak_star, compute_mac_s, recover_sqn_ms, verify_mac_s};
+    use crate::{generate_eap_aka_prime_vector_with_rand, types::EapAkaPrimeKeys};
+    use crate::{generate_vector_with_rand, regenerate_vector};
 
     #[test]
     fn test_generation_eps_5g_combined() {
@@ -122,7 +282,9 @@ mod tests {
             .unwrap();
         let sqn: types::Sqn = hex::decode("000000003803").unwrap().try_into().unwrap();
 
-        let result = generate_vector_with_rand("910", "54", &k, &opc, &rand, &sqn);
+        let result =
+            generate_vector_with_rand("910", "54", Algorithm::Milenage, &k, &opc, &rand, &sqn)
+                .unwrap();
 
         // Shared EPS and 5G values:
         assert_eq!(
@@ -149,6 +311,107 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generation_eap_aka_prime() {
+        let k: types::K = hex::decode("3aef49b1c6ee9700d42afca230cb0589")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let opc: types::Opc = hex::decode("e006d9ca10142b42b2830f3c603c3d63")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let rand: types::Rand = hex::decode("645f677edb96b100e3db14eae181c8e4")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let sqn: types::Sqn = hex::decode("000000003803").unwrap().try_into().unwrap();
+
+        let aka =
+            generate_vector_with_rand("910", "54", Algorithm::Milenage, &k, &opc, &rand, &sqn)
+                .unwrap();
+        let eap = generate_eap_aka_prime_vector_with_rand(
+            "WLAN",
+            "0910540000000001",
+            Algorithm::Milenage,
+            &k,
+            &opc,
+            &rand,
+            &sqn,
+        )
+        .unwrap();
+
+        // The challenge is shared with the other AKA variants
+        assert_eq!(aka.autn, eap.autn);
+        assert_eq!(aka.xres, eap.xres);
+        assert_eq!(aka.xres_hash, eap.xres_hash);
+
+        // The keys are bound to the access network name
+        let (_, ck, ik, _) = Milenage::new_with_opc(k, opc).f2345(&rand.as_array());
+        let (ck_prime, ik_prime) = types::gen_ck_ik_prime("WLAN", &ck, &ik, &eap.autn);
+        assert_eq!(
+            EapAkaPrimeKeys::derive("0910540000000001", &ck_prime, &ik_prime),
+            eap.keys
+        );
+        assert_ne!(aka.kseaf, eap.keys.anchor_key("910", "54"));
+    }
+
+    #[test]
+    fn test_generation_eap_aka_prime_known_answer() {
+        // TS 35.208 test set 1 inputs, with the keys derived from its CK,
+        // IK and SQN^AK as in RFC 5448 Appendix C test case 1
+        let k: types::K = hex::decode("465b5ce8b199b49faa5f0a2ee238a6bc")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let opc: types::Opc = hex::decode("cd63cb71954a9f4e48a5994e37a02baf")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let rand: types::Rand = hex::decode("23553cbe9637a89d218ae64dae47bf35")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let sqn: types::Sqn = hex::decode("ff9bb4d0b607").unwrap().try_into().unwrap();
+
+        let eap = generate_eap_aka_prime_vector_with_rand(
+            "WLAN",
+            "0555444333222111",
+            Algorithm::Milenage,
+            &k,
+            &opc,
+            &rand,
+            &sqn,
+        )
+        .unwrap();
+
+        assert_eq!("a54211d5e3ba50bf", hex::encode(eap.xres));
+        assert_eq!("55f328b43577", hex::encode(&eap.autn[..6]));
+
+        assert_eq!(
+            "afea5ce62aa4d37e54421f9cea4deda4",
+            hex::encode(eap.keys.k_encr)
+        );
+        assert_eq!(
+            "0324f2159820fd7a9392a7e11f3f01c758f066b53e928d4735a22d4587289cef",
+            hex::encode(eap.keys.k_aut)
+        );
+        assert_eq!(
+            "befc82b8dc3a292867ab17f277693e84c4775cd67c85f3346e0c91671d6f9d4f",
+            hex::encode(eap.keys.k_re)
+        );
+        assert_eq!(
+            "69f44c797856011a3b5d184a438925cc8b2c5519aebe16d4777a73101652b36a\
+            d99ec52513e55348f9985cc42a0be69a990a6c0b960f78fdcfb3d4b3036d0df0",
+            hex::encode(eap.keys.msk)
+        );
+        assert_eq!(
+            "c883f020fd3b8dda02dc00363ca1527efa87231c4492a748e9fa5ac55103ab2e\
+            53bb3cb40522c6bf3e79fe7d82ae7f48917fcafc95aeaf6802a102eb89af5ee9",
+            hex::encode(eap.keys.emsk)
+        );
+    }
+
     #[test]
     fn test_plmn_encode() {
         // Used from successful ueransim 5G attach
@@ -182,7 +445,9 @@ mod tests {
             .unwrap();
         let sqn: types::Sqn = hex::decode("000000000021").unwrap().try_into().unwrap();
 
-        let result = generate_vector_with_rand("901", "70", &k, &opc, &rand, &sqn);
+        let result =
+            generate_vector_with_rand("901", "70", Algorithm::Milenage, &k, &opc, &rand, &sqn)
+                .unwrap();
 
         assert_eq!(
             "562d716dbd058b475cfecdbb48ed038f",
@@ -266,6 +531,258 @@ mod tests {
         assert_eq!("4a9ffac354dfafb3", encode_hex(&mac_a));
     }
 
+    /// TS 35.208 test sets 1 to 3 as (k, opc, rand, sqn, amf, f1*, f5*)
+    const RESYNC_TEST_SETS: [(&str, &str, &str, &str, &str, &str, &str); 3] = [
+        (
+            "465b5ce8b199b49faa5f0a2ee238a6bc",
+            "cd63cb71954a9f4e48a5994e37a02baf",
+            "23553cbe9637a89d218ae64dae47bf35",
+            "ff9bb4d0b607",
+            "b9b9",
+            "01cfaf9ec4e871e9",
+            "451e8beca43b",
+        ),
+        (
+            "0396eb317b6d1c36f19c1c84cd6ffd16",
+            "53c15671c60a4b731c55b4a441c0bde2",
+            "c00d603103dcee52c4478119494202e8",
+            "fd8eef40df7d",
+            "af17",
+            "a8c016e51ef4a343",
+            "30f1197061c1",
+        ),
+        (
+            "fec86ba6eb707ed08905757b1bb44b8f",
+            "1006020f0a478bf6b699f15c062e42b3",
+            "9f7c8d021accf4db213ccff0c7f71a6a",
+            "9d0277595ffc",
+            "725c",
+            "95814ba2b3044324",
+            "deacdd848cc6",
+        ),
+    ];
+
+    #[test]
+    fn test_milenage_f1star_f5star() {
+        for (k, opc, rand, sqn, amf, f1_star, f5_star) in RESYNC_TEST_SETS {
+            let k: types::K = hex::decode(k).unwrap().try_into().unwrap();
+            let opc: types::Opc = hex::decode(opc).unwrap().try_into().unwrap();
+            let rand: types::Rand = hex::decode(rand).unwrap().try_into().unwrap();
+            let sqn: types::Sqn = hex::decode(sqn).unwrap().try_into().unwrap();
+            let amf: [u8; types::AMF_LENGTH] = hex::decode(amf).unwrap().try_into().unwrap();
+
+            assert_eq!(
+                f5_star,
+                hex::encode(compute_ak_star(Algorithm::Milenage, &k, &opc, &rand).unwrap())
+            );
+            assert_eq!(
+                f1_star,
+                hex::encode(
+                    compute_mac_s(Algorithm::Milenage, &k, &opc, &rand, &sqn, &amf).unwrap()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_mac_s() {
+        for (k, opc, rand, sqn, amf, f1_star, _) in RESYNC_TEST_SETS {
+            let k: types::K = hex::decode(k).unwrap().try_into().unwrap();
+            let opc: types::Opc = hex::decode(opc).unwrap().try_into().unwrap();
+            let rand: types::Rand = hex::decode(rand).unwrap().try_into().unwrap();
+            let sqn: types::Sqn = hex::decode(sqn).unwrap().try_into().unwrap();
+            let amf: [u8; types::AMF_LENGTH] = hex::decode(amf).unwrap().try_into().unwrap();
+            let mut mac_s: types::MacS = hex::decode(f1_star).unwrap().try_into().unwrap();
+
+            assert!(verify_mac_s(Algorithm::Milenage, &k, &opc, &rand, &sqn, &amf, &mac_s).is_ok());
+            assert!(matches!(
+                verify_mac_s(
+                    Algorithm::Milenage,
+                    &k,
+                    &opc,
+                    &rand,
+                    &sqn,
+                    &types::RESYNC_AMF,
+                    &mac_s
+                ),
+                Err(types::ResyncError::MacFailure())
+            ));
+
+            mac_s[0] ^= 0x01;
+            assert!(matches!(
+                verify_mac_s(Algorithm::Milenage, &k, &opc, &rand, &sqn, &amf, &mac_s),
+                Err(types::ResyncError::MacFailure())
+            ));
+        }
+    }
+
+    #[test]
+    fn test_recover_sqn_ms() {
+        // AUTS built from test sets 1 and 2 with the resync AMF
+        for (k, opc, rand, sqn, auts) in [
+            (
+                "465b5ce8b199b49faa5f0a2ee238a6bc",
+                "cd63cb71954a9f4e48a5994e37a02baf",
+                "23553cbe9637a89d218ae64dae47bf35",
+                "ff9bb4d0b607",
+                "ba853f3c123ccf44e93596e355c6",
+            ),
+            (
+                "0396eb317b6d1c36f19c1c84cd6ffd16",
+                "53c15671c60a4b731c55b4a441c0bde2",
+                "c00d603103dcee52c4478119494202e8",
+                "fd8eef40df7d",
+                "cd7ff630bebc1fb5eba74924b0e0",
+            ),
+        ] {
+            let k: types::K = hex::decode(k).unwrap().try_into().unwrap();
+            let opc: types::Opc = hex::decode(opc).unwrap().try_into().unwrap();
+            let rand: types::Rand = hex::decode(rand).unwrap().try_into().unwrap();
+            let sqn: types::Sqn = hex::decode(sqn).unwrap().try_into().unwrap();
+            let auts: types::Auts = hex::decode(auts).unwrap()[..].try_into().unwrap();
+
+            let sqn_ms = recover_sqn_ms(Algorithm::Milenage, &k, &opc, &rand, &auts).unwrap();
+            assert_eq!(sqn.as_bytes(), sqn_ms.as_bytes());
+
+            assert_eq!(
+                auts,
+                build_auts(Algorithm::Milenage, &k, &opc, &rand, &sqn).unwrap()
+            );
+
+            // AUTS from a different subscriber fails
+            let wrong_k = [0u8; 16];
+            assert!(matches!(
+                recover_sqn_ms(Algorithm::Milenage, &wrong_k, &opc, &rand, &auts),
+                Err(types::ResyncError::MacFailure())
+            ));
+        }
+    }
+
+    #[test]
+    fn test_tuak_generation_and_resync() {
+        // TS 35.232 test set 1
+        let k: types::K = hex::decode("abababababababababababababababab")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let topc: types::Topc =
+            hex::decode("bd04d9530e87513c5d837ac2ad954623a8e2330c115305a73eb45d1f40cccbff")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let rand: types::Rand = hex::decode("42424242424242424242424242424242")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let sqn: types::Sqn = hex::decode("111111111111").unwrap().try_into().unwrap();
+
+        let result =
+            generate_vector_with_rand("901", "70", Algorithm::Tuak, &k, &topc, &rand, &sqn)
+                .unwrap();
+        let milenage_result = generate_vector_with_rand(
+            "901",
+            "70",
+            Algorithm::Milenage,
+            &k,
+            &topc[..16],
+            &rand,
+            &sqn,
+        )
+        .unwrap();
+        assert_ne!(result.autn, milenage_result.autn);
+        assert_ne!(result.xres_star_hash, milenage_result.xres_star_hash);
+
+        let auts = build_auts(Algorithm::Tuak, &k, &topc, &rand, &sqn).unwrap();
+        let sqn_ms = recover_sqn_ms(Algorithm::Tuak, &k, &topc, &rand, &auts).unwrap();
+        assert_eq!(sqn.as_bytes(), sqn_ms.as_bytes());
+
+        assert!(matches!(
+            recover_sqn_ms(Algorithm::Milenage, &k, &topc[..16], &rand, &auts),
+            Err(types::ResyncError::MacFailure())
+        ));
+
+        // The operator constant must match the algorithm
+        assert!(generate_vector_with_rand(
+            "901",
+            "70",
+            Algorithm::Milenage,
+            &k,
+            &topc,
+            &rand,
+            &sqn
+        )
+        .is_err());
+        assert!(generate_vector_with_rand(
+            "901",
+            "70",
+            Algorithm::Tuak,
+            &k,
+            &topc[..16],
+            &rand,
+            &sqn
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_regenerate_vector() {
+        let k: types::K = hex::decode("3aef49b1c6ee9700d42afca230cb0589")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let opc: types::Opc = hex::decode("e006d9ca10142b42b2830f3c603c3d63")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let rand: types::Rand = hex::decode("645f677edb96b100e3db14eae181c8e4")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let sqn: types::Sqn = hex::decode("000000003803").unwrap().try_into().unwrap();
+
+        let vector =
+            generate_vector_with_rand("901", "70", Algorithm::Milenage, &k, &opc, &rand, &sqn)
+                .unwrap();
+        let regenerated = regenerate_vector(
+            "901",
+            "70",
+            Algorithm::Milenage,
+            &k,
+            &opc,
+            &rand,
+            &vector.autn,
+        )
+        .unwrap();
+
+        assert_eq!(vector.autn, regenerated.autn);
+        assert_eq!(vector.xres_star_hash, regenerated.xres_star_hash);
+        assert_eq!(vector.kseaf, regenerated.kseaf);
+        assert_eq!(vector.kasme.as_array(), regenerated.kasme.as_array());
+
+        // Keys of another PLMN differ
+        let other = regenerate_vector(
+            "901",
+            "71",
+            Algorithm::Milenage,
+            &k,
+            &opc,
+            &rand,
+            &vector.autn,
+        )
+        .unwrap();
+        assert_ne!(vector.kseaf, other.kseaf);
+    }
+
+    #[test]
+    fn test_auts_parse() {
+        let bytes = hex::decode("ba853f3c123ccf44e93596e355c6").unwrap();
+        let auts: types::Auts = bytes[..].try_into().unwrap();
+        assert_eq!(bytes, auts.as_array().to_vec());
+
+        assert!(types::Auts::try_from(&bytes[1..]).is_err());
+        assert!(types::Auts::try_from(&[0u8; types::AUTS_LENGTH + 1][..]).is_err());
+    }
+
     fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
         (0..s.len())
             .step_by(2)
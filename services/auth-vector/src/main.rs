@@ -1,3 +1,4 @@
+use auth_vector::types;
 use milenage::Milenage;
 use std::{fmt::Write, num::ParseIntError};
 
@@ -43,8 +44,20 @@ fn test_sqn_from_ak(k_str: &str, opc_str: &str, rand_str: &str, sqn_xor_str: &st
     ];
 
     let mac_a = m.f1(&rand, &sqn, &amf);
-    let mac_s = m.f1star(&rand, &sqn, &amf);
-    let ak_rs = m.f5star(&rand);
+
+    let resync_rand: types::Rand = rand[..].try_into().unwrap();
+    let resync_sqn: types::Sqn = sqn[..].try_into().unwrap();
+    let mac_s = auth_vector::compute_mac_s(
+        types::Algorithm::Milenage,
+        &k,
+        &opc,
+        &resync_rand,
+        &resync_sqn,
+        &amf,
+    )
+    .unwrap();
+    let ak_rs =
+        auth_vector::compute_ak_star(types::Algorithm::Milenage, &k, &opc, &resync_rand).unwrap();
 
     println!("ak:    {}", encode_hex(&ak));
     println!("sqn:   {}", encode_hex(&sqn));
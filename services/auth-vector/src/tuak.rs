@@ -0,0 +1,324 @@
+use crate::types::{Ak, Ck, Ik, Topc, AMF_LENGTH, K, RAND_LENGTH, SQN_LENGTH, TOPC_LENGTH};
+
+const ALGONAME: &[u8] = b"TUAK1.0";
+const STATE_LENGTH: usize = 200;
+const MAC_LENGTH: usize = 8;
+
+// Byte offsets of each field in the Keccak state, per TS 35.231 6
+const TOP_OFFSET: usize = 0;
+const INSTANCE_OFFSET: usize = 32;
+const ALGONAME_OFFSET: usize = 33;
+const RAND_OFFSET: usize = 40;
+const AMF_OFFSET: usize = 56;
+const SQN_OFFSET: usize = 58;
+const KEY_OFFSET: usize = 64;
+const CK_OFFSET: usize = 32;
+const IK_OFFSET: usize = 64;
+const AK_OFFSET: usize = 96;
+
+// Instance values for 128 bit keys, 64 bit MACs and RES, 128 bit CK and IK
+const INSTANCE_TOPC: u8 = 0x00;
+const INSTANCE_F1: u8 = 0x08;
+const INSTANCE_F1_STAR: u8 = 0x88;
+const INSTANCE_F2345: u8 = 0x48;
+const INSTANCE_F5_STAR: u8 = 0xc0;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+const ROTATION_OFFSETS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// TUAK algorithm set, per TS 35.231.
+/// Supports 128 bit keys, 64 bit MACs and RES, and 128 bit CK and IK.
+#[derive(Debug, Clone)]
+pub struct Tuak {
+    k: K,
+    topc: Topc,
+    keccak_iterations: u8,
+}
+
+impl Tuak {
+    pub fn new_with_topc(k: K, topc: Topc) -> Self {
+        Tuak {
+            k,
+            topc,
+            keccak_iterations: 1,
+        }
+    }
+
+    /// Derives TOPc from the operator TOP.
+    pub fn compute_topc(k: &K, top: &Topc) -> Topc {
+        let out = keccak_main(k, top, INSTANCE_TOPC, None, None, None, 1);
+
+        pull(&out, TOP_OFFSET)
+    }
+
+    /// Computes the network authentication code MAC-A.
+    pub fn f1(
+        &self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> [u8; MAC_LENGTH] {
+        let out = self.main(INSTANCE_F1, Some(rand), Some(sqn), Some(amf));
+
+        pull(&out, 0)
+    }
+
+    /// Computes the resync authentication code MAC-S.
+    pub fn f1star(
+        &self,
+        rand: &[u8; RAND_LENGTH],
+        sqn: &[u8; SQN_LENGTH],
+        amf: &[u8; AMF_LENGTH],
+    ) -> [u8; MAC_LENGTH] {
+        let out = self.main(INSTANCE_F1_STAR, Some(rand), Some(sqn), Some(amf));
+
+        pull(&out, 0)
+    }
+
+    /// Computes (RES, CK, IK, AK).
+    pub fn f2345(&self, rand: &[u8; RAND_LENGTH]) -> ([u8; 8], Ck, Ik, Ak) {
+        let out = self.main(INSTANCE_F2345, Some(rand), None, None);
+
+        (
+            pull(&out, 0),
+            pull(&out, CK_OFFSET),
+            pull(&out, IK_OFFSET),
+            pull(&out, AK_OFFSET),
+        )
+    }
+
+    /// Computes the resync anonymity key AK*.
+    pub fn f5star(&self, rand: &[u8; RAND_LENGTH]) -> Ak {
+        let out = self.main(INSTANCE_F5_STAR, Some(rand), None, None);
+
+        pull(&out, AK_OFFSET)
+    }
+
+    fn main(
+        &self,
+        instance: u8,
+        rand: Option<&[u8; RAND_LENGTH]>,
+        sqn: Option<&[u8; SQN_LENGTH]>,
+        amf: Option<&[u8; AMF_LENGTH]>,
+    ) -> [u8; STATE_LENGTH] {
+        keccak_main(
+            &self.k,
+            &self.topc,
+            instance,
+            rand,
+            sqn,
+            amf,
+            self.keccak_iterations,
+        )
+    }
+}
+
+/// Builds the TUAK input state and applies Keccak-f[1600].
+/// All fields are stored byte-reversed, per TS 35.231 6.
+fn keccak_main(
+    k: &K,
+    top: &[u8; TOPC_LENGTH],
+    instance: u8,
+    rand: Option<&[u8; RAND_LENGTH]>,
+    sqn: Option<&[u8; SQN_LENGTH]>,
+    amf: Option<&[u8; AMF_LENGTH]>,
+    iterations: u8,
+) -> [u8; STATE_LENGTH] {
+    let mut inout = [0u8; STATE_LENGTH];
+
+    push(&mut inout, TOP_OFFSET, top);
+    inout[INSTANCE_OFFSET] = instance;
+    push(&mut inout, ALGONAME_OFFSET, ALGONAME);
+    if let Some(rand) = rand {
+        push(&mut inout, RAND_OFFSET, rand);
+    }
+    if let Some(amf) = amf {
+        push(&mut inout, AMF_OFFSET, amf);
+    }
+    if let Some(sqn) = sqn {
+        push(&mut inout, SQN_OFFSET, sqn);
+    }
+    push(&mut inout, KEY_OFFSET, k);
+
+    // Padding
+    inout[96] = 0x1f;
+    inout[135] = 0x80;
+
+    let mut state = [0u64; 25];
+    for (index, lane) in state.iter_mut().enumerate() {
+        *lane = u64::from_le_bytes(
+            inout[(index * 8)..(index * 8 + 8)]
+                .try_into()
+                .expect("All data should have correct size"),
+        );
+    }
+
+    for _ in 0..iterations {
+        keccak_f1600(&mut state);
+    }
+
+    for (index, lane) in state.iter().enumerate() {
+        inout[(index * 8)..(index * 8 + 8)].copy_from_slice(&lane.to_le_bytes());
+    }
+
+    inout
+}
+
+fn push(inout: &mut [u8; STATE_LENGTH], offset: usize, data: &[u8]) {
+    for (index, byte) in data.iter().rev().enumerate() {
+        inout[offset + index] = *byte;
+    }
+}
+
+fn pull<const N: usize>(inout: &[u8; STATE_LENGTH], offset: usize) -> [u8; N] {
+    let mut res = [0u8; N];
+    for (index, byte) in res.iter_mut().rev().enumerate() {
+        *byte = inout[offset + index];
+    }
+    res
+}
+
+fn keccak_f1600(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // Theta
+        let mut c = [0u64; 5];
+        for x in 0..5 {
+            c[x] = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+
+        // Rho and pi
+        let mut b = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] =
+                    state[x + 5 * y].rotate_left(ROTATION_OFFSETS[x + 5 * y]);
+            }
+        }
+
+        // Chi
+        for x in 0..5 {
+            for y in 0..5 {
+                state[x + 5 * y] =
+                    b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // TS 35.232 test set 1
+    const TOP: &str = "5555555555555555555555555555555555555555555555555555555555555555";
+    const TOPC: &str = "bd04d9530e87513c5d837ac2ad954623a8e2330c115305a73eb45d1f40cccbff";
+    const K: &str = "abababababababababababababababab";
+    const RAND: &str = "42424242424242424242424242424242";
+    const SQN: &str = "111111111111";
+    const AMF: &str = "ffff";
+
+    fn build() -> Tuak {
+        Tuak::new_with_topc(
+            hex::decode(K).unwrap().try_into().unwrap(),
+            hex::decode(TOPC).unwrap().try_into().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_keccak_f1600() {
+        // SHA3-256 of the empty string uses a single permutation
+        let mut state = [0u64; 25];
+        state[0] = 0x06;
+        state[16] = 0x8000000000000000;
+        keccak_f1600(&mut state);
+
+        let mut digest = Vec::new();
+        for lane in &state[..4] {
+            digest.extend(lane.to_le_bytes());
+        }
+        assert_eq!(
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+            hex::encode(digest)
+        );
+    }
+
+    #[test]
+    fn test_compute_topc() {
+        let k: K = hex::decode(K).unwrap().try_into().unwrap();
+        let top: Topc = hex::decode(TOP).unwrap().try_into().unwrap();
+
+        assert_eq!(TOPC, hex::encode(Tuak::compute_topc(&k, &top)));
+    }
+
+    #[test]
+    fn test_f1_f1star() {
+        let tuak = build();
+        let rand: [u8; RAND_LENGTH] = hex::decode(RAND).unwrap().try_into().unwrap();
+        let sqn: [u8; SQN_LENGTH] = hex::decode(SQN).unwrap().try_into().unwrap();
+        let amf: [u8; AMF_LENGTH] = hex::decode(AMF).unwrap().try_into().unwrap();
+
+        assert_eq!("f9a54e6aeaa8618d", hex::encode(tuak.f1(&rand, &sqn, &amf)));
+        assert_eq!(
+            "e94b4dc6c7297df3",
+            hex::encode(tuak.f1star(&rand, &sqn, &amf))
+        );
+    }
+
+    #[test]
+    fn test_f2345_f5star() {
+        let tuak = build();
+        let rand: [u8; RAND_LENGTH] = hex::decode(RAND).unwrap().try_into().unwrap();
+
+        // Test set 1 uses a 32 bit RES, which changes the instance
+        let out = tuak.main(INSTANCE_F2345 & !0x08, Some(&rand), None, None);
+        assert_eq!("657acd64", hex::encode(pull::<4>(&out, 0)));
+        assert_eq!(
+            "d71a1e5c6caffe986a26f783e5c78be1",
+            hex::encode(pull::<16>(&out, CK_OFFSET))
+        );
+        assert_eq!(
+            "be849fa2564f869aecee6f62d4337e72",
+            hex::encode(pull::<16>(&out, IK_OFFSET))
+        );
+        assert_eq!("719f1e9b9054", hex::encode(pull::<6>(&out, AK_OFFSET)));
+
+        assert_eq!("e7af6b3d0e38", hex::encode(tuak.f5star(&rand)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::algorithm::AkaAlgorithm;
 use crate::types::SQN_LENGTH;
 use crate::types::{Ak, Rand, Sqn};
 
@@ -10,7 +11,7 @@ pub type Autn = [u8; AUTN_LENGTH];
 const MAC_LENGTH: usize = 8;
 type Mac = [u8; MAC_LENGTH];
 
-pub fn build_autn(sqn: &Sqn, ak: &Ak, rand: &Rand, m: &mut milenage::Milenage) -> Autn {
+pub fn build_autn(sqn: &Sqn, ak: &Ak, rand: &Rand, m: &mut dyn AkaAlgorithm) -> Autn {
     let sqn_xor_ak: Sqn = sqn
         .as_bytes()
         .iter()
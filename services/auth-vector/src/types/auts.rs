@@ -0,0 +1,87 @@
+use thiserror::Error;
+
+use crate::types::{Ak, AuthVectorConversionError, Sqn, AMF_LENGTH, SQN_LENGTH};
+
+pub const MAC_S_LENGTH: usize = 8;
+pub const AUTS_LENGTH: usize = SQN_LENGTH + MAC_S_LENGTH;
+
+/// Dummy AMF used to compute MAC-S, per TS 33.102 6.3.3
+pub const RESYNC_AMF: [u8; AMF_LENGTH] = [0x00, 0x00];
+
+pub type MacS = [u8; MAC_S_LENGTH];
+
+/// Error type for resynchronization failures
+#[derive(Error, Debug)]
+pub enum ResyncError {
+    #[error("MAC-S verification failed")]
+    MacFailure(),
+
+    #[error("Invalid resync data -- {0}")]
+    ConversionError(#[from] AuthVectorConversionError),
+}
+
+/// Resynchronization token sent by the UE, per TS 33.102 6.3.3.
+/// Contains (SQN_MS xor AK*) || MAC-S.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Auts {
+    sqn_xor_ak_star: [u8; SQN_LENGTH],
+    mac_s: MacS,
+}
+
+impl TryFrom<&[u8]> for Auts {
+    type Error = AuthVectorConversionError;
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != AUTS_LENGTH {
+            return Err(AuthVectorConversionError::BoundsError());
+        }
+
+        Ok(Auts {
+            sqn_xor_ak_star: value[..SQN_LENGTH].try_into()?,
+            mac_s: value[SQN_LENGTH..].try_into()?,
+        })
+    }
+}
+
+impl TryFrom<&Vec<u8>> for Auts {
+    type Error = AuthVectorConversionError;
+    fn try_from(value: &Vec<u8>) -> Result<Self, Self::Error> {
+        value.as_slice().try_into()
+    }
+}
+
+impl Auts {
+    pub fn build(sqn_ms: &Sqn, ak_star: &Ak, mac_s: &MacS) -> Self {
+        let mut sqn_xor_ak_star = [0; SQN_LENGTH];
+        for (index, byte) in sqn_xor_ak_star.iter_mut().enumerate() {
+            *byte = sqn_ms.as_bytes()[index] ^ ak_star[index];
+        }
+
+        Auts {
+            sqn_xor_ak_star,
+            mac_s: mac_s.clone(),
+        }
+    }
+
+    /// Recovers SQN_MS using the AK* of the resync rand
+    pub fn recover_sqn(&self, ak_star: &Ak) -> Sqn {
+        let mut sqn = [0; SQN_LENGTH];
+        for (index, byte) in sqn.iter_mut().enumerate() {
+            *byte = self.sqn_xor_ak_star[index] ^ ak_star[index];
+        }
+
+        sqn[..]
+            .try_into()
+            .expect("All data should have correct size")
+    }
+
+    pub fn mac_s(&self) -> &MacS {
+        &self.mac_s
+    }
+
+    pub fn as_array(&self) -> [u8; AUTS_LENGTH] {
+        let mut auts = [0; AUTS_LENGTH];
+        auts[..SQN_LENGTH].copy_from_slice(&self.sqn_xor_ak_star);
+        auts[SQN_LENGTH..].copy_from_slice(&self.mac_s);
+        auts
+    }
+}
@@ -1,12 +1,13 @@
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::types::{Autn, Ck, Ik};
+use crate::types::{Autn, Ck, Ik, Rand, XRes, XResStar, XRES_STAR_LENGTH};
 
 pub const KAUSF_LENGTH: usize = 32;
 pub const KSEAF_LENGTH: usize = 32;
 const FC_KAUSF: u8 = 0x6A;
 const FC_KSEAF: u8 = 0x6C;
+const FC_RES_STAR: u8 = 0x6B;
 
 pub type Kausf = [u8; KAUSF_LENGTH];
 pub type Kseaf = [u8; KSEAF_LENGTH];
@@ -38,6 +39,39 @@ pub fn gen_kausf(mcc: &str, mnc: &str, ck: &Ck, ik: &Ik, autn: &Autn) -> Kausf {
         .expect("All data should have correct size")
 }
 
+/// Derives RES* from RES per TS 33.501 A.4
+pub fn gen_xres_star(mcc: &str, mnc: &str, ck: &Ck, ik: &Ik, rand: &Rand, xres: &XRes) -> XResStar {
+    let mut key = Vec::new();
+    key.extend(ck);
+    key.extend(ik);
+
+    let mut data = vec![FC_RES_STAR];
+
+    data.extend(get_snn(mcc, mnc).as_bytes());
+    let snn_len = get_snn(mcc, mnc).as_bytes().len() as u16;
+    let snn_len = snn_len.to_be_bytes();
+    data.extend(snn_len);
+
+    data.extend(rand.as_array());
+    let rand_len = rand.as_array().len() as u16;
+    let rand_len = rand_len.to_be_bytes();
+    data.extend(rand_len);
+
+    data.extend(xres);
+    let xres_len = xres.len() as u16;
+    let xres_len = xres_len.to_be_bytes();
+    data.extend(xres_len);
+
+    type HmacSha256 = Hmac<Sha256>;
+    let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC can take key of any size");
+    mac.update(&data);
+
+    // RES* is the 128 least significant bits of the output
+    mac.finalize().into_bytes()[(32 - XRES_STAR_LENGTH)..]
+        .try_into()
+        .expect("All data should have correct size")
+}
+
 pub fn gen_kseaf(mcc: &str, mnc: &str, kausf: &Kausf) -> Kseaf {
     let mut data = vec![FC_KSEAF];
     data.extend(get_snn(mcc, mnc).as_bytes());
@@ -0,0 +1,184 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::types::{gen_kseaf, Autn, Ck, Ik, Kausf, Kseaf, CK_LENGTH, KAUSF_LENGTH};
+
+pub const K_ENCR_LENGTH: usize = 16;
+pub const K_AUT_LENGTH: usize = 32;
+pub const K_RE_LENGTH: usize = 32;
+pub const MSK_LENGTH: usize = 64;
+pub const EMSK_LENGTH: usize = 64;
+const MK_LENGTH: usize = K_ENCR_LENGTH + K_AUT_LENGTH + K_RE_LENGTH + MSK_LENGTH + EMSK_LENGTH;
+const FC_CK_IK_PRIME: u8 = 0x20;
+const PRF_LABEL: &[u8] = b"EAP-AKA'";
+
+pub type KEncr = [u8; K_ENCR_LENGTH];
+pub type KAut = [u8; K_AUT_LENGTH];
+pub type KRe = [u8; K_RE_LENGTH];
+pub type Msk = [u8; MSK_LENGTH];
+pub type Emsk = [u8; EMSK_LENGTH];
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Derives CK' and IK' bound to the access network name, per RFC 9048 3.3
+/// and TS 33.402 A.2.
+pub fn gen_ck_ik_prime(network_name: &str, ck: &Ck, ik: &Ik, autn: &Autn) -> (Ck, Ik) {
+    let mut key = Vec::new();
+    key.extend(ck);
+    key.extend(ik);
+
+    let mut data = vec![FC_CK_IK_PRIME];
+
+    data.extend(network_name.as_bytes());
+    let network_name_len = network_name.as_bytes().len() as u16;
+    data.extend(network_name_len.to_be_bytes());
+
+    let sqn_xor_ak = &autn[..6];
+    data.extend(sqn_xor_ak);
+    let autn_len = sqn_xor_ak.len() as u16;
+    data.extend(autn_len.to_be_bytes());
+
+    let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC can take key of any size");
+    mac.update(&data);
+    let out = mac.finalize().into_bytes();
+
+    (
+        out[..CK_LENGTH]
+            .try_into()
+            .expect("All data should have correct size"),
+        out[CK_LENGTH..]
+            .try_into()
+            .expect("All data should have correct size"),
+    )
+}
+
+/// PRF' of RFC 9048 3.4.1, expanding HMAC-SHA-256 to the requested length.
+fn prf_prime(key: &[u8], data: &[u8], length: usize) -> Vec<u8> {
+    let mut res = Vec::with_capacity(length);
+    let mut block: Vec<u8> = Vec::new();
+    let mut counter: u8 = 1;
+
+    while res.len() < length {
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+        mac.update(&block);
+        mac.update(data);
+        mac.update(&[counter]);
+        block = mac.finalize().into_bytes().to_vec();
+
+        res.extend(&block);
+        counter += 1;
+    }
+
+    res.truncate(length);
+    res
+}
+
+/// Keys derived from the EAP-AKA' master key MK, per RFC 9048 3.3
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EapAkaPrimeKeys {
+    pub k_encr: KEncr,
+    pub k_aut: KAut,
+    pub k_re: KRe,
+    pub msk: Msk,
+    pub emsk: Emsk,
+}
+
+impl EapAkaPrimeKeys {
+    /// Derives MK = PRF'(IK'|CK', "EAP-AKA'"|Identity) and splits it.
+    pub fn derive(identity: &str, ck_prime: &Ck, ik_prime: &Ik) -> Self {
+        let mut key = Vec::new();
+        key.extend(ik_prime);
+        key.extend(ck_prime);
+
+        let mut data = PRF_LABEL.to_vec();
+        data.extend(identity.as_bytes());
+
+        let mk = prf_prime(&key, &data, MK_LENGTH);
+        let (k_encr, rest) = mk.split_at(K_ENCR_LENGTH);
+        let (k_aut, rest) = rest.split_at(K_AUT_LENGTH);
+        let (k_re, rest) = rest.split_at(K_RE_LENGTH);
+        let (msk, emsk) = rest.split_at(MSK_LENGTH);
+
+        EapAkaPrimeKeys {
+            k_encr: k_encr
+                .try_into()
+                .expect("All data should have correct size"),
+            k_aut: k_aut.try_into().expect("All data should have correct size"),
+            k_re: k_re.try_into().expect("All data should have correct size"),
+            msk: msk.try_into().expect("All data should have correct size"),
+            emsk: emsk.try_into().expect("All data should have correct size"),
+        }
+    }
+
+    /// K_AUSF is the most significant 256 bits of EMSK, per TS 33.501 6.1.3.1
+    pub fn kausf(&self) -> Kausf {
+        self.emsk[..KAUSF_LENGTH]
+            .try_into()
+            .expect("All data should have correct size")
+    }
+
+    /// Anchor key for the serving network, derived from K_AUSF
+    pub fn anchor_key(&self, mcc: &str, mnc: &str) -> Kseaf {
+        gen_kseaf(mcc, mnc, &self.kausf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc5448_case_1() {
+        // RFC 5448 Appendix C, test case 1
+        let ck: Ck = hex::decode("5349fbe098649f948f5d2e973a81c00f")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let ik: Ik = hex::decode("9744871ad32bf9bbd1dd5ce54e3e2e5a")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let autn: Autn = hex::decode("bb52e91c747ac3ab2a5c23d15ee351d5")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let (ck_prime, ik_prime) = gen_ck_ik_prime("WLAN", &ck, &ik, &autn);
+        assert_eq!("0093962d0dd84aa5684b045c9edffa04", hex::encode(ck_prime));
+        assert_eq!("ccfc230ca74fcc96c0a5d61164f5a76c", hex::encode(ik_prime));
+
+        let keys = EapAkaPrimeKeys::derive("0555444333222111", &ck_prime, &ik_prime);
+        assert_eq!("766fa0a6c317174b812d52fbcd11a179", hex::encode(keys.k_encr));
+        assert_eq!(
+            "0842ea722ff6835bfa2032499fc3ec23c2f0e388b4f07543ffc677f1696d71ea",
+            hex::encode(keys.k_aut)
+        );
+        assert_eq!(
+            "cf83aa8bc7e0aced892acc98e76a9b2095b558c7795c7094715cb3393aa7d17a",
+            hex::encode(keys.k_re)
+        );
+        assert_eq!(
+            "67c42d9aa56c1b79e295e3459fc3d187d42be0bf818d3070e362c5e967a4d544\
+            e8ecfe19358ab3039aff03b7c930588c055babee58a02650b067ec4e9347c75a",
+            hex::encode(keys.msk)
+        );
+        assert_eq!(
+            "f861703cd775590e16c7679ea3874ada866311de290764d760cf76df647ea01c\
+            313f69924bdd7650ca9bac141ea075c4ef9e8029c0e290cdbad5638b63bc23fb",
+            hex::encode(keys.emsk)
+        );
+        assert_eq!(keys.emsk[..KAUSF_LENGTH], keys.kausf());
+    }
+
+    #[test]
+    fn test_ck_ik_prime_network_binding() {
+        let ck: Ck = [0x01; 16];
+        let ik: Ik = [0x02; 16];
+        let autn: Autn = [0x03; 16];
+
+        assert_ne!(
+            gen_ck_ik_prime("WLAN", &ck, &ik, &autn),
+            gen_ck_ik_prime("HRPD", &ck, &ik, &autn)
+        );
+    }
+}
@@ -1,10 +1,14 @@
 mod autn;
+mod auts;
 mod keys_5g;
+mod keys_eap;
 mod keys_eps;
 mod res;
 
 pub use autn::*;
+pub use auts::*;
 pub use keys_5g::*;
+pub use keys_eap::*;
 pub use keys_eps::*;
 pub use res::*;
 
@@ -13,6 +17,7 @@ use thiserror::Error;
 pub const ID_LENGTH: usize = 7;
 pub const K_LENGTH: usize = 16;
 pub const OPC_LENGTH: usize = 16;
+pub const TOPC_LENGTH: usize = 32;
 pub const RAND_LENGTH: usize = 16;
 pub const CK_LENGTH: usize = 16;
 pub const IK_LENGTH: usize = 16;
@@ -32,15 +37,62 @@ pub enum AuthVectorConversionError {
 
     #[error("Could not parse integer input")]
     IntConversionError(#[from] std::num::ParseIntError),
+
+    #[error("Unknown algorithm: {0}")]
+    UnknownAlgorithmError(String),
 }
 
 pub type Id = String;
 pub type K = [u8; K_LENGTH];
 pub type Opc = [u8; OPC_LENGTH];
+pub type Topc = [u8; TOPC_LENGTH];
 pub type Ck = [u8; CK_LENGTH];
 pub type Ik = [u8; IK_LENGTH];
 pub type Ak = [u8; 6];
 
+/// Algorithm set used by a subscriber's SIM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Milenage,
+    Tuak,
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Milenage => "milenage",
+            Algorithm::Tuak => "tuak",
+        }
+    }
+
+    /// Length of the operator variant constant, OPc or TOPc
+    pub fn opc_length(&self) -> usize {
+        match self {
+            Algorithm::Milenage => OPC_LENGTH,
+            Algorithm::Tuak => TOPC_LENGTH,
+        }
+    }
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::Milenage
+    }
+}
+
+impl TryFrom<&str> for Algorithm {
+    type Error = AuthVectorConversionError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "milenage" => Ok(Algorithm::Milenage),
+            "tuak" => Ok(Algorithm::Tuak),
+            _ => Err(AuthVectorConversionError::UnknownAlgorithmError(
+                value.to_string(),
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Sqn {
     data: [u8; SQN_LENGTH],
@@ -158,6 +210,15 @@ impl Rand {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_algorithm_from_str() {
+        for algorithm in [Algorithm::Milenage, Algorithm::Tuak] {
+            assert_eq!(algorithm, Algorithm::try_from(algorithm.as_str()).unwrap());
+        }
+        assert_eq!(Algorithm::Tuak, Algorithm::try_from("TUAK").unwrap());
+        assert!(Algorithm::try_from("comp128").is_err());
+    }
+
     #[test]
     fn test_sqn_bytes_into_int() {
         let integer_rep: i64 = Sqn::try_from(vec![0, 0, 0, 0, 1, 0]).unwrap().into();
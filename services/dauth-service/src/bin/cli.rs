@@ -24,6 +24,8 @@ struct CliOpt {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CliConfig {
     pub users: Vec<UserInfoConfig>,
+    /// Address of the local server of the running instance,
+    /// its local_auth_addr, which is the only server for management
     pub host_addr: String,
 }
 
@@ -45,6 +47,11 @@ async fn main() {
     for user_info in config.users {
         tracing::info!(?user_info, "Adding user");
 
+        let serving_policy = user_info
+            .get_serving_policy()
+            .expect("Invalid serving policy")
+            .map(|policy| policy.to_serving_network_policy());
+
         let mut backups = Vec::new();
 
         for backup in user_info.backups {
@@ -59,9 +66,14 @@ async fn main() {
             .add_user(tonic::Request::new(AddUserReq {
                 user_id: user_info.user_id,
                 k: user_info.k,
-                opc: user_info.opc,
+                opc: user_info.opc.unwrap_or_default(),
+                op: user_info.op.unwrap_or_default(),
                 sqn_max: user_info.sqn_max,
                 backups,
+                auto_backups: user_info.auto_backups.unwrap_or_default(),
+                key_threshold: user_info.key_threshold.unwrap_or_default(),
+                algorithm: user_info.algorithm.unwrap_or_default(),
+                serving_policy,
             }))
             .await
             .expect("Unable to send request to dAuth server")
@@ -1,24 +1,37 @@
 use std::sync::Arc;
 
-use auth_vector::{self, data::AuthVectorData};
+use auth_vector::{
+    self,
+    data::{AuthVectorData, EapAkaPrimeVectorData},
+};
 use sqlx::{Sqlite, Transaction};
 
-use crate::data::vector::AuthVectorRes;
-use crate::data::{context::DauthContext, error::DauthError};
+use crate::data::keys::EapAkaPrimeSessionKeys;
+use crate::data::vector::{AuthVectorRes, DelegatedVector, EapAkaPrimeVectorRes, Plmn};
+use crate::data::{context::DauthContext, error::DauthError, user_info::UserInfo};
 use crate::database;
+use crate::rpc::dauth::common::AkaResyncInfo;
 
 /// Generates an auth vector that will be verified locally.
 /// Stores the kseaf directly, without key shares.
+/// The keys are bound to the serving network PLMN.
+/// Resynchronizes the user's sqn first if resync info is provided.
 pub async fn generate_local_vector(
     context: Arc<DauthContext>,
     user_id: &str,
+    plmn: &Plmn,
+    resync_info: Option<&AkaResyncInfo>,
 ) -> Result<AuthVectorRes, DauthError> {
-    tracing::info!(?user_id, "Generating local vector");
+    tracing::info!(?user_id, ?plmn, "Generating local vector");
 
     let mut transaction = context.local_context.database_pool.begin().await?;
 
+    if let Some(resync_info) = resync_info {
+        resync_sqn(context.clone(), &mut transaction, user_id, 0, resync_info).await?;
+    }
+
     let (auth_vector_data, seqnum) =
-        build_auth_vector(context.clone(), &mut transaction, &user_id, 0).await?;
+        build_auth_vector(context.clone(), &mut transaction, &user_id, 0, plmn).await?;
 
     let auth_vector_res = AuthVectorRes {
         user_id: user_id.to_string(),
@@ -27,6 +40,7 @@ pub async fn generate_local_vector(
         autn: auth_vector_data.autn,
         xres_star_hash: auth_vector_data.xres_star_hash,
         xres_hash: auth_vector_data.xres_hash,
+        plmn: plmn.clone(),
     };
 
     database::kseafs::add(
@@ -49,15 +63,17 @@ pub async fn generate_local_vector(
     Ok(auth_vector_res)
 }
 
-/// Builds an auth vector and updates the user state.
+/// Builds an auth vector with keys bound to the serving network PLMN,
+/// and updates the user state.
 /// Returns the auth vector and seqnum values.
 pub async fn build_auth_vector(
     context: Arc<DauthContext>,
     transaction: &mut Transaction<'_, Sqlite>,
     user_id: &str,
     sqn_slice: i64,
+    plmn: &Plmn,
 ) -> Result<(AuthVectorData, i64), DauthError> {
-    tracing::info!(?user_id, ?sqn_slice, "Building auth vector");
+    tracing::info!(?user_id, ?sqn_slice, ?plmn, "Building auth vector");
 
     let mut user_info =
         database::user_infos::get(transaction, &user_id.to_string(), sqn_slice).await?;
@@ -65,25 +81,193 @@ pub async fn build_auth_vector(
     tracing::debug!(?user_info, "User info found");
 
     let auth_vector_data = auth_vector::generate_vector(
-        &context.local_context.mcc,
-        &context.local_context.mnc,
+        &plmn.mcc,
+        &plmn.mnc,
+        user_info.algorithm,
         &user_info.k,
         &user_info.opc,
         &user_info.sqn.try_into()?,
+    )?;
+
+    advance_sqn(context, transaction, &mut user_info, sqn_slice).await?;
+
+    tracing::debug!(?auth_vector_data, "sqn"=?user_info.sqn, "Auth vector built successfully");
+    Ok((auth_vector_data, user_info.sqn))
+}
+
+/// Builds a delegated vector again from the user's K, so that its keys
+/// can be split again without being stored.
+/// Fails if the user's K or OPc changed since the vector was built.
+pub async fn rebuild_delegated_vector(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    delegated: &DelegatedVector,
+) -> Result<AuthVectorData, DauthError> {
+    let user_info = database::user_infos::get(transaction, &user_id.to_string(), 0).await?;
+
+    let auth_vector_data = auth_vector::regenerate_vector(
+        &delegated.plmn.mcc,
+        &delegated.plmn.mnc,
+        user_info.algorithm,
+        &user_info.k,
+        &user_info.opc,
+        &delegated.rand,
+        &delegated.autn,
+    )?;
+
+    if auth_vector_data.xres_star_hash != delegated.xres_star_hash {
+        return Err(DauthError::DataError(
+            "Delegated vector does not match the user's keys".to_string(),
+        ));
+    }
+    Ok(auth_vector_data)
+}
+
+/// Generates an EAP-AKA' vector that will be verified locally.
+/// Stores the MSK and the EMSK-derived anchor key, keyed by the xres.
+/// The identity the keys are bound to is the user id, and the anchor key
+/// is bound to the serving network PLMN.
+pub async fn generate_local_eap_aka_prime_vector(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    network_name: &str,
+    plmn: &Plmn,
+    resync_info: Option<&AkaResyncInfo>,
+) -> Result<EapAkaPrimeVectorRes, DauthError> {
+    tracing::info!(
+        ?user_id,
+        ?network_name,
+        ?plmn,
+        "Generating local EAP-AKA' vector"
     );
 
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    if let Some(resync_info) = resync_info {
+        resync_sqn(context.clone(), &mut transaction, user_id, 0, resync_info).await?;
+    }
+
+    let (vector_data, seqnum) =
+        build_eap_aka_prime_vector(context.clone(), &mut transaction, user_id, network_name, 0)
+            .await?;
+
+    database::eap_keys::add(
+        &mut transaction,
+        &vector_data.xres,
+        &EapAkaPrimeSessionKeys {
+            msk: vector_data.keys.msk,
+            kseaf: vector_data.keys.anchor_key(&plmn.mcc, &plmn.mnc),
+        },
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    let vector_res = EapAkaPrimeVectorRes {
+        user_id: user_id.to_string(),
+        seqnum,
+        network_name: network_name.to_string(),
+        xres_hash: vector_data.xres_hash,
+        autn: vector_data.autn,
+        rand: vector_data.rand,
+        k_encr: vector_data.keys.k_encr,
+        k_aut: vector_data.keys.k_aut,
+    };
+
+    tracing::debug!(?vector_res, "Local EAP-AKA' vector generated");
+    Ok(vector_res)
+}
+
+/// Builds an EAP-AKA' vector bound to the network name and updates the user state.
+/// Returns the vector and seqnum values.
+pub async fn build_eap_aka_prime_vector(
+    context: Arc<DauthContext>,
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    network_name: &str,
+    sqn_slice: i64,
+) -> Result<(EapAkaPrimeVectorData, i64), DauthError> {
+    tracing::info!(?user_id, ?sqn_slice, "Building EAP-AKA' vector");
+
+    let mut user_info =
+        database::user_infos::get(transaction, &user_id.to_string(), sqn_slice).await?;
+
+    let vector_data = auth_vector::generate_eap_aka_prime_vector(
+        network_name,
+        user_id,
+        user_info.algorithm,
+        &user_info.k,
+        &user_info.opc,
+        &user_info.sqn.try_into()?,
+    )?;
+
+    advance_sqn(context, transaction, &mut user_info, sqn_slice).await?;
+
+    tracing::debug!(sqn = ?user_info.sqn, "EAP-AKA' vector built successfully");
+    Ok((vector_data, user_info.sqn))
+}
+
+/// Moves the user's slice to its next sqn once a vector used the current one.
+async fn advance_sqn(
+    context: Arc<DauthContext>,
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_info: &mut UserInfo,
+    sqn_slice: i64,
+) -> Result<(), DauthError> {
     user_info.sqn += context.local_context.num_sqn_slices;
 
     database::user_infos::upsert(
         transaction,
-        &user_id.to_string(),
+        &user_info.id,
         &user_info.k,
         &user_info.opc,
+        user_info.algorithm,
         user_info.sqn,
         sqn_slice,
     )
-    .await?;
+    .await
+}
 
-    tracing::debug!(?auth_vector_data, "sqn"=?user_info.sqn, "Auth vector built successfully");
-    Ok((auth_vector_data, user_info.sqn))
+/// Resynchronizes the sqn of a user slice from the UE's AUTS.
+/// Verifies the MAC-S and recovers SQN_MS, then advances the slice
+/// past SQN_MS so the next vector built for it is accepted by the UE.
+pub async fn resync_sqn(
+    context: Arc<DauthContext>,
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    sqn_slice: i64,
+    resync_info: &AkaResyncInfo,
+) -> Result<(), DauthError> {
+    tracing::info!(?user_id, ?sqn_slice, "Resynchronizing sqn");
+
+    let user_info = database::user_infos::get(transaction, &user_id.to_string(), sqn_slice).await?;
+
+    let sqn_ms: i64 = auth_vector::recover_sqn_ms(
+        user_info.algorithm,
+        &user_info.k,
+        &user_info.opc,
+        &resync_info.rand[..].try_into()?,
+        &resync_info.auts[..].try_into()?,
+    )?
+    .into();
+
+    // Next sqn in this slice with a higher SEQ than the UE has seen
+    let num_sqn_slices = context.local_context.num_sqn_slices;
+    let resync_sqn = (sqn_ms / num_sqn_slices + 1) * num_sqn_slices + sqn_slice;
+
+    if resync_sqn > user_info.sqn {
+        database::user_infos::upsert(
+            transaction,
+            &user_id.to_string(),
+            &user_info.k,
+            &user_info.opc,
+            user_info.algorithm,
+            resync_sqn,
+            sqn_slice,
+        )
+        .await?;
+    }
+
+    tracing::debug!(?sqn_ms, ?resync_sqn, "Sqn resynchronized");
+    Ok(())
 }
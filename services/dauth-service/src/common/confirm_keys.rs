@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::data::{context::DauthContext, error::DauthError};
+use crate::data::{context::DauthContext, error::DauthError, keys::EapAkaPrimeSessionKeys};
 use crate::database;
 
 /// Gets the Kseaf value for the auth vector from this network.
@@ -36,3 +36,20 @@ pub async fn get_confirm_key_res(
     transaction.commit().await?;
     Ok(kasme)
 }
+
+/// Gets the MSK and anchor key for the EAP-AKA' vector from this network.
+/// Vector must have been generated by this network.
+pub async fn get_confirm_key_eap_res(
+    context: Arc<DauthContext>,
+    res: auth_vector::types::Res,
+) -> Result<EapAkaPrimeSessionKeys, DauthError> {
+    tracing::info!(?res, "Getting confirm key for EAP-AKA' res");
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    let keys = database::eap_keys::get(&mut transaction, &res).await?;
+    database::eap_keys::remove(&mut transaction, &res).await?;
+
+    transaction.commit().await?;
+    Ok(keys)
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use crate::data::{
+    context::DauthContext,
+    error::DauthError,
+    misbehaviour::{self, Misbehaviour, MisbehaviourKind},
+    utilities,
+};
+use crate::database;
+
+/// Stores evidence that a network misbehaved.
+/// Evidence that is already stored, such as a retried report,
+/// is not counted again.
+/// Failing to store the evidence is logged rather than returned,
+/// so that it never changes the outcome of the request that found it.
+pub async fn record(
+    context: &Arc<DauthContext>,
+    network_id: &str,
+    kind: MisbehaviourKind,
+    detail: &str,
+    evidence: Vec<u8>,
+) {
+    let misbehaviour = Misbehaviour {
+        network_id: network_id.to_string(),
+        kind,
+        detail: detail.to_string(),
+        evidence,
+        timestamp: utilities::unix_timestamp(),
+    };
+
+    match add_misbehaviour(context, &misbehaviour).await {
+        Ok(true) => tracing::warn!(?network_id, ?kind, ?detail, "Recorded network misbehaviour"),
+        Ok(false) => tracing::debug!(?network_id, ?kind, "Misbehaviour already recorded"),
+        Err(e) => tracing::error!(?e, ?network_id, "Failed to record misbehaviour"),
+    }
+}
+
+async fn add_misbehaviour(
+    context: &Arc<DauthContext>,
+    misbehaviour: &Misbehaviour,
+) -> Result<bool, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let added = database::misbehaviour::add(&mut transaction, misbehaviour).await?;
+    transaction.commit().await?;
+    Ok(added)
+}
+
+/// Returns the reputation score of a network.
+pub async fn reputation(context: &Arc<DauthContext>, network_id: &str) -> Result<f64, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let records = database::misbehaviour::get_all_by_network(&mut transaction, network_id).await?;
+    transaction.commit().await?;
+
+    Ok(misbehaviour::reputation_score(
+        &records,
+        utilities::unix_timestamp(),
+        context.backup_context.reputation_half_life,
+    ))
+}
+
+/// Returns whether a network has at least the configured
+/// minimum reputation to be used as a backup network.
+pub async fn is_reputable(
+    context: &Arc<DauthContext>,
+    network_id: &str,
+) -> Result<bool, DauthError> {
+    let score = reputation(context, network_id).await?;
+
+    if score < context.backup_context.min_backup_reputation {
+        tracing::warn!(?network_id, ?score, "Network below minimum reputation");
+        Ok(false)
+    } else {
+        Ok(true)
+    }
+}
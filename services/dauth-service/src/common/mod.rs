@@ -1,2 +1,4 @@
 pub mod auth_vectors;
 pub mod confirm_keys;
+pub mod misbehaviour;
+pub mod quotas;
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::{context::DauthContext, error::DauthError, quota::BackupUsage};
+use crate::database;
+
+/// Returns what this network stores as a backup for a home network.
+pub async fn get_usage(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<BackupUsage, DauthError> {
+    Ok(BackupUsage {
+        users: database::backup_users::count_by_home_network(transaction, home_network_id).await?,
+        auth_vectors: database::auth_vectors::count_by_home_network(transaction, home_network_id)
+            .await?,
+        key_shares: database::key_shares::count_by_home_network(transaction, home_network_id)
+            .await?,
+        flood_vectors: database::flood_vectors::count_by_home_network(transaction, home_network_id)
+            .await?,
+    })
+}
+
+/// Checks that storing the added amounts for a home network keeps it
+/// within its backup quota.
+/// Returns a quota exceeded error otherwise, which refuses the request.
+pub async fn check(
+    context: &Arc<DauthContext>,
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+    added: &BackupUsage,
+) -> Result<(), DauthError> {
+    let usage = get_usage(transaction, home_network_id).await?;
+
+    context
+        .backup_context
+        .backup_quotas
+        .get(home_network_id)
+        .check(&usage, added)
+        .map_err(|error| {
+            tracing::warn!(
+                ?home_network_id,
+                ?usage,
+                ?added,
+                "Refusing over quota backup"
+            );
+            error
+        })
+}
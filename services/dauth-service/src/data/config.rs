@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-use auth_vector::types::{Opc, K, K_LENGTH, OPC_LENGTH};
+use auth_vector::types::{Algorithm, K, K_LENGTH};
 
-use crate::data::{error::DauthError, utilities};
+use crate::data::{
+    error::DauthError,
+    keys,
+    policy::{self, ServingPolicy},
+    utilities,
+};
 
 /// Holds all configuration data from a corresponding YAML file
 #[derive(Serialize, Deserialize, Debug)]
@@ -22,6 +27,78 @@ pub struct DauthConfig {
     pub local_auth_addr: Option<String>,
     pub max_recorded_metrics: Option<i64>,
     pub backup_key_threshold: Option<i64>,
+    pub min_backup_reputation: Option<f64>,
+    pub reputation_half_life: Option<f64>,
+    pub key_share_refresh_interval: Option<f64>,
+    pub replay_window: Option<f64>,
+    pub max_replay_cache_size: Option<i64>,
+    pub max_replay_cache_size_per_signer: Option<i64>,
+    pub auth_state_lifetime: Option<f64>,
+    pub op: Option<String>,
+    pub suci_keys: Option<Vec<SuciKeyConfig>>,
+    pub suci_routes: Option<Vec<SuciRouteConfig>>,
+    pub serving_plmns: Option<Vec<PlmnConfig>>,
+    pub region: Option<String>,
+    pub operator_group: Option<String>,
+    pub backup_capacity: Option<i64>,
+    pub backup_placement: Option<BackupPlacementConfig>,
+    pub backup_quotas: Option<Vec<BackupQuotaConfig>>,
+    pub tls: Option<TlsConfig>,
+}
+
+/// Represents the certificates for mutual TLS with other networks
+/// and the directory. Network certificates are issued by the CA for
+/// the network id, as a DNS subject alternative name.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub ca_cert_path: String,
+    pub directory_domain: String,
+}
+
+/// Represents the placement policy for automatically selected backups.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupPlacementConfig {
+    pub distinct_operator_groups: Option<bool>,
+    pub distinct_regions: Option<bool>,
+    pub allowed_regions: Option<Vec<String>>,
+}
+
+/// Represents limits on what is stored as a backup for a home network,
+/// or for every home network without its own quota if no id is set.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupQuotaConfig {
+    pub home_network_id: Option<String>,
+    pub max_users: Option<i64>,
+    pub max_auth_vectors: Option<i64>,
+    pub max_key_shares: Option<i64>,
+    pub max_flood_vectors: Option<i64>,
+}
+
+/// Represents a serving network PLMN that backup vectors are bound to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlmnConfig {
+    pub mcc: String,
+    pub mnc: String,
+}
+
+/// Represents a home network private key for SUCI de-concealment.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SuciKeyConfig {
+    pub key_id: u8,
+    pub profile: String,
+    pub private_key: String,
+}
+
+/// Represents the home network of SUCIs from a PLMN,
+/// optionally for a single routing indicator.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SuciRouteConfig {
+    pub mcc: String,
+    pub mnc: String,
+    pub routing_indicator: Option<String>,
+    pub network_id: String,
 }
 
 /// Represents configuration data for adding a user.
@@ -29,9 +106,14 @@ pub struct DauthConfig {
 pub struct UserInfoConfig {
     pub user_id: String,
     pub k: String,
-    pub opc: String,
+    pub opc: Option<String>,
+    pub op: Option<String>,
+    pub algorithm: Option<String>,
     pub sqn_max: i64,
     pub backups: Vec<BackupConfig>,
+    pub auto_backups: Option<i64>,
+    pub key_threshold: Option<i64>,
+    pub serving_policy: Option<ServingPolicyConfig>,
 }
 
 /// Represents configuration for a backup.
@@ -42,6 +124,22 @@ pub struct BackupConfig {
     pub sqn_max: i64,
 }
 
+/// Represents the serving networks that backups may give
+/// a user's vectors and key shares to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServingPolicyConfig {
+    pub allowed_network_ids: Option<Vec<String>>,
+    pub denied_network_ids: Option<Vec<String>>,
+    pub allowed_mcc_ranges: Option<Vec<MccRangeConfig>>,
+}
+
+/// Represents an inclusive range of mcc values.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MccRangeConfig {
+    pub first: String,
+    pub last: String,
+}
+
 impl UserInfoConfig {
     /// Generates a user info object with byte arrays
     pub fn get_k(&self) -> Result<K, DauthError> {
@@ -51,10 +149,258 @@ impl UserInfoConfig {
         )
     }
     /// Generates a user info object with byte arrays
-    pub fn get_opc(&self) -> Result<Opc, DauthError> {
-        Ok(
-            utilities::convert_hex_string_to_byte_vec_with_length(&self.opc, OPC_LENGTH)?[..]
-                .try_into()?,
-        )
+    /// Holds the OPc for Milenage or the TOPc for TUAK.
+    /// Derived from the user's OP if set, or else from the network OP.
+    /// A configured opc must match the derived value.
+    pub fn get_opc(&self, network_op: Option<&str>) -> Result<Vec<u8>, DauthError> {
+        let opc_length = self.get_algorithm()?.opc_length();
+
+        let opc = match &self.opc {
+            Some(opc) => Some(utilities::convert_hex_string_to_byte_vec_with_length(
+                opc, opc_length,
+            )?),
+            None => None,
+        };
+
+        let op = match self.op.as_deref().or(network_op) {
+            Some(op) => op,
+            None => {
+                return opc.ok_or_else(|| {
+                    DauthError::ConfigError(format!("No opc or op for user {}", self.user_id))
+                })
+            }
+        };
+
+        let derived_opc = auth_vector::algorithm::compute_opc(
+            self.get_algorithm()?,
+            &self.get_k()?,
+            &utilities::convert_hex_string_to_byte_vec_with_length(op, opc_length)?,
+        )?;
+
+        match opc {
+            Some(opc) if opc != derived_opc => Err(DauthError::ConfigError(format!(
+                "Opc does not match k and op for user {}",
+                self.user_id
+            ))),
+            _ => Ok(derived_opc),
+        }
+    }
+    /// Returns the configured algorithm, Milenage by default
+    pub fn get_algorithm(&self) -> Result<Algorithm, DauthError> {
+        match &self.algorithm {
+            Some(algorithm) => Ok(algorithm.as_str().try_into()?),
+            None => Ok(Algorithm::default()),
+        }
+    }
+    /// Returns the configured key threshold, if any.
+    /// A threshold must fit the number of shares a key can be split into.
+    pub fn get_key_threshold(&self) -> Result<Option<u8>, DauthError> {
+        match self.key_threshold {
+            Some(key_threshold) => match u8::try_from(key_threshold) {
+                Ok(key_threshold) if key_threshold > 0 => Ok(Some(key_threshold)),
+                _ => Err(DauthError::ConfigError(format!(
+                    "Invalid key threshold {} for user {}",
+                    key_threshold, self.user_id
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+    /// Checks that the user has at least as many backups as its key
+    /// threshold, or the default threshold if it has none, counting
+    /// the backups to be selected automatically.
+    /// A user without backups splits no keys, so has no threshold to meet.
+    /// A user cannot have more backups than a key can be split between.
+    pub fn check_key_threshold(&self, default_threshold: u8) -> Result<(), DauthError> {
+        let key_threshold = self.get_key_threshold()?.unwrap_or(default_threshold);
+        let num_backups = i64::try_from(self.backups.len())
+            .unwrap_or(i64::MAX)
+            .saturating_add(self.auto_backups.unwrap_or(0).max(0));
+
+        if num_backups > i64::from(keys::MAX_SHARE_COUNT) {
+            Err(DauthError::ConfigError(format!(
+                "User {} has {} backup(s), at most {} are supported",
+                self.user_id,
+                num_backups,
+                keys::MAX_SHARE_COUNT
+            )))
+        } else if num_backups > 0 && num_backups < i64::from(key_threshold) {
+            Err(DauthError::ConfigError(format!(
+                "Key threshold {} exceeds the {} backup(s) of user {}",
+                key_threshold, num_backups, self.user_id
+            )))
+        } else {
+            Ok(())
+        }
+    }
+    /// Returns the configured serving policy, if any
+    pub fn get_serving_policy(&self) -> Result<Option<ServingPolicy>, DauthError> {
+        let config = match &self.serving_policy {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+
+        let mut allowed_mcc_ranges = Vec::new();
+        for range in config.allowed_mcc_ranges.iter().flatten() {
+            match (
+                policy::parse_mcc(&range.first),
+                policy::parse_mcc(&range.last),
+            ) {
+                (Ok(first), Ok(last)) if first <= last => allowed_mcc_ranges.push((first, last)),
+                _ => {
+                    return Err(DauthError::ConfigError(format!(
+                        "Invalid mcc range {}-{} for user {}",
+                        range.first, range.last, self.user_id
+                    )))
+                }
+            }
+        }
+
+        Ok(Some(ServingPolicy {
+            allowed_network_ids: config.allowed_network_ids.clone().unwrap_or_default(),
+            denied_network_ids: config.denied_network_ids.clone().unwrap_or_default(),
+            allowed_mcc_ranges,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::config::{BackupConfig, MccRangeConfig, ServingPolicyConfig, UserInfoConfig};
+
+    // TS 35.208 test set 1
+    const K: &str = "465b5ce8b199b49faa5f0a2ee238a6bc";
+    const OP: &str = "cdc202d5123e20f62b6d676ac72cb318";
+    const OPC: &str = "cd63cb71954a9f4e48a5994e37a02baf";
+
+    fn build_user_info(opc: Option<&str>, op: Option<&str>) -> UserInfoConfig {
+        UserInfoConfig {
+            user_id: "test_user_id".to_string(),
+            k: K.to_string(),
+            opc: opc.map(str::to_string),
+            op: op.map(str::to_string),
+            algorithm: None,
+            sqn_max: 0,
+            backups: Vec::new(),
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
+        }
+    }
+
+    #[test]
+    fn test_get_opc() {
+        let opc = hex::decode(OPC).unwrap();
+
+        // Configured opc
+        assert_eq!(opc, build_user_info(Some(OPC), None).get_opc(None).unwrap());
+
+        // Derived from the user or network op
+        assert_eq!(opc, build_user_info(None, Some(OP)).get_opc(None).unwrap());
+        assert_eq!(opc, build_user_info(None, None).get_opc(Some(OP)).unwrap());
+        assert_eq!(
+            opc,
+            build_user_info(Some(OPC), Some(OP)).get_opc(None).unwrap()
+        );
+
+        // The user op takes precedence over the network op
+        assert_eq!(
+            opc,
+            build_user_info(None, Some(OP))
+                .get_opc(Some("00000000000000000000000000000000"))
+                .unwrap()
+        );
+
+        // Mismatched or missing values
+        assert!(
+            build_user_info(Some("00000000000000000000000000000000"), Some(OP))
+                .get_opc(None)
+                .is_err()
+        );
+        assert!(
+            build_user_info(Some("00000000000000000000000000000000"), None)
+                .get_opc(Some(OP))
+                .is_err()
+        );
+        assert!(build_user_info(None, None).get_opc(None).is_err());
+    }
+
+    #[test]
+    fn test_get_key_threshold() {
+        let mut user_info = build_user_info(Some(OPC), None);
+        assert_eq!(None, user_info.get_key_threshold().unwrap());
+
+        user_info.key_threshold = Some(5);
+        assert_eq!(Some(5), user_info.get_key_threshold().unwrap());
+
+        for key_threshold in [0, -1, 256] {
+            user_info.key_threshold = Some(key_threshold);
+            assert!(user_info.get_key_threshold().is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_key_threshold() {
+        let mut user_info = build_user_info(Some(OPC), None);
+        // No backups to split keys between
+        user_info.check_key_threshold(3).unwrap();
+
+        user_info.backups.push(BackupConfig {
+            backup_id: "backup_a".to_string(),
+            sqn_slice: 1,
+            sqn_max: 33,
+        });
+        assert!(user_info.check_key_threshold(3).is_err());
+        user_info.check_key_threshold(1).unwrap();
+
+        // Automatic backups count towards the threshold
+        user_info.auto_backups = Some(2);
+        user_info.check_key_threshold(3).unwrap();
+
+        // The user's threshold replaces the default
+        user_info.key_threshold = Some(4);
+        assert!(user_info.check_key_threshold(3).is_err());
+        user_info.key_threshold = Some(2);
+        user_info.check_key_threshold(4).unwrap();
+
+        // Keys cannot be split between more backups than share indexes
+        user_info.auto_backups = Some(253);
+        user_info.check_key_threshold(3).unwrap();
+        user_info.auto_backups = Some(254);
+        assert!(user_info.check_key_threshold(3).is_err());
+        user_info.auto_backups = Some(i64::MAX);
+        assert!(user_info.check_key_threshold(3).is_err());
+    }
+
+    #[test]
+    fn test_get_serving_policy() {
+        let mut user_info = build_user_info(Some(OPC), None);
+        assert!(user_info.get_serving_policy().unwrap().is_none());
+
+        user_info.serving_policy = Some(ServingPolicyConfig {
+            allowed_network_ids: None,
+            denied_network_ids: Some(vec!["network_a".to_string()]),
+            allowed_mcc_ranges: Some(vec![MccRangeConfig {
+                first: "310".to_string(),
+                last: "316".to_string(),
+            }]),
+        });
+        let policy = user_info.get_serving_policy().unwrap().unwrap();
+        assert!(policy.allowed_network_ids.is_empty());
+        assert_eq!(vec!["network_a".to_string()], policy.denied_network_ids);
+        assert_eq!(vec![(310, 316)], policy.allowed_mcc_ranges);
+
+        // Reversed or malformed ranges
+        for (first, last) in [("316", "310"), ("31", "316"), ("310", "abc")] {
+            user_info.serving_policy = Some(ServingPolicyConfig {
+                allowed_network_ids: None,
+                denied_network_ids: None,
+                allowed_mcc_ranges: Some(vec![MccRangeConfig {
+                    first: first.to_string(),
+                    last: last.to_string(),
+                }]),
+            });
+            assert!(user_info.get_serving_policy().is_err());
+        }
     }
 }
@@ -1,12 +1,17 @@
 use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 
-use ed25519_dalek::{Keypair, PublicKey};
+use ed25519_dalek::Keypair;
 use sqlx::SqlitePool;
 use tokio_metrics::{TaskMetrics, TaskMonitor};
-use tonic::transport::Channel;
+use tonic::transport::{Certificate, Channel, Identity};
 
-use crate::data::state::AuthState;
+use crate::data::error::DauthError;
+use crate::data::placement::PlacementPolicy;
+use crate::data::quota::BackupQuotas;
+use crate::data::suci::SuciKey;
+use crate::data::vector::Plmn;
+use crate::rpc::clients::directory::NetworkInfo;
 use crate::rpc::dauth::directory::directory_client::DirectoryClient;
 use crate::rpc::dauth::remote::{
     backup_network_client::BackupNetworkClient, home_network_client::HomeNetworkClient,
@@ -27,21 +32,66 @@ pub struct DauthContext {
 pub struct LocalContext {
     pub id: String,
     pub database_pool: SqlitePool,
-    pub signing_keys: Keypair,
+    pub signing_keys: std::sync::RwLock<Keypair>,
+    pub keyfile_path: String,
     pub num_sqn_slices: i64,
     pub max_backup_vectors: i64,
     pub mcc: String,
     pub mnc: String,
+    pub op: Option<String>,
+    pub suci_keys: HashMap<u8, SuciKey>,
+    pub suci_routes: HashMap<String, String>,
+    pub region: String,
+    pub operator_group: String,
+}
+
+impl LocalContext {
+    /// Returns the PLMN of this network.
+    pub fn plmn(&self) -> Plmn {
+        Plmn {
+            mcc: self.mcc.clone(),
+            mnc: self.mnc.clone(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct BackupContext {
     pub backup_key_threshold: u8,
-    pub auth_states: tokio::sync::Mutex<HashMap<String, AuthState>>,
-    pub directory_network_cache: tokio::sync::Mutex<HashMap<String, (String, PublicKey)>>,
+    pub min_backup_reputation: f64,
+    pub reputation_half_life: Duration,
+    pub serving_plmns: Vec<Plmn>,
+    pub auth_state_lifetime: Duration,
+    pub backup_capacity: i64,
+    pub placement_policy: PlacementPolicy,
+    pub backup_quotas: BackupQuotas,
+    pub directory_network_cache: tokio::sync::Mutex<HashMap<String, NetworkInfo>>,
+    pub directory_key_refreshes: tokio::sync::Mutex<HashMap<String, Instant>>,
     pub directory_user_cache: tokio::sync::Mutex<HashMap<String, (String, Vec<String>)>>,
 }
 
+impl BackupContext {
+    /// Returns the number of shares needed to recover a key split into
+    /// the given number of shares, from the user's threshold if set.
+    /// Fails if there are fewer shares than the threshold, rather than
+    /// letting fewer backup networks than required recover the key.
+    pub fn key_threshold(
+        &self,
+        user_threshold: Option<u8>,
+        num_shares: u8,
+    ) -> Result<u8, DauthError> {
+        let threshold = user_threshold.unwrap_or(self.backup_key_threshold);
+        if num_shares < threshold {
+            Err(DauthError::ConfigError(format!(
+                "Key threshold {} needs more than {} share(s)",
+                threshold, num_shares
+            )))
+        } else {
+            Ok(threshold)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RpcContext {
     pub host_addr: String,
@@ -52,6 +102,18 @@ pub struct RpcContext {
     pub known_offline_networks: tokio::sync::Mutex<HashMap<String, std::time::Instant>>,
     pub local_auth_addr: String,
     pub failed_connection_retry_cooldown: std::time::Duration,
+    pub replay_window: Duration,
+    pub max_replay_cache_size: i64,
+    pub max_replay_cache_size_per_signer: i64,
+    pub tls: Option<TlsContext>,
+}
+
+/// Certificates for mutual TLS with other networks and the directory.
+#[derive(Debug)]
+pub struct TlsContext {
+    pub identity: Identity,
+    pub ca_certificate: Certificate,
+    pub directory_domain: String,
 }
 
 #[derive(Debug)]
@@ -63,6 +125,8 @@ pub struct TasksContext {
     pub replace_key_share_delay: Duration,
     pub metrics_report_interval: Duration,
     pub metrics_last_report: tokio::sync::Mutex<Instant>,
+    pub key_share_refresh_interval: Option<Duration>,
+    pub key_shares_last_refresh: tokio::sync::Mutex<Instant>,
 }
 
 #[derive(Debug)]
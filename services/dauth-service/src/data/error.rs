@@ -27,9 +27,15 @@ pub enum DauthError {
     #[error("Conversion error -- {0}")]
     AuthConversionError(#[from] auth_vector::types::AuthVectorConversionError),
 
+    #[error("Resync error -- {0}")]
+    ResyncError(#[from] auth_vector::types::ResyncError),
+
     #[error("Invalid message error -- {0}")]
     InvalidMessageError(String),
 
+    #[error("Policy error -- {0}")]
+    PolicyError(String),
+
     #[error("Invalid UTF8 error -- {0}")]
     InvalidUtf8Error(#[from] std::str::Utf8Error),
 
@@ -45,6 +51,15 @@ pub enum DauthError {
     #[error("Error while generating shamir share")]
     ShamirShareError(),
 
+    #[error("Quota exceeded error -- {0}")]
+    QuotaExceededError(String),
+
+    #[error("Invalid key share error -- {0}")]
+    InvalidShareError(String),
+
+    #[error("Peer identity error -- {0}")]
+    PeerIdentityError(String),
+
     #[error("Tonic transport error -- {0}")]
     TransportError(#[from] tonic::transport::Error),
 
@@ -1,12 +1,32 @@
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use sha2::{Digest, Sha512};
 use tracing::instrument;
 
-use auth_vector::types::{Kasme, Kseaf, XResHash, XResStarHash};
-use auth_vector::types::{KASME_LENGTH, KSEAF_LENGTH};
+use auth_vector::types::KSEAF_LENGTH;
+use auth_vector::types::{Kasme, Kseaf, Msk, XResHash, XResStarHash};
 
 use crate::data::error::DauthError;
 
-pub const TEMPORARY_CONSTANT_THRESHOLD: u8 = 3;
-const SHARE_LENGTH: usize = KSEAF_LENGTH + 1;
+/// Shares needed to recover a key, unless set for the network or user
+pub const DEFAULT_KEY_THRESHOLD: u8 = 3;
+/// Most shares a key can be split into, and so the most
+/// backups a user can have. Share indexes are a single byte.
+pub const MAX_SHARE_COUNT: u8 = 254;
+
+// Each half of a key is shared as its own scalar, since a full
+// 256 bit key does not fit in the Ristretto scalar field.
+const HALF_KEY_LENGTH: usize = KSEAF_LENGTH / 2;
+const SCALAR_LENGTH: usize = 32;
+const POINT_LENGTH: usize = 32;
+/// A share index followed by the low and high half scalars,
+/// then the blinding scalars of the low and high half
+pub const SHARE_LENGTH: usize = 1 + 4 * SCALAR_LENGTH;
+/// Hashed to the second commitment generator, whose discrete log
+/// relative to the basepoint is unknown
+const BLINDING_GENERATOR_LABEL: &[u8] = b"dAuth key share blinding generator";
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum KeyKind {
@@ -14,17 +34,144 @@ pub enum KeyKind {
     Kseaf(Kseaf),
 }
 
+/// Keys released when an EAP-AKA' vector is confirmed.
+/// The kseaf is the anchor key derived from the EMSK.
 #[derive(Debug, PartialEq, Copy, Clone)]
+pub struct EapAkaPrimeSessionKeys {
+    pub msk: Msk,
+    pub kseaf: Kseaf,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct CombinedKeyShare {
     pub kseaf_share: KseafShare,
     pub kasme_share: KasmeShare,
     pub xres_star_hash: XResStarHash,
     pub xres_hash: XResHash,
+    pub kseaf_commitment: KeyCommitment,
+    pub kasme_commitment: KeyCommitment,
+    /// Number of times the keys were split before this share's split
+    pub epoch: u32,
+}
+
+impl CombinedKeyShare {
+    /// The number of shares needed to recover the keys.
+    pub fn threshold(&self) -> u8 {
+        self.kseaf_commitment.threshold()
+    }
+
+    /// Checks both shares against the commitments of their keys,
+    /// which must share a threshold.
+    pub fn verify(&self) -> Result<(), DauthError> {
+        self.kasme_commitment
+            .check_threshold(self.kseaf_commitment.threshold().into())?;
+        if !self.kseaf_share.verify(&self.kseaf_commitment) {
+            return Err(DauthError::InvalidShareError(
+                "Kseaf share does not match commitment".to_string(),
+            ));
+        }
+        if !self.kasme_share.verify(&self.kasme_commitment) {
+            return Err(DauthError::InvalidShareError(
+                "Kasme share does not match commitment".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Pedersen commitments to the coefficients of the polynomials a key
+/// was split with, one polynomial per half of the key.
+/// Each coefficient is committed together with the matching coefficient
+/// of a random blinding polynomial, so the commitments reveal nothing
+/// about the key. Lets any holder of a share verify it without the
+/// other shares.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct KeyCommitment {
+    low: Vec<RistrettoPoint>,
+    high: Vec<RistrettoPoint>,
+}
+
+impl KeyCommitment {
+    /// The number of shares needed to recover the committed key.
+    pub fn threshold(&self) -> u8 {
+        self.low.len() as u8
+    }
+
+    /// Checks that the committed key was split with the threshold
+    /// signed alongside the commitment.
+    pub fn check_threshold(&self, threshold: u32) -> Result<(), DauthError> {
+        if u32::from(self.threshold()) == threshold {
+            Ok(())
+        } else {
+            Err(DauthError::InvalidShareError(format!(
+                "Commitment threshold {} does not match signed threshold {}",
+                self.threshold(),
+                threshold
+            )))
+        }
+    }
+
+    /// Encodes the low half coefficients followed by the high half.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.low
+            .iter()
+            .chain(self.high.iter())
+            .flat_map(|point| point.compress().to_bytes())
+            .collect()
+    }
+
+    fn check_share(&self, share: &[u8; SHARE_LENGTH]) -> bool {
+        match parse_share(share) {
+            Some(point) => {
+                let x = Scalar::from(point.index);
+                let blinding_generator = blinding_generator();
+
+                evaluate_commitment(&self.low, &x)
+                    == commit(&point.low, &point.low_blinding, &blinding_generator)
+                    && evaluate_commitment(&self.high, &x)
+                        == commit(&point.high, &point.high_blinding, &blinding_generator)
+            }
+            None => false,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for KeyCommitment {
+    type Error = DauthError;
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.is_empty() || value.len() % (2 * POINT_LENGTH) != 0 {
+            return Err(DauthError::InvalidShareError(format!(
+                "Invalid commitment length: {}",
+                value.len()
+            )));
+        }
+
+        let mut points = Vec::with_capacity(value.len() / POINT_LENGTH);
+        for chunk in value.chunks(POINT_LENGTH) {
+            points.push(
+                CompressedRistretto::from_slice(chunk)
+                    .decompress()
+                    .ok_or_else(|| {
+                        DauthError::InvalidShareError("Invalid commitment point".to_string())
+                    })?,
+            );
+        }
+
+        let high = points.split_off(points.len() / 2);
+        Ok(KeyCommitment { low: points, high })
+    }
+}
+
+impl TryFrom<Vec<u8>> for KeyCommitment {
+    type Error = DauthError;
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        value.as_slice().try_into()
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct KasmeShare {
-    pub share: [u8; KASME_LENGTH + 1],
+    pub share: [u8; SHARE_LENGTH],
 }
 
 impl KasmeShare {
@@ -34,6 +181,10 @@ impl KasmeShare {
     pub fn to_vec(self) -> Vec<u8> {
         self.share.to_vec()
     }
+    /// Checks that the share lies on the committed polynomials.
+    pub fn verify(&self, commitment: &KeyCommitment) -> bool {
+        commitment.check_share(&self.share)
+    }
 }
 
 impl TryFrom<Vec<u8>> for KasmeShare {
@@ -64,34 +215,35 @@ impl TryFrom<&[u8]> for KasmeShare {
     }
 }
 
+/// Returns the number of shares to split a key into
+/// for the given number of share holders.
+pub fn share_count(num_holders: usize) -> Result<u8, DauthError> {
+    match u8::try_from(num_holders) {
+        Ok(share_count) if share_count <= MAX_SHARE_COUNT => Ok(share_count),
+        _ => Err(DauthError::ConfigError(format!(
+            "Cannot split a key between {} share holders, at most {} are supported",
+            num_holders, MAX_SHARE_COUNT
+        ))),
+    }
+}
+
 #[instrument(level = "info")]
 pub fn create_shares_from_kasme<T: rand_0_8::RngCore + std::fmt::Debug>(
     input: &Kasme,
     share_count: u8,
     threshold_share_count: u8,
     rng: &mut T,
-) -> Result<Vec<KasmeShare>, DauthError> {
-    if threshold_share_count > share_count {
-        tracing::error!(
-            share_count,
-            threshold_share_count,
-            "Invalid share request with threshold greater than total share count."
-        );
-        return Err(DauthError::ShamirShareError());
-    }
-    let secret =
-        shamir::SecretData::with_secret_bytes(&input.as_array(), threshold_share_count, rng);
-
-    let mut shares: Vec<KasmeShare> = Vec::new();
-    for i in 1u8..share_count + 1 {
-        let share = secret.get_share(i).or_else(|e| {
-            tracing::error!(share_index = i, ?e, "Failed to get share");
-            Err(DauthError::ShamirShareError())
-        })?;
-        shares.push(share.try_into()?);
-    }
-
-    Ok(shares)
+) -> Result<(Vec<KasmeShare>, KeyCommitment), DauthError> {
+    let (shares, commitment) =
+        create_shares(&input.as_array(), share_count, threshold_share_count, rng)?;
+
+    Ok((
+        shares
+            .into_iter()
+            .map(|share| KasmeShare { share })
+            .collect(),
+        commitment,
+    ))
 }
 
 #[instrument(level = "debug")]
@@ -99,14 +251,23 @@ pub fn recover_kasme_from_shares(
     input: &Vec<KasmeShare>,
     threshold: u8,
 ) -> Result<Kasme, DauthError> {
-    let byte_inputs: Vec<Vec<u8>> = input.iter().map(|x| x.share.to_vec()).collect();
+    let shares: Vec<[u8; SHARE_LENGTH]> = input.iter().map(|x| x.share).collect();
 
-    let res = shamir::SecretData::recover_secret_bytes(threshold, byte_inputs);
+    Ok(recover_secret(&shares, threshold)?.to_vec().try_into()?)
+}
 
-    match res {
-        Some(value) => Ok(value.try_into().unwrap()),
-        None => Err(DauthError::ShamirShareError()),
-    }
+/// Recovers the kasme from any threshold of the shares
+/// that match the commitment.
+#[instrument(level = "debug")]
+pub fn recover_kasme_with_commitment(
+    input: &Vec<KasmeShare>,
+    commitment: &KeyCommitment,
+) -> Result<Kasme, DauthError> {
+    let shares: Vec<[u8; SHARE_LENGTH]> = input.iter().map(|x| x.share).collect();
+
+    Ok(recover_secret_with_commitment(&shares, commitment)?
+        .to_vec()
+        .try_into()?)
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -121,6 +282,10 @@ impl KseafShare {
     pub fn to_vec(self) -> Vec<u8> {
         self.share.to_vec()
     }
+    /// Checks that the share lies on the committed polynomials.
+    pub fn verify(&self, commitment: &KeyCommitment) -> bool {
+        commitment.check_share(&self.share)
+    }
 }
 
 impl TryFrom<Vec<u8>> for KseafShare {
@@ -157,8 +322,54 @@ pub fn create_shares_from_kseaf<T: rand_0_8::RngCore + std::fmt::Debug>(
     share_count: u8,
     threshold_share_count: u8,
     rng: &mut T,
-) -> Result<Vec<KseafShare>, DauthError> {
-    if threshold_share_count > share_count {
+) -> Result<(Vec<KseafShare>, KeyCommitment), DauthError> {
+    let (shares, commitment) = create_shares(input, share_count, threshold_share_count, rng)?;
+
+    Ok((
+        shares
+            .into_iter()
+            .map(|share| KseafShare { share })
+            .collect(),
+        commitment,
+    ))
+}
+
+#[instrument(level = "debug")]
+pub fn recover_kseaf_from_shares(
+    input: &Vec<KseafShare>,
+    threshold: u8,
+) -> Result<Kseaf, DauthError> {
+    let shares: Vec<[u8; SHARE_LENGTH]> = input.iter().map(|x| x.share).collect();
+
+    recover_secret(&shares, threshold)
+}
+
+/// Recovers the kseaf from any threshold of the shares
+/// that match the commitment.
+#[instrument(level = "debug")]
+pub fn recover_kseaf_with_commitment(
+    input: &Vec<KseafShare>,
+    commitment: &KeyCommitment,
+) -> Result<Kseaf, DauthError> {
+    let shares: Vec<[u8; SHARE_LENGTH]> = input.iter().map(|x| x.share).collect();
+
+    recover_secret_with_commitment(&shares, commitment)
+}
+
+/// Splits a key with Pedersen verifiable secret sharing over the
+/// Ristretto group. Each half of the key is the constant term of
+/// its own random polynomial of degree threshold - 1, blinded by
+/// a second fully random polynomial of the same degree.
+fn create_shares<T: rand_0_8::RngCore>(
+    secret: &[u8; KSEAF_LENGTH],
+    share_count: u8,
+    threshold_share_count: u8,
+    rng: &mut T,
+) -> Result<(Vec<[u8; SHARE_LENGTH]>, KeyCommitment), DauthError> {
+    if threshold_share_count > share_count
+        || threshold_share_count == 0
+        || share_count > MAX_SHARE_COUNT
+    {
         tracing::error!(
             share_count,
             threshold_share_count,
@@ -166,33 +377,215 @@ pub fn create_shares_from_kseaf<T: rand_0_8::RngCore + std::fmt::Debug>(
         );
         return Err(DauthError::ShamirShareError());
     }
-    let secret = shamir::SecretData::with_secret_bytes(input, threshold_share_count, rng);
 
-    let mut shares: Vec<KseafShare> = Vec::new();
-    for i in 1u8..share_count + 1 {
-        let share = secret.get_share(i).or_else(|e| {
-            tracing::error!(share_index = i, ?e, "Failed to get share");
-            Err(DauthError::ShamirShareError())
-        })?;
-        shares.push(share.try_into()?);
+    let (low, high) = split_secret(secret);
+    let low = random_polynomial(low, threshold_share_count, rng);
+    let high = random_polynomial(high, threshold_share_count, rng);
+    let low_blinding = random_polynomial(random_scalar(rng), threshold_share_count, rng);
+    let high_blinding = random_polynomial(random_scalar(rng), threshold_share_count, rng);
+
+    let mut shares = Vec::with_capacity(share_count.into());
+    for index in 1u8..=share_count {
+        let x = Scalar::from(index);
+
+        let mut share = [0u8; SHARE_LENGTH];
+        share[0] = index;
+        for (i, polynomial) in [&low, &high, &low_blinding, &high_blinding]
+            .iter()
+            .enumerate()
+        {
+            let offset = 1 + i * SCALAR_LENGTH;
+            share[offset..offset + SCALAR_LENGTH]
+                .copy_from_slice(evaluate_polynomial(polynomial, &x).as_bytes());
+        }
+        shares.push(share);
     }
 
-    Ok(shares)
+    let blinding_generator = blinding_generator();
+    let commitment = KeyCommitment {
+        low: low
+            .iter()
+            .zip(low_blinding.iter())
+            .map(|(coefficient, blinding)| commit(coefficient, blinding, &blinding_generator))
+            .collect(),
+        high: high
+            .iter()
+            .zip(high_blinding.iter())
+            .map(|(coefficient, blinding)| commit(coefficient, blinding, &blinding_generator))
+            .collect(),
+    };
+
+    Ok((shares, commitment))
 }
 
-#[instrument(level = "debug")]
-pub fn recover_kseaf_from_shares(
-    input: &Vec<KseafShare>,
+/// Recovers a key by Lagrange interpolation of the first threshold shares.
+/// Fails on duplicate indices, or if a recovered half is too large to
+/// be a key half, which a corrupted share almost always causes.
+fn recover_secret(
+    shares: &[[u8; SHARE_LENGTH]],
     threshold: u8,
-) -> Result<Kseaf, DauthError> {
-    let byte_inputs: Vec<Vec<u8>> = input.iter().map(|x| x.share.to_vec()).collect();
+) -> Result<[u8; KSEAF_LENGTH], DauthError> {
+    if threshold == 0 || shares.len() < threshold.into() {
+        return Err(DauthError::ShamirShareError());
+    }
+
+    let points = shares[..threshold as usize]
+        .iter()
+        .map(parse_share)
+        .collect::<Option<Vec<SharePoint>>>()
+        .ok_or(DauthError::ShamirShareError())?;
+
+    let mut low = Scalar::zero();
+    let mut high = Scalar::zero();
+    for (i, point_i) in points.iter().enumerate() {
+        let x_i = Scalar::from(point_i.index);
+
+        let mut numerator = Scalar::one();
+        let mut denominator = Scalar::one();
+        for (j, point_j) in points.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            if point_i.index == point_j.index {
+                return Err(DauthError::ShamirShareError());
+            }
+            let x_j = Scalar::from(point_j.index);
+            numerator *= x_j;
+            denominator *= x_j - x_i;
+        }
+
+        let lagrange_coefficient = numerator * denominator.invert();
+        low += lagrange_coefficient * point_i.low;
+        high += lagrange_coefficient * point_i.high;
+    }
 
-    let res = shamir::SecretData::recover_secret_bytes(threshold, byte_inputs);
+    let (low, high) = (low.to_bytes(), high.to_bytes());
+    if low[HALF_KEY_LENGTH..].iter().any(|b| *b != 0)
+        || high[HALF_KEY_LENGTH..].iter().any(|b| *b != 0)
+    {
+        return Err(DauthError::ShamirShareError());
+    }
+
+    let mut secret = [0u8; KSEAF_LENGTH];
+    secret[..HALF_KEY_LENGTH].copy_from_slice(&low[..HALF_KEY_LENGTH]);
+    secret[HALF_KEY_LENGTH..].copy_from_slice(&high[..HALF_KEY_LENGTH]);
+    Ok(secret)
+}
 
-    match res {
-        Some(value) => Ok(value.try_into().unwrap()),
-        None => Err(DauthError::ShamirShareError()),
+/// Recovers the committed key from the first threshold shares with
+/// distinct indices that verify against the commitment. Verified shares
+/// lie on the committed polynomials, so any threshold of them recover
+/// the committed key, and shares from faulty backups are skipped.
+fn recover_secret_with_commitment(
+    shares: &[[u8; SHARE_LENGTH]],
+    commitment: &KeyCommitment,
+) -> Result<[u8; KSEAF_LENGTH], DauthError> {
+    let mut verified_shares: Vec<[u8; SHARE_LENGTH]> = Vec::new();
+    for share in shares {
+        if commitment.check_share(share)
+            && verified_shares
+                .iter()
+                .all(|verified_share| verified_share[0] != share[0])
+        {
+            verified_shares.push(*share);
+        }
     }
+
+    recover_secret(&verified_shares, commitment.threshold())
+}
+
+/// Splits a key into its low and high half scalars.
+fn split_secret(secret: &[u8; KSEAF_LENGTH]) -> (Scalar, Scalar) {
+    let mut low = [0u8; SCALAR_LENGTH];
+    low[..HALF_KEY_LENGTH].copy_from_slice(&secret[..HALF_KEY_LENGTH]);
+    let mut high = [0u8; SCALAR_LENGTH];
+    high[..HALF_KEY_LENGTH].copy_from_slice(&secret[HALF_KEY_LENGTH..]);
+
+    (
+        Scalar::from_bytes_mod_order(low),
+        Scalar::from_bytes_mod_order(high),
+    )
+}
+
+/// Returns a polynomial with the provided constant term
+/// and uniformly random higher coefficients.
+fn random_polynomial<T: rand_0_8::RngCore>(
+    constant: Scalar,
+    threshold: u8,
+    rng: &mut T,
+) -> Vec<Scalar> {
+    let mut coefficients = vec![constant];
+    for _ in 1..threshold {
+        coefficients.push(random_scalar(rng));
+    }
+    coefficients
+}
+
+fn random_scalar<T: rand_0_8::RngCore>(rng: &mut T) -> Scalar {
+    let mut wide = [0u8; 2 * SCALAR_LENGTH];
+    rng.fill_bytes(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn evaluate_polynomial(coefficients: &[Scalar], x: &Scalar) -> Scalar {
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient)
+}
+
+fn evaluate_commitment(points: &[RistrettoPoint], x: &Scalar) -> RistrettoPoint {
+    points
+        .iter()
+        .rev()
+        .fold(RistrettoPoint::identity(), |acc, point| acc * x + point)
+}
+
+/// Returns the second generator of the commitments.
+fn blinding_generator() -> RistrettoPoint {
+    let mut uniform_bytes = [0u8; 64];
+    uniform_bytes.copy_from_slice(&Sha512::digest(BLINDING_GENERATOR_LABEL));
+    RistrettoPoint::from_uniform_bytes(&uniform_bytes)
+}
+
+/// Commits to the value, hidden by the blinding value.
+fn commit(
+    value: &Scalar,
+    blinding: &Scalar,
+    blinding_generator: &RistrettoPoint,
+) -> RistrettoPoint {
+    value * RISTRETTO_BASEPOINT_POINT + blinding * blinding_generator
+}
+
+/// A share split into its index, half scalars, and blinding scalars.
+struct SharePoint {
+    index: u8,
+    low: Scalar,
+    high: Scalar,
+    low_blinding: Scalar,
+    high_blinding: Scalar,
+}
+
+/// Splits a share into its index and scalars.
+/// Rejects the zero index, which would reveal the key,
+/// and scalars that are not canonically encoded.
+fn parse_share(share: &[u8; SHARE_LENGTH]) -> Option<SharePoint> {
+    if share[0] == 0 {
+        return None;
+    }
+
+    let scalar = |i: usize| {
+        let offset = 1 + i * SCALAR_LENGTH;
+        Scalar::from_canonical_bytes(share[offset..offset + SCALAR_LENGTH].try_into().ok()?)
+    };
+
+    Some(SharePoint {
+        index: share[0],
+        low: scalar(0)?,
+        high: scalar(1)?,
+        low_blinding: scalar(2)?,
+        high_blinding: scalar(3)?,
+    })
 }
 
 #[cfg(test)]
@@ -210,7 +603,7 @@ mod tests {
                 .unwrap()
                 .try_into()
                 .unwrap();
-        let res = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+        let (res, _) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
 
         let res_kseaf: Kseaf = recover_kseaf_from_shares(&res, 3).unwrap();
         assert_eq!(kseaf, res_kseaf);
@@ -224,7 +617,7 @@ mod tests {
                 .unwrap()
                 .try_into()
                 .unwrap();
-        let mut res = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+        let (mut res, _) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
 
         // Remove two arbitrary shares of the 5.
         res.remove(2);
@@ -243,31 +636,132 @@ mod tests {
                 .unwrap()
                 .try_into()
                 .unwrap();
-        let mut res = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+        let (mut res, _) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
         println!("{:?}", res);
 
-        // Corrupt one of the shares, technically possible for the test to fail
-        // since the rand is non-deterministic, but should be cryptographically
-        // rare.
-        res[1] = KseafShare {
-            share: [
-                2, 102, 144, 138, 212, 69, 222, 118, 11, 242, 176, 156, 89, 234, 255, 82, 79, 102,
-                144, 138, 212, 69, 222, 118, 11, 242, 176, 156, 89, 234, 255, 82, 79,
-            ],
-        };
-
-        assert_ne!(kseaf, recover_kseaf_from_shares(&res, 3).unwrap());
-
-        // Corrupt a share index, should always cause an error.
-        res[2] = KseafShare {
-            share: [
-                2, 102, 144, 138, 212, 69, 222, 118, 11, 242, 176, 156, 89, 234, 255, 82, 79, 102,
-                144, 138, 212, 69, 222, 118, 11, 242, 176, 156, 89, 234, 255, 82, 79,
-            ],
-        };
+        // Corrupt one of the shares, which should never recover the key.
+        res[1].share[5] ^= 0x01;
+        assert!(recover_kseaf_from_shares(&res, 3)
+            .map(|res_kseaf| res_kseaf != kseaf)
+            .unwrap_or(true));
+
+        // Duplicate a share index, should always cause an error.
+        res[2].share[0] = res[0].share[0];
         recover_kseaf_from_shares(&res, 3).unwrap();
     }
 
+    #[test]
+    fn test_max_share_count() {
+        let mut rng = rand_0_8::thread_rng();
+        let kseaf: Kseaf = [0x5a; KSEAF_LENGTH];
+
+        assert_eq!(share_count(0).unwrap(), 0);
+        assert_eq!(share_count(254).unwrap(), MAX_SHARE_COUNT);
+        assert!(share_count(255).is_err());
+        assert!(share_count(256).is_err());
+
+        let (res, _) = create_shares_from_kseaf(&kseaf, MAX_SHARE_COUNT, 3, &mut rng).unwrap();
+        assert_eq!(res.len(), usize::from(MAX_SHARE_COUNT));
+        assert_eq!(res.last().unwrap().share[0], MAX_SHARE_COUNT);
+        assert_eq!(
+            recover_kseaf_from_shares(&res[..3].to_vec(), 3).unwrap(),
+            kseaf
+        );
+
+        assert!(create_shares_from_kseaf(&kseaf, 255, 3, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_recovery_with_commitment() {
+        let mut rng = rand_0_8::thread_rng();
+        let kseaf: Kseaf = [7; KSEAF_LENGTH];
+        let (mut res, commitment) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+
+        // Corrupt two of the five shares, leaving three correct shares.
+        res[0].share[SHARE_LENGTH - 1] ^= 0x01;
+        res[3].share[1] ^= 0x01;
+
+        assert_eq!(
+            kseaf,
+            recover_kseaf_with_commitment(&res, &commitment).unwrap()
+        );
+
+        // Too few correct shares remain after corrupting a third.
+        res[4].share[1] ^= 0x01;
+        assert!(recover_kseaf_with_commitment(&res, &commitment).is_err());
+    }
+
+    #[test]
+    fn test_kasme_recovery_with_commitment() {
+        let mut rng = rand_0_8::thread_rng();
+        let kasme: Kasme = vec![9; KSEAF_LENGTH].try_into().unwrap();
+        let (mut res, commitment) = create_shares_from_kasme(&kasme, 4, 2, &mut rng).unwrap();
+
+        res[1].share[0] = res[0].share[0];
+        res.remove(0);
+        assert_eq!(
+            kasme,
+            recover_kasme_with_commitment(&res, &commitment).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_share_verification() {
+        let mut rng = rand_0_8::thread_rng();
+        let (mut res, commitment) =
+            create_shares_from_kseaf(&[7; KSEAF_LENGTH], 5, 3, &mut rng).unwrap();
+        let (_, other_commitment) =
+            create_shares_from_kseaf(&[8; KSEAF_LENGTH], 5, 3, &mut rng).unwrap();
+
+        assert_eq!(3, commitment.threshold());
+        assert!(commitment.check_threshold(3).is_ok());
+        assert!(commitment.check_threshold(2).is_err());
+        for share in &res {
+            assert!(share.verify(&commitment));
+            assert!(!share.verify(&other_commitment));
+        }
+
+        // Corrupt a share value and a share index
+        res[0].share[SHARE_LENGTH - 1] ^= 0x01;
+        assert!(!res[0].verify(&commitment));
+        res[1].share[0] = 3;
+        assert!(!res[1].verify(&commitment));
+        res[2].share[0] = 0;
+        assert!(!res[2].verify(&commitment));
+    }
+
+    #[test]
+    fn test_commitment_hides_key() {
+        let mut rng = rand_0_8::thread_rng();
+        let kseaf: Kseaf = [7; KSEAF_LENGTH];
+        let (_, commitment) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+        let (_, other_commitment) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+
+        // The committed constant terms are blinded, so they are
+        // neither the key halves times the basepoint nor repeated
+        let (low, high) = split_secret(&kseaf);
+        assert_ne!(commitment.low[0], &low * &RISTRETTO_BASEPOINT_POINT);
+        assert_ne!(commitment.high[0], &high * &RISTRETTO_BASEPOINT_POINT);
+        assert_ne!(commitment.low[0], other_commitment.low[0]);
+    }
+
+    #[test]
+    fn test_commitment_encoding() {
+        let mut rng = rand_0_8::thread_rng();
+        let (_, commitment) = create_shares_from_kseaf(&[7; KSEAF_LENGTH], 5, 3, &mut rng).unwrap();
+
+        let bytes = commitment.to_vec();
+        assert_eq!(3 * 2 * POINT_LENGTH, bytes.len());
+        assert_eq!(
+            commitment,
+            KeyCommitment::try_from(bytes.as_slice()).unwrap()
+        );
+
+        assert!(KeyCommitment::try_from(&bytes[1..]).is_err());
+        assert!(KeyCommitment::try_from(Vec::new()).is_err());
+        assert!(KeyCommitment::try_from(vec![0xff; 2 * POINT_LENGTH]).is_err());
+    }
+
     #[test]
     fn test_vector_creation_deterministic() {
         let mut rng = rand_0_8::rngs::mock::StepRng::new(1, 1);
@@ -276,38 +770,68 @@ mod tests {
                 .unwrap()
                 .try_into()
                 .unwrap();
-        let res = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
+        let (res, _) = create_shares_from_kseaf(&kseaf, 5, 3, &mut rng).unwrap();
         println!("{:?}", res);
 
         let expected_res = vec![
             KseafShare {
                 share: [
-                    1, 87, 47, 114, 105, 184, 3, 140, 79, 85, 244, 198, 183, 69, 227, 12, 159, 71,
-                    63, 98, 121, 168, 19, 156, 95, 69, 228, 214, 167, 85, 243, 28, 175,
+                    1, 201, 151, 136, 137, 36, 175, 197, 246, 204, 150, 79, 182, 190, 148, 231,
+                    212, 25, 4, 7, 29, 8, 128, 93, 166, 229, 117, 202, 122, 84, 198, 211, 1, 28,
+                    39, 214, 168, 76, 125, 67, 227, 113, 128, 185, 189, 62, 205, 52, 84, 80, 197,
+                    56, 200, 119, 177, 203, 54, 98, 239, 232, 159, 84, 212, 25, 15, 13, 129, 62,
+                    14, 74, 131, 217, 41, 238, 171, 121, 246, 231, 70, 32, 170, 61, 114, 82, 13,
+                    229, 38, 0, 225, 220, 138, 118, 53, 191, 24, 138, 13, 169, 208, 254, 49, 182,
+                    101, 212, 35, 42, 167, 246, 105, 194, 208, 7, 36, 248, 100, 130, 78, 32, 22,
+                    248, 37, 53, 28, 251, 136, 63, 184, 103, 15,
                 ],
             },
             KseafShare {
                 share: [
-                    2, 84, 41, 119, 101, 183, 9, 133, 87, 78, 234, 219, 163, 82, 241, 29, 175, 116,
-                    9, 87, 69, 151, 41, 165, 119, 110, 202, 251, 131, 114, 209, 61, 207,
+                    2, 127, 69, 200, 96, 67, 14, 95, 38, 77, 41, 235, 85, 66, 240, 185, 5, 154,
+                    124, 225, 65, 116, 12, 52, 23, 16, 0, 167, 121, 125, 214, 204, 12, 177, 119,
+                    207, 167, 108, 79, 161, 227, 184, 15, 66, 131, 38, 172, 4, 69, 61, 192, 118,
+                    67, 195, 160, 126, 200, 133, 108, 2, 233, 125, 0, 159, 4, 193, 215, 52, 177,
+                    173, 249, 139, 63, 119, 190, 80, 140, 146, 219, 25, 239, 205, 176, 37, 138,
+                    127, 170, 253, 35, 118, 116, 235, 237, 232, 110, 225, 10, 143, 89, 252, 27, 67,
+                    29, 201, 246, 30, 160, 36, 45, 81, 33, 76, 168, 43, 231, 234, 204, 9, 46, 64,
+                    26, 68, 114, 203, 176, 105, 56, 145, 4,
                 ],
             },
             KseafShare {
                 share: [
-                    3, 85, 43, 116, 97, 178, 15, 130, 95, 71, 224, 208, 175, 95, 255, 18, 191, 101,
-                    27, 68, 81, 130, 63, 178, 111, 119, 208, 224, 159, 111, 207, 34, 239,
+                    3, 158, 142, 68, 57, 229, 92, 50, 38, 48, 124, 177, 84, 22, 12, 189, 247, 128,
+                    105, 143, 110, 68, 165, 131, 82, 127, 158, 149, 252, 122, 48, 235, 0, 239, 198,
+                    72, 36, 82, 66, 201, 248, 221, 229, 86, 82, 189, 125, 49, 139, 199, 240, 185,
+                    113, 226, 205, 24, 181, 106, 119, 76, 219, 123, 132, 143, 0, 55, 198, 46, 117,
+                    107, 177, 169, 72, 20, 139, 95, 66, 222, 192, 21, 41, 205, 198, 200, 58, 21,
+                    182, 135, 154, 11, 162, 62, 135, 231, 49, 141, 10, 61, 231, 118, 39, 217, 153,
+                    220, 243, 51, 99, 45, 202, 81, 26, 23, 214, 159, 226, 237, 255, 21, 24, 186,
+                    26, 138, 194, 39, 241, 104, 58, 248, 7,
                 ],
             },
             KseafShare {
                 share: [
-                    4, 82, 37, 125, 125, 169, 29, 151, 103, 120, 214, 225, 139, 124, 213, 63, 207,
-                    18, 101, 61, 61, 233, 93, 215, 39, 56, 150, 161, 203, 60, 149, 127, 15,
+                    4, 237, 238, 222, 41, 89, 196, 118, 254, 248, 101, 137, 155, 214, 213, 141,
+                    233, 206, 202, 16, 163, 120, 74, 76, 88, 51, 81, 150, 3, 77, 212, 46, 14, 214,
+                    20, 66, 30, 253, 85, 187, 34, 225, 2, 248, 42, 3, 66, 187, 38, 239, 86, 2, 83,
+                    213, 56, 154, 252, 16, 16, 199, 118, 78, 96, 235, 2, 111, 76, 44, 90, 131, 170,
+                    50, 69, 197, 17, 166, 24, 203, 246, 19, 88, 59, 180, 59, 31, 166, 73, 158, 68,
+                    157, 19, 112, 1, 187, 97, 141, 12, 198, 165, 120, 247, 93, 120, 252, 194, 146,
+                    83, 25, 158, 229, 193, 137, 152, 84, 87, 139, 231, 68, 212, 101, 39, 7, 13, 16,
+                    74, 61, 190, 156, 9,
                 ],
             },
             KseafShare {
                 share: [
-                    5, 83, 39, 126, 121, 172, 27, 144, 111, 113, 220, 234, 135, 113, 219, 48, 223,
-                    3, 119, 46, 41, 252, 75, 192, 63, 33, 140, 186, 215, 33, 139, 96, 47,
+                    5, 165, 234, 181, 27, 80, 27, 245, 166, 36, 16, 140, 65, 231, 95, 143, 156,
+                    131, 160, 101, 223, 16, 252, 141, 40, 44, 24, 169, 142, 243, 193, 151, 4, 102,
+                    97, 187, 149, 109, 138, 119, 97, 194, 102, 37, 13, 248, 248, 161, 23, 180, 242,
+                    79, 231, 155, 225, 2, 159, 120, 54, 114, 187, 245, 147, 178, 11, 124, 150, 55,
+                    3, 219, 129, 20, 221, 179, 181, 44, 108, 122, 131, 53, 103, 24, 121, 126, 55,
+                    50, 101, 65, 34, 43, 201, 127, 92, 99, 254, 225, 0, 42, 149, 1, 140, 209, 184,
+                    40, 100, 59, 113, 232, 168, 12, 24, 164, 239, 73, 69, 195, 131, 150, 98, 67,
+                    64, 187, 81, 132, 187, 230, 195, 126, 9,
                 ],
             },
         ];
@@ -325,38 +849,68 @@ mod tests {
                 .unwrap()
                 .try_into()
                 .unwrap();
-        let res = create_shares_from_kasme(&kasme, 5, 3, &mut rng).unwrap();
+        let (res, _) = create_shares_from_kasme(&kasme, 5, 3, &mut rng).unwrap();
         println!("{:?}", res);
 
         let expected_res = vec![
             KasmeShare {
                 share: [
-                    1, 87, 47, 114, 105, 184, 3, 140, 79, 85, 244, 198, 183, 69, 227, 12, 159, 71,
-                    63, 98, 121, 168, 19, 156, 95, 69, 228, 214, 167, 85, 243, 28, 175,
+                    1, 201, 151, 136, 137, 36, 175, 197, 246, 204, 150, 79, 182, 190, 148, 231,
+                    212, 25, 4, 7, 29, 8, 128, 93, 166, 229, 117, 202, 122, 84, 198, 211, 1, 28,
+                    39, 214, 168, 76, 125, 67, 227, 113, 128, 185, 189, 62, 205, 52, 84, 80, 197,
+                    56, 200, 119, 177, 203, 54, 98, 239, 232, 159, 84, 212, 25, 15, 13, 129, 62,
+                    14, 74, 131, 217, 41, 238, 171, 121, 246, 231, 70, 32, 170, 61, 114, 82, 13,
+                    229, 38, 0, 225, 220, 138, 118, 53, 191, 24, 138, 13, 169, 208, 254, 49, 182,
+                    101, 212, 35, 42, 167, 246, 105, 194, 208, 7, 36, 248, 100, 130, 78, 32, 22,
+                    248, 37, 53, 28, 251, 136, 63, 184, 103, 15,
                 ],
             },
             KasmeShare {
                 share: [
-                    2, 84, 41, 119, 101, 183, 9, 133, 87, 78, 234, 219, 163, 82, 241, 29, 175, 116,
-                    9, 87, 69, 151, 41, 165, 119, 110, 202, 251, 131, 114, 209, 61, 207,
+                    2, 127, 69, 200, 96, 67, 14, 95, 38, 77, 41, 235, 85, 66, 240, 185, 5, 154,
+                    124, 225, 65, 116, 12, 52, 23, 16, 0, 167, 121, 125, 214, 204, 12, 177, 119,
+                    207, 167, 108, 79, 161, 227, 184, 15, 66, 131, 38, 172, 4, 69, 61, 192, 118,
+                    67, 195, 160, 126, 200, 133, 108, 2, 233, 125, 0, 159, 4, 193, 215, 52, 177,
+                    173, 249, 139, 63, 119, 190, 80, 140, 146, 219, 25, 239, 205, 176, 37, 138,
+                    127, 170, 253, 35, 118, 116, 235, 237, 232, 110, 225, 10, 143, 89, 252, 27, 67,
+                    29, 201, 246, 30, 160, 36, 45, 81, 33, 76, 168, 43, 231, 234, 204, 9, 46, 64,
+                    26, 68, 114, 203, 176, 105, 56, 145, 4,
                 ],
             },
             KasmeShare {
                 share: [
-                    3, 85, 43, 116, 97, 178, 15, 130, 95, 71, 224, 208, 175, 95, 255, 18, 191, 101,
-                    27, 68, 81, 130, 63, 178, 111, 119, 208, 224, 159, 111, 207, 34, 239,
+                    3, 158, 142, 68, 57, 229, 92, 50, 38, 48, 124, 177, 84, 22, 12, 189, 247, 128,
+                    105, 143, 110, 68, 165, 131, 82, 127, 158, 149, 252, 122, 48, 235, 0, 239, 198,
+                    72, 36, 82, 66, 201, 248, 221, 229, 86, 82, 189, 125, 49, 139, 199, 240, 185,
+                    113, 226, 205, 24, 181, 106, 119, 76, 219, 123, 132, 143, 0, 55, 198, 46, 117,
+                    107, 177, 169, 72, 20, 139, 95, 66, 222, 192, 21, 41, 205, 198, 200, 58, 21,
+                    182, 135, 154, 11, 162, 62, 135, 231, 49, 141, 10, 61, 231, 118, 39, 217, 153,
+                    220, 243, 51, 99, 45, 202, 81, 26, 23, 214, 159, 226, 237, 255, 21, 24, 186,
+                    26, 138, 194, 39, 241, 104, 58, 248, 7,
                 ],
             },
             KasmeShare {
                 share: [
-                    4, 82, 37, 125, 125, 169, 29, 151, 103, 120, 214, 225, 139, 124, 213, 63, 207,
-                    18, 101, 61, 61, 233, 93, 215, 39, 56, 150, 161, 203, 60, 149, 127, 15,
+                    4, 237, 238, 222, 41, 89, 196, 118, 254, 248, 101, 137, 155, 214, 213, 141,
+                    233, 206, 202, 16, 163, 120, 74, 76, 88, 51, 81, 150, 3, 77, 212, 46, 14, 214,
+                    20, 66, 30, 253, 85, 187, 34, 225, 2, 248, 42, 3, 66, 187, 38, 239, 86, 2, 83,
+                    213, 56, 154, 252, 16, 16, 199, 118, 78, 96, 235, 2, 111, 76, 44, 90, 131, 170,
+                    50, 69, 197, 17, 166, 24, 203, 246, 19, 88, 59, 180, 59, 31, 166, 73, 158, 68,
+                    157, 19, 112, 1, 187, 97, 141, 12, 198, 165, 120, 247, 93, 120, 252, 194, 146,
+                    83, 25, 158, 229, 193, 137, 152, 84, 87, 139, 231, 68, 212, 101, 39, 7, 13, 16,
+                    74, 61, 190, 156, 9,
                 ],
             },
             KasmeShare {
                 share: [
-                    5, 83, 39, 126, 121, 172, 27, 144, 111, 113, 220, 234, 135, 113, 219, 48, 223,
-                    3, 119, 46, 41, 252, 75, 192, 63, 33, 140, 186, 215, 33, 139, 96, 47,
+                    5, 165, 234, 181, 27, 80, 27, 245, 166, 36, 16, 140, 65, 231, 95, 143, 156,
+                    131, 160, 101, 223, 16, 252, 141, 40, 44, 24, 169, 142, 243, 193, 151, 4, 102,
+                    97, 187, 149, 109, 138, 119, 97, 194, 102, 37, 13, 248, 248, 161, 23, 180, 242,
+                    79, 231, 155, 225, 2, 159, 120, 54, 114, 187, 245, 147, 178, 11, 124, 150, 55,
+                    3, 219, 129, 20, 221, 179, 181, 44, 108, 122, 131, 53, 103, 24, 121, 126, 55,
+                    50, 101, 65, 34, 43, 201, 127, 92, 99, 254, 225, 0, 42, 149, 1, 140, 209, 184,
+                    40, 100, 59, 113, 232, 168, 12, 24, 164, 239, 73, 69, 195, 131, 150, 98, 67,
+                    64, 187, 81, 132, 187, 230, 195, 126, 9,
                 ],
             },
         ];
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use crate::data::error::DauthError;
+
+/// A kind of misbehaviour by another network that this network
+/// can prove with a stored message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MisbehaviourKind {
+    /// A backup network returned a key share that failed verification.
+    InvalidShare,
+    /// A backup network issued a vector it had already issued.
+    OverIssuedVector,
+    /// A backup network reported a vector or key share as used
+    /// without proof that it was used.
+    UnprovenConsumption,
+}
+
+impl MisbehaviourKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MisbehaviourKind::InvalidShare => "invalid_share",
+            MisbehaviourKind::OverIssuedVector => "over_issued_vector",
+            MisbehaviourKind::UnprovenConsumption => "unproven_consumption",
+        }
+    }
+
+    /// The amount a single occurrence lowers the reputation of a network.
+    /// Reissuing a vector breaks the one-time use of the vector,
+    /// so it weighs more than faults that only cost availability.
+    pub fn weight(&self) -> f64 {
+        match self {
+            MisbehaviourKind::InvalidShare => 1.0,
+            MisbehaviourKind::OverIssuedVector => 2.0,
+            MisbehaviourKind::UnprovenConsumption => 1.0,
+        }
+    }
+}
+
+impl TryFrom<&str> for MisbehaviourKind {
+    type Error = DauthError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "invalid_share" => Ok(MisbehaviourKind::InvalidShare),
+            "over_issued_vector" => Ok(MisbehaviourKind::OverIssuedVector),
+            "unproven_consumption" => Ok(MisbehaviourKind::UnprovenConsumption),
+            _ => Err(DauthError::DataError(format!(
+                "Unknown misbehaviour kind: {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// A recorded misbehaviour of a network.
+/// The evidence is the encoded message that proves the misbehaviour,
+/// as received from or signed by the offending network, or an encoded
+/// `SignedMessageSet` when it takes more than one message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misbehaviour {
+    pub network_id: String,
+    pub kind: MisbehaviourKind,
+    pub detail: String,
+    pub evidence: Vec<u8>,
+    pub timestamp: i64,
+}
+
+/// Computes the reputation of a network from its recorded misbehaviour.
+/// The score is 1.0 for a network with no misbehaviour, and falls
+/// towards 0.0 as weighted misbehaviour accumulates.
+/// The weight of each record halves every half life since it was
+/// recorded, so a network recovers once it stops misbehaving.
+pub fn reputation_score(records: &[Misbehaviour], now: i64, half_life: Duration) -> f64 {
+    let total: f64 = records
+        .iter()
+        .map(|record| {
+            let age = (now - record.timestamp).max(0) as f64;
+            record.kind.weight() * 0.5_f64.powf(age / half_life.as_secs_f64())
+        })
+        .sum();
+    1.0 / (1.0 + total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_LIFE: Duration = Duration::from_secs(100);
+
+    fn gen_record(kind: MisbehaviourKind, timestamp: i64) -> Misbehaviour {
+        Misbehaviour {
+            network_id: "test_network".to_string(),
+            kind,
+            detail: "test detail".to_string(),
+            evidence: vec![1, 2, 3],
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_kind_conversion() {
+        for kind in [
+            MisbehaviourKind::InvalidShare,
+            MisbehaviourKind::OverIssuedVector,
+            MisbehaviourKind::UnprovenConsumption,
+        ] {
+            assert_eq!(kind, MisbehaviourKind::try_from(kind.as_str()).unwrap());
+        }
+        assert!(MisbehaviourKind::try_from("unknown").is_err());
+    }
+
+    #[test]
+    fn test_reputation_score() {
+        assert_eq!(1.0, reputation_score(&[], 0, HALF_LIFE));
+        assert_eq!(
+            0.5,
+            reputation_score(
+                &[gen_record(MisbehaviourKind::InvalidShare, 0)],
+                0,
+                HALF_LIFE
+            )
+        );
+        assert_eq!(
+            0.25,
+            reputation_score(
+                &[
+                    gen_record(MisbehaviourKind::OverIssuedVector, 0),
+                    gen_record(MisbehaviourKind::UnprovenConsumption, 0),
+                ],
+                0,
+                HALF_LIFE
+            )
+        );
+    }
+
+    #[test]
+    fn test_reputation_score_decays() {
+        let records = [gen_record(MisbehaviourKind::OverIssuedVector, 1000)];
+
+        assert_eq!(1.0 / 3.0, reputation_score(&records, 1000, HALF_LIFE));
+        assert_eq!(0.5, reputation_score(&records, 1100, HALF_LIFE));
+        assert_eq!(0.8, reputation_score(&records, 1300, HALF_LIFE));
+        assert!(reputation_score(&records, 10000, HALF_LIFE) > 0.99);
+    }
+}
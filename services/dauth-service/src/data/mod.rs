@@ -3,9 +3,14 @@ pub mod config;
 pub mod context;
 pub mod error;
 pub mod keys;
+pub mod misbehaviour;
 pub mod opt;
+pub mod placement;
+pub mod policy;
+pub mod quota;
 pub mod signing;
 pub mod state;
+pub mod suci;
 pub mod user_info;
 pub mod utilities;
 pub mod vector;
@@ -0,0 +1,290 @@
+use std::cmp::Ordering;
+
+/// Restricts which networks may be selected automatically
+/// as backup networks, and how they are spread out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlacementPolicy {
+    /// No two backups of a user share an operator group
+    pub distinct_operator_groups: bool,
+    /// No two backups of a user share a region
+    pub distinct_regions: bool,
+    /// Regions backups may be selected from, empty for any region
+    pub allowed_regions: Vec<String>,
+    pub min_reputation: f64,
+}
+
+/// A registered network that may be selected as a backup network.
+/// An empty region or operator group is unknown, and never
+/// conflicts with that of another network.
+/// The region, operator group and capacity are whatever the network
+/// registered with the directory, and are not verified. A network may
+/// claim a new region or group to be preferred for diversity, so the
+/// placement policy only spreads backups between honest networks.
+/// Reputation is scored locally and does not rely on the directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackupCandidate {
+    pub network_id: String,
+    pub region: String,
+    pub operator_group: String,
+    /// Max number of users the network will back up, 0 for no limit
+    pub backup_capacity: i64,
+    /// Number of users the network currently backs up
+    pub backup_count: i64,
+    pub reputation: f64,
+}
+
+impl BackupCandidate {
+    /// Returns whether the network will back up another user.
+    pub fn has_capacity(&self) -> bool {
+        self.backup_capacity <= 0 || self.backup_count < self.backup_capacity
+    }
+
+    fn shares_region(&self, other: &BackupCandidate) -> bool {
+        !self.region.is_empty() && self.region == other.region
+    }
+
+    fn shares_operator_group(&self, other: &BackupCandidate) -> bool {
+        !self.operator_group.is_empty() && self.operator_group == other.operator_group
+    }
+}
+
+impl PlacementPolicy {
+    /// Returns whether the network may be a backup network at all.
+    /// Capacity is not checked, since a network already backing
+    /// up the user does not need more.
+    fn allows(&self, candidate: &BackupCandidate) -> bool {
+        candidate.reputation >= self.min_reputation
+            && (self.allowed_regions.is_empty() || self.allowed_regions.contains(&candidate.region))
+    }
+
+    /// Returns whether the network may back up a user alongside the others.
+    fn allows_with(&self, candidate: &BackupCandidate, others: &[&BackupCandidate]) -> bool {
+        others.iter().all(|other| {
+            !(self.distinct_regions && candidate.shares_region(other))
+                && !(self.distinct_operator_groups && candidate.shares_operator_group(other))
+        })
+    }
+}
+
+/// Selects up to `count` backup networks for a user from the candidates.
+/// The user's `configured` backups are never selected, but are
+/// spread out from like any selected network.
+/// Networks from a `previous` selection are kept first while the policy
+/// still allows them, so repeated selections do not move the user.
+/// Other networks are taken by reputation and then by fewest users,
+/// preferring a region and operator group not yet used by the user.
+pub fn select_backups(
+    policy: &PlacementPolicy,
+    candidates: &[BackupCandidate],
+    configured: &[String],
+    previous: &[String],
+    count: usize,
+) -> Vec<String> {
+    let mut chosen: Vec<&BackupCandidate> = candidates
+        .iter()
+        .filter(|candidate| configured.contains(&candidate.network_id))
+        .collect();
+    let mut selected: Vec<&BackupCandidate> = Vec::new();
+
+    let is_new = |candidate: &BackupCandidate, selected: &[&BackupCandidate]| {
+        !configured.contains(&candidate.network_id)
+            && !selected
+                .iter()
+                .any(|other| other.network_id == candidate.network_id)
+    };
+
+    for network_id in previous {
+        if selected.len() >= count {
+            break;
+        }
+        if let Some(candidate) = candidates
+            .iter()
+            .find(|candidate| &candidate.network_id == network_id)
+        {
+            if is_new(candidate, &selected)
+                && policy.allows(candidate)
+                && policy.allows_with(candidate, &chosen)
+            {
+                chosen.push(candidate);
+                selected.push(candidate);
+            }
+        }
+    }
+
+    let mut ranked: Vec<&BackupCandidate> = candidates
+        .iter()
+        .filter(|candidate| {
+            !previous.contains(&candidate.network_id)
+                && candidate.has_capacity()
+                && policy.allows(candidate)
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.reputation
+            .partial_cmp(&a.reputation)
+            .unwrap_or(Ordering::Equal)
+            .then(a.backup_count.cmp(&b.backup_count))
+            .then(a.network_id.cmp(&b.network_id))
+    });
+
+    // First pass only takes networks unlike any chosen so far
+    for diverse_only in [true, false] {
+        for &candidate in &ranked {
+            if selected.len() >= count {
+                break;
+            }
+
+            let is_diverse = chosen.iter().all(|other| {
+                !candidate.shares_region(other) && !candidate.shares_operator_group(other)
+            });
+
+            if is_new(candidate, &selected)
+                && (is_diverse || !diverse_only)
+                && policy.allows_with(candidate, &chosen)
+            {
+                chosen.push(candidate);
+                selected.push(candidate);
+            }
+        }
+    }
+
+    selected
+        .into_iter()
+        .map(|candidate| candidate.network_id.clone())
+        .collect()
+}
+
+/// Returns up to `count` sqn slices not used by any backup of a user.
+/// Slice 0 is always reserved for the home network.
+pub fn free_sqn_slices(used: &[i64], num_sqn_slices: i64, count: usize) -> Vec<i64> {
+    (1..num_sqn_slices)
+        .filter(|slice| !used.contains(slice))
+        .take(count)
+        .collect()
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use crate::data::placement::{self, BackupCandidate, PlacementPolicy};
+
+    fn candidate(network_id: &str, region: &str, operator_group: &str) -> BackupCandidate {
+        BackupCandidate {
+            network_id: network_id.to_string(),
+            region: region.to_string(),
+            operator_group: operator_group.to_string(),
+            backup_capacity: 0,
+            backup_count: 0,
+            reputation: 1.0,
+        }
+    }
+
+    fn ids(network_ids: &[&str]) -> Vec<String> {
+        network_ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    /// Tests that diverse networks are preferred, then ranked by reputation and load
+    #[test]
+    fn test_select_prefers_diverse() {
+        let mut candidates = vec![
+            candidate("a", "west", "op_1"),
+            candidate("b", "west", "op_1"),
+            candidate("c", "east", "op_2"),
+            candidate("d", "east", "op_3"),
+        ];
+        candidates[0].backup_count = 5;
+        candidates[1].reputation = 0.9;
+
+        let policy = PlacementPolicy::default();
+        assert_eq!(
+            ids(&["c", "a"]),
+            placement::select_backups(&policy, &candidates, &[], &[], 2)
+        );
+
+        // Without enough diverse networks, the rest are still used
+        assert_eq!(
+            ids(&["c", "a", "d", "b"]),
+            placement::select_backups(&policy, &candidates, &[], &[], 5)
+        );
+
+        // Configured backups are not selected, but are spread out from
+        assert_eq!(
+            ids(&["a", "d"]),
+            placement::select_backups(&policy, &candidates, &ids(&["c"]), &[], 2)
+        );
+    }
+
+    /// Tests that hard constraints of the policy are never broken
+    #[test]
+    fn test_select_policy() {
+        let mut candidates = vec![
+            candidate("a", "west", "op_1"),
+            candidate("b", "east", "op_1"),
+            candidate("c", "east", "op_2"),
+            candidate("d", "north", "op_3"),
+            candidate("e", "", ""),
+        ];
+        candidates[3].reputation = 0.1;
+
+        let policy = PlacementPolicy {
+            distinct_operator_groups: true,
+            distinct_regions: true,
+            allowed_regions: Vec::new(),
+            min_reputation: 0.5,
+        };
+        assert_eq!(
+            ids(&["a", "c", "e"]),
+            placement::select_backups(&policy, &candidates, &[], &[], 5)
+        );
+
+        let policy = PlacementPolicy {
+            allowed_regions: ids(&["east"]),
+            ..PlacementPolicy::default()
+        };
+        assert_eq!(
+            ids(&["b", "c"]),
+            placement::select_backups(&policy, &candidates, &[], &[], 5)
+        );
+    }
+
+    /// Tests that full networks are only kept from a previous selection
+    #[test]
+    fn test_select_capacity_previous() {
+        let mut candidates = vec![
+            candidate("a", "west", "op_1"),
+            candidate("b", "east", "op_2"),
+            candidate("c", "north", "op_3"),
+        ];
+        candidates[0].backup_capacity = 1;
+        candidates[0].backup_count = 1;
+
+        let policy = PlacementPolicy::default();
+        assert_eq!(
+            ids(&["b", "c"]),
+            placement::select_backups(&policy, &candidates, &[], &[], 3)
+        );
+        assert_eq!(
+            ids(&["a", "b"]),
+            placement::select_backups(&policy, &candidates, &[], &ids(&["a", "x"]), 2)
+        );
+
+        // Previous networks the policy no longer allows are replaced
+        candidates[0].reputation = 0.0;
+        let policy = PlacementPolicy {
+            min_reputation: 0.5,
+            ..PlacementPolicy::default()
+        };
+        assert_eq!(
+            ids(&["b"]),
+            placement::select_backups(&policy, &candidates, &[], &ids(&["a"]), 1)
+        );
+    }
+
+    #[test]
+    fn test_free_sqn_slices() {
+        assert_eq!(vec![2, 4], placement::free_sqn_slices(&[0, 1, 3], 8, 2));
+        assert_eq!(vec![3], placement::free_sqn_slices(&[0, 1, 2], 4, 2));
+        assert!(placement::free_sqn_slices(&[0], 1, 2).is_empty());
+    }
+}
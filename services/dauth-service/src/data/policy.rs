@@ -0,0 +1,159 @@
+use crate::data::{error::DauthError, vector::Plmn};
+use crate::rpc::dauth::common::{serving_network_policy::MccRange, ServingNetworkPolicy};
+
+/// Restricts which serving networks a backup network may give
+/// a user's vectors and key shares to.
+/// Empty lists place no restriction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServingPolicy {
+    pub allowed_network_ids: Vec<String>,
+    pub denied_network_ids: Vec<String>,
+    /// Inclusive ranges of mcc values
+    pub allowed_mcc_ranges: Vec<(u16, u16)>,
+}
+
+impl ServingPolicy {
+    /// Returns an error if the serving network is not allowed.
+    /// An mcc range restriction is never met without a serving PLMN.
+    pub fn check(&self, network_id: &str, plmn: Option<&Plmn>) -> Result<(), DauthError> {
+        if self.denied_network_ids.iter().any(|id| id == network_id) {
+            return Err(DauthError::PolicyError(format!(
+                "Serving network {} is denied",
+                network_id
+            )));
+        }
+
+        if !self.allowed_network_ids.is_empty()
+            && !self.allowed_network_ids.iter().any(|id| id == network_id)
+        {
+            return Err(DauthError::PolicyError(format!(
+                "Serving network {} is not allowed",
+                network_id
+            )));
+        }
+
+        if !self.allowed_mcc_ranges.is_empty() {
+            let mcc = match plmn {
+                Some(plmn) => parse_mcc(&plmn.mcc)?,
+                None => {
+                    return Err(DauthError::PolicyError(format!(
+                        "Serving network {} sent no plmn",
+                        network_id
+                    )))
+                }
+            };
+
+            if !self
+                .allowed_mcc_ranges
+                .iter()
+                .any(|(first, last)| (*first..=*last).contains(&mcc))
+            {
+                return Err(DauthError::PolicyError(format!(
+                    "Serving network mcc {} is not allowed",
+                    mcc
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_serving_network_policy(&self) -> ServingNetworkPolicy {
+        ServingNetworkPolicy {
+            allowed_network_ids: self.allowed_network_ids.clone(),
+            denied_network_ids: self.denied_network_ids.clone(),
+            allowed_mcc_ranges: self
+                .allowed_mcc_ranges
+                .iter()
+                .map(|(first, last)| MccRange {
+                    first: format!("{:03}", first),
+                    last: format!("{:03}", last),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<ServingNetworkPolicy> for ServingPolicy {
+    type Error = DauthError;
+    fn try_from(value: ServingNetworkPolicy) -> Result<Self, Self::Error> {
+        let mut allowed_mcc_ranges = Vec::new();
+        for range in value.allowed_mcc_ranges {
+            allowed_mcc_ranges.push((parse_mcc(&range.first)?, parse_mcc(&range.last)?));
+        }
+
+        Ok(ServingPolicy {
+            allowed_network_ids: value.allowed_network_ids,
+            denied_network_ids: value.denied_network_ids,
+            allowed_mcc_ranges,
+        })
+    }
+}
+
+/// Parses a three digit mcc.
+pub fn parse_mcc(mcc: &str) -> Result<u16, DauthError> {
+    if mcc.len() != 3 || !mcc.chars().all(|c| c.is_ascii_digit()) {
+        return Err(DauthError::DataError(format!("Invalid mcc: {}", mcc)));
+    }
+
+    mcc.parse()
+        .map_err(|_| DauthError::DataError(format!("Invalid mcc: {}", mcc)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::policy::ServingPolicy;
+    use crate::data::vector::Plmn;
+
+    fn gen_plmn(mcc: &str) -> Plmn {
+        Plmn {
+            mcc: mcc.to_string(),
+            mnc: "70".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_check_network_ids() {
+        let policy = ServingPolicy {
+            allowed_network_ids: vec!["network_a".to_string(), "network_b".to_string()],
+            denied_network_ids: vec!["network_b".to_string()],
+            allowed_mcc_ranges: Vec::new(),
+        };
+
+        assert!(policy.check("network_a", None).is_ok());
+        assert!(policy.check("network_b", None).is_err());
+        assert!(policy.check("network_c", None).is_err());
+
+        assert!(ServingPolicy::default().check("network_c", None).is_ok());
+    }
+
+    #[test]
+    fn test_check_mcc_ranges() {
+        let policy = ServingPolicy {
+            allowed_network_ids: Vec::new(),
+            denied_network_ids: Vec::new(),
+            allowed_mcc_ranges: vec![(310, 316), (901, 901)],
+        };
+
+        assert!(policy.check("network", Some(&gen_plmn("310"))).is_ok());
+        assert!(policy.check("network", Some(&gen_plmn("316"))).is_ok());
+        assert!(policy.check("network", Some(&gen_plmn("901"))).is_ok());
+        assert!(policy.check("network", Some(&gen_plmn("317"))).is_err());
+        assert!(policy.check("network", Some(&gen_plmn("31a"))).is_err());
+        assert!(policy.check("network", None).is_err());
+    }
+
+    #[test]
+    fn test_serving_network_policy_round_trip() {
+        let policy = ServingPolicy {
+            allowed_network_ids: vec!["network_a".to_string()],
+            denied_network_ids: vec!["network_b".to_string()],
+            allowed_mcc_ranges: vec![(1, 99), (310, 316)],
+        };
+
+        assert_eq!(
+            policy,
+            ServingPolicy::try_from(policy.to_serving_network_policy()).unwrap()
+        );
+    }
+}
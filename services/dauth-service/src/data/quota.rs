@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::data::error::DauthError;
+
+/// What this network stores as a backup for one home network.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BackupUsage {
+    pub users: i64,
+    pub auth_vectors: i64,
+    pub key_shares: i64,
+    pub flood_vectors: i64,
+}
+
+/// Limits on what this network stores as a backup for one home network.
+/// Unset limits are not enforced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackupQuota {
+    pub max_users: Option<i64>,
+    pub max_auth_vectors: Option<i64>,
+    pub max_key_shares: Option<i64>,
+    pub max_flood_vectors: Option<i64>,
+}
+
+impl BackupQuota {
+    /// Checks that the usage stays within the quota once the added
+    /// amounts are stored. Only added resources are checked, so a home
+    /// network over a lowered quota may still remove and replace data.
+    pub fn check(&self, usage: &BackupUsage, added: &BackupUsage) -> Result<(), DauthError> {
+        for (resource, max, used, added) in [
+            ("users", self.max_users, usage.users, added.users),
+            (
+                "auth vectors",
+                self.max_auth_vectors,
+                usage.auth_vectors,
+                added.auth_vectors,
+            ),
+            (
+                "key shares",
+                self.max_key_shares,
+                usage.key_shares,
+                added.key_shares,
+            ),
+            (
+                "flood vectors",
+                self.max_flood_vectors,
+                usage.flood_vectors,
+                added.flood_vectors,
+            ),
+        ] {
+            if let Some(max) = max {
+                if added > 0 && used + added > max {
+                    return Err(DauthError::QuotaExceededError(format!(
+                        "Storing {} more {} exceeds quota of {} ({} stored)",
+                        added, resource, max, used
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Backup quotas of each home network, with a default
+/// for home networks without their own quota.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackupQuotas {
+    pub default: BackupQuota,
+    pub home_networks: HashMap<String, BackupQuota>,
+}
+
+impl BackupQuotas {
+    /// Returns the quota of the home network.
+    pub fn get(&self, home_network_id: &str) -> &BackupQuota {
+        self.home_networks
+            .get(home_network_id)
+            .unwrap_or(&self.default)
+    }
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::data::quota::{BackupQuota, BackupQuotas, BackupUsage};
+
+    /// Tests that only added resources are checked against their limits
+    #[test]
+    fn test_check() {
+        let quota = BackupQuota {
+            max_users: Some(2),
+            max_auth_vectors: Some(10),
+            max_key_shares: None,
+            max_flood_vectors: Some(0),
+        };
+        let usage = BackupUsage {
+            users: 2,
+            auth_vectors: 8,
+            key_shares: 100,
+            flood_vectors: 1,
+        };
+
+        assert!(quota.check(&usage, &BackupUsage::default()).is_ok());
+        assert!(quota
+            .check(
+                &usage,
+                &BackupUsage {
+                    auth_vectors: 2,
+                    key_shares: 100,
+                    ..BackupUsage::default()
+                }
+            )
+            .is_ok());
+
+        for added in [
+            BackupUsage {
+                users: 1,
+                ..BackupUsage::default()
+            },
+            BackupUsage {
+                auth_vectors: 3,
+                ..BackupUsage::default()
+            },
+            BackupUsage {
+                flood_vectors: 1,
+                ..BackupUsage::default()
+            },
+        ] {
+            assert!(quota.check(&usage, &added).is_err());
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let quota = BackupQuota {
+            max_users: Some(1),
+            ..BackupQuota::default()
+        };
+        let quotas = BackupQuotas {
+            default: BackupQuota::default(),
+            home_networks: HashMap::from([("network_a".to_string(), quota.clone())]),
+        };
+
+        assert_eq!(&quota, quotas.get("network_a"));
+        assert_eq!(&BackupQuota::default(), quotas.get("network_b"));
+    }
+}
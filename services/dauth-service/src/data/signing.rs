@@ -1,23 +1,39 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use ed25519_dalek::{Signature, Signer, Verifier};
 use prost::Message;
+use rand::Rng;
+use sqlx::{Sqlite, Transaction};
 
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
+use crate::data::utilities;
+use crate::database;
 use crate::rpc::clients::directory;
 use crate::rpc::dauth::remote;
 
+const NONCE_LENGTH: usize = 16;
+
+/// Minimum time between refreshes of a signer's keys after a
+/// signature fails to verify, so that bad signatures cannot
+/// each cause a directory lookup.
+const MIN_KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
 /// All payload types that expect to be signed
 #[derive(Debug)]
 pub enum SignPayloadType {
     DelegatedAuthVector5G(remote::delegated_auth_vector5_g::Payload),
+    DelegatedAuthVectorEapAkaPrime(remote::delegated_auth_vector_eap_aka_prime::Payload),
     DelegatedConfirmationShare(remote::delegated_confirmation_share::Payload),
+    DelegatedServingNetworkPolicy(remote::delegated_serving_network_policy::Payload),
     GetHomeAuthVectorReq(remote::get_home_auth_vector_req::Payload),
+    GetHomeEapAkaPrimeVectorReq(remote::get_home_eap_aka_prime_vector_req::Payload),
     GetHomeConfirmKeyReq(remote::get_home_confirm_key_req::Payload),
     EnrollBackupPrepareReq(remote::enroll_backup_prepare_req::Payload),
     GetBackupAuthVectorReq(remote::get_backup_auth_vector_req::Payload),
     GetKeyShareReq(remote::get_key_share_req::Payload),
+    GetKeyShareResp(remote::get_key_share_resp::Payload),
     WithdrawBackupReq(remote::withdraw_backup_req::Payload),
     WithdrawSharesReq(remote::withdraw_shares_req::Payload),
     FloodVectorReq(remote::flood_vector_req::Payload),
@@ -29,14 +45,26 @@ pub fn sign_message(context: Arc<DauthContext>, payload: SignPayloadType) -> rem
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::DelegatedAuthVector5G,
         ),
+        SignPayloadType::DelegatedAuthVectorEapAkaPrime(payload_message) => (
+            payload_message.encode_to_vec(),
+            remote::SignedMessageKind::DelegatedAuthVectorEapAkaPrime,
+        ),
         SignPayloadType::DelegatedConfirmationShare(payload_message) => (
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::DelegatedConfirmationShare,
         ),
+        SignPayloadType::DelegatedServingNetworkPolicy(payload_message) => (
+            payload_message.encode_to_vec(),
+            remote::SignedMessageKind::DelegatedServingNetworkPolicy,
+        ),
         SignPayloadType::GetHomeAuthVectorReq(payload_message) => (
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::GetHomeAuthVectorReq,
         ),
+        SignPayloadType::GetHomeEapAkaPrimeVectorReq(payload_message) => (
+            payload_message.encode_to_vec(),
+            remote::SignedMessageKind::GetHomeEapAkaPrimeVectorReq,
+        ),
         SignPayloadType::GetHomeConfirmKeyReq(payload_message) => (
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::GetHomeConfirmKeyReq,
@@ -53,6 +81,10 @@ pub fn sign_message(context: Arc<DauthContext>, payload: SignPayloadType) -> rem
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::GetKeyShareReq,
         ),
+        SignPayloadType::GetKeyShareResp(payload_message) => (
+            payload_message.encode_to_vec(),
+            remote::SignedMessageKind::GetKeyShareResp,
+        ),
         SignPayloadType::WithdrawBackupReq(payload_message) => (
             payload_message.encode_to_vec(),
             remote::SignedMessageKind::WithdrawBackupReq,
@@ -70,6 +102,8 @@ pub fn sign_message(context: Arc<DauthContext>, payload: SignPayloadType) -> rem
     let container = remote::signed_message::Container {
         kind: payload_kind as i32,
         payload: payload_bytes,
+        timestamp: utilities::unix_timestamp(),
+        nonce: rand::thread_rng().gen::<[u8; NONCE_LENGTH]>().to_vec(),
     }
     .encode_to_vec();
 
@@ -77,6 +111,8 @@ pub fn sign_message(context: Arc<DauthContext>, payload: SignPayloadType) -> rem
         context
             .local_context
             .signing_keys
+            .read()
+            .expect("Signing keys lock poisoned")
             .sign(&container)
             .to_bytes(),
     );
@@ -88,24 +124,219 @@ pub fn sign_message(context: Arc<DauthContext>, payload: SignPayloadType) -> rem
     }
 }
 
+/// Verifies the message with the signer's public key.
+/// Falls back to the signer's previous key during its rollover window,
+/// or for delegated material signed before the key change.
+/// On failure, refreshes the signer's info from the directory and retries
+/// once, if the signer's info was not refreshed too recently.
 async fn verify_message_with_id(
     context: Arc<DauthContext>,
     message: &remote::SignedMessage,
+    container: &remote::signed_message::Container,
+    signer_id: &str,
+) -> Result<(), DauthError> {
+    let signature = Signature::from_bytes(&message.signature)?;
+
+    let info = directory::lookup_network_info(&context, signer_id).await?;
+    let result = verify_with_info(&info, message, container, &signature);
+    if result.is_ok() || !take_key_refresh(&context, signer_id).await {
+        return result;
+    }
+
+    // The cached keys may be stale if the signer rotated keys
+    directory::invalidate_network(&context, signer_id).await;
+    let info = directory::lookup_network_info(&context, signer_id).await?;
+
+    verify_with_info(&info, message, container, &signature)
+}
+
+/// Returns whether the signer's keys may be refreshed now,
+/// and if so records the refresh.
+async fn take_key_refresh(context: &Arc<DauthContext>, signer_id: &str) -> bool {
+    let mut refreshes = context.backup_context.directory_key_refreshes.lock().await;
+    let now = Instant::now();
+
+    match refreshes.get(signer_id) {
+        Some(last_refresh) if now.duration_since(*last_refresh) < MIN_KEY_REFRESH_INTERVAL => false,
+        _ => {
+            refreshes.insert(signer_id.to_string(), now);
+            true
+        }
+    }
+}
+
+fn verify_with_info(
+    info: &directory::NetworkInfo,
+    message: &remote::SignedMessage,
+    container: &remote::signed_message::Container,
+    signature: &Signature,
+) -> Result<(), DauthError> {
+    let current_result = info.public_key.verify(&message.container, signature);
+
+    match (current_result, &info.previous_key) {
+        (Ok(()), _) => Ok(()),
+        (Err(e), Some(previous_key)) => {
+            // Backups hold delegated material until it is used, which may
+            // be long after the rollover window of the key that signed it
+            let signed_before_change =
+                is_delegated(container) && container.timestamp <= previous_key.replaced;
+
+            if SystemTime::now() < previous_key.expiration || signed_before_change {
+                Ok(previous_key
+                    .public_key
+                    .verify(&message.container, signature)?)
+            } else {
+                Err(e.into())
+            }
+        }
+        (Err(e), None) => Err(e.into()),
+    }
+}
+
+/// Returns whether the message was signed with the signer's previous key,
+/// even if that key's rollover window has ended. Such a message was
+/// genuinely signed by the signer, but cannot be verified anymore.
+pub async fn signed_by_previous_key(
+    context: &Arc<DauthContext>,
+    message: &remote::SignedMessage,
+) -> Result<bool, DauthError> {
+    let signature = match Signature::from_bytes(&message.signature) {
+        Ok(signature) => signature,
+        Err(_) => return Ok(false),
+    };
+    let info = directory::lookup_network_info(context, &message.signer_id).await?;
+
+    Ok(match info.previous_key {
+        Some(previous_key) => previous_key
+            .public_key
+            .verify(&message.container, &signature)
+            .is_ok(),
+        None => false,
+    })
+}
+
+/// Returns whether the message is delegated material,
+/// which is stored by backups until it is used.
+fn is_delegated(container: &remote::signed_message::Container) -> bool {
+    matches!(
+        container.kind(),
+        remote::SignedMessageKind::DelegatedAuthVector5G
+            | remote::SignedMessageKind::DelegatedAuthVectorEapAkaPrime
+            | remote::SignedMessageKind::DelegatedConfirmationShare
+            | remote::SignedMessageKind::DelegatedServingNetworkPolicy
+    )
+}
+
+/// Rejects the message if it is stale or its nonce was already seen.
+/// Seen nonces are stored until they would be rejected as stale anyway.
+/// Evicting a nonce any earlier would allow its message to be replayed,
+/// so new messages are rejected while the cache is full.
+async fn check_replay(
+    context: &Arc<DauthContext>,
+    container: &remote::signed_message::Container,
     signer_id: &str,
 ) -> Result<(), DauthError> {
-    let (_, public_key) = directory::lookup_network(&context, signer_id).await?;
+    let now = utilities::unix_timestamp();
+    let replay_window = context.rpc_context.replay_window.as_secs() as i64;
+
+    if (now - container.timestamp).abs() > replay_window {
+        return Err(DauthError::InvalidMessageError(format!(
+            "Stale message with timestamp {}",
+            container.timestamp
+        )));
+    }
+
+    if container.nonce.len() != NONCE_LENGTH {
+        return Err(DauthError::InvalidMessageError(format!(
+            "Invalid nonce length: {}",
+            container.nonce.len()
+        )));
+    }
 
-    public_key.verify(
-        &message.container,
-        &Signature::from_bytes(&message.signature)?,
-    )?;
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    database::replay_cache::remove_expired(&mut transaction, now - replay_window).await?;
+    record_nonce(
+        &mut transaction,
+        container,
+        signer_id,
+        context.rpc_context.max_replay_cache_size,
+        context.rpc_context.max_replay_cache_size_per_signer,
+    )
+    .await?;
+    transaction.commit().await?;
 
     Ok(())
 }
 
+/// Stores the nonce of the message, rejecting the message if the nonce
+/// was already seen or the cache is full. Each signer is limited to its
+/// own share of the cache, so one signer cannot fill it for all others.
+async fn record_nonce(
+    transaction: &mut Transaction<'_, Sqlite>,
+    container: &remote::signed_message::Container,
+    signer_id: &str,
+    max_size: i64,
+    max_size_per_signer: i64,
+) -> Result<(), DauthError> {
+    if database::replay_cache::count_by_signer(transaction, signer_id).await? >= max_size_per_signer
+    {
+        return Err(DauthError::InvalidMessageError(format!(
+            "Replay cache full for signer {}",
+            signer_id
+        )));
+    }
+
+    if database::replay_cache::count(transaction).await? >= max_size {
+        return Err(DauthError::InvalidMessageError(
+            "Replay cache full".to_string(),
+        ));
+    }
+
+    if !database::replay_cache::add(
+        transaction,
+        signer_id,
+        &container.nonce,
+        container.timestamp,
+    )
+    .await?
+    {
+        return Err(DauthError::InvalidMessageError(
+            "Replayed message".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verifies the signature of a message and checks it for replays.
+/// Delegated vectors and shares are stored and forwarded by design,
+/// so they are exempt from the replay check.
 pub async fn verify_message(
     context: &Arc<DauthContext>,
     message: &remote::SignedMessage,
+) -> Result<SignPayloadType, DauthError> {
+    let payload = verify_message_signature(context, message).await?;
+
+    match payload {
+        SignPayloadType::DelegatedAuthVector5G(_)
+        | SignPayloadType::DelegatedAuthVectorEapAkaPrime(_)
+        | SignPayloadType::DelegatedConfirmationShare(_) => (),
+        _ => {
+            let container =
+                remote::signed_message::Container::decode(message.container.as_slice())?;
+            check_replay(context, &container, &message.signer_id).await?;
+        }
+    }
+
+    Ok(payload)
+}
+
+/// Verifies the signature of a message without checking it for replays.
+/// Used for requests forwarded by another network, which may
+/// legitimately arrive late or more than once.
+pub async fn verify_message_signature(
+    context: &Arc<DauthContext>,
+    message: &remote::SignedMessage,
 ) -> Result<SignPayloadType, DauthError> {
     let container = remote::signed_message::Container::decode(message.container.as_slice())?;
 
@@ -113,62 +344,104 @@ pub async fn verify_message(
         remote::SignedMessageKind::DelegatedAuthVector5G => {
             let payload =
                 remote::delegated_auth_vector5_g::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::DelegatedAuthVector5G(payload))
         }
+        remote::SignedMessageKind::DelegatedAuthVectorEapAkaPrime => {
+            let payload = remote::delegated_auth_vector_eap_aka_prime::Payload::decode(
+                container.payload.as_slice(),
+            )?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
+            Ok(SignPayloadType::DelegatedAuthVectorEapAkaPrime(payload))
+        }
         remote::SignedMessageKind::DelegatedConfirmationShare => {
             let payload = remote::delegated_confirmation_share::Payload::decode(
                 container.payload.as_slice(),
             )?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            // Shares are forwarded by backups as signed by the home network
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::DelegatedConfirmationShare(payload))
         }
+        remote::SignedMessageKind::DelegatedServingNetworkPolicy => {
+            let payload = remote::delegated_serving_network_policy::Payload::decode(
+                container.payload.as_slice(),
+            )?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
+            Ok(SignPayloadType::DelegatedServingNetworkPolicy(payload))
+        }
         remote::SignedMessageKind::GetHomeAuthVectorReq => {
             let payload =
                 remote::get_home_auth_vector_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::GetHomeAuthVectorReq(payload))
         }
+        remote::SignedMessageKind::GetHomeEapAkaPrimeVectorReq => {
+            let payload = remote::get_home_eap_aka_prime_vector_req::Payload::decode(
+                container.payload.as_slice(),
+            )?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
+            Ok(SignPayloadType::GetHomeEapAkaPrimeVectorReq(payload))
+        }
         remote::SignedMessageKind::GetHomeConfirmKeyReq => {
             let payload =
                 remote::get_home_confirm_key_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::GetHomeConfirmKeyReq(payload))
         }
         remote::SignedMessageKind::EnrollBackupPrepareReq => {
             let payload =
                 remote::enroll_backup_prepare_req::Payload::decode(container.payload.as_slice())?;
             // TODO (nickfh7): Figure out how to get id from context
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::EnrollBackupPrepareReq(payload))
         }
         remote::SignedMessageKind::GetBackupAuthVectorReq => {
             let payload =
                 remote::get_backup_auth_vector_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::GetBackupAuthVectorReq(payload))
         }
         remote::SignedMessageKind::GetKeyShareReq => {
             let payload = remote::get_key_share_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::GetKeyShareReq(payload))
         }
+        remote::SignedMessageKind::GetKeyShareResp => {
+            let payload =
+                remote::get_key_share_resp::Payload::decode(container.payload.as_slice())?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
+            Ok(SignPayloadType::GetKeyShareResp(payload))
+        }
         remote::SignedMessageKind::WithdrawBackupReq => {
             let payload =
                 remote::withdraw_backup_req::Payload::decode(container.payload.as_slice())?;
             // TODO (nickfh7): Figure out how to get id from context
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::WithdrawBackupReq(payload))
         }
         remote::SignedMessageKind::WithdrawSharesReq => {
             let payload =
                 remote::withdraw_shares_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::WithdrawSharesReq(payload))
         }
         remote::SignedMessageKind::FloodVectorReq => {
             let payload = remote::flood_vector_req::Payload::decode(container.payload.as_slice())?;
-            verify_message_with_id(context.clone(), message, &message.signer_id).await?;
+            verify_message_with_id(context.clone(), message, &container, &message.signer_id)
+                .await?;
             Ok(SignPayloadType::FloodVectorReq(payload))
         }
         _ => Err(DauthError::InvalidMessageError(format!(
@@ -179,3 +452,91 @@ pub async fn verify_message(
 
     Ok(payload)
 }
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::data::signing::{record_nonce, NONCE_LENGTH};
+    use crate::database::{general, replay_cache};
+    use crate::rpc::dauth::remote;
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        replay_cache::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn container(nonce: u8) -> remote::signed_message::Container {
+        remote::signed_message::Container {
+            kind: remote::SignedMessageKind::GetKeyShareReq as i32,
+            payload: Vec::new(),
+            timestamp: 0,
+            nonce: vec![nonce; NONCE_LENGTH],
+        }
+    }
+
+    /// Test that a signer filling its share of the replay cache
+    /// does not block other signers
+    #[tokio::test]
+    async fn test_record_nonce_per_signer() {
+        let (pool, _dir) = init().await;
+        let mut transaction = pool.begin().await.unwrap();
+
+        for nonce in 0..3 {
+            record_nonce(&mut transaction, &container(nonce), "test_network", 10, 3)
+                .await
+                .unwrap();
+        }
+
+        // Replays are rejected
+        assert!(
+            record_nonce(&mut transaction, &container(0), "test_network_2", 10, 3)
+                .await
+                .is_ok()
+        );
+        assert!(
+            record_nonce(&mut transaction, &container(0), "test_network_2", 10, 3)
+                .await
+                .is_err()
+        );
+
+        // The full signer is rejected, but others are not
+        assert!(
+            record_nonce(&mut transaction, &container(3), "test_network", 10, 3)
+                .await
+                .is_err()
+        );
+        record_nonce(&mut transaction, &container(3), "test_network_2", 10, 3)
+            .await
+            .unwrap();
+        record_nonce(&mut transaction, &container(3), "test_network_3", 10, 3)
+            .await
+            .unwrap();
+
+        // Until the whole cache is full
+        assert!(
+            record_nonce(&mut transaction, &container(4), "test_network_4", 6, 3)
+                .await
+                .is_err()
+        );
+
+        transaction.commit().await.unwrap();
+    }
+}
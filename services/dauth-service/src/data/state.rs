@@ -1,4 +1,6 @@
-use auth_vector::types::{Rand, XResStarHash};
+use auth_vector::types::{Rand, XResHash, XResStarHash};
+
+use crate::data::error::DauthError;
 
 #[derive(Clone, Debug)]
 pub enum AuthSource {
@@ -6,9 +8,30 @@ pub enum AuthSource {
     BackupNetwork,
 }
 
+impl AuthSource {
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            AuthSource::HomeNetwork => 0,
+            AuthSource::BackupNetwork => 1,
+        }
+    }
+
+    pub fn from_i64(value: i64) -> Result<AuthSource, DauthError> {
+        match value {
+            0 => Ok(AuthSource::HomeNetwork),
+            1 => Ok(AuthSource::BackupNetwork),
+            _ => Err(DauthError::DataError(format!(
+                "Unknown auth source: {}",
+                value
+            ))),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct AuthState {
     pub rand: Rand,
     pub source: AuthSource,
     pub xres_star_hash: XResStarHash,
+    pub xres_hash: XResHash,
 }
@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use aes::cipher::generic_array::GenericArray;
+use aes::Aes128;
+use ctr::cipher::{NewCipher, StreamCipher};
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::{Digest, Sha256};
+
+use crate::data::context::LocalContext;
+use crate::data::error::DauthError;
+
+pub const SUCI_PREFIX: &str = "suci";
+pub const PRIVATE_KEY_LENGTH: usize = 32;
+
+// Only IMSI based SUPIs are supported
+const SUPI_TYPE_IMSI: &str = "0";
+const SUPI_PREFIX_IMSI: &str = "imsi";
+
+// ECIES parameters, per TS 33.501 C.3.4
+const PROFILE_A_PUBLIC_KEY_LENGTH: usize = 32;
+const PROFILE_B_PUBLIC_KEY_LENGTH: usize = 33;
+const ENC_KEY_LENGTH: usize = 16;
+const ICB_LENGTH: usize = 16;
+const MAC_KEY_LENGTH: usize = 32;
+const MAC_TAG_LENGTH: usize = 8;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// SUCI protection scheme, per TS 33.501 C.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionScheme {
+    Null,
+    ProfileA,
+    ProfileB,
+}
+
+impl ProtectionScheme {
+    pub fn from_id(id: u8) -> Result<Self, DauthError> {
+        match id {
+            0 => Ok(ProtectionScheme::Null),
+            1 => Ok(ProtectionScheme::ProfileA),
+            2 => Ok(ProtectionScheme::ProfileB),
+            _ => Err(DauthError::DataError(format!(
+                "Unsupported protection scheme: {}",
+                id
+            ))),
+        }
+    }
+
+    /// Parses a profile name as used in the config ("A" or "B").
+    pub fn from_profile(profile: &str) -> Result<Self, DauthError> {
+        match profile.to_ascii_uppercase().as_str() {
+            "A" => Ok(ProtectionScheme::ProfileA),
+            "B" => Ok(ProtectionScheme::ProfileB),
+            _ => Err(DauthError::ConfigError(format!(
+                "Unknown ECIES profile: {}",
+                profile
+            ))),
+        }
+    }
+}
+
+/// Home network private key used to de-conceal SUCIs.
+#[derive(Clone)]
+pub struct SuciKey {
+    pub scheme: ProtectionScheme,
+    private_key: [u8; PRIVATE_KEY_LENGTH],
+}
+
+// Never log the private key
+impl std::fmt::Debug for SuciKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SuciKey")
+            .field("scheme", &self.scheme)
+            .finish()
+    }
+}
+
+impl SuciKey {
+    pub fn new(scheme: ProtectionScheme, private_key: &[u8]) -> Result<Self, DauthError> {
+        let key = SuciKey {
+            scheme,
+            private_key: private_key.try_into()?,
+        };
+
+        // Fails for invalid profile B scalars
+        key.public_key()?;
+        Ok(key)
+    }
+
+    /// Returns the home network public key provisioned on the UE.
+    /// Profile B keys are returned in compressed form.
+    pub fn public_key(&self) -> Result<Vec<u8>, DauthError> {
+        match self.scheme {
+            ProtectionScheme::ProfileA => Ok(x25519(&self.private_key, &X25519_BASEPOINT).to_vec()),
+            ProtectionScheme::ProfileB => Ok(self
+                .profile_b_secret()?
+                .public_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec()),
+            ProtectionScheme::Null => {
+                Err(DauthError::DataError("Null scheme has no key".to_string()))
+            }
+        }
+    }
+
+    /// Computes the ECDH shared secret with the UE's ephemeral public key.
+    fn shared_secret(&self, ephemeral_public_key: &[u8]) -> Result<Vec<u8>, DauthError> {
+        match self.scheme {
+            ProtectionScheme::ProfileA => {
+                let ephemeral_public_key: [u8; PROFILE_A_PUBLIC_KEY_LENGTH] =
+                    ephemeral_public_key.try_into()?;
+
+                Ok(x25519(&self.private_key, &MontgomeryPoint(ephemeral_public_key)).to_vec())
+            }
+            ProtectionScheme::ProfileB => {
+                let ephemeral_public_key = p256::PublicKey::from_sec1_bytes(ephemeral_public_key)
+                    .or(Err(DauthError::DataError(
+                    "Invalid ephemeral public key".to_string(),
+                )))?;
+
+                Ok(p256::elliptic_curve::ecdh::diffie_hellman(
+                    self.profile_b_secret()?.to_nonzero_scalar(),
+                    ephemeral_public_key.as_affine(),
+                )
+                .as_bytes()
+                .to_vec())
+            }
+            ProtectionScheme::Null => {
+                Err(DauthError::DataError("Null scheme has no key".to_string()))
+            }
+        }
+    }
+
+    fn profile_b_secret(&self) -> Result<p256::SecretKey, DauthError> {
+        p256::SecretKey::from_be_bytes(&self.private_key).or(Err(DauthError::ConfigError(
+            "Invalid profile B private key".to_string(),
+        )))
+    }
+}
+
+/// Subscription concealed identifier, per TS 23.003 2.2B.
+/// Uses the NAI form "suci-0-<mcc>-<mnc>-<routing indicator>-
+/// <protection scheme>-<home network key id>-<scheme output>".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suci {
+    pub mcc: String,
+    pub mnc: String,
+    pub routing_indicator: String,
+    pub scheme: ProtectionScheme,
+    pub key_id: u8,
+    pub scheme_output: String,
+}
+
+impl TryFrom<&str> for Suci {
+    type Error = DauthError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || DauthError::DataError(format!("Invalid SUCI: {}", value));
+
+        let fields: Vec<&str> = value.split('-').collect();
+        if fields.len() != 8 || fields[0] != SUCI_PREFIX {
+            return Err(invalid());
+        }
+        if fields[1] != SUPI_TYPE_IMSI {
+            return Err(DauthError::DataError(format!(
+                "Unsupported SUPI type: {}",
+                fields[1]
+            )));
+        }
+
+        let (mcc, mnc, routing_indicator) = (fields[2], fields[3], fields[4]);
+        if mcc.len() != 3
+            || !(2..=3).contains(&mnc.len())
+            || !(1..=4).contains(&routing_indicator.len())
+            || ![mcc, mnc, routing_indicator]
+                .iter()
+                .all(|field| field.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(invalid());
+        }
+
+        Ok(Suci {
+            mcc: mcc.to_string(),
+            mnc: mnc.to_string(),
+            routing_indicator: routing_indicator.to_string(),
+            scheme: ProtectionScheme::from_id(fields[5].parse().or(Err(invalid()))?)?,
+            key_id: fields[6].parse().or(Err(invalid()))?,
+            scheme_output: fields[7].to_string(),
+        })
+    }
+}
+
+impl Suci {
+    /// Finds the id of the dAuth network that can de-conceal this SUCI.
+    /// Routes for the routing indicator take precedence over routes
+    /// for the whole PLMN. Without a route, SUCIs of this network's
+    /// PLMN belong to this network.
+    pub fn home_network_id(&self, local_context: &LocalContext) -> Result<String, DauthError> {
+        match self.lookup_route(&local_context.suci_routes) {
+            Some(network_id) => Ok(network_id.clone()),
+            None if self.mcc == local_context.mcc && self.mnc == local_context.mnc => {
+                Ok(local_context.id.clone())
+            }
+            None => Err(DauthError::NotFoundError(format!(
+                "No SUCI route for {}-{}-{}",
+                self.mcc, self.mnc, self.routing_indicator
+            ))),
+        }
+    }
+
+    /// Recovers the SUPI with the home network private keys.
+    pub fn deconceal(&self, keys: &HashMap<u8, SuciKey>) -> Result<String, DauthError> {
+        let msin = match self.scheme {
+            ProtectionScheme::Null => self.scheme_output.clone(),
+            ProtectionScheme::ProfileA | ProtectionScheme::ProfileB => {
+                let key = keys
+                    .get(&self.key_id)
+                    .filter(|key| key.scheme == self.scheme)
+                    .ok_or(DauthError::NotFoundError(format!(
+                        "No {:?} key with id {}",
+                        self.scheme, self.key_id
+                    )))?;
+
+                decode_bcd(&self.decrypt(key)?)
+            }
+        };
+
+        if msin.is_empty() || !msin.chars().all(|c| c.is_ascii_digit()) {
+            return Err(DauthError::DataError("Invalid MSIN".to_string()));
+        }
+
+        Ok(format!(
+            "{}-{}{}{}",
+            SUPI_PREFIX_IMSI, self.mcc, self.mnc, msin
+        ))
+    }
+
+    /// Decrypts the scheme output, per TS 33.501 C.3.3.
+    fn decrypt(&self, key: &SuciKey) -> Result<Vec<u8>, DauthError> {
+        let output = hex::decode(&self.scheme_output).or(Err(DauthError::DataError(
+            "Invalid scheme output".to_string(),
+        )))?;
+
+        let public_key_length = match self.scheme {
+            ProtectionScheme::ProfileA => PROFILE_A_PUBLIC_KEY_LENGTH,
+            _ => PROFILE_B_PUBLIC_KEY_LENGTH,
+        };
+        if output.len() <= public_key_length + MAC_TAG_LENGTH {
+            return Err(DauthError::DataError("Scheme output too short".to_string()));
+        }
+
+        let ephemeral_public_key = &output[..public_key_length];
+        let ciphertext = &output[public_key_length..(output.len() - MAC_TAG_LENGTH)];
+        let mac_tag = &output[(output.len() - MAC_TAG_LENGTH)..];
+
+        let keys = ansi_x963_kdf(
+            &key.shared_secret(ephemeral_public_key)?,
+            ephemeral_public_key,
+            ENC_KEY_LENGTH + ICB_LENGTH + MAC_KEY_LENGTH,
+        );
+        let enc_key = &keys[..ENC_KEY_LENGTH];
+        let icb = &keys[ENC_KEY_LENGTH..(ENC_KEY_LENGTH + ICB_LENGTH)];
+        let mac_key = &keys[(ENC_KEY_LENGTH + ICB_LENGTH)..];
+
+        let mut mac = HmacSha256::new_from_slice(mac_key).expect("HMAC can take key of any size");
+        mac.update(ciphertext);
+        mac.verify_truncated_left(mac_tag)
+            .or(Err(DauthError::DataError(
+                "SUCI MAC verification failed".to_string(),
+            )))?;
+
+        let mut plaintext = ciphertext.to_vec();
+        Aes128Ctr::new(
+            GenericArray::from_slice(enc_key),
+            GenericArray::from_slice(icb),
+        )
+        .apply_keystream(&mut plaintext);
+
+        Ok(plaintext)
+    }
+
+    fn lookup_route<'a>(&self, routes: &'a HashMap<String, String>) -> Option<&'a String> {
+        routes
+            .get(&route_key(
+                &self.mcc,
+                &self.mnc,
+                Some(&self.routing_indicator),
+            ))
+            .or(routes.get(&route_key(&self.mcc, &self.mnc, None)))
+    }
+}
+
+/// Builds the key of a SUCI route, optionally for a single routing indicator.
+pub fn route_key(mcc: &str, mnc: &str, routing_indicator: Option<&str>) -> String {
+    match routing_indicator {
+        Some(routing_indicator) => format!("{}-{}-{}", mcc, mnc, routing_indicator),
+        None => format!("{}-{}", mcc, mnc),
+    }
+}
+
+/// X25519 function, per RFC 7748 5
+fn x25519(private_key: &[u8; PRIVATE_KEY_LENGTH], point: &MontgomeryPoint) -> [u8; 32] {
+    let mut scalar = *private_key;
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+
+    (point * Scalar::from_bits(scalar)).to_bytes()
+}
+
+/// ANSI X9.63 KDF with SHA-256, per TS 33.501 C.3.4
+fn ansi_x963_kdf(shared_secret: &[u8], shared_info: &[u8], length: usize) -> Vec<u8> {
+    let mut res = Vec::with_capacity(length);
+    let mut counter: u32 = 1;
+
+    while res.len() < length {
+        let mut hasher = Sha256::new();
+        hasher.update(shared_secret);
+        hasher.update(counter.to_be_bytes());
+        hasher.update(shared_info);
+        res.extend(hasher.finalize());
+        counter += 1;
+    }
+
+    res.truncate(length);
+    res
+}
+
+/// Decodes swapped-nibble BCD digits, ending at an 0xf filler.
+fn decode_bcd(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len() * 2);
+
+    for byte in data {
+        for nibble in [byte & 0x0f, byte >> 4] {
+            if nibble == 0x0f {
+                return res;
+            }
+            res.push(char::from_digit(nibble as u32, 16).expect("Nibble is always a digit"));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::data::suci::{route_key, ProtectionScheme, Suci, SuciKey};
+
+    // TS 33.501 C.4.3 and C.4.4
+    const PROFILE_A_PRIVATE_KEY: &str =
+        "c53c22208b61860b06c62e5406a7b330c2b577aa5558981510d128247d38bd1d";
+    const PROFILE_A_PUBLIC_KEY: &str =
+        "5a8d38864820197c3394b92613b20b91633cbd897119273bf8e4a6f4eec0a650";
+    const PROFILE_A_SUCI: &str = "suci-0-274-012-0-1-1-\
+        b2e92f836055a255837debf850b528997ce0201cb82adfe4be1f587d07d8457d\
+        cb02352410cddd9e730ef3fa87";
+    const PROFILE_B_PRIVATE_KEY: &str =
+        "f1ab1074477ebcc7f554ea1c5fc368b1616730155e0041ac447d6301975fecda";
+    const PROFILE_B_PUBLIC_KEY: &str =
+        "0272da71976234ce833a6907425867b82e074d44ef907dfb4b3e21c1c2256ebcd1";
+    const PROFILE_B_SUCI: &str = "suci-0-274-012-0-2-2-\
+        039aab8376597021e855679a9778ea0b67396e68c66df32c0f41e9acca2da9b9d1\
+        46a33fc2716ac7dae96aa30a4d";
+    const SUPI: &str = "imsi-274012001002086";
+
+    fn build_keys() -> HashMap<u8, SuciKey> {
+        let mut keys = HashMap::new();
+        keys.insert(
+            1,
+            SuciKey::new(
+                ProtectionScheme::ProfileA,
+                &hex::decode(PROFILE_A_PRIVATE_KEY).unwrap(),
+            )
+            .unwrap(),
+        );
+        keys.insert(
+            2,
+            SuciKey::new(
+                ProtectionScheme::ProfileB,
+                &hex::decode(PROFILE_B_PRIVATE_KEY).unwrap(),
+            )
+            .unwrap(),
+        );
+        keys
+    }
+
+    #[test]
+    fn test_parse() {
+        let suci = Suci::try_from("suci-0-901-70-0001-0-0-0000000001").unwrap();
+        assert_eq!("901", suci.mcc);
+        assert_eq!("70", suci.mnc);
+        assert_eq!("0001", suci.routing_indicator);
+        assert_eq!(ProtectionScheme::Null, suci.scheme);
+        assert_eq!(0, suci.key_id);
+        assert_eq!("0000000001", suci.scheme_output);
+
+        assert!(Suci::try_from(SUPI).is_err());
+        assert!(Suci::try_from("suci-1-901-70-0001-0-0-0000000001").is_err());
+        assert!(Suci::try_from("suci-0-901-70-0001-3-0-0000000001").is_err());
+        assert!(Suci::try_from("suci-0-9a1-70-0001-0-0-0000000001").is_err());
+        assert!(Suci::try_from("suci-0-901-70-0001-0-0").is_err());
+    }
+
+    #[test]
+    fn test_public_keys() {
+        let keys = build_keys();
+
+        assert_eq!(
+            PROFILE_A_PUBLIC_KEY,
+            hex::encode(keys[&1].public_key().unwrap())
+        );
+        assert_eq!(
+            PROFILE_B_PUBLIC_KEY,
+            hex::encode(keys[&2].public_key().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deconceal() {
+        let keys = build_keys();
+
+        let suci = Suci::try_from(PROFILE_A_SUCI).unwrap();
+        assert_eq!(SUPI, suci.deconceal(&keys).unwrap());
+
+        let suci = Suci::try_from(PROFILE_B_SUCI).unwrap();
+        assert_eq!(SUPI, suci.deconceal(&keys).unwrap());
+
+        let suci = Suci::try_from("suci-0-274-012-0-0-0-001002086").unwrap();
+        assert_eq!(SUPI, suci.deconceal(&HashMap::new()).unwrap());
+    }
+
+    #[test]
+    fn test_deconceal_failures() {
+        let keys = build_keys();
+
+        // Modified MAC tag
+        let suci = Suci::try_from(&PROFILE_A_SUCI.replace("fa87", "fa88")[..]).unwrap();
+        assert!(suci.deconceal(&keys).is_err());
+
+        // Unknown key id
+        let suci = Suci::try_from(&PROFILE_A_SUCI.replace("-1-1-", "-1-3-")[..]).unwrap();
+        assert!(suci.deconceal(&keys).is_err());
+
+        // Key id of a key for the other profile
+        let suci = Suci::try_from(&PROFILE_A_SUCI.replace("-1-1-", "-1-2-")[..]).unwrap();
+        assert!(suci.deconceal(&keys).is_err());
+
+        // Truncated scheme output
+        let suci = Suci::try_from("suci-0-274-012-0-1-1-b2e92f83").unwrap();
+        assert!(suci.deconceal(&keys).is_err());
+    }
+
+    #[test]
+    fn test_lookup_route() {
+        let mut routes = HashMap::new();
+        routes.insert(route_key("274", "012", None), "network_a".to_string());
+        routes.insert(route_key("274", "012", Some("1")), "network_b".to_string());
+
+        let suci = Suci::try_from("suci-0-274-012-0-0-0-001002086").unwrap();
+        assert_eq!(Some(&"network_a".to_string()), suci.lookup_route(&routes));
+
+        let suci = Suci::try_from("suci-0-274-012-1-0-0-001002086").unwrap();
+        assert_eq!(Some(&"network_b".to_string()), suci.lookup_route(&routes));
+
+        let suci = Suci::try_from("suci-0-901-70-1-0-0-001002086").unwrap();
+        assert_eq!(None, suci.lookup_route(&routes));
+    }
+}
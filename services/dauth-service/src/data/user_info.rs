@@ -1,10 +1,12 @@
-use auth_vector::types::{Opc, K};
+use auth_vector::types::{Algorithm, K};
 
 /// Holds sensitive user info needed for auth vector generation
 #[derive(Debug)]
 pub struct UserInfo {
     pub id: String,
     pub k: K,
-    pub opc: Opc,
+    /// OPc for Milenage or TOPc for TUAK
+    pub opc: Vec<u8>,
+    pub algorithm: Algorithm,
     pub sqn: i64,
 }
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use crate::data::error::DauthError;
 
 /// Converts hex string to byte vec
@@ -74,6 +76,14 @@ pub fn _convert_int_string_to_byte_vec_with_length(
     zero_pad(_convert_int_string_to_byte_vec(s)?, length)
 }
 
+/// Returns the current time in seconds since the unix epoch
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
 #[cfg(test)]
 mod tests {
     use crate::data::utilities;
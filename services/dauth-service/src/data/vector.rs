@@ -1,12 +1,55 @@
-use auth_vector::types::{Autn, Id, Rand, XResHash, XResStarHash};
+use auth_vector::types::{Autn, Id, KAut, KEncr, Rand, XResHash, XResStarHash};
 
 use crate::data::error::DauthError;
-use crate::rpc::dauth::common::AuthVector5G;
-use crate::rpc::dauth::local::AkaVectorResp;
+use crate::rpc::dauth::common::{
+    AuthVector5G, AuthVectorEapAkaPrime, AuthVectorEps, ServingPlmn, UserIdKind,
+};
+use crate::rpc::dauth::local::{aka_vector_resp, AkaVectorResp, EapAkaPrimeVectorResp};
+
+/// The PLMN of a serving network, which vector keys are bound to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plmn {
+    pub mcc: String,
+    pub mnc: String,
+}
+
+impl Plmn {
+    pub fn to_serving_plmn(&self) -> ServingPlmn {
+        ServingPlmn {
+            mcc: self.mcc.clone(),
+            mnc: self.mnc.clone(),
+        }
+    }
+}
+
+impl From<ServingPlmn> for Plmn {
+    fn from(value: ServingPlmn) -> Self {
+        Plmn {
+            mcc: value.mcc,
+            mnc: value.mnc,
+        }
+    }
+}
+
+/// A vector whose keys were split into shares for backup networks.
+/// Kept by the home network so that the keys can be derived again from
+/// the user's K to refresh the shares, without storing the keys.
+/// The epoch counts the times the keys were split, and is signed with
+/// each share so that shares of different splits are never combined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegatedVector {
+    pub xres_star_hash: XResStarHash,
+    pub xres_hash: XResHash,
+    pub rand: Rand,
+    pub autn: Autn,
+    pub plmn: Plmn,
+    pub epoch: u32,
+}
 
 #[derive(Debug)]
 pub struct AuthVectorReq {
     pub user_id: Id,
+    pub plmn: Plmn,
 }
 
 #[derive(Debug)]
@@ -17,21 +60,52 @@ pub struct AuthVectorRes {
     pub xres_hash: XResHash,
     pub autn: Autn,
     pub rand: Rand,
+    pub plmn: Plmn,
 }
 
 impl AuthVectorRes {
-    pub fn to_resp(&self) -> AkaVectorResp {
+    pub fn to_resp(&self, user_id_kind: UserIdKind) -> AkaVectorResp {
+        let user_id_type = match user_id_kind {
+            UserIdKind::Suci => aka_vector_resp::UserIdKind::Suci,
+            _ => aka_vector_resp::UserIdKind::Supi,
+        };
+
         AkaVectorResp {
             user_id: self.user_id.clone().into_bytes(),
-            user_id_type: 1,
+            user_id_type: user_id_type as i32,
             error: 0,
-            auth_vector: Some(AuthVector5G {
-                rand: self.rand.to_vec(),
-                xres_star_hash: self.xres_star_hash.to_vec(),
-                autn: self.autn.to_vec(),
-                seqnum: self.seqnum,
-                xres_hash: self.xres_hash.to_vec(),
-            }),
+            auth_vector: Some(self.to_av5_g()),
+            eps_auth_vector: None,
+        }
+    }
+
+    /// Builds the response for an EPS-AKA vector request.
+    pub fn to_eps_resp(&self, user_id_kind: UserIdKind) -> AkaVectorResp {
+        AkaVectorResp {
+            auth_vector: None,
+            eps_auth_vector: Some(self.to_eps()),
+            ..self.to_resp(user_id_kind)
+        }
+    }
+
+    pub fn to_av5_g(&self) -> AuthVector5G {
+        AuthVector5G {
+            rand: self.rand.to_vec(),
+            xres_star_hash: self.xres_star_hash.to_vec(),
+            autn: self.autn.to_vec(),
+            seqnum: self.seqnum,
+            xres_hash: self.xres_hash.to_vec(),
+            plmn: Some(self.plmn.to_serving_plmn()),
+        }
+    }
+
+    pub fn to_eps(&self) -> AuthVectorEps {
+        AuthVectorEps {
+            rand: self.rand.to_vec(),
+            xres_hash: self.xres_hash.to_vec(),
+            autn: self.autn.to_vec(),
+            seqnum: self.seqnum,
+            plmn: Some(self.plmn.to_serving_plmn()),
         }
     }
 
@@ -43,6 +117,65 @@ impl AuthVectorRes {
             autn: vector.autn[..].try_into()?,
             rand: vector.rand[..].try_into()?,
             xres_hash: vector.xres_hash[..].try_into()?,
+            plmn: vector
+                .plmn
+                .ok_or(DauthError::InvalidMessageError(
+                    "Missing vector plmn".to_string(),
+                ))?
+                .into(),
+        })
+    }
+}
+
+/// An EAP-AKA' vector bound to an access network name.
+/// Carries K_encr and K_aut, which the EAP server needs for the challenge.
+#[derive(Debug)]
+pub struct EapAkaPrimeVectorRes {
+    pub user_id: Id,
+    pub seqnum: i64,
+    pub network_name: String,
+    pub xres_hash: XResHash,
+    pub autn: Autn,
+    pub rand: Rand,
+    pub k_encr: KEncr,
+    pub k_aut: KAut,
+}
+
+impl EapAkaPrimeVectorRes {
+    pub fn to_eap_aka_prime(&self) -> AuthVectorEapAkaPrime {
+        AuthVectorEapAkaPrime {
+            rand: self.rand.to_vec(),
+            xres_hash: self.xres_hash.to_vec(),
+            autn: self.autn.to_vec(),
+            seqnum: self.seqnum,
+            network_name: self.network_name.clone(),
+            k_encr: self.k_encr.to_vec(),
+            k_aut: self.k_aut.to_vec(),
+        }
+    }
+
+    pub fn to_resp(&self, user_id_kind: UserIdKind) -> EapAkaPrimeVectorResp {
+        EapAkaPrimeVectorResp {
+            user_id: self.user_id.clone().into_bytes(),
+            user_id_type: user_id_kind as i32,
+            error: 0,
+            auth_vector: Some(self.to_eap_aka_prime()),
+        }
+    }
+
+    pub fn from_eap_aka_prime(
+        user_id: &str,
+        vector: AuthVectorEapAkaPrime,
+    ) -> Result<EapAkaPrimeVectorRes, DauthError> {
+        Ok(EapAkaPrimeVectorRes {
+            user_id: user_id.to_string(),
+            seqnum: vector.seqnum,
+            network_name: vector.network_name,
+            xres_hash: vector.xres_hash[..].try_into()?,
+            autn: vector.autn[..].try_into()?,
+            rand: vector.rand[..].try_into()?,
+            k_encr: vector.k_encr[..].try_into()?,
+            k_aut: vector.k_aut[..].try_into()?,
         })
     }
 }
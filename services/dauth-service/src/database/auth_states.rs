@@ -0,0 +1,295 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+use crate::data::state::AuthState;
+use crate::database::utilities::DauthDataUtilities;
+
+/// Creates the auth state table if it does not exist already.
+/// Contains the state of all outstanding auth challenges
+/// issued to users of other networks.
+#[tracing::instrument(skip(pool), name = "database::auth_states")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS auth_state_table (
+            xres_star_hash BLOB PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            rand BLOB NOT NULL,
+            xres_hash BLOB NOT NULL,
+            source INT NOT NULL,
+            expiration INT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_auth_state_user_id
+        ON auth_state_table (user_id);",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds the state of an auth challenge, valid until the expiration time.
+/// Replaces any existing state for the same challenge.
+#[tracing::instrument(skip(transaction), name = "database::auth_states")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    state: &AuthState,
+    expiration: i64,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding auth state");
+
+    sqlx::query(
+        "REPLACE INTO auth_state_table
+        VALUES ($1,$2,$3,$4,$5,$6)",
+    )
+    .bind(&state.xres_star_hash[..])
+    .bind(user_id)
+    .bind(&state.rand.as_array()[..])
+    .bind(&state.xres_hash[..])
+    .bind(state.source.to_i64())
+    .bind(expiration)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns all unexpired auth states of a user, newest first.
+#[tracing::instrument(skip(transaction), name = "database::auth_states")]
+pub async fn get_all_by_user(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    timestamp: i64,
+) -> Result<Vec<AuthState>, DauthError> {
+    tracing::debug!("Getting all auth states for user");
+
+    let rows = sqlx::query(
+        "SELECT * FROM auth_state_table
+        WHERE user_id=$1 AND expiration>=$2
+        ORDER BY expiration DESC;",
+    )
+    .bind(user_id)
+    .bind(timestamp)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut res = Vec::with_capacity(rows.len());
+    for row in rows {
+        res.push(row.to_auth_state()?);
+    }
+    Ok(res)
+}
+
+/// Removes the auth state of a challenge.
+#[tracing::instrument(skip(transaction), name = "database::auth_states")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing auth state");
+
+    sqlx::query(
+        "DELETE FROM auth_state_table
+        WHERE xres_star_hash=$1",
+    )
+    .bind(xres_star_hash)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes all auth states that expired before the provided time.
+#[tracing::instrument(skip(transaction), name = "database::auth_states")]
+pub async fn remove_expired(
+    transaction: &mut Transaction<'_, Sqlite>,
+    timestamp: i64,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing expired auth states");
+
+    sqlx::query(
+        "DELETE FROM auth_state_table
+        WHERE expiration<$1",
+    )
+    .bind(timestamp)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use auth_vector::types::{RAND_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH};
+
+    use crate::data::state::{AuthSource, AuthState};
+    use crate::database::{auth_states, general};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        auth_states::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn gen_state(value: u8, source: AuthSource) -> AuthState {
+        AuthState {
+            rand: [value; RAND_LENGTH][..].try_into().unwrap(),
+            source,
+            xres_star_hash: [value; XRES_STAR_HASH_LENGTH],
+            xres_hash: [value; XRES_HASH_LENGTH],
+        }
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Test that several outstanding states per user are kept
+    #[tokio::test]
+    async fn test_get_all_by_user() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            let source = if row % 2 == 0 {
+                AuthSource::HomeNetwork
+            } else {
+                AuthSource::BackupNetwork
+            };
+            auth_states::add(
+                &mut transaction,
+                "test_user_id",
+                &gen_state(row as u8, source),
+                row,
+            )
+            .await
+            .unwrap();
+        }
+        auth_states::add(
+            &mut transaction,
+            "test_user_id_2",
+            &gen_state(num_rows as u8, AuthSource::HomeNetwork),
+            num_rows,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let states = auth_states::get_all_by_user(&mut transaction, "test_user_id", 0)
+            .await
+            .unwrap();
+        assert_eq!(states.len(), num_rows as usize);
+
+        // Newest first, with the source preserved
+        for (index, state) in states.iter().enumerate() {
+            let row = num_rows - 1 - index as i64;
+            assert_eq!(state.xres_star_hash, [row as u8; XRES_STAR_HASH_LENGTH]);
+            assert_eq!(state.rand.as_array(), [row as u8; RAND_LENGTH]);
+            assert_eq!(state.xres_hash, [row as u8; XRES_HASH_LENGTH]);
+            match state.source {
+                AuthSource::HomeNetwork => assert_eq!(row % 2, 0),
+                AuthSource::BackupNetwork => assert_eq!(row % 2, 1),
+            }
+        }
+
+        // Expired states are not returned
+        let states = auth_states::get_all_by_user(&mut transaction, "test_user_id", num_rows / 2)
+            .await
+            .unwrap();
+        assert_eq!(states.len(), (num_rows / 2) as usize);
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that removing a state leaves the others
+    #[tokio::test]
+    async fn test_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            auth_states::add(
+                &mut transaction,
+                "test_user_id",
+                &gen_state(row as u8, AuthSource::BackupNetwork),
+                row,
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        auth_states::remove(&mut transaction, &[0u8; XRES_STAR_HASH_LENGTH])
+            .await
+            .unwrap();
+        let states = auth_states::get_all_by_user(&mut transaction, "test_user_id", 0)
+            .await
+            .unwrap();
+        assert_eq!(states.len(), (num_rows - 1) as usize);
+        assert!(states
+            .iter()
+            .all(|state| state.xres_star_hash != [0u8; XRES_STAR_HASH_LENGTH]));
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that expired states are removed
+    #[tokio::test]
+    async fn test_remove_expired() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            auth_states::add(
+                &mut transaction,
+                "test_user_id",
+                &gen_state(row as u8, AuthSource::HomeNetwork),
+                row,
+            )
+            .await
+            .unwrap();
+        }
+        auth_states::remove_expired(&mut transaction, num_rows / 2)
+            .await
+            .unwrap();
+
+        let states = auth_states::get_all_by_user(&mut transaction, "test_user_id", 0)
+            .await
+            .unwrap();
+        assert_eq!(states.len(), (num_rows / 2) as usize);
+        transaction.commit().await.unwrap();
+    }
+}
@@ -1,10 +1,8 @@
 use sqlx::sqlite::SqlitePool;
 use sqlx::{Row, Sqlite, Transaction};
 
-use auth_vector::types::XResHash;
-
 use crate::data::error::DauthError;
-use crate::data::vector::AuthVectorRes;
+use crate::data::vector::{AuthVectorRes, Plmn};
 use crate::database::utilities::DauthDataUtilities;
 
 /// Creates the auth vector table if it does not exist already.
@@ -20,6 +18,8 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
             xres_hash BLOB NOT NULL,
             autn BLOB NOT NULL,
             rand BLOB NOT NULL,
+            mcc TEXT NOT NULL,
+            mnc TEXT NOT NULL,
             sent INTEGER NOT NULL,
             PRIMARY KEY (user_id, seqnum)
         );",
@@ -50,25 +50,23 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
 #[tracing::instrument(skip(transaction), name = "database::auth_vectors")]
 pub async fn add(
     transaction: &mut Transaction<'_, Sqlite>,
-    id: &str,
-    seqnum: i64,
-    xres_star_hash: &[u8],
-    xres_hash: &XResHash,
-    autn: &[u8],
-    rand: &[u8],
+    vector: &AuthVectorRes,
 ) -> Result<(), DauthError> {
     tracing::debug!("Adding auth vector");
 
     sqlx::query(
         "INSERT INTO auth_vector_table
-        VALUES ($1,$2,$3,$4,$5,$6,FALSE)",
+        (user_id,seqnum,xres_star_hash,xres_hash,autn,rand,mcc,mnc,sent)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,FALSE)",
     )
-    .bind(id)
-    .bind(seqnum)
-    .bind(xres_star_hash)
-    .bind(xres_hash.as_slice())
-    .bind(autn)
-    .bind(rand)
+    .bind(&vector.user_id)
+    .bind(vector.seqnum)
+    .bind(vector.xres_star_hash.as_slice())
+    .bind(vector.xres_hash.as_slice())
+    .bind(vector.autn.as_slice())
+    .bind(vector.rand.to_vec())
+    .bind(&vector.plmn.mcc)
+    .bind(&vector.plmn.mnc)
     .execute(transaction)
     .await?;
 
@@ -95,6 +93,30 @@ pub async fn get_first(
     .to_auth_vector()?)
 }
 
+/// Returns the first for a given id with keys bound to the PLMN,
+/// sorted by rank (seqnum).
+#[tracing::instrument(skip(transaction), name = "database::auth_vectors")]
+pub async fn get_first_by_plmn(
+    transaction: &mut Transaction<'_, Sqlite>,
+    id: &str,
+    plmn: &Plmn,
+) -> Result<AuthVectorRes, DauthError> {
+    tracing::debug!("Getting first auth vector for plmn");
+
+    Ok(sqlx::query(
+        "SELECT * FROM auth_vector_table
+        WHERE (user_id,mcc,mnc)=($1,$2,$3)
+        ORDER BY seqnum
+        LIMIT 1;",
+    )
+    .bind(id)
+    .bind(&plmn.mcc)
+    .bind(&plmn.mnc)
+    .fetch_one(transaction)
+    .await?
+    .to_auth_vector()?)
+}
+
 /// Returns the auth vector with the corresponding xres_star_hash.
 /// Not currently used.
 #[allow(dead_code)]
@@ -138,6 +160,27 @@ pub async fn mark_sent(
     Ok(())
 }
 
+/// Counts the auth vectors stored for users of a home network.
+#[tracing::instrument(skip(transaction), name = "database::auth_vectors")]
+pub async fn count_by_home_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<i64, DauthError> {
+    tracing::debug!("Counting auth vectors of home network");
+
+    Ok(sqlx::query(
+        "SELECT count(*) as count FROM auth_vector_table
+        WHERE user_id IN (
+            SELECT user_id FROM backup_users_table
+            WHERE home_network_id=$1
+        );",
+    )
+    .bind(home_network_id)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<i64, &str>("count")?)
+}
+
 /// Removes the vector with the (user_id, xres_star_hash) pair.
 #[tracing::instrument(skip(transaction), name = "database::auth_vectors")]
 pub async fn remove(
@@ -199,7 +242,8 @@ mod tests {
 
     use auth_vector::types::{AUTN_LENGTH, RAND_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH};
 
-    use crate::database::{auth_vectors, general};
+    use crate::data::vector::{AuthVectorRes, Plmn};
+    use crate::database::{auth_vectors, backup_users, general};
 
     fn gen_name() -> String {
         let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
@@ -218,6 +262,30 @@ mod tests {
         (pool, dir)
     }
 
+    fn gen_plmn(mnc: &str) -> Plmn {
+        Plmn {
+            mcc: "901".to_string(),
+            mnc: mnc.to_string(),
+        }
+    }
+
+    fn gen_vector(
+        user_id: &str,
+        seqnum: i64,
+        xres_star_hash: &[u8],
+        xres_hash: &[u8],
+    ) -> AuthVectorRes {
+        AuthVectorRes {
+            user_id: user_id.to_string(),
+            seqnum,
+            xres_star_hash: xres_star_hash.try_into().unwrap(),
+            xres_hash: xres_hash.try_into().unwrap(),
+            autn: [0_u8; AUTN_LENGTH],
+            rand: [0_u8; RAND_LENGTH][..].try_into().unwrap(),
+            plmn: gen_plmn("70"),
+        }
+    }
+
     /// Test that db and table creation will work
     #[tokio::test]
     async fn test_db_init() {
@@ -253,12 +321,12 @@ mod tests {
             for row in 0..num_rows {
                 auth_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -277,24 +345,24 @@ mod tests {
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
@@ -311,36 +379,36 @@ mod tests {
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            2,
-            &[2_u8; XRES_STAR_HASH_LENGTH],
-            &[2_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                2,
+                &[2_u8; XRES_STAR_HASH_LENGTH],
+                &[2_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            0,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                0,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[1_u8; XRES_STAR_HASH_LENGTH],
-            &[1_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[1_u8; XRES_STAR_HASH_LENGTH],
+                &[1_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
@@ -359,6 +427,51 @@ mod tests {
         transaction.commit().await.unwrap();
     }
 
+    /// Test that only vectors bound to the plmn are returned
+    #[tokio::test]
+    async fn test_get_first_by_plmn() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+
+        for row in 0..4 {
+            let mut vector = gen_vector(
+                "test_id_1",
+                row,
+                &[row as u8; XRES_STAR_HASH_LENGTH],
+                &[row as u8; XRES_HASH_LENGTH],
+            );
+            vector.plmn = gen_plmn(&format!("{}", 70 + row % 2));
+            auth_vectors::add(&mut transaction, &vector).await.unwrap();
+        }
+
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+
+        let res = auth_vectors::get_first_by_plmn(&mut transaction, "test_id_1", &gen_plmn("71"))
+            .await
+            .unwrap();
+
+        assert_eq!(1, res.seqnum);
+        assert_eq!(gen_plmn("71"), res.plmn);
+
+        let res = auth_vectors::get_first(&mut transaction, "test_id_1")
+            .await
+            .unwrap();
+
+        assert_eq!(0, res.seqnum);
+        assert_eq!(gen_plmn("70"), res.plmn);
+
+        assert!(
+            auth_vectors::get_first_by_plmn(&mut transaction, "test_id_1", &gen_plmn("72"))
+                .await
+                .is_err()
+        );
+
+        transaction.commit().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_get_by_hash() {
         let (pool, _dir) = init().await;
@@ -371,12 +484,7 @@ mod tests {
 
         auth_vectors::add(
             &mut transaction,
-            "test_id_1",
-            0,
-            &good_hash,
-            &good_hash,
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector("test_id_1", 0, &good_hash, &good_hash),
         )
         .await
         .unwrap();
@@ -415,12 +523,12 @@ mod tests {
             for row in 0..num_rows {
                 auth_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -464,12 +572,12 @@ mod tests {
             for row in 0..num_rows {
                 auth_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -519,12 +627,12 @@ mod tests {
             for row in 0..num_rows {
                 auth_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -560,4 +668,49 @@ mod tests {
 
         transaction.commit().await.unwrap();
     }
+
+    /// Test that vectors are counted by the home network of their user
+    #[tokio::test]
+    async fn test_count_by_home_network() {
+        let (pool, _dir) = init().await;
+        backup_users::init_table(&pool).await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        backup_users::add(&mut transaction, "test_id_0", "test_home_network_0")
+            .await
+            .unwrap();
+        backup_users::add(&mut transaction, "test_id_1", "test_home_network_1")
+            .await
+            .unwrap();
+
+        for (user, num_rows) in [(0, 3), (1, 2), (2, 4)] {
+            for row in 0..num_rows {
+                auth_vectors::add(
+                    &mut transaction,
+                    &gen_vector(
+                        &format!("test_id_{}", user),
+                        row,
+                        &[(user * 10 + row) as u8; XRES_STAR_HASH_LENGTH],
+                        &[(user * 10 + row) as u8; XRES_HASH_LENGTH],
+                    ),
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        for (home_network_id, count) in [
+            ("test_home_network_0", 3),
+            ("test_home_network_1", 2),
+            ("test_home_network_2", 0),
+        ] {
+            assert_eq!(
+                count,
+                auth_vectors::count_by_home_network(&mut transaction, home_network_id)
+                    .await
+                    .unwrap()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
 }
@@ -92,8 +92,6 @@ pub async fn get_slice(
 }
 
 /// Removes the network as a backup for this network
-/// Not currently used.
-#[allow(dead_code)]
 #[tracing::instrument(skip(transaction), name = "database::backup_networks")]
 pub async fn remove(
     transaction: &mut Transaction<'_, Sqlite>,
@@ -113,6 +111,47 @@ pub async fn remove(
     Ok(())
 }
 
+/// Gets the ids of all backup networks for a given user id
+#[tracing::instrument(skip(transaction), name = "database::backup_networks")]
+pub async fn get_all_by_user(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<Vec<String>, DauthError> {
+    tracing::debug!("Getting all backups for user");
+
+    let rows = sqlx::query(
+        "SELECT backup_network_id FROM backup_networks_table
+        WHERE user_id=$1;",
+    )
+    .bind(user_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(row.try_get::<String, &str>("backup_network_id")?);
+    }
+    Ok(result)
+}
+
+/// Removes all backup networks for a given user id
+#[tracing::instrument(skip(transaction), name = "database::backup_networks")]
+pub async fn remove_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing all backup info for user");
+
+    sqlx::query(
+        "DELETE FROM backup_networks_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -265,4 +304,59 @@ mod tests {
         }
         transaction.commit().await.unwrap();
     }
+
+    /// Test that removing all backups for a user works
+    #[tokio::test]
+    async fn test_remove_all() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+
+        let num_rows = 10;
+        let num_sections = 10;
+
+        for section in 0..num_sections {
+            for row in 0..num_rows {
+                backup_networks::upsert(
+                    &mut transaction,
+                    &format!("test_user_id_{}", row),
+                    &format!("test_network_id_{}", section),
+                    section,
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        transaction.commit().await.unwrap();
+        let mut transaction = pool.begin().await.unwrap();
+
+        for row in 0..num_rows {
+            let res = backup_networks::get_all_by_user(
+                &mut transaction,
+                &format!("test_user_id_{}", row),
+            )
+            .await
+            .unwrap();
+            assert_eq!(res.len(), num_sections as usize);
+
+            backup_networks::remove_all(&mut transaction, &format!("test_user_id_{}", row))
+                .await
+                .unwrap();
+        }
+
+        transaction.commit().await.unwrap();
+        let mut transaction = pool.begin().await.unwrap();
+
+        for row in 0..num_rows {
+            assert!(backup_networks::get_all_by_user(
+                &mut transaction,
+                &format!("test_user_id_{}", row)
+            )
+            .await
+            .unwrap()
+            .is_empty());
+        }
+        transaction.commit().await.unwrap();
+    }
 }
@@ -1,5 +1,5 @@
 use sqlx::sqlite::SqlitePool;
-use sqlx::{Sqlite, Transaction};
+use sqlx::{Row, Sqlite, Transaction};
 
 use crate::data::error::DauthError;
 use crate::database::utilities::DauthDataUtilities;
@@ -63,6 +63,45 @@ pub async fn get(
     .to_backup_user_home_network_id()?)
 }
 
+/// Counts the users backed up on this network for a home network
+#[tracing::instrument(skip(transaction), name = "database::backup_users")]
+pub async fn count_by_home_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<i64, DauthError> {
+    tracing::debug!("Counting backup users of home network");
+
+    Ok(sqlx::query(
+        "SELECT count(*) as count FROM backup_users_table
+        WHERE home_network_id=$1;",
+    )
+    .bind(home_network_id)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<i64, &str>("count")?)
+}
+
+/// Gets every home network with users backed up on this network
+#[tracing::instrument(skip(transaction), name = "database::backup_users")]
+pub async fn get_home_network_ids(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<String>, DauthError> {
+    tracing::debug!("Getting home networks of backup users");
+
+    let rows = sqlx::query(
+        "SELECT DISTINCT home_network_id FROM backup_users_table
+        ORDER BY home_network_id;",
+    )
+    .fetch_all(transaction)
+    .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(row.to_backup_user_home_network_id()?);
+    }
+    Ok(result)
+}
+
 /// Removes the user id from the backups
 #[tracing::instrument(skip(transaction), name = "database::backup_users")]
 pub async fn remove(
@@ -209,4 +248,41 @@ mod tests {
         }
         transaction.commit().await.unwrap();
     }
+
+    /// Tests that users are counted and listed by home network
+    #[tokio::test]
+    async fn test_home_networks() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..5 {
+            backup_users::add(
+                &mut transaction,
+                &format!("test_user_id_{}", row),
+                &format!("test_home_network_{}", row % 2),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            vec!["test_home_network_0", "test_home_network_1"],
+            backup_users::get_home_network_ids(&mut transaction)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            3,
+            backup_users::count_by_home_network(&mut transaction, "test_home_network_0")
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            0,
+            backup_users::count_by_home_network(&mut transaction, "test_home_network_2")
+                .await
+                .unwrap()
+        );
+        transaction.commit().await.unwrap();
+    }
 }
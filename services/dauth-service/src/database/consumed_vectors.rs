@@ -0,0 +1,216 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Row, Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the consumed vector table if it does not exist already.
+/// Contains the signed serving network request of each vector that a
+/// backup network reported as used, kept as proof of the report.
+#[tracing::instrument(skip(pool), name = "database::consumed_vectors")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS consumed_vector_table (
+            xres_star_hash BLOB PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            backup_network_id TEXT NOT NULL,
+            signed_request BLOB NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_consumed_vector_signed_request
+        ON consumed_vector_table (signed_request);",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds the report of a consumed vector.
+#[tracing::instrument(skip(transaction, signed_request), name = "database::consumed_vectors")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+    user_id: &str,
+    backup_network_id: &str,
+    signed_request: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding consumed vector");
+
+    sqlx::query(
+        "INSERT INTO consumed_vector_table
+        VALUES ($1,$2,$3,$4)",
+    )
+    .bind(xres_star_hash)
+    .bind(user_id)
+    .bind(backup_network_id)
+    .bind(signed_request)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the reporting network and signed request of a consumed vector.
+#[tracing::instrument(skip(transaction), name = "database::consumed_vectors")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<Option<(String, Vec<u8>)>, DauthError> {
+    tracing::debug!("Getting consumed vector");
+
+    let possible_row = sqlx::query(
+        "SELECT * FROM consumed_vector_table
+        WHERE xres_star_hash=$1;",
+    )
+    .bind(xres_star_hash)
+    .fetch_optional(transaction)
+    .await?;
+
+    match possible_row {
+        Some(row) => Ok(Some((
+            row.try_get::<String, &str>("backup_network_id")?,
+            row.try_get::<Vec<u8>, &str>("signed_request")?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Returns whether the signed request was already reported for any vector.
+#[tracing::instrument(skip(transaction, signed_request), name = "database::consumed_vectors")]
+pub async fn is_request_reported(
+    transaction: &mut Transaction<'_, Sqlite>,
+    signed_request: &[u8],
+) -> Result<bool, DauthError> {
+    tracing::debug!("Checking for reported request");
+
+    let possible_row = sqlx::query(
+        "SELECT xres_star_hash FROM consumed_vector_table
+        WHERE signed_request=$1;",
+    )
+    .bind(signed_request)
+    .fetch_optional(transaction)
+    .await?;
+
+    Ok(possible_row.is_some())
+}
+
+/// Removes all consumed vectors of a user.
+#[tracing::instrument(skip(transaction), name = "database::consumed_vectors")]
+pub async fn remove_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing all consumed vectors for user");
+
+    sqlx::query(
+        "DELETE FROM consumed_vector_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::database::{consumed_vectors, general};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        consumed_vectors::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Tests that added reports are returned and removed by user
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            consumed_vectors::add(
+                &mut transaction,
+                &[row; 16],
+                &format!("test_user_{}", row % 2),
+                &format!("test_network_{}", row),
+                &[row; 32],
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert_eq!(
+                Some((format!("test_network_{}", row), vec![row; 32])),
+                consumed_vectors::get(&mut transaction, &[row; 16])
+                    .await
+                    .unwrap()
+            );
+        }
+        assert!(consumed_vectors::get(&mut transaction, &[num_rows; 16])
+            .await
+            .unwrap()
+            .is_none());
+        assert!(
+            consumed_vectors::is_request_reported(&mut transaction, &[0; 32])
+                .await
+                .unwrap()
+        );
+        assert!(
+            !consumed_vectors::is_request_reported(&mut transaction, &[num_rows; 32])
+                .await
+                .unwrap()
+        );
+
+        consumed_vectors::remove_all(&mut transaction, "test_user_0")
+            .await
+            .unwrap();
+        for row in 0..num_rows {
+            assert_eq!(
+                row % 2 == 1,
+                consumed_vectors::get(&mut transaction, &[row; 16])
+                    .await
+                    .unwrap()
+                    .is_some()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
+}
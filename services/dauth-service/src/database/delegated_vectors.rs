@@ -0,0 +1,302 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Row, Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+use crate::data::vector::{DelegatedVector, Plmn};
+
+/// Creates the delegated vector table if it does not exist already.
+/// Contains the vectors given to backup networks, so that their keys
+/// can be derived again to refresh their key shares while the vectors
+/// are outstanding. The keys themselves are never stored.
+#[tracing::instrument(skip(pool), name = "database::delegated_vectors")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS delegated_vector_table (
+            xres_star_hash BLOB PRIMARY KEY,
+            xres_hash BLOB NOT NULL,
+            user_id TEXT NOT NULL,
+            rand BLOB NOT NULL,
+            autn BLOB NOT NULL,
+            mcc TEXT NOT NULL,
+            mnc TEXT NOT NULL,
+            epoch INT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a delegated vector.
+#[tracing::instrument(skip(transaction, vector), name = "database::delegated_vectors")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    vector: &DelegatedVector,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding delegated vector");
+
+    sqlx::query(
+        "INSERT INTO delegated_vector_table
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8)",
+    )
+    .bind(vector.xres_star_hash.as_slice())
+    .bind(vector.xres_hash.as_slice())
+    .bind(user_id)
+    .bind(vector.rand.as_array().as_slice())
+    .bind(vector.autn.as_slice())
+    .bind(&vector.plmn.mcc)
+    .bind(&vector.plmn.mnc)
+    .bind(i64::from(vector.epoch))
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns all delegated vectors of a user.
+#[tracing::instrument(skip(transaction), name = "database::delegated_vectors")]
+pub async fn get_all_by_user(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<Vec<DelegatedVector>, DauthError> {
+    tracing::debug!("Getting all delegated vectors for user");
+
+    let rows = sqlx::query(
+        "SELECT * FROM delegated_vector_table
+        WHERE user_id=$1;",
+    )
+    .bind(user_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(DelegatedVector {
+            xres_star_hash: row.try_get::<&[u8], &str>("xres_star_hash")?.try_into()?,
+            xres_hash: row.try_get::<&[u8], &str>("xres_hash")?.try_into()?,
+            rand: row.try_get::<&[u8], &str>("rand")?.try_into()?,
+            autn: row.try_get::<&[u8], &str>("autn")?.try_into()?,
+            plmn: Plmn {
+                mcc: row.try_get::<String, &str>("mcc")?,
+                mnc: row.try_get::<String, &str>("mnc")?,
+            },
+            epoch: row.try_get::<i64, &str>("epoch")? as u32,
+        });
+    }
+    Ok(result)
+}
+
+/// Returns the ids of all users with delegated vectors.
+#[tracing::instrument(skip(transaction), name = "database::delegated_vectors")]
+pub async fn get_user_ids(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<String>, DauthError> {
+    tracing::debug!("Getting all user ids");
+
+    let rows = sqlx::query("SELECT DISTINCT user_id FROM delegated_vector_table")
+        .fetch_all(transaction)
+        .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(row.try_get::<String, &str>("user_id")?);
+    }
+    Ok(result)
+}
+
+/// Sets the epoch of the latest key shares of a delegated vector.
+#[tracing::instrument(skip(transaction), name = "database::delegated_vectors")]
+pub async fn set_epoch(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+    epoch: u32,
+) -> Result<(), DauthError> {
+    tracing::debug!("Setting delegated vector epoch");
+
+    sqlx::query(
+        "UPDATE delegated_vector_table
+        SET epoch=$2
+        WHERE xres_star_hash=$1",
+    )
+    .bind(xres_star_hash)
+    .bind(i64::from(epoch))
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a delegated vector if found.
+#[tracing::instrument(skip(transaction), name = "database::delegated_vectors")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing delegated vector");
+
+    sqlx::query(
+        "DELETE FROM delegated_vector_table
+        WHERE xres_star_hash=$1",
+    )
+    .bind(xres_star_hash)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes all delegated vectors of a user.
+#[tracing::instrument(skip(transaction), name = "database::delegated_vectors")]
+pub async fn remove_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing all delegated vectors for user");
+
+    sqlx::query(
+        "DELETE FROM delegated_vector_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use auth_vector::types::{AUTN_LENGTH, RAND_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH};
+
+    use crate::data::vector::{DelegatedVector, Plmn};
+    use crate::database::{delegated_vectors, general};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        delegated_vectors::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn gen_vector(row: u8) -> DelegatedVector {
+        DelegatedVector {
+            xres_star_hash: [row; XRES_STAR_HASH_LENGTH],
+            xres_hash: [row; XRES_HASH_LENGTH],
+            rand: [row; RAND_LENGTH][..].try_into().unwrap(),
+            autn: [row; AUTN_LENGTH],
+            plmn: Plmn {
+                mcc: "901".to_string(),
+                mnc: format!("{:02}", row),
+            },
+            epoch: row.into(),
+        }
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Tests that vectors are returned by user and removed
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            delegated_vectors::add(
+                &mut transaction,
+                &format!("test_user_{}", row % 2),
+                &gen_vector(row),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut user_ids = delegated_vectors::get_user_ids(&mut transaction)
+            .await
+            .unwrap();
+        user_ids.sort();
+        assert_eq!(vec!["test_user_0", "test_user_1"], user_ids);
+
+        let mut vectors = delegated_vectors::get_all_by_user(&mut transaction, "test_user_0")
+            .await
+            .unwrap();
+        vectors.sort_by_key(|vector| vector.xres_star_hash);
+        assert_eq!(
+            (0..num_rows)
+                .step_by(2)
+                .map(gen_vector)
+                .collect::<Vec<DelegatedVector>>(),
+            vectors
+        );
+
+        delegated_vectors::remove(&mut transaction, &[0; XRES_STAR_HASH_LENGTH])
+            .await
+            .unwrap();
+        assert_eq!(
+            (num_rows / 2 - 1) as usize,
+            delegated_vectors::get_all_by_user(&mut transaction, "test_user_0")
+                .await
+                .unwrap()
+                .len()
+        );
+
+        delegated_vectors::remove_all(&mut transaction, "test_user_1")
+            .await
+            .unwrap();
+        assert!(
+            delegated_vectors::get_all_by_user(&mut transaction, "test_user_1")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    /// Tests that the epoch of a vector is updated
+    #[tokio::test]
+    async fn test_set_epoch() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        delegated_vectors::add(&mut transaction, "test_user", &gen_vector(1))
+            .await
+            .unwrap();
+        delegated_vectors::set_epoch(&mut transaction, &[1; XRES_STAR_HASH_LENGTH], 7)
+            .await
+            .unwrap();
+
+        let vectors = delegated_vectors::get_all_by_user(&mut transaction, "test_user")
+            .await
+            .unwrap();
+        assert_eq!(7, vectors[0].epoch);
+        transaction.commit().await.unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+use crate::data::keys::EapAkaPrimeSessionKeys;
+use crate::database::utilities::DauthDataUtilities;
+
+/// Creates the EAP-AKA' key table if it does not exist already.
+/// Contains the session keys of vectors generated by this network,
+/// keyed by the expected res.
+#[tracing::instrument(skip(pool), name = "database::eap_keys")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS eap_key_table (
+            eap_key_uuid BLOB PRIMARY KEY,
+            msk BLOB NOT NULL,
+            kseaf BLOB NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Inserts the session keys with a given uuid.
+#[tracing::instrument(skip(transaction, keys), name = "database::eap_keys")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    uuid: &[u8],
+    keys: &EapAkaPrimeSessionKeys,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding new EAP-AKA' keys");
+
+    sqlx::query(
+        "INSERT INTO eap_key_table
+        VALUES ($1,$2,$3)",
+    )
+    .bind(uuid)
+    .bind(&keys.msk[..])
+    .bind(&keys.kseaf[..])
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the session keys if found.
+#[tracing::instrument(skip(transaction), name = "database::eap_keys")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+    uuid: &[u8],
+) -> Result<EapAkaPrimeSessionKeys, DauthError> {
+    tracing::debug!("Getting EAP-AKA' keys");
+
+    Ok(sqlx::query(
+        "SELECT * FROM eap_key_table
+        WHERE eap_key_uuid=$1;",
+    )
+    .bind(uuid)
+    .fetch_one(transaction)
+    .await?
+    .to_eap_aka_prime_keys()?)
+}
+
+/// Deletes the session keys if found.
+#[tracing::instrument(skip(transaction), name = "database::eap_keys")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    uuid: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing EAP-AKA' keys");
+
+    sqlx::query(
+        "DELETE FROM eap_key_table
+        WHERE eap_key_uuid=$1",
+    )
+    .bind(uuid)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use auth_vector::types::{KSEAF_LENGTH, MSK_LENGTH, XRES_LENGTH};
+
+    use crate::data::keys::EapAkaPrimeSessionKeys;
+    use crate::database::{eap_keys, general};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        eap_keys::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn gen_keys(value: u8) -> EapAkaPrimeSessionKeys {
+        EapAkaPrimeSessionKeys {
+            msk: [value; MSK_LENGTH],
+            kseaf: [value + 1; KSEAF_LENGTH],
+        }
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Tests that get returns the stored keys
+    #[tokio::test]
+    async fn test_get() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            eap_keys::add(&mut transaction, &[row; XRES_LENGTH], &gen_keys(row))
+                .await
+                .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            let res = eap_keys::get(&mut transaction, &[row; XRES_LENGTH])
+                .await
+                .unwrap();
+            assert_eq!(gen_keys(row), res);
+        }
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that deletes work
+    #[tokio::test]
+    async fn test_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            eap_keys::add(&mut transaction, &[row; XRES_LENGTH], &gen_keys(row))
+                .await
+                .unwrap();
+        }
+        for row in 0..num_rows {
+            eap_keys::remove(&mut transaction, &[row; XRES_LENGTH])
+                .await
+                .unwrap();
+            assert!(eap_keys::get(&mut transaction, &[row; XRES_LENGTH])
+                .await
+                .is_err());
+        }
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that duplicate inserts cause an error
+    #[tokio::test]
+    async fn test_add_duplicate_fail() {
+        let (pool, _dir) = init().await;
+        let mut transaction = pool.begin().await.unwrap();
+
+        eap_keys::add(&mut transaction, &[0; XRES_LENGTH], &gen_keys(0))
+            .await
+            .unwrap();
+        assert!(
+            eap_keys::add(&mut transaction, &[0; XRES_LENGTH], &gen_keys(1))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -2,10 +2,8 @@ use sqlx::sqlite::SqlitePool;
 use sqlx::Error as SqlxError;
 use sqlx::{Row, Sqlite, Transaction};
 
-use auth_vector::types::XResHash;
-
 use crate::data::error::DauthError;
-use crate::data::vector::AuthVectorRes;
+use crate::data::vector::{AuthVectorRes, Plmn};
 use crate::database::utilities::DauthDataUtilities;
 
 /// Creates the flood vector table if it does not exist already.
@@ -23,6 +21,8 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
             xres_hash BLOB NOT NULL,
             autn BLOB NOT NULL,
             rand BLOB NOT NULL,
+            mcc TEXT NOT NULL,
+            mnc TEXT NOT NULL,
             sent INTEGER NOT NULL,
             UNIQUE(user_id, seqnum)
         );",
@@ -52,26 +52,23 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
 #[tracing::instrument(skip(transaction), name = "database::flood_vectors")]
 pub async fn add(
     transaction: &mut Transaction<'_, Sqlite>,
-    id: &str,
-    seqnum: i64,
-    xres_star_hash: &[u8],
-    xres_hash: &XResHash,
-    autn: &[u8],
-    rand: &[u8],
+    vector: &AuthVectorRes,
 ) -> Result<(), DauthError> {
     tracing::debug!("Adding flood vector");
 
     sqlx::query(
         "INSERT INTO flood_vector_table
-        (user_id,seqnum,xres_star_hash,xres_hash,autn,rand, sent)
-        VALUES ($1,$2,$3,$4,$5, $6, FALSE)",
+        (user_id,seqnum,xres_star_hash,xres_hash,autn,rand,mcc,mnc,sent)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,FALSE)",
     )
-    .bind(id)
-    .bind(seqnum)
-    .bind(xres_star_hash)
-    .bind(xres_hash.as_slice())
-    .bind(autn)
-    .bind(rand)
+    .bind(&vector.user_id)
+    .bind(vector.seqnum)
+    .bind(vector.xres_star_hash.as_slice())
+    .bind(vector.xres_hash.as_slice())
+    .bind(vector.autn.as_slice())
+    .bind(vector.rand.to_vec())
+    .bind(&vector.plmn.mcc)
+    .bind(&vector.plmn.mnc)
     .execute(transaction)
     .await?;
 
@@ -103,6 +100,34 @@ pub async fn get_first(
     }
 }
 
+/// Check first for higher priority flood vectors with keys bound to the PLMN
+/// Generally, it is normal for this to return Ok(None)
+#[tracing::instrument(skip(transaction), name = "database::flood_vectors")]
+pub async fn get_first_by_plmn(
+    transaction: &mut Transaction<'_, Sqlite>,
+    id: &str,
+    plmn: &Plmn,
+) -> Result<Option<AuthVectorRes>, DauthError> {
+    tracing::debug!("Getting first available flood vector for plmn");
+
+    let res = sqlx::query(
+        "SELECT * FROM flood_vector_table
+        WHERE (user_id,mcc,mnc)=($1,$2,$3)
+        ORDER BY rank
+        LIMIT 1;",
+    )
+    .bind(id)
+    .bind(&plmn.mcc)
+    .bind(&plmn.mnc)
+    .fetch_optional(transaction)
+    .await?;
+
+    match res {
+        Some(row) => Ok(Some(row.to_auth_vector()?)),
+        None => Ok(None),
+    }
+}
+
 /// Returns the auth vector with the corresponding xres_star_hash.
 /// Not currently used.
 #[allow(dead_code)]
@@ -146,6 +171,27 @@ pub async fn mark_sent(
     Ok(())
 }
 
+/// Counts the flood vectors stored for users of a home network.
+#[tracing::instrument(skip(transaction), name = "database::flood_vectors")]
+pub async fn count_by_home_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<i64, DauthError> {
+    tracing::debug!("Counting flood vectors of home network");
+
+    Ok(sqlx::query(
+        "SELECT count(*) as count FROM flood_vector_table
+        WHERE user_id IN (
+            SELECT user_id FROM backup_users_table
+            WHERE home_network_id=$1
+        );",
+    )
+    .bind(home_network_id)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<i64, &str>("count")?)
+}
+
 /// Removes the vector with the (id, seqnum) pair.
 #[tracing::instrument(skip(transaction), name = "database::flood_vectors")]
 pub async fn remove(
@@ -179,8 +225,6 @@ pub async fn remove(
 }
 
 /// Removes all vectors belonging to an id.
-/// Not currently used.
-#[allow(dead_code)]
 #[tracing::instrument(skip(transaction), name = "database::flood_vectors")]
 pub async fn remove_all(
     transaction: &mut Transaction<'_, Sqlite>,
@@ -209,7 +253,8 @@ mod tests {
 
     use auth_vector::types::{AUTN_LENGTH, RAND_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH};
 
-    use crate::database::{flood_vectors, general};
+    use crate::data::vector::{AuthVectorRes, Plmn};
+    use crate::database::{backup_users, flood_vectors, general};
 
     fn gen_name() -> String {
         let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
@@ -228,6 +273,30 @@ mod tests {
         (pool, dir)
     }
 
+    fn gen_plmn(mnc: &str) -> Plmn {
+        Plmn {
+            mcc: "901".to_string(),
+            mnc: mnc.to_string(),
+        }
+    }
+
+    fn gen_vector(
+        user_id: &str,
+        seqnum: i64,
+        xres_star_hash: &[u8],
+        xres_hash: &[u8],
+    ) -> AuthVectorRes {
+        AuthVectorRes {
+            user_id: user_id.to_string(),
+            seqnum,
+            xres_star_hash: xres_star_hash.try_into().unwrap(),
+            xres_hash: xres_hash.try_into().unwrap(),
+            autn: [0_u8; AUTN_LENGTH],
+            rand: [0_u8; RAND_LENGTH][..].try_into().unwrap(),
+            plmn: gen_plmn("70"),
+        }
+    }
+
     /// Test that db and table creation will work
     #[tokio::test]
     async fn test_db_init() {
@@ -263,12 +332,12 @@ mod tests {
             for row in 0..num_rows {
                 flood_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -287,24 +356,24 @@ mod tests {
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
@@ -321,36 +390,36 @@ mod tests {
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            1,
-            &[1_u8; XRES_STAR_HASH_LENGTH],
-            &[1_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                1,
+                &[1_u8; XRES_STAR_HASH_LENGTH],
+                &[1_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            0,
-            &[0_u8; XRES_STAR_HASH_LENGTH],
-            &[0_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                0,
+                &[0_u8; XRES_STAR_HASH_LENGTH],
+                &[0_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            2,
-            &[2_u8; XRES_STAR_HASH_LENGTH],
-            &[2_u8; XRES_HASH_LENGTH],
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector(
+                "test_id_1",
+                2,
+                &[2_u8; XRES_STAR_HASH_LENGTH],
+                &[2_u8; XRES_HASH_LENGTH],
+            ),
         )
         .await
         .unwrap();
@@ -370,6 +439,46 @@ mod tests {
         transaction.commit().await.unwrap();
     }
 
+    /// Test that only flood vectors bound to the plmn are returned
+    #[tokio::test]
+    async fn test_get_first_by_plmn() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+
+        for row in 0..4 {
+            let mut vector = gen_vector(
+                "test_id_1",
+                row,
+                &[row as u8; XRES_STAR_HASH_LENGTH],
+                &[row as u8; XRES_HASH_LENGTH],
+            );
+            vector.plmn = gen_plmn(&format!("{}", 70 + row % 2));
+            flood_vectors::add(&mut transaction, &vector).await.unwrap();
+        }
+
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+
+        let res = flood_vectors::get_first_by_plmn(&mut transaction, "test_id_1", &gen_plmn("71"))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(1, res.seqnum);
+        assert_eq!(gen_plmn("71"), res.plmn);
+
+        assert!(
+            flood_vectors::get_first_by_plmn(&mut transaction, "test_id_1", &gen_plmn("72"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        transaction.commit().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_get_by_hash() {
         let (pool, _dir) = init().await;
@@ -382,12 +491,7 @@ mod tests {
 
         flood_vectors::add(
             &mut transaction,
-            "test_id_1",
-            0,
-            &good_hash,
-            &good_hash,
-            &[0_u8; AUTN_LENGTH],
-            &[0_u8; RAND_LENGTH],
+            &gen_vector("test_id_1", 0, &good_hash, &good_hash),
         )
         .await
         .unwrap();
@@ -426,12 +530,12 @@ mod tests {
             for row in 0..num_rows {
                 flood_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -475,12 +579,12 @@ mod tests {
             for row in 0..num_rows {
                 flood_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -531,12 +635,12 @@ mod tests {
             for row in 0..num_rows {
                 flood_vectors::add(
                     &mut transaction,
-                    &format!("test_id_{}", section),
-                    row,
-                    &[row as u8; XRES_STAR_HASH_LENGTH],
-                    &[row as u8; XRES_HASH_LENGTH],
-                    &[0_u8; AUTN_LENGTH],
-                    &[0_u8; RAND_LENGTH],
+                    &gen_vector(
+                        &format!("test_id_{}", section),
+                        row,
+                        &[row as u8; XRES_STAR_HASH_LENGTH],
+                        &[row as u8; XRES_HASH_LENGTH],
+                    ),
                 )
                 .await
                 .unwrap();
@@ -573,4 +677,49 @@ mod tests {
 
         transaction.commit().await.unwrap();
     }
+
+    /// Test that vectors are counted by the home network of their user
+    #[tokio::test]
+    async fn test_count_by_home_network() {
+        let (pool, _dir) = init().await;
+        backup_users::init_table(&pool).await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        backup_users::add(&mut transaction, "test_id_0", "test_home_network_0")
+            .await
+            .unwrap();
+        backup_users::add(&mut transaction, "test_id_1", "test_home_network_1")
+            .await
+            .unwrap();
+
+        for (user, num_rows) in [(0, 3), (1, 2), (2, 4)] {
+            for row in 0..num_rows {
+                flood_vectors::add(
+                    &mut transaction,
+                    &gen_vector(
+                        &format!("test_id_{}", user),
+                        row,
+                        &[(user * 10 + row) as u8; XRES_STAR_HASH_LENGTH],
+                        &[(user * 10 + row) as u8; XRES_HASH_LENGTH],
+                    ),
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        for (home_network_id, count) in [
+            ("test_home_network_0", 3),
+            ("test_home_network_1", 2),
+            ("test_home_network_2", 0),
+        ] {
+            assert_eq!(
+                count,
+                flood_vectors::count_by_home_network(&mut transaction, home_network_id)
+                    .await
+                    .unwrap()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
 }
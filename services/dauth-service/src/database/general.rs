@@ -35,18 +35,29 @@ pub async fn database_init(database_path: &str) -> Result<SqlitePool, DauthError
 
     database::flood_vectors::init_table(&pool).await?;
     database::auth_vectors::init_table(&pool).await?;
+    database::auth_states::init_table(&pool).await?;
     database::kasmes::init_table(&pool).await?;
     database::kseafs::init_table(&pool).await?;
+    database::eap_keys::init_table(&pool).await?;
     database::user_infos::init_table(&pool).await?;
     database::key_shares::init_table(&pool).await?;
     database::key_share_state::init_table(&pool).await?;
     database::backup_networks::init_table(&pool).await?;
     database::backup_users::init_table(&pool).await?;
+    database::serving_policies::init_table(&pool).await?;
     database::vector_state::init_table(&pool).await?;
+    database::replay_cache::init_table(&pool).await?;
+    database::misbehaviour::init_table(&pool).await?;
+    database::consumed_vectors::init_table(&pool).await?;
+    database::delegated_vectors::init_table(&pool).await?;
     database::tasks::update_users::init_table(&pool).await?;
     database::tasks::replace_key_shares::init_table(&pool).await?;
+    database::tasks::refresh_key_shares::init_table(&pool).await?;
+    database::tasks::select_backups::init_table(&pool).await?;
     database::tasks::report_key_shares::init_table(&pool).await?;
     database::tasks::report_auth_vectors::init_table(&pool).await?;
+    database::tasks::withdraw_backups::init_table(&pool).await?;
+    database::tasks::withdraw_shares::init_table(&pool).await?;
 
     Ok(pool)
 }
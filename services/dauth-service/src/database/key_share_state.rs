@@ -203,6 +203,79 @@ pub async fn remove_by_xres_hash(
     Ok(())
 }
 
+/// Returns the xres* hashes of all key shares held by the backup network
+/// for a given user.
+#[tracing::instrument(skip(transaction), name = "database::key_share_state")]
+pub async fn get_all_by_id(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    backup_network_id: &str,
+) -> Result<Vec<XResStarHash>, DauthError> {
+    tracing::debug!("Getting all key share states for given id");
+
+    let rows = sqlx::query(
+        "SELECT * FROM key_share_state_table
+        WHERE (user_id,backup_network_id)=($1,$2)",
+    )
+    .bind(user_id)
+    .bind(backup_network_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut hashes = Vec::with_capacity(rows.len());
+    for row in rows {
+        hashes.push(
+            row.try_get::<Vec<u8>, &str>("xres_star_hash")?
+                .as_slice()
+                .try_into()?,
+        )
+    }
+    Ok(hashes)
+}
+
+/// Returns the ids of all backup networks holding a key share
+/// for the given xres* hash.
+#[tracing::instrument(skip(transaction), name = "database::key_share_state")]
+pub async fn get_holders(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &XResStarHash,
+) -> Result<Vec<String>, DauthError> {
+    tracing::debug!("Getting all key share holders");
+
+    let rows = sqlx::query(
+        "SELECT backup_network_id FROM key_share_state_table
+        WHERE xres_star_hash=$1",
+    )
+    .bind(xres_star_hash.as_slice())
+    .fetch_all(transaction)
+    .await?;
+
+    let mut holders = Vec::with_capacity(rows.len());
+    for row in rows {
+        holders.push(row.try_get::<String, &str>("backup_network_id")?)
+    }
+    Ok(holders)
+}
+
+/// Deletes all key share references for a given user.
+#[tracing::instrument(skip(transaction), name = "database::key_share_state")]
+pub async fn remove_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing all key share states for user");
+
+    sqlx::query(
+        "DELETE FROM key_share_state_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -362,4 +435,104 @@ mod tests {
         }
         transaction.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_remove_all() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            key_share_state::add(
+                &mut transaction,
+                &[row as u8; 16],
+                &[row as u8; 16],
+                "test_backup_network",
+                &format!("test_user_id_{}", row % 2),
+                &[0u8; RAND_LENGTH],
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        assert_eq!(
+            key_share_state::get_all_by_id(
+                &mut transaction,
+                "test_user_id_0",
+                "test_backup_network"
+            )
+            .await
+            .unwrap()
+            .len(),
+            num_rows / 2
+        );
+
+        key_share_state::remove_all(&mut transaction, "test_user_id_0")
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        assert!(key_share_state::get_all_by_id(
+            &mut transaction,
+            "test_user_id_0",
+            "test_backup_network"
+        )
+        .await
+        .unwrap()
+        .is_empty());
+        assert_eq!(
+            key_share_state::get_all_by_id(
+                &mut transaction,
+                "test_user_id_1",
+                "test_backup_network"
+            )
+            .await
+            .unwrap()
+            .len(),
+            num_rows / 2
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_holders() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            key_share_state::add(
+                &mut transaction,
+                &[(row % 2) as u8; 16],
+                &[row as u8; 16],
+                &format!("test_backup_network_{}", row),
+                "test_user_id",
+                &[0u8; RAND_LENGTH],
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut holders = key_share_state::get_holders(&mut transaction, &[0u8; 16])
+            .await
+            .unwrap();
+        holders.sort();
+        assert_eq!(
+            holders,
+            (0..num_rows)
+                .step_by(2)
+                .map(|row| format!("test_backup_network_{}", row))
+                .collect::<Vec<String>>()
+        );
+        assert!(key_share_state::get_holders(&mut transaction, &[2u8; 16])
+            .await
+            .unwrap()
+            .is_empty());
+        transaction.commit().await.unwrap();
+    }
 }
@@ -1,5 +1,5 @@
 use sqlx::sqlite::SqlitePool;
-use sqlx::{Row, Sqlite, Transaction};
+use sqlx::{Error as SqlxError, Row, Sqlite, Transaction};
 
 use crate::data::error::DauthError;
 use crate::data::keys;
@@ -13,9 +13,15 @@ struct KeyShareRow {
     pub user_id: String,
     pub kseaf_share: Vec<u8>,
     pub kasme_share: Vec<u8>,
+    pub kseaf_commitment: Vec<u8>,
+    pub kasme_commitment: Vec<u8>,
+    pub epoch: i64,
+    pub signed_share: Vec<u8>,
 }
 
 /// Creates the kseaf table if it does not exist already.
+/// Each share is also stored as signed by its home network,
+/// so it can be forwarded to serving networks unchanged.
 #[tracing::instrument(skip(pool), name = "database::key_shares")]
 pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
     tracing::info!("Initialzing table");
@@ -26,7 +32,11 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
             xres_hash BLOB NOT NULL,
             user_id TEXT NOT NULL,
             kseaf_share BLOB NOT NULL,
-            kasme_share BLOB NOT NULL
+            kasme_share BLOB NOT NULL,
+            kseaf_commitment BLOB NOT NULL,
+            kasme_commitment BLOB NOT NULL,
+            epoch INT NOT NULL,
+            signed_share BLOB NOT NULL
         );",
     )
     .execute(pool)
@@ -44,25 +54,31 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
 
 /* Queries */
 
-/// Inserts a key share
-#[tracing::instrument(skip(transaction), name = "database::key_shares")]
+/// Inserts a key share along with its encoded signed form
+#[tracing::instrument(skip(transaction, signed_share), name = "database::key_shares")]
 pub async fn add(
     transaction: &mut Transaction<'_, Sqlite>,
     user_id: &str,
     key_share: &keys::CombinedKeyShare,
+    signed_share: &[u8],
 ) -> Result<(), DauthError> {
     tracing::debug!("Adding key share");
 
     sqlx::query(
         "INSERT INTO key_share_table
-        (xres_star_hash, xres_hash, user_id, kseaf_share, kasme_share)
-        VALUES ($1,$2,$3,$4,$5)",
+        (xres_star_hash, xres_hash, user_id, kseaf_share, kasme_share,
+        kseaf_commitment, kasme_commitment, epoch, signed_share)
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
     )
     .bind(key_share.xres_star_hash.as_slice())
     .bind(key_share.xres_hash.as_slice())
     .bind(user_id)
     .bind(key_share.kseaf_share.as_slice())
     .bind(key_share.kasme_share.as_slice())
+    .bind(key_share.kseaf_commitment.to_vec())
+    .bind(key_share.kasme_commitment.to_vec())
+    .bind(i64::from(key_share.epoch))
+    .bind(signed_share)
     .execute(transaction)
     .await?;
 
@@ -88,6 +104,9 @@ pub async fn get_from_xres_star_hash(
     Ok(keys::CombinedKeyShare {
         kasme_share: row.kasme_share.try_into()?,
         kseaf_share: row.kseaf_share.try_into()?,
+        kseaf_commitment: row.kseaf_commitment.try_into()?,
+        kasme_commitment: row.kasme_commitment.try_into()?,
+        epoch: row.epoch as u32,
         xres_hash: row
             .xres_hash
             .try_into()
@@ -118,6 +137,9 @@ pub async fn get_from_xres_hash(
     Ok(keys::CombinedKeyShare {
         kasme_share: row.kasme_share.try_into()?,
         kseaf_share: row.kseaf_share.try_into()?,
+        kseaf_commitment: row.kseaf_commitment.try_into()?,
+        kasme_commitment: row.kasme_commitment.try_into()?,
+        epoch: row.epoch as u32,
         xres_hash: row
             .xres_hash
             .try_into()
@@ -129,6 +151,24 @@ pub async fn get_from_xres_hash(
     })
 }
 
+/// Returns the encoded key share as signed by its home network.
+#[tracing::instrument(skip(transaction), name = "database::key_shares")]
+pub async fn get_signed_share(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<Vec<u8>, DauthError> {
+    tracing::debug!("Getting signed key share with xres* hash");
+
+    Ok(sqlx::query(
+        "SELECT * FROM key_share_table
+        WHERE xres_star_hash=$1;",
+    )
+    .bind(xres_star_hash)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<Vec<u8>, &str>("signed_share")?)
+}
+
 /// Returns the user id that the key share/vector belongs to.
 #[tracing::instrument(skip(transaction), name = "database::key_shares")]
 pub async fn get_user_id(
@@ -147,6 +187,49 @@ pub async fn get_user_id(
     .try_get::<String, &str>("user_id")?)
 }
 
+/// Returns the user id that the key share/vector belongs to, if held.
+#[tracing::instrument(skip(transaction), name = "database::key_shares")]
+pub async fn find_user_id(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<Option<String>, DauthError> {
+    tracing::debug!("Finding user id from xres* hash");
+
+    let res = sqlx::query(
+        "SELECT * FROM key_share_table
+        WHERE xres_star_hash=$1;",
+    )
+    .bind(xres_star_hash)
+    .fetch_one(transaction)
+    .await;
+
+    match res {
+        Err(SqlxError::RowNotFound) => Ok(None),
+        _ => Ok(Some(res?.try_get::<String, &str>("user_id")?)),
+    }
+}
+
+/// Counts the key shares stored for users of a home network.
+#[tracing::instrument(skip(transaction), name = "database::key_shares")]
+pub async fn count_by_home_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<i64, DauthError> {
+    tracing::debug!("Counting key shares of home network");
+
+    Ok(sqlx::query(
+        "SELECT count(*) as count FROM key_share_table
+        WHERE user_id IN (
+            SELECT user_id FROM backup_users_table
+            WHERE home_network_id=$1
+        );",
+    )
+    .bind(home_network_id)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<i64, &str>("count")?)
+}
+
 /// Deletes a key share if found.
 #[tracing::instrument(skip(transaction), name = "database::key_shares")]
 pub async fn remove(
@@ -179,8 +262,10 @@ mod tests {
         KSEAF_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH, XRES_STAR_LENGTH,
     };
 
-    use crate::data::keys::CombinedKeyShare;
-    use crate::database::{general, key_shares};
+    use crate::data::keys::{self, CombinedKeyShare, SHARE_LENGTH};
+    use crate::database::{backup_users, general, key_shares};
+
+    const SIGNED_SHARE_LENGTH: usize = 16;
 
     fn gen_name() -> String {
         let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
@@ -199,6 +284,26 @@ mod tests {
         (pool, dir)
     }
 
+    fn gen_share(value: u8) -> CombinedKeyShare {
+        let (_, commitment) = keys::create_shares_from_kseaf(
+            &[value; KSEAF_LENGTH],
+            1,
+            1,
+            &mut rand_0_8::rngs::mock::StepRng::new(1, 1),
+        )
+        .unwrap();
+
+        CombinedKeyShare {
+            xres_star_hash: [value; XRES_STAR_HASH_LENGTH],
+            xres_hash: [value; XRES_HASH_LENGTH],
+            kseaf_share: vec![value; SHARE_LENGTH].try_into().unwrap(),
+            kasme_share: vec![value; SHARE_LENGTH].try_into().unwrap(),
+            kseaf_commitment: commitment.clone(),
+            kasme_commitment: commitment,
+            epoch: value.into(),
+        }
+    }
+
     /// Test that db and table creation will work
     #[tokio::test]
     async fn test_db_init() {
@@ -217,19 +322,15 @@ mod tests {
 
         for section in 0..num_sections {
             for row in 0..num_rows {
-                let combined_share = CombinedKeyShare {
-                    xres_star_hash: [section * num_rows + row; XRES_STAR_HASH_LENGTH],
-                    xres_hash: [section * num_rows + row; XRES_HASH_LENGTH],
-                    kseaf_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                    kasme_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                };
-                key_shares::add(&mut transaction, "test_user_id", &combined_share)
-                    .await
-                    .unwrap();
+                let value = section * num_rows + row;
+                key_shares::add(
+                    &mut transaction,
+                    "test_user_id",
+                    &gen_share(value),
+                    &[value; SIGNED_SHARE_LENGTH],
+                )
+                .await
+                .unwrap();
             }
         }
         transaction.commit().await.unwrap();
@@ -247,19 +348,15 @@ mod tests {
 
         for section in 0..num_sections {
             for row in 0..num_rows {
-                let combined_share = CombinedKeyShare {
-                    xres_star_hash: [section * num_rows + row; XRES_STAR_HASH_LENGTH],
-                    xres_hash: [section * num_rows + row; XRES_HASH_LENGTH],
-                    kseaf_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                    kasme_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                };
-                key_shares::add(&mut transaction, "test_user_id", &combined_share)
-                    .await
-                    .unwrap();
+                let value = section * num_rows + row;
+                key_shares::add(
+                    &mut transaction,
+                    "test_user_id",
+                    &gen_share(value),
+                    &[value; SIGNED_SHARE_LENGTH],
+                )
+                .await
+                .unwrap();
             }
         }
 
@@ -275,9 +372,16 @@ mod tests {
                 .await
                 .unwrap();
 
+                assert_eq!(gen_share(section * num_rows + row), res);
+
                 assert_eq!(
-                    &[section * num_rows + row; KSEAF_LENGTH + 1],
-                    res.kseaf_share.as_slice()
+                    vec![section * num_rows + row; SIGNED_SHARE_LENGTH],
+                    key_shares::get_signed_share(
+                        &mut transaction,
+                        &[section * num_rows + row; XRES_STAR_HASH_LENGTH],
+                    )
+                    .await
+                    .unwrap()
                 );
 
                 assert_eq!(
@@ -289,8 +393,25 @@ mod tests {
                     .unwrap(),
                     "test_user_id"
                 );
+
+                assert_eq!(
+                    key_shares::find_user_id(
+                        &mut transaction,
+                        &[section * num_rows + row; XRES_STAR_HASH_LENGTH],
+                    )
+                    .await
+                    .unwrap(),
+                    Some("test_user_id".to_string())
+                );
             }
         }
+
+        assert_eq!(
+            key_shares::find_user_id(&mut transaction, &[u8::MAX; XRES_STAR_HASH_LENGTH])
+                .await
+                .unwrap(),
+            None
+        );
         transaction.commit().await.unwrap();
     }
 
@@ -306,19 +427,15 @@ mod tests {
 
         for section in 0..num_sections {
             for row in 0..num_rows {
-                let combined_share = CombinedKeyShare {
-                    xres_star_hash: [section * num_rows + row; XRES_STAR_HASH_LENGTH],
-                    xres_hash: [section * num_rows + row; XRES_HASH_LENGTH],
-                    kseaf_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                    kasme_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                };
-                key_shares::add(&mut transaction, "test_user_id", &combined_share)
-                    .await
-                    .unwrap();
+                let value = section * num_rows + row;
+                key_shares::add(
+                    &mut transaction,
+                    "test_user_id",
+                    &gen_share(value),
+                    &[value; SIGNED_SHARE_LENGTH],
+                )
+                .await
+                .unwrap();
             }
         }
 
@@ -365,19 +482,15 @@ mod tests {
 
         for section in 0..num_sections {
             for row in 0..num_rows {
-                let combined_share = CombinedKeyShare {
-                    xres_star_hash: [section * num_rows + row; XRES_STAR_HASH_LENGTH],
-                    xres_hash: [section * num_rows + row; XRES_HASH_LENGTH],
-                    kseaf_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                    kasme_share: vec![section * num_rows + row; KSEAF_LENGTH + 1]
-                        .try_into()
-                        .unwrap(),
-                };
-                key_shares::add(&mut transaction, "test_user_id", &combined_share)
-                    .await
-                    .unwrap();
+                let value = section * num_rows + row;
+                key_shares::add(
+                    &mut transaction,
+                    "test_user_id",
+                    &gen_share(value),
+                    &[value; SIGNED_SHARE_LENGTH],
+                )
+                .await
+                .unwrap();
             }
         }
 
@@ -394,7 +507,7 @@ mod tests {
                 .unwrap();
 
                 assert_eq!(
-                    &[section * num_rows + row; KSEAF_LENGTH + 1],
+                    &[section * num_rows + row; SHARE_LENGTH],
                     res.kseaf_share.as_slice()
                 );
 
@@ -427,20 +540,62 @@ mod tests {
     async fn test_add_dupicate_fail() {
         let (pool, _dir) = init().await;
         let mut transaction = pool.begin().await.unwrap();
-        let combined_share = CombinedKeyShare {
-            xres_star_hash: [1_u8; XRES_STAR_HASH_LENGTH],
-            xres_hash: [1_u8; XRES_HASH_LENGTH],
-            kseaf_share: vec![1_u8; KSEAF_LENGTH + 1].try_into().unwrap(),
-            kasme_share: vec![1_u8; KSEAF_LENGTH + 1].try_into().unwrap(),
-        };
-        key_shares::add(&mut transaction, "test_user_id", &combined_share)
+        let combined_share = gen_share(1);
+        key_shares::add(
+            &mut transaction,
+            "test_user_id",
+            &combined_share,
+            &[1; SIGNED_SHARE_LENGTH],
+        )
+        .await
+        .unwrap();
+
+        key_shares::add(
+            &mut transaction,
+            "test_user_id",
+            &combined_share,
+            &[1; SIGNED_SHARE_LENGTH],
+        )
+        .await
+        .unwrap();
+
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that key shares are counted by the home network of their user
+    #[tokio::test]
+    async fn test_count_by_home_network() {
+        let (pool, _dir) = init().await;
+        backup_users::init_table(&pool).await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        backup_users::add(&mut transaction, "test_user_id_0", "test_home_network")
             .await
             .unwrap();
 
-        key_shares::add(&mut transaction, "test_user_id", &combined_share)
+        for value in 0..5 {
+            key_shares::add(
+                &mut transaction,
+                &format!("test_user_id_{}", value % 2),
+                &gen_share(value),
+                &[value; SIGNED_SHARE_LENGTH],
+            )
             .await
             .unwrap();
+        }
 
+        assert_eq!(
+            3,
+            key_shares::count_by_home_network(&mut transaction, "test_home_network")
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            0,
+            key_shares::count_by_home_network(&mut transaction, "other_home_network")
+                .await
+                .unwrap()
+        );
         transaction.commit().await.unwrap();
     }
 }
@@ -0,0 +1,226 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+use crate::data::misbehaviour::Misbehaviour;
+use crate::database::utilities::DauthDataUtilities;
+
+/// Creates the misbehaviour table if it does not exist already.
+/// Contains the evidence of misbehaviour by other networks.
+/// The same evidence is only recorded once per network and kind,
+/// since a retried message does not prove further misbehaviour.
+#[tracing::instrument(skip(pool), name = "database::misbehaviour")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS misbehaviour_table (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            network_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            detail TEXT NOT NULL,
+            evidence BLOB NOT NULL,
+            timestamp INT NOT NULL,
+            UNIQUE (network_id, kind, evidence)
+        );",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_misbehaviour_network_id
+        ON misbehaviour_table (network_id);",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a record of misbehaviour if its evidence is not already recorded.
+/// Returns false if the evidence was already recorded.
+#[tracing::instrument(skip(transaction, misbehaviour), name = "database::misbehaviour")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    misbehaviour: &Misbehaviour,
+) -> Result<bool, DauthError> {
+    tracing::debug!("Adding misbehaviour");
+
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO misbehaviour_table (network_id, kind, detail, evidence, timestamp)
+        VALUES ($1,$2,$3,$4,$5)",
+    )
+    .bind(&misbehaviour.network_id)
+    .bind(misbehaviour.kind.as_str())
+    .bind(&misbehaviour.detail)
+    .bind(&misbehaviour.evidence)
+    .bind(misbehaviour.timestamp)
+    .execute(transaction)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Returns all recorded misbehaviour of a network, oldest first.
+#[tracing::instrument(skip(transaction), name = "database::misbehaviour")]
+pub async fn get_all_by_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+) -> Result<Vec<Misbehaviour>, DauthError> {
+    tracing::debug!("Getting misbehaviour by network");
+
+    let rows = sqlx::query(
+        "SELECT * FROM misbehaviour_table
+        WHERE network_id=$1
+        ORDER BY id;",
+    )
+    .bind(network_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut res = Vec::with_capacity(rows.len());
+    for row in rows {
+        res.push(row.to_misbehaviour()?);
+    }
+    Ok(res)
+}
+
+/// Returns all recorded misbehaviour, oldest first.
+#[tracing::instrument(skip(transaction), name = "database::misbehaviour")]
+pub async fn get_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<Misbehaviour>, DauthError> {
+    tracing::debug!("Getting all misbehaviour");
+
+    let rows = sqlx::query(
+        "SELECT * FROM misbehaviour_table
+        ORDER BY id;",
+    )
+    .fetch_all(transaction)
+    .await?;
+
+    let mut res = Vec::with_capacity(rows.len());
+    for row in rows {
+        res.push(row.to_misbehaviour()?);
+    }
+    Ok(res)
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::data::misbehaviour::{Misbehaviour, MisbehaviourKind};
+    use crate::database::{general, misbehaviour};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        misbehaviour::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn gen_misbehaviour(network: u8, row: u8) -> Misbehaviour {
+        Misbehaviour {
+            network_id: format!("test_network_{}", network),
+            kind: MisbehaviourKind::InvalidShare,
+            detail: format!("test_detail_{}", row),
+            evidence: vec![network, row],
+            timestamp: row as i64,
+        }
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Tests that the same evidence is only recorded once
+    #[tokio::test]
+    async fn test_add_repeated() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut record = gen_misbehaviour(0, 0);
+        assert!(misbehaviour::add(&mut transaction, &record).await.unwrap());
+
+        record.timestamp += 1;
+        assert!(!misbehaviour::add(&mut transaction, &record).await.unwrap());
+
+        record.kind = MisbehaviourKind::OverIssuedVector;
+        assert!(misbehaviour::add(&mut transaction, &record).await.unwrap());
+
+        assert_eq!(
+            2,
+            misbehaviour::get_all_by_network(&mut transaction, "test_network_0")
+                .await
+                .unwrap()
+                .len()
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    /// Tests that records are returned for their network in order
+    #[tokio::test]
+    async fn test_add_get() {
+        let (pool, _dir) = init().await;
+        let num_networks = 3;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            for network in 0..num_networks {
+                assert!(
+                    misbehaviour::add(&mut transaction, &gen_misbehaviour(network, row))
+                        .await
+                        .unwrap()
+                );
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for network in 0..num_networks {
+            assert_eq!(
+                (0..num_rows)
+                    .map(|row| gen_misbehaviour(network, row))
+                    .collect::<Vec<Misbehaviour>>(),
+                misbehaviour::get_all_by_network(
+                    &mut transaction,
+                    &format!("test_network_{}", network)
+                )
+                .await
+                .unwrap()
+            );
+        }
+        assert!(
+            misbehaviour::get_all_by_network(&mut transaction, "test_network_none")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            (num_networks * num_rows) as usize,
+            misbehaviour::get_all(&mut transaction).await.unwrap().len()
+        );
+        transaction.commit().await.unwrap();
+    }
+}
@@ -1,12 +1,19 @@
+pub mod auth_states;
 pub mod auth_vectors;
 pub mod backup_networks;
 pub mod backup_users;
+pub mod consumed_vectors;
+pub mod delegated_vectors;
+pub mod eap_keys;
 pub mod flood_vectors;
 pub mod general;
 pub mod kasmes;
 pub mod key_share_state;
 pub mod key_shares;
 pub mod kseafs;
+pub mod misbehaviour;
+pub mod replay_cache;
+pub mod serving_policies;
 pub mod tasks;
 pub mod user_infos;
 pub mod utilities;
@@ -0,0 +1,216 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the replay cache table if it does not exist already.
+/// Contains the nonces of all recently received signed messages.
+#[tracing::instrument(skip(pool), name = "database::replay_cache")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS replay_cache_table (
+            signer_id TEXT NOT NULL,
+            nonce BLOB NOT NULL,
+            timestamp INT NOT NULL,
+            PRIMARY KEY (signer_id, nonce)
+        );",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_replay_cache_timestamp
+        ON replay_cache_table (timestamp);",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds the nonce of a signer if not already present.
+/// Returns false if the nonce was already seen.
+#[tracing::instrument(skip(transaction), name = "database::replay_cache")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    signer_id: &str,
+    nonce: &[u8],
+    timestamp: i64,
+) -> Result<bool, DauthError> {
+    tracing::debug!("Adding nonce");
+
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO replay_cache_table
+        VALUES ($1,$2,$3)",
+    )
+    .bind(signer_id)
+    .bind(nonce)
+    .bind(timestamp)
+    .execute(transaction)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Removes all nonces with a timestamp before the provided time.
+#[tracing::instrument(skip(transaction), name = "database::replay_cache")]
+pub async fn remove_expired(
+    transaction: &mut Transaction<'_, Sqlite>,
+    timestamp: i64,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing expired nonces");
+
+    sqlx::query(
+        "DELETE FROM replay_cache_table
+        WHERE timestamp<$1",
+    )
+    .bind(timestamp)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the number of stored nonces.
+#[tracing::instrument(skip(transaction), name = "database::replay_cache")]
+pub async fn count(transaction: &mut Transaction<'_, Sqlite>) -> Result<i64, DauthError> {
+    tracing::debug!("Counting nonces");
+
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM replay_cache_table")
+        .fetch_one(transaction)
+        .await?;
+
+    Ok(count.0)
+}
+
+/// Returns the number of stored nonces of a signer.
+#[tracing::instrument(skip(transaction), name = "database::replay_cache")]
+pub async fn count_by_signer(
+    transaction: &mut Transaction<'_, Sqlite>,
+    signer_id: &str,
+) -> Result<i64, DauthError> {
+    tracing::debug!("Counting nonces of signer");
+
+    let count: (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM replay_cache_table WHERE signer_id=$1")
+            .bind(signer_id)
+            .fetch_one(transaction)
+            .await?;
+
+    Ok(count.0)
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::database::{general, replay_cache};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        replay_cache::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Test that repeated nonces are detected
+    #[tokio::test]
+    async fn test_add() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert!(
+                replay_cache::add(&mut transaction, "test_network", &[row as u8; 16], row)
+                    .await
+                    .unwrap()
+            );
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert!(
+                !replay_cache::add(&mut transaction, "test_network", &[row as u8; 16], row)
+                    .await
+                    .unwrap()
+            );
+            // Same nonce from a different signer is not a replay
+            assert!(
+                replay_cache::add(&mut transaction, "test_network_2", &[row as u8; 16], row)
+                    .await
+                    .unwrap()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that expired nonces are removed
+    #[tokio::test]
+    async fn test_remove_expired() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            replay_cache::add(&mut transaction, "test_network", &[row as u8; 16], row)
+                .await
+                .unwrap();
+        }
+
+        replay_cache::remove_expired(&mut transaction, num_rows / 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            replay_cache::count(&mut transaction).await.unwrap(),
+            num_rows / 2
+        );
+
+        assert_eq!(
+            replay_cache::count_by_signer(&mut transaction, "test_network")
+                .await
+                .unwrap(),
+            num_rows / 2
+        );
+        assert_eq!(
+            replay_cache::count_by_signer(&mut transaction, "test_network_2")
+                .await
+                .unwrap(),
+            0
+        );
+
+        // Removed nonces are no longer detected
+        assert!(
+            replay_cache::add(&mut transaction, "test_network", &[0u8; 16], 0)
+                .await
+                .unwrap()
+        );
+        transaction.commit().await.unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use prost::Message;
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Error as SqlxError, Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+use crate::data::policy::ServingPolicy;
+use crate::database::utilities::DauthDataUtilities;
+
+/// Creates the serving policy table if it does not exist already.
+/// Contains the serving network policy of users owned or backed up
+/// by this network. Users without a policy are unrestricted.
+#[tracing::instrument(skip(pool), name = "database::serving_policies")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS serving_policy_table (
+            user_id TEXT PRIMARY KEY,
+            policy BLOB NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Sets the serving policy of a user, replacing any existing policy.
+#[tracing::instrument(skip(transaction), name = "database::serving_policies")]
+pub async fn upsert(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    policy: &ServingPolicy,
+) -> Result<(), DauthError> {
+    tracing::debug!("Setting serving policy");
+
+    sqlx::query(
+        "REPLACE INTO serving_policy_table
+        VALUES ($1,$2)",
+    )
+    .bind(user_id)
+    .bind(policy.to_serving_network_policy().encode_to_vec())
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the serving policy of a user, if it has one.
+#[tracing::instrument(skip(transaction), name = "database::serving_policies")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<Option<ServingPolicy>, DauthError> {
+    tracing::debug!("Getting serving policy");
+
+    let res = sqlx::query(
+        "SELECT * FROM serving_policy_table
+        WHERE user_id=$1;",
+    )
+    .bind(user_id)
+    .fetch_one(transaction)
+    .await;
+
+    match res {
+        Err(SqlxError::RowNotFound) => Ok(None),
+        _ => Ok(Some(res?.to_serving_policy()?)),
+    }
+}
+
+/// Removes the serving policy of a user, if it has one.
+#[tracing::instrument(skip(transaction), name = "database::serving_policies")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing serving policy");
+
+    sqlx::query(
+        "DELETE FROM serving_policy_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::data::policy::ServingPolicy;
+    use crate::database::{general, serving_policies};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        serving_policies::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn gen_policy(row: u16) -> ServingPolicy {
+        ServingPolicy {
+            allowed_network_ids: vec![format!("allowed_network_{}", row)],
+            denied_network_ids: vec![format!("denied_network_{}", row)],
+            allowed_mcc_ranges: vec![(row, row + 10)],
+        }
+    }
+
+    /// Test that db and table creation will work
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    /// Tests that upsert replaces the policy and get returns it
+    #[tokio::test]
+    async fn test_upsert_get() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            serving_policies::upsert(
+                &mut transaction,
+                &format!("test_id_{}", row),
+                &ServingPolicy::default(),
+            )
+            .await
+            .unwrap();
+            serving_policies::upsert(
+                &mut transaction,
+                &format!("test_id_{}", row),
+                &gen_policy(row),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert_eq!(
+                Some(gen_policy(row)),
+                serving_policies::get(&mut transaction, &format!("test_id_{}", row))
+                    .await
+                    .unwrap()
+            );
+        }
+        assert!(serving_policies::get(&mut transaction, "test_id_none")
+            .await
+            .unwrap()
+            .is_none());
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that deletes work
+    #[tokio::test]
+    async fn test_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            serving_policies::upsert(
+                &mut transaction,
+                &format!("test_id_{}", row),
+                &gen_policy(row),
+            )
+            .await
+            .unwrap();
+        }
+        for row in 0..num_rows {
+            serving_policies::remove(&mut transaction, &format!("test_id_{}", row))
+                .await
+                .unwrap();
+            assert!(
+                serving_policies::get(&mut transaction, &format!("test_id_{}", row))
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
+}
@@ -1,4 +1,8 @@
+pub mod refresh_key_shares;
 pub mod replace_key_shares;
 pub mod report_auth_vectors;
 pub mod report_key_shares;
+pub mod select_backups;
 pub mod update_users;
+pub mod withdraw_backups;
+pub mod withdraw_shares;
@@ -0,0 +1,147 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the refresh key shares table if it does not exist already.
+/// Contains the users whose key shares are due to be refreshed.
+#[tracing::instrument(skip(pool), name = "database::tasks::refresh_key_shares")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS task_refresh_key_shares_table (
+            user_id TEXT PRIMARY KEY
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a user id to be refreshed, if not already pending.
+#[tracing::instrument(skip(transaction), name = "database::tasks::refresh_key_shares")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding task");
+
+    sqlx::query(
+        "REPLACE INTO task_refresh_key_shares_table
+        VALUES ($1)",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets all user ids pending a refresh.
+#[tracing::instrument(skip(transaction), name = "database::tasks::refresh_key_shares")]
+pub async fn get_user_ids(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<String>, DauthError> {
+    tracing::debug!("Getting all user ids");
+
+    let rows = sqlx::query("SELECT * FROM task_refresh_key_shares_table")
+        .fetch_all(transaction)
+        .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(row.try_get::<String, &str>("user_id")?);
+    }
+    Ok(result)
+}
+
+/// Removes a pending refresh for a user id.
+#[tracing::instrument(skip(transaction), name = "database::tasks::refresh_key_shares")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing task");
+
+    sqlx::query(
+        "DELETE FROM task_refresh_key_shares_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::database::{general, tasks};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        tasks::refresh_key_shares::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            // Repeated refreshes of a user are only queued once
+            for _ in 0..2 {
+                tasks::refresh_key_shares::add(&mut transaction, &format!("test_user_{}", row))
+                    .await
+                    .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let user_ids = tasks::refresh_key_shares::get_user_ids(&mut transaction)
+            .await
+            .unwrap();
+        assert_eq!(num_rows, user_ids.len());
+
+        for user_id in user_ids {
+            tasks::refresh_key_shares::remove(&mut transaction, &user_id)
+                .await
+                .unwrap();
+        }
+        assert!(tasks::refresh_key_shares::get_user_ids(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        transaction.commit().await.unwrap();
+    }
+}
@@ -3,7 +3,6 @@ use sqlx::{FromRow, Sqlite, Transaction};
 
 use crate::data::error::DauthError;
 use crate::data::keys;
-use auth_vector::types::XResHash;
 
 #[derive(Clone)]
 pub struct ReplaceKeyShareTask {
@@ -25,6 +24,9 @@ impl TryFrom<ReplaceKeyShareTaskRow> for ReplaceKeyShareTask {
                 xres_hash: value.xres_hash.as_slice().try_into()?,
                 kseaf_share: value.kseaf_share.as_slice().try_into()?,
                 kasme_share: value.kasme_share.as_slice().try_into()?,
+                kseaf_commitment: value.kseaf_commitment.as_slice().try_into()?,
+                kasme_commitment: value.kasme_commitment.as_slice().try_into()?,
+                epoch: value.epoch as u32,
             },
         })
     }
@@ -38,6 +40,9 @@ pub struct ReplaceKeyShareTaskRow {
     pub old_xres_star_hash: Vec<u8>,
     pub kseaf_share: Vec<u8>,
     pub kasme_share: Vec<u8>,
+    pub kseaf_commitment: Vec<u8>,
+    pub kasme_commitment: Vec<u8>,
+    pub epoch: i64,
 }
 
 /// Creates the backup networks table if it does not exist already.
@@ -53,6 +58,9 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
             old_xres_star_hash BLOB NOT NULL,
             kseaf_share BLOB NOT NULL,
             kasme_share BLOB NOT NULL,
+            kseaf_commitment BLOB NOT NULL,
+            kasme_commitment BLOB NOT NULL,
+            epoch INT NOT NULL,
             PRIMARY KEY (backup_network_id, xres_star_hash)
         );",
     )
@@ -68,24 +76,61 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
 pub async fn add(
     transaction: &mut Transaction<'_, Sqlite>,
     backup_network_id: &str,
-    xres_star_hash: &[u8],
-    xres_hash: &XResHash,
     old_xres_star_hash: &[u8],
-    kseaf_share: &keys::KseafShare,
-    kasme_share: &keys::KasmeShare,
+    key_share: &keys::CombinedKeyShare,
 ) -> Result<(), DauthError> {
     tracing::debug!("Adding task");
 
     sqlx::query(
         "INSERT INTO replace_key_share_task_table
-        VALUES ($1,$2,$3,$4,$5,$6)",
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
     )
     .bind(backup_network_id)
-    .bind(xres_star_hash)
-    .bind(xres_hash.as_slice())
+    .bind(key_share.xres_star_hash.as_slice())
+    .bind(key_share.xres_hash.as_slice())
     .bind(old_xres_star_hash)
-    .bind(kseaf_share.as_slice())
-    .bind(kasme_share.as_slice())
+    .bind(key_share.kseaf_share.as_slice())
+    .bind(key_share.kasme_share.as_slice())
+    .bind(key_share.kseaf_commitment.to_vec())
+    .bind(key_share.kasme_commitment.to_vec())
+    .bind(i64::from(key_share.epoch))
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Adds a pending key share refresh, where the new key share replaces
+/// the old key share of the same vector.
+/// If a replace is already pending for the vector, only its key share
+/// is updated, so that the share it replaces is still removed.
+#[tracing::instrument(skip(transaction), name = "database::tasks::replace_key_shares")]
+pub async fn add_refresh(
+    transaction: &mut Transaction<'_, Sqlite>,
+    backup_network_id: &str,
+    key_share: &keys::CombinedKeyShare,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding refresh task");
+
+    sqlx::query(
+        "INSERT INTO replace_key_share_task_table
+        VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
+        ON CONFLICT (backup_network_id, xres_star_hash) DO UPDATE SET
+            kseaf_share=excluded.kseaf_share,
+            kasme_share=excluded.kasme_share,
+            kseaf_commitment=excluded.kseaf_commitment,
+            kasme_commitment=excluded.kasme_commitment,
+            epoch=excluded.epoch",
+    )
+    .bind(backup_network_id)
+    .bind(key_share.xres_star_hash.as_slice())
+    .bind(key_share.xres_hash.as_slice())
+    .bind(key_share.xres_star_hash.as_slice())
+    .bind(key_share.kseaf_share.as_slice())
+    .bind(key_share.kasme_share.as_slice())
+    .bind(key_share.kseaf_commitment.to_vec())
+    .bind(key_share.kasme_commitment.to_vec())
+    .bind(i64::from(key_share.epoch))
     .execute(transaction)
     .await?;
 
@@ -112,6 +157,7 @@ pub async fn get(
 }
 
 /// Removes a specific key share replace.
+#[allow(dead_code)]
 #[tracing::instrument(skip(transaction), name = "database::tasks::replace_key_shares")]
 pub async fn remove(
     transaction: &mut Transaction<'_, Sqlite>,
@@ -132,6 +178,50 @@ pub async fn remove(
     Ok(())
 }
 
+/// Removes a key share replace once sent, unless its key share
+/// was refreshed after it was read.
+#[tracing::instrument(
+    skip(transaction, replace),
+    name = "database::tasks::replace_key_shares"
+)]
+pub async fn remove_sent(
+    transaction: &mut Transaction<'_, Sqlite>,
+    replace: &ReplaceKeyShareTask,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing sent task");
+
+    sqlx::query(
+        "DELETE FROM replace_key_share_task_table
+        WHERE (backup_network_id,xres_star_hash,kseaf_share)=($1,$2,$3)",
+    )
+    .bind(&replace.backup_network_id)
+    .bind(&replace.xres_star_hash)
+    .bind(replace.key_share.kseaf_share.as_slice())
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes all key share replaces for an xres* hash, across all backup networks.
+#[tracing::instrument(skip(transaction), name = "database::tasks::replace_key_shares")]
+pub async fn remove_by_xres_star_hash(
+    transaction: &mut Transaction<'_, Sqlite>,
+    xres_star_hash: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing tasks with xres* hash");
+
+    sqlx::query(
+        "DELETE FROM replace_key_share_task_table
+        WHERE xres_star_hash=$1",
+    )
+    .bind(xres_star_hash)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -141,6 +231,8 @@ mod tests {
     use sqlx::SqlitePool;
     use tempfile::{tempdir, TempDir};
 
+    use auth_vector::types::{KSEAF_LENGTH, XRES_HASH_LENGTH, XRES_STAR_HASH_LENGTH};
+
     use crate::data::keys;
     use crate::database::{general, tasks};
 
@@ -161,6 +253,28 @@ mod tests {
         (pool, dir)
     }
 
+    fn gen_share(xres_star_hash: u8, xres_hash: u8) -> keys::CombinedKeyShare {
+        let (mut kseaf_shares, kseaf_commitment) = keys::create_shares_from_kseaf(
+            &[xres_star_hash; KSEAF_LENGTH],
+            1,
+            1,
+            &mut rand_0_8::thread_rng(),
+        )
+        .unwrap();
+
+        keys::CombinedKeyShare {
+            xres_star_hash: [xres_star_hash; XRES_STAR_HASH_LENGTH],
+            xres_hash: [xres_hash; XRES_HASH_LENGTH],
+            kseaf_share: kseaf_shares.pop().unwrap(),
+            kasme_share: keys::KasmeShare {
+                share: [0xEE; keys::SHARE_LENGTH],
+            },
+            kseaf_commitment: kseaf_commitment.clone(),
+            kasme_commitment: kseaf_commitment,
+            epoch: 0,
+        }
+    }
+
     #[tokio::test]
     async fn test_db_init() {
         init().await;
@@ -176,11 +290,8 @@ mod tests {
             tasks::replace_key_shares::add(
                 &mut transaction,
                 &format!("test_backup_network_{}", row),
-                &vec![row as u8],
-                &[row as u8; 16],
-                &vec![row as u8],
-                &keys::KseafShare { share: [0xFF; 33] },
-                &keys::KasmeShare { share: [0xEE; 33] },
+                &vec![row as u8; XRES_STAR_HASH_LENGTH],
+                &gen_share(row as u8, row as u8),
             )
             .await
             .unwrap()
@@ -200,11 +311,8 @@ mod tests {
             tasks::replace_key_shares::add(
                 &mut transaction,
                 &format!("test_backup_network_{}", row),
-                &vec![row as u8; 16],
-                &[row as u8; 16],
-                &vec![row as u8; 16],
-                &keys::KseafShare { share: [0xEE; 33] },
-                &keys::KasmeShare { share: [0xEE; 33] },
+                &vec![row as u8; XRES_STAR_HASH_LENGTH],
+                &gen_share(row as u8, row as u8),
             )
             .await
             .unwrap();
@@ -234,11 +342,8 @@ mod tests {
             tasks::replace_key_shares::add(
                 &mut transaction,
                 &format!("test_backup_network_{}", row),
-                &vec![row as u8; 16],
-                &[row as u8; 16],
-                &vec![row as u8; 16],
-                &keys::KseafShare { share: [0xDD; 33] },
-                &keys::KasmeShare { share: [0xEE; 33] },
+                &vec![row as u8; XRES_STAR_HASH_LENGTH],
+                &gen_share(row as u8, row as u8),
             )
             .await
             .unwrap();
@@ -277,4 +382,136 @@ mod tests {
         );
         transaction.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_add_refresh() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        tasks::replace_key_shares::add(
+            &mut transaction,
+            "test_backup_network_0",
+            &vec![0u8; XRES_STAR_HASH_LENGTH],
+            &gen_share(1, 1),
+        )
+        .await
+        .unwrap();
+
+        // Refreshing a pending replace keeps the share it replaces
+        let refreshed_share = gen_share(1, 1);
+        tasks::replace_key_shares::add_refresh(
+            &mut transaction,
+            "test_backup_network_0",
+            &refreshed_share,
+        )
+        .await
+        .unwrap();
+
+        // Refreshing with nothing pending replaces the share itself
+        tasks::replace_key_shares::add_refresh(
+            &mut transaction,
+            "test_backup_network_1",
+            &gen_share(1, 1),
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut replaces = tasks::replace_key_shares::get(&mut transaction)
+            .await
+            .unwrap();
+        replaces.sort_by(|a, b| a.backup_network_id.cmp(&b.backup_network_id));
+        assert_eq!(replaces.len(), 2);
+        assert_eq!(replaces[0].old_xres_star_hash, vec![0u8; 16]);
+        assert_eq!(replaces[0].key_share, refreshed_share);
+        assert_eq!(replaces[1].old_xres_star_hash, vec![1u8; 16]);
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_sent() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        tasks::replace_key_shares::add_refresh(
+            &mut transaction,
+            "test_backup_network_0",
+            &gen_share(0, 0),
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let sent = tasks::replace_key_shares::get(&mut transaction)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        // A refresh while the replace is sent is kept for the next send
+        let mut transaction = pool.begin().await.unwrap();
+        tasks::replace_key_shares::add_refresh(
+            &mut transaction,
+            "test_backup_network_0",
+            &gen_share(0, 0),
+        )
+        .await
+        .unwrap();
+        tasks::replace_key_shares::remove_sent(&mut transaction, &sent)
+            .await
+            .unwrap();
+        let pending = tasks::replace_key_shares::get(&mut transaction)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_ne!(pending.key_share, sent.key_share);
+
+        tasks::replace_key_shares::remove_sent(&mut transaction, &pending)
+            .await
+            .unwrap();
+        assert!(tasks::replace_key_shares::get(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_by_xres_star_hash() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            tasks::replace_key_shares::add(
+                &mut transaction,
+                &format!("test_backup_network_{}", row),
+                &vec![row as u8; XRES_STAR_HASH_LENGTH],
+                &gen_share((row % 2) as u8, row as u8),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        tasks::replace_key_shares::remove_by_xres_star_hash(&mut transaction, &vec![0u8; 16])
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let remaining = tasks::replace_key_shares::get(&mut transaction)
+            .await
+            .unwrap();
+        assert_eq!(remaining.len(), num_rows / 2);
+        for res in remaining {
+            assert_eq!(res.xres_star_hash, vec![1u8; 16]);
+        }
+        transaction.commit().await.unwrap();
+    }
 }
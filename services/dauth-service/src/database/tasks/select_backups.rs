@@ -0,0 +1,169 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the select backups table if it does not exist already.
+/// Contains the users waiting for backup networks to be selected
+/// automatically, with the number of backups and the sqn max to start from.
+#[tracing::instrument(skip(pool), name = "database::tasks::select_backups")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS task_select_backups_table (
+            user_id TEXT PRIMARY KEY,
+            num_backups INT NOT NULL,
+            sqn_max INT NOT NULL
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a user to select backups for, replacing any pending selection.
+#[tracing::instrument(skip(transaction), name = "database::tasks::select_backups")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    num_backups: i64,
+    sqn_max: i64,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding task");
+
+    sqlx::query(
+        "REPLACE INTO task_select_backups_table
+        VALUES ($1,$2,$3)",
+    )
+    .bind(user_id)
+    .bind(num_backups)
+    .bind(sqn_max)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the number of backups and the sqn max of a pending selection, if any.
+#[tracing::instrument(skip(transaction), name = "database::tasks::select_backups")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<Option<(i64, i64)>, DauthError> {
+    tracing::debug!("Getting task");
+
+    let possible_row = sqlx::query(
+        "SELECT * FROM task_select_backups_table
+        WHERE user_id=$1;",
+    )
+    .bind(user_id)
+    .fetch_optional(transaction)
+    .await?;
+
+    match possible_row {
+        Some(row) => Ok(Some((
+            row.try_get::<i64, &str>("num_backups")?,
+            row.try_get::<i64, &str>("sqn_max")?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Removes a pending selection for a user id.
+#[tracing::instrument(skip(transaction), name = "database::tasks::select_backups")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing task");
+
+    sqlx::query(
+        "DELETE FROM task_select_backups_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::database::{general, tasks};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        tasks::select_backups::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            // A later selection replaces the pending one
+            for num_backups in [1, row] {
+                tasks::select_backups::add(
+                    &mut transaction,
+                    &format!("test_user_{}", row),
+                    num_backups,
+                    32,
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            let user_id = format!("test_user_{}", row);
+            assert_eq!(
+                Some((row, 32)),
+                tasks::select_backups::get(&mut transaction, &user_id)
+                    .await
+                    .unwrap()
+            );
+
+            tasks::select_backups::remove(&mut transaction, &user_id)
+                .await
+                .unwrap();
+            assert!(tasks::select_backups::get(&mut transaction, &user_id)
+                .await
+                .unwrap()
+                .is_none());
+        }
+        transaction.commit().await.unwrap();
+    }
+}
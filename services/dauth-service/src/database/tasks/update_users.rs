@@ -121,6 +121,8 @@ mod tests {
     use sqlx::SqlitePool;
     use tempfile::{tempdir, TempDir};
 
+    use auth_vector::types::Algorithm;
+
     use crate::database::{general, tasks, user_infos};
 
     fn gen_name() -> String {
@@ -159,6 +161,7 @@ mod tests {
                     &format!("test_user_id_{}", row),
                     &[0u8, 3],
                     &[0u8, 3],
+                    Algorithm::Milenage,
                     sqn_max,
                     sqn_max,
                 )
@@ -212,6 +215,7 @@ mod tests {
                 &"test_user_id".to_string(),
                 &[0u8, 3],
                 &[0u8, 3],
+                Algorithm::Milenage,
                 sqn_max,
                 sqn_max,
             )
@@ -256,6 +260,7 @@ mod tests {
                     &format!("test_user_id_{}", row),
                     &[0u8, 3],
                     &[0u8, 3],
+                    Algorithm::Milenage,
                     sqn_max,
                     sqn_max,
                 )
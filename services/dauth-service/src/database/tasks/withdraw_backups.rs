@@ -0,0 +1,161 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the withdraw backups table if it does not exist already.
+/// Contains the backup networks that are due to be withdrawn
+/// as a backup of a user.
+#[tracing::instrument(skip(pool), name = "database::tasks::withdraw_backups")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS task_withdraw_backups_table (
+            user_id TEXT NOT NULL,
+            backup_network_id TEXT NOT NULL,
+            PRIMARY KEY (user_id, backup_network_id)
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a backup network to be withdrawn, if not already pending.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_backups")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    backup_network_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding task");
+
+    sqlx::query(
+        "REPLACE INTO task_withdraw_backups_table
+        VALUES ($1,$2)",
+    )
+    .bind(user_id)
+    .bind(backup_network_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets all pending withdrawals, as user id and backup network id.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_backups")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<(String, String)>, DauthError> {
+    tracing::debug!("Getting all tasks");
+
+    let rows = sqlx::query("SELECT * FROM task_withdraw_backups_table")
+        .fetch_all(transaction)
+        .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push((
+            row.try_get::<String, &str>("user_id")?,
+            row.try_get::<String, &str>("backup_network_id")?,
+        ));
+    }
+    Ok(result)
+}
+
+/// Removes a pending withdrawal if found.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_backups")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    backup_network_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing task");
+
+    sqlx::query(
+        "DELETE FROM task_withdraw_backups_table
+        WHERE (user_id,backup_network_id)=($1,$2)",
+    )
+    .bind(user_id)
+    .bind(backup_network_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use crate::database::{general, tasks};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        tasks::withdraw_backups::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            // Repeated withdrawals of a backup are only queued once
+            for _ in 0..2 {
+                tasks::withdraw_backups::add(
+                    &mut transaction,
+                    &format!("test_user_{}", row),
+                    &format!("test_backup_{}", row % 2),
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let withdrawals = tasks::withdraw_backups::get(&mut transaction)
+            .await
+            .unwrap();
+        assert_eq!(num_rows, withdrawals.len());
+
+        for (user_id, backup_network_id) in withdrawals {
+            tasks::withdraw_backups::remove(&mut transaction, &user_id, &backup_network_id)
+                .await
+                .unwrap();
+        }
+        assert!(tasks::withdraw_backups::get(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        transaction.commit().await.unwrap();
+    }
+}
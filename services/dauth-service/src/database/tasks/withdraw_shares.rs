@@ -0,0 +1,162 @@
+use auth_vector::types::XResStarHash;
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+use sqlx::{Sqlite, Transaction};
+
+use crate::data::error::DauthError;
+
+/// Creates the withdraw shares table if it does not exist already.
+/// Contains the key shares that are due to be withdrawn from
+/// the backup networks holding them.
+#[tracing::instrument(skip(pool), name = "database::tasks::withdraw_shares")]
+pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
+    tracing::info!("Initialzing table");
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS task_withdraw_shares_table (
+            backup_network_id TEXT NOT NULL,
+            xres_star_hash BLOB NOT NULL,
+            PRIMARY KEY (backup_network_id, xres_star_hash)
+        );",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/* Queries */
+
+/// Adds a key share to be withdrawn, if not already pending.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_shares")]
+pub async fn add(
+    transaction: &mut Transaction<'_, Sqlite>,
+    backup_network_id: &str,
+    xres_star_hash: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Adding task");
+
+    sqlx::query(
+        "REPLACE INTO task_withdraw_shares_table
+        VALUES ($1,$2)",
+    )
+    .bind(backup_network_id)
+    .bind(xres_star_hash)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets all pending withdrawals, as backup network id and xres* hash.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_shares")]
+pub async fn get(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<(String, XResStarHash)>, DauthError> {
+    tracing::debug!("Getting all tasks");
+
+    let rows = sqlx::query("SELECT * FROM task_withdraw_shares_table")
+        .fetch_all(transaction)
+        .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push((
+            row.try_get::<String, &str>("backup_network_id")?,
+            row.try_get::<&[u8], &str>("xres_star_hash")?.try_into()?,
+        ));
+    }
+    Ok(result)
+}
+
+/// Removes a pending withdrawal if found.
+#[tracing::instrument(skip(transaction), name = "database::tasks::withdraw_shares")]
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    backup_network_id: &str,
+    xres_star_hash: &[u8],
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing task");
+
+    sqlx::query(
+        "DELETE FROM task_withdraw_shares_table
+        WHERE (backup_network_id,xres_star_hash)=($1,$2)",
+    )
+    .bind(backup_network_id)
+    .bind(xres_star_hash)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::SqlitePool;
+    use tempfile::{tempdir, TempDir};
+
+    use auth_vector::types::XRES_STAR_HASH_LENGTH;
+
+    use crate::database::{general, tasks};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::build_pool(&path).await.unwrap();
+        tasks::withdraw_shares::init_table(&pool).await.unwrap();
+
+        (pool, dir)
+    }
+
+    #[tokio::test]
+    async fn test_db_init() {
+        init().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_get_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            // Repeated withdrawals of a share are only queued once
+            for _ in 0..2 {
+                tasks::withdraw_shares::add(
+                    &mut transaction,
+                    &format!("test_backup_{}", row % 2),
+                    &[row; XRES_STAR_HASH_LENGTH],
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let withdrawals = tasks::withdraw_shares::get(&mut transaction).await.unwrap();
+        assert_eq!(num_rows as usize, withdrawals.len());
+
+        for (backup_network_id, xres_star_hash) in withdrawals {
+            tasks::withdraw_shares::remove(&mut transaction, &backup_network_id, &xres_star_hash)
+                .await
+                .unwrap();
+        }
+        assert!(tasks::withdraw_shares::get(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        transaction.commit().await.unwrap();
+    }
+}
@@ -1,6 +1,6 @@
-use auth_vector::types::Id;
+use auth_vector::types::{Algorithm, Id};
 use sqlx::sqlite::SqlitePool;
-use sqlx::{Sqlite, Transaction};
+use sqlx::{Row, Sqlite, Transaction};
 
 use crate::data::error::DauthError;
 use crate::data::user_info::UserInfo;
@@ -17,8 +17,10 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
             id TEXT NOT NULL,
             k BLOB NOT NULL,
             opc BLOB NOT NULL,
+            algorithm TEXT NOT NULL,
             sqn_max INT NOT NULL,
             sqn_slice INT NOT NULL,
+            key_threshold INT,
             PRIMARY KEY (id, sqn_slice)
         );",
     )
@@ -49,17 +51,43 @@ pub async fn get(
     .to_user_info()?)
 }
 
+/// Gets all sqn slices with user info for a user.
+#[tracing::instrument(skip(transaction), name = "database::user_infos")]
+pub async fn get_slices(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &Id,
+) -> Result<Vec<i64>, DauthError> {
+    tracing::debug!("Getting user info slices");
+
+    let rows = sqlx::query(
+        "SELECT sqn_slice FROM user_info_table
+        WHERE id=$1;",
+    )
+    .bind(user_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in rows {
+        result.push(row.try_get::<i64, &str>("sqn_slice")?);
+    }
+    Ok(result)
+}
+
 #[derive(Debug, Clone, Copy, sqlx::FromRow)]
 struct SqnMaxRow {
     pub sqn_max: i64,
 }
 /// Insert user info and replace if exists.
+/// The opc is the OPc for Milenage or the TOPc for TUAK.
+/// The key threshold of an existing user is kept.
 #[tracing::instrument(skip(transaction), name = "database::user_infos")]
 pub async fn upsert(
     transaction: &mut Transaction<'_, Sqlite>,
     user_id: &Id,
     k: &[u8],
     opc: &[u8],
+    algorithm: Algorithm,
     sqn_max: i64,
     sqn_slice: i64,
 ) -> Result<(), DauthError> {
@@ -85,12 +113,18 @@ pub async fn upsert(
     }
 
     sqlx::query(
-        "REPLACE INTO user_info_table
-        VALUES ($1,$2,$3,$4,$5);",
+        "INSERT INTO user_info_table (id, k, opc, algorithm, sqn_max, sqn_slice)
+        VALUES ($1,$2,$3,$4,$5,$6)
+        ON CONFLICT(id, sqn_slice) DO UPDATE SET
+            k=excluded.k,
+            opc=excluded.opc,
+            algorithm=excluded.algorithm,
+            sqn_max=excluded.sqn_max;",
     )
     .bind(user_id)
     .bind(k)
     .bind(opc)
+    .bind(algorithm.as_str())
     .bind(sqn_to_insert)
     .bind(sqn_slice)
     .execute::<&mut Transaction<'_, Sqlite>>(transaction)
@@ -99,9 +133,51 @@ pub async fn upsert(
     Ok(())
 }
 
-/// Remove user info if exists.
-/// Not currently used.
-#[allow(dead_code)]
+/// Sets the number of key shares needed to recover the keys
+/// of the user's vectors, or the network default if none.
+/// Kept with the user info of the home network slice.
+#[tracing::instrument(skip(transaction), name = "database::user_infos")]
+pub async fn set_key_threshold(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &Id,
+    key_threshold: Option<u8>,
+) -> Result<(), DauthError> {
+    tracing::debug!("Setting user key threshold");
+
+    sqlx::query(
+        "UPDATE user_info_table
+        SET key_threshold=$2
+        WHERE (id,sqn_slice)=($1,0);",
+    )
+    .bind(user_id)
+    .bind(key_threshold.map(i64::from))
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the key threshold of the user, if set.
+#[tracing::instrument(skip(transaction), name = "database::user_infos")]
+pub async fn get_key_threshold(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &Id,
+) -> Result<Option<u8>, DauthError> {
+    tracing::debug!("Getting user key threshold");
+
+    let key_threshold = sqlx::query(
+        "SELECT key_threshold FROM user_info_table
+        WHERE (id,sqn_slice)=($1,0);",
+    )
+    .bind(user_id)
+    .fetch_one(transaction)
+    .await?
+    .try_get::<Option<i64>, &str>("key_threshold")?;
+
+    Ok(key_threshold.map(|key_threshold| key_threshold as u8))
+}
+
+/// Remove user info for all sqn slices if exists.
 #[tracing::instrument(skip(transaction), name = "database::user_infos")]
 pub async fn remove(
     transaction: &mut Transaction<'_, Sqlite>,
@@ -129,7 +205,7 @@ mod tests {
     use sqlx::SqlitePool;
     use tempfile::{tempdir, TempDir};
 
-    use auth_vector::types::{K_LENGTH, OPC_LENGTH};
+    use auth_vector::types::{Algorithm, K_LENGTH, OPC_LENGTH, TOPC_LENGTH};
 
     use crate::database::{general, user_infos};
 
@@ -173,6 +249,7 @@ mod tests {
                     &format!("user_info_{}", section * num_rows + row),
                     &[section * num_rows + row; K_LENGTH],
                     &[section * num_rows + row; OPC_LENGTH],
+                    Algorithm::Milenage,
                     (section * num_rows + row) as i64,
                     0,
                 )
@@ -200,6 +277,7 @@ mod tests {
                     &format!("user_info_{}", section * num_rows + row),
                     &[section * num_rows + row; K_LENGTH],
                     &[section * num_rows + row; OPC_LENGTH],
+                    Algorithm::Milenage,
                     (section * num_rows + row) as i64,
                     0,
                 )
@@ -222,7 +300,7 @@ mod tests {
 
                 assert_eq!(format!("user_info_{}", section * num_rows + row), res.id);
                 assert_eq!([section * num_rows + row; K_LENGTH], res.k);
-                assert_eq!([section * num_rows + row; OPC_LENGTH], res.opc);
+                assert_eq!([section * num_rows + row; OPC_LENGTH].to_vec(), res.opc);
                 assert_eq!((section * num_rows + row) as i64, res.sqn);
             }
         }
@@ -246,6 +324,7 @@ mod tests {
                     &format!("user_info_{}", section * num_rows + row),
                     &[section * num_rows + row; K_LENGTH],
                     &[section * num_rows + row; OPC_LENGTH],
+                    Algorithm::Milenage,
                     (section * num_rows + row) as i64,
                     0,
                 )
@@ -282,6 +361,51 @@ mod tests {
         transaction.commit().await.unwrap();
     }
 
+    /// Test that the algorithm and a TUAK TOPc are stored
+    #[tokio::test]
+    async fn test_algorithm() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        user_infos::upsert(
+            &mut transaction,
+            &"user_info_milenage".to_string(),
+            &[1; K_LENGTH],
+            &[2; OPC_LENGTH],
+            Algorithm::Milenage,
+            1,
+            0,
+        )
+        .await
+        .unwrap();
+        user_infos::upsert(
+            &mut transaction,
+            &"user_info_tuak".to_string(),
+            &[3; K_LENGTH],
+            &[4; TOPC_LENGTH],
+            Algorithm::Tuak,
+            1,
+            0,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let res = user_infos::get(&mut transaction, &"user_info_milenage".to_string(), 0)
+            .await
+            .unwrap();
+        assert_eq!(Algorithm::Milenage, res.algorithm);
+        assert_eq!(vec![2; OPC_LENGTH], res.opc);
+
+        let res = user_infos::get(&mut transaction, &"user_info_tuak".to_string(), 0)
+            .await
+            .unwrap();
+        assert_eq!(Algorithm::Tuak, res.algorithm);
+        assert_eq!(vec![4; TOPC_LENGTH], res.opc);
+        transaction.commit().await.unwrap();
+    }
+
     /// Test that updates works
     #[tokio::test]
     async fn test_update() {
@@ -299,6 +423,7 @@ mod tests {
                     &format!("user_info_{}", section * num_rows + row),
                     &[section * num_rows + row; K_LENGTH],
                     &[section * num_rows + row; OPC_LENGTH],
+                    Algorithm::Milenage,
                     1,
                     0,
                 )
@@ -321,7 +446,7 @@ mod tests {
 
                 assert_eq!(format!("user_info_{}", section * num_rows + row), res.id);
                 assert_eq!([section * num_rows + row; K_LENGTH], res.k);
-                assert_eq!([section * num_rows + row; OPC_LENGTH], res.opc);
+                assert_eq!([section * num_rows + row; OPC_LENGTH].to_vec(), res.opc);
                 assert_eq!(1, res.sqn);
             }
         }
@@ -335,6 +460,7 @@ mod tests {
                     &format!("user_info_{}", section * num_rows + row),
                     &[section * num_rows + row + 1; K_LENGTH],
                     &[section * num_rows + row + 2; OPC_LENGTH],
+                    Algorithm::Milenage,
                     2,
                     0,
                 )
@@ -359,15 +485,123 @@ mod tests {
 
                 // old values
                 assert_ne!([section * num_rows + row; K_LENGTH], res.k);
-                assert_ne!([section * num_rows + row; OPC_LENGTH], res.opc);
+                assert_ne!([section * num_rows + row; OPC_LENGTH].to_vec(), res.opc);
                 assert_ne!(1, res.sqn);
 
                 // new values
                 assert_eq!([section * num_rows + row + 1; K_LENGTH], res.k);
-                assert_eq!([section * num_rows + row + 2; OPC_LENGTH], res.opc);
+                assert_eq!([section * num_rows + row + 2; OPC_LENGTH].to_vec(), res.opc);
                 assert_eq!(2, res.sqn);
             }
         }
         transaction.commit().await.unwrap();
     }
+
+    /// Test that all slices of a user are returned
+    #[tokio::test]
+    async fn test_get_slices() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for slice in [0, 2, 5] {
+            for user in 0..2 {
+                user_infos::upsert(
+                    &mut transaction,
+                    &format!("user_info_{}", user),
+                    &[user; K_LENGTH],
+                    &[user; OPC_LENGTH],
+                    Algorithm::Milenage,
+                    32 + slice,
+                    slice,
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut slices = user_infos::get_slices(&mut transaction, &"user_info_0".to_string())
+            .await
+            .unwrap();
+        slices.sort();
+        assert_eq!(vec![0, 2, 5], slices);
+        assert!(
+            user_infos::get_slices(&mut transaction, &"user_info_2".to_string())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    /// Test that the key threshold is set, and kept on update
+    #[tokio::test]
+    async fn test_key_threshold() {
+        let (pool, _dir) = init().await;
+        let user_id = "user_info_0".to_string();
+
+        let mut transaction = pool.begin().await.unwrap();
+        user_infos::upsert(
+            &mut transaction,
+            &user_id,
+            &[0; K_LENGTH],
+            &[0; OPC_LENGTH],
+            Algorithm::Milenage,
+            32,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            None,
+            user_infos::get_key_threshold(&mut transaction, &user_id)
+                .await
+                .unwrap()
+        );
+
+        user_infos::set_key_threshold(&mut transaction, &user_id, Some(4))
+            .await
+            .unwrap();
+        user_infos::upsert(
+            &mut transaction,
+            &user_id,
+            &[1; K_LENGTH],
+            &[1; OPC_LENGTH],
+            Algorithm::Milenage,
+            64,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            Some(4),
+            user_infos::get_key_threshold(&mut transaction, &user_id)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            [1; K_LENGTH],
+            user_infos::get(&mut transaction, &user_id, 0)
+                .await
+                .unwrap()
+                .k
+        );
+
+        user_infos::set_key_threshold(&mut transaction, &user_id, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            None,
+            user_infos::get_key_threshold(&mut transaction, &user_id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            user_infos::get_key_threshold(&mut transaction, &"user_info_1".to_string())
+                .await
+                .is_err()
+        );
+        transaction.commit().await.unwrap();
+    }
 }
@@ -1,19 +1,28 @@
 use auth_vector::types::{Kasme, Kseaf};
+use prost::Message;
 use sqlx::sqlite::SqliteRow;
 use sqlx::Row;
 
 use crate::data::error::DauthError;
 use crate::data::keys;
+use crate::data::misbehaviour::Misbehaviour;
+use crate::data::policy::ServingPolicy;
+use crate::data::state::{AuthSource, AuthState};
 use crate::data::user_info::UserInfo;
-use crate::data::vector::AuthVectorRes;
+use crate::data::vector::{AuthVectorRes, Plmn};
+use crate::rpc::dauth::common::ServingNetworkPolicy;
 
 pub trait DauthDataUtilities {
     fn to_auth_vector(&self) -> Result<AuthVectorRes, DauthError>;
     fn to_kseaf(&self) -> Result<Kseaf, DauthError>;
     fn to_kasme(&self) -> Result<Kasme, DauthError>;
+    fn to_eap_aka_prime_keys(&self) -> Result<keys::EapAkaPrimeSessionKeys, DauthError>;
     fn to_key_share(&self) -> Result<keys::KseafShare, DauthError>;
     fn to_user_info(&self) -> Result<UserInfo, DauthError>;
     fn to_backup_user_home_network_id(&self) -> Result<String, DauthError>;
+    fn to_auth_state(&self) -> Result<AuthState, DauthError>;
+    fn to_serving_policy(&self) -> Result<ServingPolicy, DauthError>;
+    fn to_misbehaviour(&self) -> Result<Misbehaviour, DauthError>;
 }
 
 /// Add functionality to the sqlite row
@@ -26,6 +35,10 @@ impl DauthDataUtilities for SqliteRow {
             autn: self.try_get::<&[u8], &str>("autn")?.try_into()?,
             rand: self.try_get::<&[u8], &str>("rand")?.try_into()?,
             xres_hash: self.try_get::<&[u8], &str>("xres_hash")?.try_into()?,
+            plmn: Plmn {
+                mcc: self.try_get::<String, &str>("mcc")?,
+                mnc: self.try_get::<String, &str>("mnc")?,
+            },
         })
     }
 
@@ -37,6 +50,13 @@ impl DauthDataUtilities for SqliteRow {
         Ok(self.try_get::<&[u8], &str>("kasme_data")?.try_into()?)
     }
 
+    fn to_eap_aka_prime_keys(&self) -> Result<keys::EapAkaPrimeSessionKeys, DauthError> {
+        Ok(keys::EapAkaPrimeSessionKeys {
+            msk: self.try_get::<&[u8], &str>("msk")?.try_into()?,
+            kseaf: self.try_get::<&[u8], &str>("kseaf")?.try_into()?,
+        })
+    }
+
     fn to_key_share(&self) -> Result<keys::KseafShare, DauthError> {
         Ok(self.try_get::<&[u8], &str>("key_share")?.try_into()?)
     }
@@ -45,7 +65,8 @@ impl DauthDataUtilities for SqliteRow {
         Ok(UserInfo {
             id: self.try_get::<String, &str>("id")?,
             k: self.try_get::<&[u8], &str>("k")?.try_into()?,
-            opc: self.try_get::<&[u8], &str>("opc")?.try_into()?,
+            opc: self.try_get::<Vec<u8>, &str>("opc")?,
+            algorithm: self.try_get::<&str, &str>("algorithm")?.try_into()?,
             sqn: self.try_get::<i64, &str>("sqn_max")?,
         })
     }
@@ -53,4 +74,27 @@ impl DauthDataUtilities for SqliteRow {
     fn to_backup_user_home_network_id(&self) -> Result<String, DauthError> {
         Ok(self.try_get::<&str, &str>("home_network_id")?.to_string())
     }
+
+    fn to_auth_state(&self) -> Result<AuthState, DauthError> {
+        Ok(AuthState {
+            rand: self.try_get::<&[u8], &str>("rand")?.try_into()?,
+            source: AuthSource::from_i64(self.try_get::<i64, &str>("source")?)?,
+            xres_star_hash: self.try_get::<&[u8], &str>("xres_star_hash")?.try_into()?,
+            xres_hash: self.try_get::<&[u8], &str>("xres_hash")?.try_into()?,
+        })
+    }
+
+    fn to_serving_policy(&self) -> Result<ServingPolicy, DauthError> {
+        ServingNetworkPolicy::decode(self.try_get::<&[u8], &str>("policy")?)?.try_into()
+    }
+
+    fn to_misbehaviour(&self) -> Result<Misbehaviour, DauthError> {
+        Ok(Misbehaviour {
+            network_id: self.try_get::<String, &str>("network_id")?,
+            kind: self.try_get::<&str, &str>("kind")?.try_into()?,
+            detail: self.try_get::<String, &str>("detail")?,
+            evidence: self.try_get::<Vec<u8>, &str>("evidence")?,
+            timestamp: self.try_get::<i64, &str>("timestamp")?,
+        })
+    }
 }
@@ -2,6 +2,7 @@ use sqlx::sqlite::SqlitePool;
 use sqlx::{Row, Sqlite, Transaction};
 
 use crate::data::error::DauthError;
+use crate::data::vector::Plmn;
 
 /// Creates the vector state table if it does not exist already.
 #[tracing::instrument(skip(pool), name = "database::vector_state")]
@@ -12,7 +13,9 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
         "CREATE TABLE IF NOT EXISTS vector_state_table (
             xres_star_hash BLOB PRIMARY KEY,
             user_id TEXT NOT NULL,
-            backup_network_id TEXT NOT NULL
+            backup_network_id TEXT NOT NULL,
+            mcc TEXT NOT NULL,
+            mnc TEXT NOT NULL
         );",
     )
     .execute(pool)
@@ -24,32 +27,36 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DauthError> {
 
 /// Adds the auth vector as owned by the backup network.
 /// Use xres* hash as the reference for the auth vector.
+/// The PLMN its keys are bound to is kept for replacement vectors.
 #[tracing::instrument(skip(transaction), name = "database::vector_state")]
 pub async fn add(
     transaction: &mut Transaction<'_, Sqlite>,
     xres_star_hash: &[u8],
     user_id: &str,
     backup_network_id: &str,
+    plmn: &Plmn,
 ) -> Result<(), DauthError> {
     sqlx::query(
         "INSERT INTO vector_state_table
-        VALUES ($1,$2,$3)",
+        VALUES ($1,$2,$3,$4,$5)",
     )
     .bind(xres_star_hash)
     .bind(user_id)
     .bind(backup_network_id)
+    .bind(&plmn.mcc)
+    .bind(&plmn.mnc)
     .execute(transaction)
     .await?;
 
     Ok(())
 }
 
-/// Returns the owning network and user id of the auth vector.
+/// Returns the owning network, user id and bound PLMN of the auth vector.
 #[tracing::instrument(skip(transaction), name = "database::vector_state")]
 pub async fn get(
     transaction: &mut Transaction<'_, Sqlite>,
     xres_star_hash: &[u8],
-) -> Result<Option<(String, String)>, DauthError> {
+) -> Result<Option<(String, String, Plmn)>, DauthError> {
     tracing::debug!("Getting vector state");
 
     let possible_row = sqlx::query(
@@ -64,6 +71,10 @@ pub async fn get(
         Some(row) => Ok(Some((
             row.try_get::<String, &str>("backup_network_id")?,
             row.try_get::<String, &str>("user_id")?,
+            Plmn {
+                mcc: row.try_get::<String, &str>("mcc")?,
+                mnc: row.try_get::<String, &str>("mnc")?,
+            },
         ))),
         None => Ok(None),
     }
@@ -113,6 +124,48 @@ pub async fn remove(
     Ok(())
 }
 
+/// Returns all xres* hashes tracked for a given user, across all networks.
+#[tracing::instrument(skip(transaction), name = "database::vector_state")]
+pub async fn get_all_by_user(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<Vec<Vec<u8>>, DauthError> {
+    tracing::debug!("Getting all vector states for user");
+
+    let res = sqlx::query(
+        "SELECT * FROM vector_state_table
+        WHERE user_id=$1;",
+    )
+    .bind(user_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut hashes = Vec::with_capacity(res.len());
+    for row in res {
+        hashes.push(row.try_get::<Vec<u8>, &str>("xres_star_hash")?)
+    }
+    Ok(hashes)
+}
+
+/// Deletes all vector references for a given user.
+#[tracing::instrument(skip(transaction), name = "database::vector_state")]
+pub async fn remove_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DauthError> {
+    tracing::debug!("Removing all vector states for user");
+
+    sqlx::query(
+        "DELETE FROM vector_state_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -122,6 +175,7 @@ mod tests {
     use sqlx::SqlitePool;
     use tempfile::{tempdir, TempDir};
 
+    use crate::data::vector::Plmn;
     use crate::database::{general, vector_state};
 
     fn gen_name() -> String {
@@ -141,6 +195,13 @@ mod tests {
         (pool, dir)
     }
 
+    fn gen_plmn(row: usize) -> Plmn {
+        Plmn {
+            mcc: "901".to_string(),
+            mnc: format!("{:02}", row),
+        }
+    }
+
     #[tokio::test]
     async fn test_db_init() {
         init().await;
@@ -158,6 +219,7 @@ mod tests {
                 &[row as u8; 1],
                 "test_user_id",
                 &format!("test_backup_network_{}", row),
+                &gen_plmn(row),
             )
             .await
             .unwrap();
@@ -177,6 +239,7 @@ mod tests {
                 &[row as u8; 1],
                 "test_user_id",
                 &format!("test_backup_network_{}", row),
+                &gen_plmn(row),
             )
             .await
             .unwrap();
@@ -185,14 +248,14 @@ mod tests {
 
         let mut transaction = pool.begin().await.unwrap();
         for row in 0..num_rows {
-            assert_eq!(
-                vector_state::get(&mut transaction, &[row as u8; 1],)
+            let (backup_network_id, user_id, plmn) =
+                vector_state::get(&mut transaction, &[row as u8; 1])
                     .await
                     .unwrap()
-                    .unwrap()
-                    .0,
-                format!("test_backup_network_{}", row)
-            );
+                    .unwrap();
+            assert_eq!(backup_network_id, format!("test_backup_network_{}", row));
+            assert_eq!(user_id, "test_user_id");
+            assert_eq!(plmn, gen_plmn(row));
         }
         transaction.commit().await.unwrap();
     }
@@ -216,6 +279,7 @@ mod tests {
                 &[row as u8; 1],
                 "test_user_id",
                 "test_backup_network_id",
+                &gen_plmn(row),
             )
             .await
             .unwrap();
@@ -243,6 +307,7 @@ mod tests {
                 &[row as u8; 1],
                 "test_user_id",
                 &format!("test_backup_network_{}", row),
+                &gen_plmn(row),
             )
             .await
             .unwrap();
@@ -279,4 +344,51 @@ mod tests {
         }
         transaction.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_remove_all() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            vector_state::add(
+                &mut transaction,
+                &[row as u8; 1],
+                &format!("test_user_id_{}", row % 2),
+                &format!("test_backup_network_{}", row),
+                &gen_plmn(row),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let res = vector_state::get_all_by_user(&mut transaction, "test_user_id_0")
+            .await
+            .unwrap();
+        assert_eq!(res.len(), num_rows / 2);
+
+        vector_state::remove_all(&mut transaction, "test_user_id_0")
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        assert!(
+            vector_state::get_all_by_user(&mut transaction, "test_user_id_0")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            vector_state::get_all_by_user(&mut transaction, "test_user_id_1")
+                .await
+                .unwrap()
+                .len(),
+            num_rows / 2
+        );
+        transaction.commit().await.unwrap();
+    }
 }
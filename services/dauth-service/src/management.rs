@@ -1,25 +1,51 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::data::{config::UserInfoConfig, context::DauthContext, error::DauthError};
+use ed25519_dalek::Keypair;
+use rand::rngs::OsRng;
+
+use crate::common;
+use crate::data::{
+    config::UserInfoConfig,
+    context::DauthContext,
+    error::DauthError,
+    misbehaviour::{self, Misbehaviour},
+    quota::{BackupQuota, BackupUsage},
+    utilities,
+};
 use crate::database;
+use crate::rpc::clients::directory;
 
 /// Adds a new user to this network.
+/// Replaces any pending update of the user, and queues the
+/// automatic selection of backups if the user has auto backups.
+/// Fails if the user has fewer backups than its key threshold.
 pub async fn add_user(
     context: Arc<DauthContext>,
     user_info: &UserInfoConfig,
 ) -> Result<(), DauthError> {
+    let opc = user_info.get_opc(context.local_context.op.as_deref())?;
+    let serving_policy = user_info.get_serving_policy()?;
+    let key_threshold = user_info.get_key_threshold()?;
+    user_info.check_key_threshold(context.backup_context.backup_key_threshold)?;
+
     let mut transaction = context.local_context.database_pool.begin().await?;
 
     database::user_infos::upsert(
         &mut transaction,
         &user_info.user_id,
         &user_info.get_k()?,
-        &user_info.get_opc()?,
+        &opc,
+        user_info.get_algorithm()?,
         user_info.sqn_max,
         0, // home network
     )
     .await?;
+    database::user_infos::set_key_threshold(&mut transaction, &user_info.user_id, key_threshold)
+        .await?;
 
+    database::tasks::update_users::remove(&mut transaction, &user_info.user_id).await?;
     database::tasks::update_users::add(
         &mut transaction,
         &user_info.user_id,
@@ -28,12 +54,34 @@ pub async fn add_user(
     )
     .await?;
 
+    match user_info.auto_backups {
+        Some(auto_backups) if auto_backups > 0 => {
+            database::tasks::select_backups::add(
+                &mut transaction,
+                &user_info.user_id,
+                auto_backups,
+                user_info.sqn_max,
+            )
+            .await?
+        }
+        _ => database::tasks::select_backups::remove(&mut transaction, &user_info.user_id).await?,
+    }
+
+    match &serving_policy {
+        Some(serving_policy) => {
+            database::serving_policies::upsert(&mut transaction, &user_info.user_id, serving_policy)
+                .await?
+        }
+        None => database::serving_policies::remove(&mut transaction, &user_info.user_id).await?,
+    }
+
     for backup in &user_info.backups {
         database::user_infos::upsert(
             &mut transaction,
             &user_info.user_id,
             &user_info.get_k()?,
-            &user_info.get_opc()?,
+            &opc,
+            user_info.get_algorithm()?,
             backup.sqn_max,
             backup.sqn_slice,
         )
@@ -52,3 +100,175 @@ pub async fn add_user(
 
     Ok(())
 }
+
+/// Removes a user owned by this network.
+/// Deletes all local state for the user and queues the withdrawal of
+/// the user and its key shares from each of its backup networks in
+/// the same transaction, then withdraws the user from the directory.
+pub async fn remove_user(context: Arc<DauthContext>, user_id: &str) -> Result<(), DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    // Fails if the user is not owned by this network
+    database::user_infos::get(&mut transaction, &user_id.to_string(), 0).await?;
+
+    for backup_network_id in
+        database::backup_networks::get_all_by_user(&mut transaction, user_id).await?
+    {
+        for xres_star_hash in
+            database::key_share_state::get_all_by_id(&mut transaction, user_id, &backup_network_id)
+                .await?
+        {
+            database::tasks::withdraw_shares::add(
+                &mut transaction,
+                &backup_network_id,
+                &xres_star_hash,
+            )
+            .await?;
+        }
+        database::tasks::withdraw_backups::add(&mut transaction, user_id, &backup_network_id)
+            .await?;
+    }
+
+    for xres_star_hash in database::vector_state::get_all_by_user(&mut transaction, user_id).await?
+    {
+        database::tasks::replace_key_shares::remove_by_xres_star_hash(
+            &mut transaction,
+            &xres_star_hash,
+        )
+        .await?;
+    }
+
+    database::tasks::update_users::remove(&mut transaction, user_id).await?;
+    database::tasks::select_backups::remove(&mut transaction, user_id).await?;
+    database::vector_state::remove_all(&mut transaction, user_id).await?;
+    database::consumed_vectors::remove_all(&mut transaction, user_id).await?;
+    database::delegated_vectors::remove_all(&mut transaction, user_id).await?;
+    database::tasks::refresh_key_shares::remove(&mut transaction, user_id).await?;
+    database::key_share_state::remove_all(&mut transaction, user_id).await?;
+    database::backup_networks::remove_all(&mut transaction, user_id).await?;
+    database::serving_policies::remove(&mut transaction, user_id).await?;
+    database::user_infos::remove(&mut transaction, &user_id.to_string()).await?;
+
+    transaction.commit().await?;
+
+    directory::delete_user(context.clone(), user_id).await?;
+
+    Ok(())
+}
+
+/// Replaces the signing key of this network with a newly generated key.
+/// The new key is stored and published to the directory, and the
+/// previous key remains valid to peers for the rollover window.
+/// Vectors and shares delegated before the rotation remain valid
+/// under the previous key after the window, until the next rotation.
+pub async fn rotate_signing_key(
+    context: Arc<DauthContext>,
+    rollover_window: Duration,
+) -> Result<(), DauthError> {
+    let new_signing_keys = Keypair::generate(&mut OsRng {});
+    let keyfile_path = &context.local_context.keyfile_path;
+
+    // Store the new key first, so a restart never uses a key
+    // that the directory no longer accepts.
+    std::fs::write(keyfile_path, new_signing_keys.to_bytes())?;
+
+    // Switch keys before publishing, so that everything signed by the
+    // previous key is timestamped before the registered key change.
+    let previous_signing_keys = std::mem::replace(
+        &mut *context
+            .local_context
+            .signing_keys
+            .write()
+            .expect("Signing keys lock poisoned"),
+        new_signing_keys,
+    );
+
+    if let Err(e) =
+        directory::register_key_change(context.clone(), &previous_signing_keys, rollover_window)
+            .await
+    {
+        tracing::warn!(?e, "Failed to publish new signing key");
+        std::fs::write(keyfile_path, previous_signing_keys.to_bytes())?;
+        *context
+            .local_context
+            .signing_keys
+            .write()
+            .expect("Signing keys lock poisoned") = previous_signing_keys;
+        return Err(e);
+    }
+
+    tracing::info!("Rotated signing key");
+    Ok(())
+}
+
+/// Returns the reputation and recorded misbehaviour of a network,
+/// or of every network with recorded misbehaviour if none is given.
+pub async fn get_reputation(
+    context: Arc<DauthContext>,
+    network_id: Option<&str>,
+) -> Result<Vec<(String, f64, Vec<Misbehaviour>)>, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    let mut records_by_network: BTreeMap<String, Vec<Misbehaviour>> = BTreeMap::new();
+    match network_id {
+        Some(network_id) => {
+            records_by_network.insert(
+                network_id.to_string(),
+                database::misbehaviour::get_all_by_network(&mut transaction, network_id).await?,
+            );
+        }
+        None => {
+            for record in database::misbehaviour::get_all(&mut transaction).await? {
+                records_by_network
+                    .entry(record.network_id.clone())
+                    .or_default()
+                    .push(record);
+            }
+        }
+    }
+
+    transaction.commit().await?;
+
+    let now = utilities::unix_timestamp();
+    Ok(records_by_network
+        .into_iter()
+        .map(|(network_id, records)| {
+            let score = misbehaviour::reputation_score(
+                &records,
+                now,
+                context.backup_context.reputation_half_life,
+            );
+            (network_id, score, records)
+        })
+        .collect())
+}
+
+/// Returns what this network stores as a backup for a home network,
+/// or for every home network with backed up users if none is given,
+/// along with the quota of each home network.
+pub async fn get_backup_usage(
+    context: Arc<DauthContext>,
+    home_network_id: Option<&str>,
+) -> Result<Vec<(String, BackupUsage, BackupQuota)>, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    let home_network_ids = match home_network_id {
+        Some(home_network_id) => vec![home_network_id.to_string()],
+        None => database::backup_users::get_home_network_ids(&mut transaction).await?,
+    };
+
+    let mut result = Vec::with_capacity(home_network_ids.len());
+    for home_network_id in home_network_ids {
+        let usage = common::quotas::get_usage(&mut transaction, &home_network_id).await?;
+        let quota = context
+            .backup_context
+            .backup_quotas
+            .get(&home_network_id)
+            .clone();
+        result.push((home_network_id, usage, quota));
+    }
+
+    transaction.commit().await?;
+
+    Ok(result)
+}
@@ -2,25 +2,31 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use auth_vector::types::{Res, ResStar, XResHash, XResStarHash};
-use tonic::transport::{Channel, Endpoint};
+use prost::Message;
+use tonic::transport::Channel;
 
+use crate::common;
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
 use crate::data::keys;
+use crate::data::misbehaviour::MisbehaviourKind;
+use crate::data::policy::ServingPolicy;
 use crate::data::signing;
 use crate::data::signing::SignPayloadType;
-use crate::data::vector::AuthVectorRes;
+use crate::data::vector::{AuthVectorRes, Plmn};
 use crate::database::tasks::replace_key_shares::ReplaceKeyShareTask;
 use crate::rpc::dauth::common::UserIdKind;
 use crate::rpc::dauth::remote::backup_network_client::BackupNetworkClient;
 use crate::rpc::dauth::remote::{
-    enroll_backup_prepare_req, flood_vector_req, get_backup_auth_vector_req, get_key_share_req,
-    withdraw_backup_req, withdraw_shares_req, ReplaceShareReq,
+    delegated_confirmation_share, enroll_backup_prepare_req, flood_vector_req,
+    get_backup_auth_vector_req, get_key_share_req, withdraw_backup_req, withdraw_shares_req,
+    GetKeyShareResp, ReplaceShareReq, SignedMessage,
 };
 use crate::rpc::dauth::remote::{
     EnrollBackupCommitReq, EnrollBackupPrepareReq, FloodVectorReq, GetBackupAuthVectorReq,
     GetKeyShareReq, WithdrawBackupReq, WithdrawSharesReq,
 };
+use crate::rpc::tls;
 use crate::rpc::utilities;
 
 /// Request a network to become a backup network.
@@ -30,7 +36,7 @@ pub async fn enroll_backup_prepare(
     backup_network_id: &str,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     let sent_payload = enroll_backup_prepare_req::Payload {
         home_network_id: context.local_context.id.clone(),
@@ -74,15 +80,19 @@ pub async fn enroll_backup_prepare(
 
 /// Send the set of initial vectors and key shares after
 /// a network has agreed to be a backup.
+/// Also sends the user's serving policy, if it has one, and the
+/// threshold the keys of the vectors are split with.
 pub async fn enroll_backup_commit(
     context: Arc<DauthContext>,
     backup_network_id: &str,
     user_id: &str,
     vectors: &Vec<AuthVectorRes>,
     key_shares: &Vec<keys::CombinedKeyShare>,
+    key_threshold: u8,
+    serving_policy: Option<&ServingPolicy>,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     let mut dvectors = Vec::new();
     let mut dshares = Vec::new();
@@ -104,6 +114,9 @@ pub async fn enroll_backup_commit(
             shares: dshares,
             user_id_kind: UserIdKind::Supi as i32,
             user_id: user_id.as_bytes().to_vec(),
+            policy: serving_policy
+                .map(|policy| utilities::build_delegated_policy(context.clone(), user_id, policy)),
+            key_threshold: key_threshold.into(),
         })
         .await?;
 
@@ -111,13 +124,16 @@ pub async fn enroll_backup_commit(
 }
 
 /// Get an auth vector from one of a user's backup networks.
+/// Only a vector with keys bound to the serving PLMN is accepted.
 pub async fn get_auth_vector(
     context: Arc<DauthContext>,
     user_id: &str,
+    backup_network_id: &str,
     address: &str,
     resync_vector: Option<XResStarHash>,
+    serving_plmn: &Plmn,
 ) -> Result<AuthVectorRes, DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     let response = client
         .get_auth_vector(GetBackupAuthVectorReq {
@@ -128,6 +144,7 @@ pub async fn get_auth_vector(
                     user_id_type: UserIdKind::Supi as i32,
                     user_id: user_id.as_bytes().to_vec(),
                     xres_star_hash_resync: resync_vector.and_then(|v| Some(v.as_slice().to_vec())),
+                    serving_plmn: Some(serving_plmn.to_serving_plmn()),
                 }),
             )),
         })
@@ -147,17 +164,21 @@ pub async fn get_auth_vector(
     if let SignPayloadType::DelegatedAuthVector5G(payload) =
         signing::verify_message(&context, &message).await?
     {
-        let vector = payload.v.ok_or(DauthError::ClientError(
-            "Missing vector content".to_string(),
-        ))?;
-        Ok(AuthVectorRes {
-            user_id: user_id.to_string(),
-            seqnum: vector.seqnum,
-            xres_star_hash: vector.xres_star_hash[..].try_into()?,
-            autn: vector.autn[..].try_into()?,
-            rand: vector.rand[..].try_into()?,
-            xres_hash: vector.xres_hash[..].try_into()?,
-        })
+        let vector = AuthVectorRes::from_av5_g(
+            user_id,
+            payload.v.ok_or(DauthError::ClientError(
+                "Missing vector content".to_string(),
+            ))?,
+        )?;
+
+        if vector.plmn != *serving_plmn {
+            return Err(DauthError::ClientError(format!(
+                "Vector bound to wrong plmn: {:?}",
+                vector.plmn
+            )));
+        }
+
+        Ok(vector)
     } else {
         Err(DauthError::ClientError(format!(
             "Incorrect message type received: {:?}",
@@ -167,13 +188,20 @@ pub async fn get_auth_vector(
 }
 
 /// Get a key share from one of a user's backup networks.
+/// The share must be signed by the user's home network and
+/// match the home network's commitment to the kseaf, which is
+/// returned alongside the share and its epoch to check the recovered key.
+/// A share that fails the commitment in a response signed by the
+/// backup network is recorded as misbehaviour of the backup network.
 pub async fn get_kseaf_key_share(
     context: Arc<DauthContext>,
     xres_star_hash: XResStarHash,
     res_star: ResStar,
+    home_network_id: String,
+    backup_network_id: String,
     address: String,
-) -> Result<keys::KseafShare, DauthError> {
-    let mut client = get_client(context.clone(), &address).await?;
+) -> Result<(keys::KseafShare, u32, keys::KeyCommitment), DauthError> {
+    let mut client = get_client(context.clone(), &backup_network_id, &address).await?;
 
     let response = client
         .get_key_share(GetKeyShareReq {
@@ -187,38 +215,69 @@ pub async fn get_kseaf_key_share(
                     hash: Some(get_key_share_req::payload::Hash::XresStarHash(
                         xres_star_hash.to_vec(),
                     )),
+                    serving_plmn: Some(context.local_context.plmn().to_serving_plmn()),
                 }),
             )),
         })
         .await?
         .into_inner();
 
-    let message = response
-        .share
-        .ok_or_else(|| DauthError::ClientError("Missing delegated key share".to_string()))?
-        .message
-        .ok_or_else(|| DauthError::ClientError("Missing delegated key share".to_string()))?;
+    let (message, payload) = handle_returned_share(
+        &context,
+        response,
+        &backup_network_id,
+        &xres_star_hash,
+        &home_network_id,
+    )
+    .await?;
+
+    let result = check_kseaf_share(&payload, &xres_star_hash);
+    record_invalid_share(&context, &backup_network_id, &message, &result).await;
+    result
+}
 
-    if let SignPayloadType::DelegatedConfirmationShare(payload) =
-        signing::verify_message(&context, &message).await?
-    {
-        Ok(payload.kseaf_confirmation_share[..].try_into()?)
+/// Checks a returned kseaf share against the requested vector and
+/// the home network's commitment.
+fn check_kseaf_share(
+    payload: &delegated_confirmation_share::Payload,
+    xres_star_hash: &XResStarHash,
+) -> Result<(keys::KseafShare, u32, keys::KeyCommitment), DauthError> {
+    if payload.xres_star_hash != xres_star_hash {
+        return Err(DauthError::InvalidShareError(
+            "Share for a different vector".to_string(),
+        ));
+    }
+
+    let share: keys::KseafShare = payload.kseaf_confirmation_share[..]
+        .try_into()
+        .map_err(|_| DauthError::InvalidShareError("Malformed kseaf share".to_string()))?;
+    let commitment: keys::KeyCommitment = payload.kseaf_commitment[..].try_into()?;
+    commitment.check_threshold(payload.threshold)?;
+
+    if share.verify(&commitment) {
+        Ok((share, payload.epoch, commitment))
     } else {
-        Err(DauthError::ClientError(format!(
-            "Incorrect message type received: {:?}",
-            message
-        )))
+        Err(DauthError::InvalidShareError(
+            "Kseaf share does not match commitment".to_string(),
+        ))
     }
 }
 
 /// Get a kasme key share from one of a user's backup networks.
+/// The share must be signed by the user's home network and
+/// match the home network's commitment to the kasme, which is
+/// returned alongside the share and its epoch to check the recovered key.
+/// A share that fails the commitment in a response signed by the
+/// backup network is recorded as misbehaviour of the backup network.
 pub async fn get_kasme_key_share(
     context: Arc<DauthContext>,
     xres_hash: XResHash,
     res: Res,
+    home_network_id: String,
+    backup_network_id: String,
     address: String,
-) -> Result<keys::KasmeShare, DauthError> {
-    let mut client = get_client(context.clone(), &address).await?;
+) -> Result<(keys::KasmeShare, u32, keys::KeyCommitment), DauthError> {
+    let mut client = get_client(context.clone(), &backup_network_id, &address).await?;
 
     let response = client
         .get_key_share(GetKeyShareReq {
@@ -230,27 +289,131 @@ pub async fn get_kasme_key_share(
                     hash: Some(get_key_share_req::payload::Hash::XresHash(
                         xres_hash.to_vec(),
                     )),
+                    serving_plmn: Some(context.local_context.plmn().to_serving_plmn()),
                 }),
             )),
         })
         .await?
         .into_inner();
 
-    let message = response
+    let (message, payload) = handle_returned_share(
+        &context,
+        response,
+        &backup_network_id,
+        &xres_hash,
+        &home_network_id,
+    )
+    .await?;
+
+    let result = check_kasme_share(&payload, &xres_hash);
+    record_invalid_share(&context, &backup_network_id, &message, &result).await;
+    result
+}
+
+/// Checks a returned kasme share against the requested vector and
+/// the home network's commitment.
+fn check_kasme_share(
+    payload: &delegated_confirmation_share::Payload,
+    xres_hash: &XResHash,
+) -> Result<(keys::KasmeShare, u32, keys::KeyCommitment), DauthError> {
+    if payload.xres_hash != xres_hash {
+        return Err(DauthError::InvalidShareError(
+            "Share for a different vector".to_string(),
+        ));
+    }
+
+    let share: keys::KasmeShare = payload.kasme_confirmation_share[..]
+        .try_into()
+        .map_err(|_| DauthError::InvalidShareError("Malformed kasme share".to_string()))?;
+    let commitment: keys::KeyCommitment = payload.kasme_commitment[..].try_into()?;
+    commitment.check_threshold(payload.threshold)?;
+
+    if share.verify(&commitment) {
+        Ok((share, payload.epoch, commitment))
+    } else {
+        Err(DauthError::InvalidShareError(
+            "Kasme share does not match commitment".to_string(),
+        ))
+    }
+}
+
+/// Records a share that failed verification as misbehaviour of the
+/// backup network that returned it, with the backup network's signed
+/// response as evidence.
+async fn record_invalid_share<T>(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    message: &SignedMessage,
+    result: &Result<T, DauthError>,
+) {
+    if let Err(DauthError::InvalidShareError(reason)) = result {
+        common::misbehaviour::record(
+            context,
+            backup_network_id,
+            MisbehaviourKind::InvalidShare,
+            &format!("{} from {}", reason, backup_network_id),
+            message.encode_to_vec(),
+        )
+        .await;
+    }
+}
+
+/// Verifies that a returned share response was signed by the backup
+/// network for the requested vector, and that the share it holds was
+/// signed by the home network.
+/// None of these failures prove the backup network misbehaved, as the
+/// response may not be its own or the home network may have rotated
+/// its key, so they are not recorded as misbehaviour.
+async fn handle_returned_share(
+    context: &Arc<DauthContext>,
+    response: GetKeyShareResp,
+    backup_network_id: &str,
+    requested_hash: &[u8],
+    home_network_id: &str,
+) -> Result<(SignedMessage, delegated_confirmation_share::Payload), DauthError> {
+    let message = response.message.ok_or(DauthError::ClientError(
+        "Missing signed message".to_string(),
+    ))?;
+
+    if message.signer_id != backup_network_id {
+        return Err(DauthError::ClientError(format!(
+            "Response not signed by backup network: {}",
+            message.signer_id
+        )));
+    }
+
+    let payload = match signing::verify_message(context, &message).await? {
+        SignPayloadType::GetKeyShareResp(payload) => payload,
+        _ => {
+            return Err(DauthError::ClientError(
+                "Incorrect message type received".to_string(),
+            ))
+        }
+    };
+
+    if payload.requested_hash != requested_hash {
+        return Err(DauthError::ClientError(
+            "Response for a different request".to_string(),
+        ));
+    }
+
+    let share_message = payload
         .share
-        .ok_or_else(|| DauthError::ClientError("Missing delegated key share".to_string()))?
-        .message
+        .and_then(|dshare| dshare.message)
         .ok_or_else(|| DauthError::ClientError("Missing delegated key share".to_string()))?;
 
-    if let SignPayloadType::DelegatedConfirmationShare(payload) =
-        signing::verify_message(&context, &message).await?
-    {
-        Ok(payload.kasme_confirmation_share[..].try_into()?)
-    } else {
-        Err(DauthError::ClientError(format!(
-            "Incorrect message type received: {:?}",
-            message
-        )))
+    if share_message.signer_id != home_network_id {
+        return Err(DauthError::ClientError(format!(
+            "Share not signed by home network: {}",
+            share_message.signer_id
+        )));
+    }
+
+    match signing::verify_message(context, &share_message).await? {
+        SignPayloadType::DelegatedConfirmationShare(share_payload) => Ok((message, share_payload)),
+        _ => Err(DauthError::ClientError(
+            "Incorrect message type received".to_string(),
+        )),
     }
 }
 
@@ -261,7 +424,7 @@ pub async fn replace_key_share(
     replace: &ReplaceKeyShareTask,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), &replace.backup_network_id, address).await?;
 
     client
         .replace_key_share(ReplaceShareReq {
@@ -277,15 +440,13 @@ pub async fn replace_key_share(
 }
 
 /// Withdraws backup status from a backup network.
-/// Not currently used.
-#[allow(dead_code)]
 pub async fn withdraw_backup(
     context: Arc<DauthContext>,
     user_id: &str,
     backup_network_id: &str,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     client
         .withdraw_backup(WithdrawBackupReq {
@@ -305,14 +466,13 @@ pub async fn withdraw_backup(
 }
 
 /// Withdraws all matching shares from a backup network.
-/// Not currently used.
-#[allow(dead_code)]
 pub async fn withdraw_shares(
     context: Arc<DauthContext>,
     xres_star_hashs: Vec<XResStarHash>,
+    backup_network_id: &str,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     let mut proc_xrhs = Vec::new();
 
@@ -345,7 +505,7 @@ pub async fn flood_vector(
     vector: &AuthVectorRes,
     address: &str,
 ) -> Result<(), DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), backup_network_id, address).await?;
 
     client
         .flood_vector(FloodVectorReq {
@@ -372,6 +532,7 @@ pub async fn flood_vector(
 /// Builds and caches a client if one does not exist.
 async fn get_client(
     context: Arc<DauthContext>,
+    backup_network_id: &str,
     address: &str,
 ) -> Result<BackupNetworkClient<Channel>, DauthError> {
     // Acquire the lock and attempt to look up the client connection.
@@ -391,8 +552,7 @@ async fn get_client(
 
     // TODO(matt9j) Keep track of if we've attempted and failed to open a
     // connection before and don't retry for every request.
-    let endpoint = Endpoint::from_shared(format!("http://{}", address))
-        .unwrap()
+    let endpoint = tls::network_endpoint(&context, backup_network_id, address)?
         .concurrency_limit(256)
         .connect_timeout(Duration::from_millis(200));
     let client = BackupNetworkClient::connect(endpoint).await?;
@@ -1,28 +1,116 @@
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use ed25519_dalek::PublicKey;
+use ed25519_dalek::{Keypair, PublicKey, Signer};
+use prost::Message;
 use tonic::transport::Channel;
 
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
+use crate::data::placement::BackupCandidate;
+use crate::data::utilities;
 use crate::rpc::dauth::directory::directory_client::DirectoryClient;
-use crate::rpc::dauth::directory::{LookupUserReq, LooukupNetworkReq, RegisterReq, UpsertUserReq};
+use crate::rpc::dauth::directory::{
+    delete_user_req, register_req, DeleteUserReq, ListNetworksReq, LookupUserReq,
+    LooukupNetworkReq, RegisterReq, UpsertUserReq,
+};
+use crate::rpc::tls;
+
+/// Network info as provided by the directory service.
+#[derive(Debug, Clone)]
+pub struct NetworkInfo {
+    pub address: String,
+    pub public_key: PublicKey,
+    pub previous_key: Option<PreviousKey>,
+}
+
+/// The public key a network replaced in its last key change.
+#[derive(Debug, Clone)]
+pub struct PreviousKey {
+    pub public_key: PublicKey,
+    /// Unix time (seconds) of the key change
+    pub replaced: i64,
+    /// End of the rollover window
+    pub expiration: SystemTime,
+}
 
 /// Registers this network with the directory service.
-/// Provides this network's id, address, and public key.
+/// Provides this network's id, address, public key, and placement
+/// metadata, signed with this network's private key.
 pub async fn register(context: Arc<DauthContext>) -> Result<(), DauthError> {
+    let signing_keys = Keypair::from_bytes(
+        &context
+            .local_context
+            .signing_keys
+            .read()
+            .expect("Signing keys lock poisoned")
+            .to_bytes(),
+    )?;
+
+    send_register(context, &signing_keys, None, Duration::ZERO).await
+}
+
+/// Registers the current public key of this network with the directory
+/// service as a change from the previous key, which signs the change and
+/// remains valid for the rollover window.
+pub async fn register_key_change(
+    context: Arc<DauthContext>,
+    previous_signing_keys: &Keypair,
+    rollover_window: Duration,
+) -> Result<(), DauthError> {
+    let signing_keys = Keypair::from_bytes(
+        &context
+            .local_context
+            .signing_keys
+            .read()
+            .expect("Signing keys lock poisoned")
+            .to_bytes(),
+    )?;
+
+    send_register(
+        context,
+        &signing_keys,
+        Some(previous_signing_keys),
+        rollover_window,
+    )
+    .await
+}
+
+async fn send_register(
+    context: Arc<DauthContext>,
+    signing_keys: &Keypair,
+    previous_signing_keys: Option<&Keypair>,
+    rollover_window: Duration,
+) -> Result<(), DauthError> {
     let mut client = get_client(context.clone()).await?;
 
+    let rollover_window = i64::try_from(rollover_window.as_secs()).map_err(|e| {
+        DauthError::InvalidMessageError(format!("Rollover window out of range: {}", e))
+    })?;
+
+    let payload = register_req::Payload {
+        network_id: context.local_context.id.clone(),
+        address: context.rpc_context.host_addr.clone(),
+        public_key: signing_keys.public.as_bytes().to_vec(),
+        rollover_window,
+        region: context.local_context.region.clone(),
+        operator_group: context.local_context.operator_group.clone(),
+        backup_capacity: context.backup_context.backup_capacity,
+        timestamp: utilities::unix_timestamp(),
+    }
+    .encode_to_vec();
+
+    let signature = signing_keys.sign(&payload).to_bytes().to_vec();
+    let previous_key_signature = match previous_signing_keys {
+        Some(previous_signing_keys) => previous_signing_keys.sign(&payload).to_bytes().to_vec(),
+        None => Vec::new(),
+    };
+
     client
         .register(RegisterReq {
-            network_id: context.local_context.id.clone(),
-            address: context.rpc_context.host_addr.clone(),
-            public_key: context
-                .local_context
-                .signing_keys
-                .public
-                .as_bytes()
-                .to_vec(),
+            payload,
+            signature,
+            previous_key_signature,
         })
         .await?;
     Ok(())
@@ -35,6 +123,17 @@ pub async fn lookup_network(
     context: &Arc<DauthContext>,
     network_id: &str,
 ) -> Result<(String, PublicKey), DauthError> {
+    let info = lookup_network_info(context, network_id).await?;
+
+    Ok((info.address, info.public_key))
+}
+
+/// Contacts directory service to find the address, public key,
+/// and any previous public key of the provided network id
+pub async fn lookup_network_info(
+    context: &Arc<DauthContext>,
+    network_id: &str,
+) -> Result<NetworkInfo, DauthError> {
     // Acquire the lock and attempt to look up the network information.
     {
         let cache = context.backup_context.directory_network_cache.lock().await;
@@ -58,10 +157,22 @@ pub async fn lookup_network(
         .await?
         .into_inner();
 
-    let res = (
-        response.address,
-        PublicKey::from_bytes(&response.public_key)?,
-    );
+    let previous_key = if response.previous_public_key.is_empty() {
+        None
+    } else {
+        Some(PreviousKey {
+            public_key: PublicKey::from_bytes(&response.previous_public_key)?,
+            replaced: response.previous_key_replaced,
+            expiration: UNIX_EPOCH
+                + Duration::from_secs(response.previous_key_expiration.max(0) as u64),
+        })
+    };
+
+    let res = NetworkInfo {
+        address: response.address,
+        public_key: PublicKey::from_bytes(&response.public_key)?,
+        previous_key,
+    };
 
     // Re-acquire the lock and update the cache
     {
@@ -71,6 +182,17 @@ pub async fn lookup_network(
     }
 }
 
+/// Removes any cached info of the provided network id,
+/// forcing the next lookup to contact the directory service.
+pub async fn invalidate_network(context: &Arc<DauthContext>, network_id: &str) {
+    context
+        .backup_context
+        .directory_network_cache
+        .lock()
+        .await
+        .remove(network_id);
+}
+
 /// Contacts directory service to find the home network
 /// and the backup networks of the provided user.
 /// Returns pair (home nework, vec<backup networks>)
@@ -111,6 +233,30 @@ pub async fn lookup_user(
     }
 }
 
+/// Contacts directory service to list all registered networks
+/// with their placement metadata and current number of backed up users.
+/// Reputation is left for the caller to score locally.
+pub async fn list_networks(
+    context: &Arc<DauthContext>,
+) -> Result<Vec<BackupCandidate>, DauthError> {
+    let mut client = get_client(context.clone()).await?;
+
+    let response = client.list_networks(ListNetworksReq {}).await?.into_inner();
+
+    Ok(response
+        .networks
+        .into_iter()
+        .map(|network| BackupCandidate {
+            network_id: network.network_id,
+            region: network.region,
+            operator_group: network.operator_group,
+            backup_capacity: network.backup_capacity,
+            backup_count: network.backup_count,
+            reputation: 0.0,
+        })
+        .collect())
+}
+
 /// Sends user info to the directory service.
 /// If the user doesn't exist, this network claims ownership.
 /// Otherwise, user info is updated iff this network owns the user.
@@ -132,15 +278,48 @@ pub async fn upsert_user(
     Ok(())
 }
 
+/// Removes a user owned by this network from the directory service.
+/// The request is signed with this network's private key.
+/// Also clears the user from the local directory cache.
+pub async fn delete_user(context: Arc<DauthContext>, user_id: &str) -> Result<(), DauthError> {
+    let mut client = get_client(context.clone()).await?;
+
+    let payload = delete_user_req::Payload {
+        user_id: user_id.to_string(),
+        home_network_id: context.local_context.id.clone(),
+        timestamp: utilities::unix_timestamp(),
+    }
+    .encode_to_vec();
+
+    let signature = context
+        .local_context
+        .signing_keys
+        .read()
+        .expect("Signing keys lock poisoned")
+        .sign(&payload)
+        .to_bytes()
+        .to_vec();
+
+    client
+        .delete_user(DeleteUserReq { payload, signature })
+        .await?;
+
+    context
+        .backup_context
+        .directory_user_cache
+        .lock()
+        .await
+        .remove(user_id);
+
+    Ok(())
+}
+
 /// Returns a client to the directory service at the provided address.
 /// Builds and caches the client if one does not exist.
 async fn get_client(context: Arc<DauthContext>) -> Result<DirectoryClient<Channel>, DauthError> {
     let mut client_option = context.rpc_context.directory_client.lock().await;
     if client_option.is_none() {
-        *client_option = Some(
-            DirectoryClient::connect(format!("http://{}", context.rpc_context.directory_addr))
-                .await?,
-        );
+        *client_option = Some(DirectoryClient::connect(tls::directory_endpoint(&context)?).await?);
     }
 
     Ok(client_option
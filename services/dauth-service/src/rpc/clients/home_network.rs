@@ -4,37 +4,50 @@ use std::time::Duration;
 use auth_vector::types::{Kasme, Kseaf, Res, ResStar, XResHash, XResStarHash};
 use prost::Message;
 use tonic::transport::Channel;
-use tonic::transport::Endpoint;
 
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
+use crate::data::keys::EapAkaPrimeSessionKeys;
 use crate::data::signing::{self, SignPayloadType};
-use crate::data::vector::AuthVectorRes;
-use crate::rpc::dauth::common::UserIdKind;
+use crate::data::vector::{AuthVectorRes, EapAkaPrimeVectorRes, Plmn};
+use crate::rpc::dauth::common::{AkaResyncInfo, UserIdKind};
 use crate::rpc::dauth::remote::home_network_client::HomeNetworkClient;
 use crate::rpc::dauth::remote::{
     get_home_auth_vector_req, get_home_confirm_key_req, get_home_confirm_key_resp,
-    ReportHomeAuthConsumedReq, ReportHomeKeyShareConsumedReq, SignedMessage,
+    get_home_eap_aka_prime_vector_req, ReportHomeAuthConsumedReq, ReportHomeKeyShareConsumedReq,
+    SignedMessage,
 };
-use crate::rpc::dauth::remote::{GetHomeAuthVectorReq, GetHomeConfirmKeyReq};
+use crate::rpc::dauth::remote::{
+    GetHomeAuthVectorReq, GetHomeConfirmKeyReq, GetHomeEapAkaPrimeVectorReq,
+};
+use crate::rpc::tls;
 use crate::rpc::utilities;
 
 /// Get an auth vector from a user's home network.
+/// Forwards the UE's resync info if the last vector was rejected.
+/// A SUCI user id is de-concealed by the home network.
+/// Only a vector with keys bound to the serving PLMN is accepted.
 pub async fn get_auth_vector(
     context: Arc<DauthContext>,
     user_id: &str,
+    user_id_kind: UserIdKind,
+    home_network_id: &str,
     address: &str,
+    resync_info: Option<AkaResyncInfo>,
+    serving_plmn: &Plmn,
     timeout: Duration,
 ) -> Result<AuthVectorRes, DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), home_network_id, address).await?;
 
     let mut request = tonic::Request::new(GetHomeAuthVectorReq {
         message: Some(signing::sign_message(
             context.clone(),
             SignPayloadType::GetHomeAuthVectorReq(get_home_auth_vector_req::Payload {
                 serving_network_id: context.local_context.id.clone(),
-                user_id_type: UserIdKind::Supi as i32,
+                user_id_type: user_id_kind as i32,
                 user_id: user_id.as_bytes().to_vec(),
+                resync_info,
+                serving_plmn: Some(serving_plmn.to_serving_plmn()),
             }),
         )),
     });
@@ -64,12 +77,96 @@ pub async fn get_auth_vector(
     if let SignPayloadType::DelegatedAuthVector5G(dvector) =
         signing::verify_message(&context, &message).await?
     {
-        Ok(AuthVectorRes::from_av5_g(
+        let vector = AuthVectorRes::from_av5_g(
             user_id,
             dvector
                 .v
                 .ok_or(DauthError::ClientError("Missing 5G vector".to_string()))?,
-        )?)
+        )?;
+
+        if vector.plmn != *serving_plmn {
+            return Err(DauthError::ClientError(format!(
+                "Vector bound to wrong plmn: {:?}",
+                vector.plmn
+            )));
+        }
+
+        Ok(vector)
+    } else {
+        Err(DauthError::ClientError(format!(
+            "Incorrect message type received: {:?}",
+            message
+        )))
+    }
+}
+
+/// Get an EAP-AKA' vector bound to the network name from a user's home network.
+/// The anchor key is bound to this network's PLMN.
+pub async fn get_eap_aka_prime_vector(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    user_id_kind: UserIdKind,
+    network_name: &str,
+    home_network_id: &str,
+    address: &str,
+    resync_info: Option<AkaResyncInfo>,
+    timeout: Duration,
+) -> Result<EapAkaPrimeVectorRes, DauthError> {
+    let mut client = get_client(context.clone(), home_network_id, address).await?;
+
+    let mut request = tonic::Request::new(GetHomeEapAkaPrimeVectorReq {
+        message: Some(signing::sign_message(
+            context.clone(),
+            SignPayloadType::GetHomeEapAkaPrimeVectorReq(
+                get_home_eap_aka_prime_vector_req::Payload {
+                    serving_network_id: context.local_context.id.clone(),
+                    user_id_type: user_id_kind as i32,
+                    user_id: user_id.as_bytes().to_vec(),
+                    network_name: network_name.to_string(),
+                    resync_info,
+                    serving_plmn: Some(context.local_context.plmn().to_serving_plmn()),
+                },
+            ),
+        )),
+    });
+
+    request.set_timeout(timeout);
+
+    let response = match client.get_eap_aka_prime_vector(request).await {
+        Ok(res) => res.into_inner(),
+        Err(status) => {
+            if status.code() == tonic::Code::Unavailable {
+                mark_endpoint_offline(&context, address).await;
+            }
+            return Err(status.into());
+        }
+    };
+
+    let message = response
+        .vector
+        .ok_or(DauthError::ClientError(
+            "Missing delegated vector".to_string(),
+        ))?
+        .message
+        .ok_or(DauthError::ClientError(
+            "Missing signed message".to_string(),
+        ))?;
+
+    if let SignPayloadType::DelegatedAuthVectorEapAkaPrime(dvector) =
+        signing::verify_message(&context, &message).await?
+    {
+        let vector = dvector.v.ok_or(DauthError::ClientError(
+            "Missing EAP-AKA' vector".to_string(),
+        ))?;
+
+        if vector.network_name != network_name {
+            return Err(DauthError::ClientError(format!(
+                "Vector bound to wrong network name: {}",
+                vector.network_name
+            )));
+        }
+
+        Ok(EapAkaPrimeVectorRes::from_eap_aka_prime(user_id, vector)?)
     } else {
         Err(DauthError::ClientError(format!(
             "Incorrect message type received: {:?}",
@@ -83,9 +180,10 @@ pub async fn get_confirm_key_kseaf(
     context: Arc<DauthContext>,
     res_star: &ResStar,
     xres_star_hash: &XResStarHash,
+    home_network_id: &str,
     address: &str,
 ) -> Result<Kseaf, DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), home_network_id, address).await?;
 
     let response = match client
         .get_confirm_key(GetHomeConfirmKeyReq {
@@ -127,9 +225,10 @@ pub async fn get_confirm_key_kasme(
     context: Arc<DauthContext>,
     res: &Res,
     xres_hash: &XResHash,
+    home_network_id: &str,
     address: &str,
 ) -> Result<Kasme, DauthError> {
-    let mut client = get_client(context.clone(), address).await?;
+    let mut client = get_client(context.clone(), home_network_id, address).await?;
 
     let response = match client
         .get_confirm_key(GetHomeConfirmKeyReq {
@@ -166,6 +265,51 @@ pub async fn get_confirm_key_kasme(
     }
 }
 
+/// Get the EAP-AKA' session keys at the end of an auth transaction.
+pub async fn get_confirm_key_eap_aka_prime(
+    context: Arc<DauthContext>,
+    res: &Res,
+    home_network_id: &str,
+    address: &str,
+) -> Result<EapAkaPrimeSessionKeys, DauthError> {
+    let mut client = get_client(context.clone(), home_network_id, address).await?;
+
+    let response = match client
+        .get_confirm_key(GetHomeConfirmKeyReq {
+            message: Some(signing::sign_message(
+                context.clone(),
+                SignPayloadType::GetHomeConfirmKeyReq(get_home_confirm_key_req::Payload {
+                    serving_network_id: context.local_context.id.clone(),
+                    preimage: Some(get_home_confirm_key_req::payload::Preimage::EapRes(
+                        res.to_vec(),
+                    )),
+                    hash: None,
+                }),
+            )),
+        })
+        .await
+    {
+        Ok(res) => res.into_inner(),
+        Err(status) => {
+            if status.code() == tonic::Code::Unavailable {
+                mark_endpoint_offline(&context, address).await;
+            }
+            return Err(status.into());
+        }
+    };
+
+    if let Some(get_home_confirm_key_resp::Key::EapAkaPrime(keys)) = response.key {
+        Ok(EapAkaPrimeSessionKeys {
+            msk: keys.msk[..].try_into()?,
+            kseaf: keys.kseaf[..].try_into()?,
+        })
+    } else {
+        Err(DauthError::KeyTypeError(
+            "Expected EAP-AKA' keys but unable to extract from message".to_string(),
+        ))
+    }
+}
+
 /// Reports an auth vector as used to the home network.
 /// Sends the original signed request for the auth vector as
 /// proof of the auth vector request.
@@ -222,6 +366,7 @@ pub async fn report_key_share_consumed(
 /// Builds and caches a client if one does not exist.
 pub async fn get_client(
     context: Arc<DauthContext>,
+    home_network_id: &str,
     address: &str,
 ) -> Result<HomeNetworkClient<Channel>, DauthError> {
     // Acquire the lock and attempt to look up the client connection.
@@ -258,8 +403,7 @@ pub async fn get_client(
     }
 
     // Attempt a connection
-    let endpoint = Endpoint::from_shared(format!("http://{}", address))
-        .unwrap()
+    let endpoint = tls::network_endpoint(&context, home_network_id, address)?
         .concurrency_limit(256)
         .timeout(Duration::from_millis(100))
         .connect_timeout(Duration::from_millis(50));
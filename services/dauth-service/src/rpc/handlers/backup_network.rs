@@ -8,19 +8,18 @@ use crate::data::combined_res::XResHashKind;
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
 use crate::data::signing::{self, SignPayloadType};
-use crate::data::vector::AuthVectorReq;
-use crate::rpc::dauth::common::{AuthVector5G, UserIdKind};
+use crate::data::vector::{AuthVectorReq, Plmn};
+use crate::rpc::dauth::common::UserIdKind;
 use crate::rpc::dauth::remote::backup_network_server::BackupNetwork;
+use crate::rpc::dauth::remote::{delegated_auth_vector5_g, SignedMessage};
 use crate::rpc::dauth::remote::{
-    delegated_auth_vector5_g, delegated_confirmation_share, SignedMessage,
-};
-use crate::rpc::dauth::remote::{
-    get_key_share_req, DelegatedAuthVector5G, DelegatedConfirmationShare, EnrollBackupCommitReq,
-    EnrollBackupCommitResp, EnrollBackupPrepareReq, EnrollBackupPrepareResp, FloodVectorReq,
-    FloodVectorResp, GetBackupAuthVectorReq, GetBackupAuthVectorResp, GetKeyShareReq,
-    GetKeyShareResp, ReplaceShareReq, ReplaceShareResp, WithdrawBackupReq, WithdrawBackupResp,
-    WithdrawSharesReq, WithdrawSharesResp,
+    get_key_share_req, get_key_share_resp, DelegatedAuthVector5G, DelegatedConfirmationShare,
+    EnrollBackupCommitReq, EnrollBackupCommitResp, EnrollBackupPrepareReq, EnrollBackupPrepareResp,
+    FloodVectorReq, FloodVectorResp, GetBackupAuthVectorReq, GetBackupAuthVectorResp,
+    GetKeyShareReq, GetKeyShareResp, ReplaceShareReq, ReplaceShareResp, WithdrawBackupReq,
+    WithdrawBackupResp, WithdrawSharesReq, WithdrawSharesResp,
 };
+use crate::rpc::tls::PeerCertificates;
 use crate::rpc::utilities;
 use crate::services::backup;
 
@@ -43,10 +42,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -64,7 +66,7 @@ impl BackupNetwork for BackupNetworkHandler {
                 {
                     Ok(result) => Ok(result),
                     Err(e) => Err(tonic::Status::new(
-                        tonic::Code::Aborted,
+                        utilities::status_code(&e),
                         format!("Error while handling request: {}", e),
                     )),
                 }
@@ -91,13 +93,18 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let content = request.into_inner();
-                match BackupNetworkHandler::enroll_backup_commit_hlp(self.context.clone(), content)
-                    .await
+                match BackupNetworkHandler::enroll_backup_commit_hlp(
+                    self.context.clone(),
+                    content,
+                    peer,
+                )
+                .await
                 {
                     Ok(result) => Ok(result),
                     Err(e) => Err(tonic::Status::new(
-                        tonic::Code::Aborted,
+                        utilities::status_code(&e),
                         format!("Error while handling request: {}", e),
                     )),
                 }
@@ -122,10 +129,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let mut signed_request_bytes = Vec::new();
                 message.encode(&mut signed_request_bytes).or_else(|e| {
                     Err(tonic::Status::new(
@@ -146,6 +156,7 @@ impl BackupNetwork for BackupNetworkHandler {
                 match BackupNetworkHandler::get_backup_auth_vector_hlp(
                     self.context.clone(),
                     verify_result,
+                    &message.signer_id,
                     &signed_request_bytes,
                 )
                 .await
@@ -177,10 +188,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -223,11 +237,21 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
-                match BackupNetworkHandler::replace_key_share_hlp(
-                    self.context.clone(),
-                    request.into_inner(),
-                )
-                .await
+                let peer = PeerCertificates::from_request(&request);
+                let content = request.into_inner();
+
+                // The replacement share is signed by the home network
+                let signer_id = content
+                    .new_share
+                    .as_ref()
+                    .and_then(|share| share.message.as_ref())
+                    .map(|message| message.signer_id.clone())
+                    .unwrap_or_default();
+
+                peer.verify_status(&self.context, &signer_id)?;
+
+                match BackupNetworkHandler::replace_key_share_hlp(self.context.clone(), content)
+                    .await
                 {
                     Ok(result) => Ok(result),
                     Err(e) => Err(tonic::Status::new(
@@ -257,10 +281,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -299,10 +326,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -341,10 +371,13 @@ impl BackupNetwork for BackupNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -359,7 +392,7 @@ impl BackupNetwork for BackupNetworkHandler {
                 {
                     Ok(result) => Ok(result),
                     Err(e) => Err(tonic::Status::new(
-                        tonic::Code::Aborted,
+                        utilities::status_code(&e),
                         format!("Error while handling request: {}", e),
                     )),
                 }
@@ -422,11 +455,20 @@ impl BackupNetworkHandler {
     async fn enroll_backup_commit_hlp(
         context: Arc<DauthContext>,
         content: EnrollBackupCommitReq,
+        peer: PeerCertificates,
     ) -> Result<tonic::Response<EnrollBackupCommitResp>, DauthError> {
         match content.user_id_kind() {
             UserIdKind::Supi => {
                 let user_id = std::str::from_utf8(content.user_id.as_slice())?.to_string();
 
+                // Only the home network that prepared the enrollment may commit it
+                let mut transaction = context.local_context.database_pool.begin().await?;
+                let home_network_id =
+                    crate::database::backup_users::get(&mut transaction, &user_id).await?;
+                transaction.commit().await?;
+
+                peer.verify(&context, &home_network_id)?;
+
                 // collect all properly formated delegated vectors
                 // log and skip on error
                 let mut processed_vectors = Vec::new();
@@ -436,14 +478,45 @@ impl BackupNetworkHandler {
                     );
                 }
 
-                // collect all properly formated delegated shares
+                // collect all properly formated delegated shares, along with
+                // their signed form to forward to serving networks
+                // shares must be split with the threshold of the enrollment
                 // log and skip on error
                 let mut processed_shares = Vec::new();
                 for dshare in content.shares {
-                    processed_shares
-                        .push(utilities::handle_key_share(context.clone(), dshare).await);
+                    let signed_share = dshare.encode_to_vec();
+                    processed_shares.push(
+                        utilities::handle_key_share(context.clone(), dshare)
+                            .await
+                            .and_then(|share| {
+                                if u32::from(share.threshold()) == content.key_threshold {
+                                    Ok((share, signed_share))
+                                } else {
+                                    Err(DauthError::InvalidShareError(format!(
+                                        "Share threshold {} does not match enrollment threshold {}",
+                                        share.threshold(),
+                                        content.key_threshold
+                                    )))
+                                }
+                            }),
+                    );
                 }
 
+                // Unlike vectors and shares, a policy that cannot be
+                // verified fails the enrollment, so no user is left unrestricted.
+                let serving_policy = match content.policy {
+                    Some(dpolicy) => Some(
+                        utilities::handle_delegated_policy(
+                            &context,
+                            dpolicy,
+                            &user_id,
+                            &home_network_id,
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+
                 backup::enroll_backup_commit(
                     context.clone(),
                     &user_id,
@@ -465,6 +538,7 @@ impl BackupNetworkHandler {
                             })
                         })
                         .collect(),
+                    serving_policy,
                 )
                 .await?;
 
@@ -480,10 +554,27 @@ impl BackupNetworkHandler {
     async fn get_backup_auth_vector_hlp(
         context: Arc<DauthContext>,
         verify_result: SignPayloadType,
+        signer_id: &str,
         signed_request_bytes: &Vec<u8>,
     ) -> Result<tonic::Response<GetBackupAuthVectorResp>, DauthError> {
         if let SignPayloadType::GetBackupAuthVectorReq(payload) = verify_result {
             let user_id = std::str::from_utf8(payload.user_id.as_slice())?.to_string();
+            let serving_plmn: Plmn = payload
+                .serving_plmn
+                .ok_or(DauthError::InvalidMessageError(
+                    "Missing serving plmn".to_string(),
+                ))?
+                .into();
+
+            // Check the user's serving policy before acting on the request,
+            // including any resync notification.
+            let mut transaction = context.local_context.database_pool.begin().await?;
+            if let Some(policy) =
+                crate::database::serving_policies::get(&mut transaction, &user_id).await?
+            {
+                policy.check(signer_id, Some(&serving_plmn))?;
+            }
+            transaction.commit().await?;
 
             // If we get any information about stale sequence numbers in our
             // auth list, remove them before proceeding.
@@ -505,6 +596,7 @@ impl BackupNetworkHandler {
                 context.clone(),
                 &AuthVectorReq {
                     user_id: user_id.to_string(),
+                    plmn: serving_plmn,
                 },
                 signed_request_bytes,
             )
@@ -512,13 +604,7 @@ impl BackupNetworkHandler {
 
             let payload = delegated_auth_vector5_g::Payload {
                 serving_network_id: context.local_context.id.clone(),
-                v: Some(AuthVector5G {
-                    rand: av_result.rand.to_vec(),
-                    xres_star_hash: av_result.xres_star_hash.to_vec(),
-                    autn: av_result.autn.to_vec(),
-                    seqnum: av_result.seqnum,
-                    xres_hash: av_result.xres_hash.to_vec(),
-                }),
+                v: Some(av_result.to_av5_g()),
             };
 
             Ok(tonic::Response::new(GetBackupAuthVectorResp {
@@ -553,38 +639,39 @@ impl BackupNetworkHandler {
                 "Missing preimage".to_string(),
             ))?;
 
-            // TODO(matt9j) This was supposed to be the actual signed share from
-            // the host, not constructed on demand in the backup network's
-            // signing key as done below : /
-            let combined_hash = match request_hash {
-                get_key_share_req::payload::Hash::XresStarHash(xres_star_hash) => {
-                    XResHashKind::XResStarHash(xres_star_hash[..].try_into()?)
-                }
+            let (combined_hash, requested_hash) = match request_hash {
+                get_key_share_req::payload::Hash::XresStarHash(xres_star_hash) => (
+                    XResHashKind::XResStarHash(xres_star_hash[..].try_into()?),
+                    xres_star_hash,
+                ),
                 get_key_share_req::payload::Hash::XresHash(xres_hash) => {
-                    XResHashKind::XResHash(xres_hash[..].try_into()?)
+                    (XResHashKind::XResHash(xres_hash[..].try_into()?), xres_hash)
                 }
             };
 
-            let key_share =
-                backup::get_key_share(context.clone(), &combined_hash, &signed_request_bytes)
-                    .await?;
+            // Forward the share as signed by the home network, so the serving
+            // network can verify it against the home network's commitments.
+            let signed_share = backup::get_key_share(
+                context.clone(),
+                &combined_hash,
+                &message.signer_id,
+                payload.serving_plmn.map(Plmn::from).as_ref(),
+                &signed_request_bytes,
+            )
+            .await?;
 
-            let payload = delegated_confirmation_share::Payload {
-                xres_star_hash: key_share.xres_star_hash.to_vec(),
-                xres_hash: key_share.xres_hash.to_vec(),
-                kseaf_confirmation_share: key_share.kseaf_share.to_vec(),
-                kasme_confirmation_share: key_share.kasme_share.to_vec(),
-            };
+            let dshare = DelegatedConfirmationShare::decode(signed_share.as_slice())?;
 
-            let dshare = DelegatedConfirmationShare {
+            // Sign the response, so a share failing verification can be
+            // attributed to this network
+            Ok(tonic::Response::new(GetKeyShareResp {
                 message: Some(signing::sign_message(
                     context,
-                    signing::SignPayloadType::DelegatedConfirmationShare(payload),
+                    SignPayloadType::GetKeyShareResp(get_key_share_resp::Payload {
+                        requested_hash,
+                        share: Some(dshare),
+                    }),
                 )),
-            };
-
-            Ok(tonic::Response::new(GetKeyShareResp {
-                share: Some(dshare),
             }))
         } else {
             Err(DauthError::InvalidMessageError(format!(
@@ -605,9 +692,11 @@ impl BackupNetworkHandler {
         let old_xres_star_hash: XResStarHash =
             request.replaced_share_xres_star_hash[..].try_into()?;
 
+        let signed_share = dshare.encode_to_vec();
         let new_key_share = utilities::handle_key_share(context.clone(), dshare).await?;
 
-        backup::replace_key_share(context, &old_xres_star_hash, &new_key_share).await?;
+        backup::replace_key_share(context, &old_xres_star_hash, &new_key_share, &signed_share)
+            .await?;
 
         Ok(tonic::Response::new(ReplaceShareResp {}))
     }
@@ -1,19 +1,24 @@
 use std::sync::Arc;
 
+use prost::Message;
+
 use crate::data::combined_res::{ResKind, XResHashKind};
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
 use crate::data::keys::KeyKind;
 use crate::data::signing::{self, SignPayloadType};
-use crate::rpc::dauth::common::AuthVector5G;
-use crate::rpc::dauth::remote::delegated_auth_vector5_g;
+use crate::data::vector::Plmn;
+use crate::rpc::dauth::common::UserIdKind;
 use crate::rpc::dauth::remote::home_network_server::HomeNetwork;
+use crate::rpc::dauth::remote::{delegated_auth_vector5_g, delegated_auth_vector_eap_aka_prime};
 use crate::rpc::dauth::remote::{
-    get_home_confirm_key_req, get_home_confirm_key_resp, get_key_share_req, DelegatedAuthVector5G,
+    get_backup_auth_vector_req, get_home_confirm_key_req, get_home_confirm_key_resp,
+    get_key_share_req, DelegatedAuthVector5G, DelegatedAuthVectorEapAkaPrime, EapAkaPrimeKeys,
     GetHomeAuthVectorReq, GetHomeAuthVectorResp, GetHomeConfirmKeyReq, GetHomeConfirmKeyResp,
-    ReportHomeAuthConsumedReq, ReportHomeAuthConsumedResp, ReportHomeKeyShareConsumedReq,
-    ReportHomeKeyShareConsumedResp,
+    GetHomeEapAkaPrimeVectorReq, GetHomeEapAkaPrimeVectorResp, ReportHomeAuthConsumedReq,
+    ReportHomeAuthConsumedResp, ReportHomeKeyShareConsumedReq, ReportHomeKeyShareConsumedResp,
 };
+use crate::rpc::tls::PeerCertificates;
 use crate::rpc::utilities;
 use crate::services::home;
 
@@ -35,10 +40,13 @@ impl HomeNetwork for HomeNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -83,10 +91,13 @@ impl HomeNetwork for HomeNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -115,24 +126,25 @@ impl HomeNetwork for HomeNetworkHandler {
         res
     }
 
-    /// Remote request to report an auth vector as used.
-    /// Sends a replacement vector in return.
-    async fn report_auth_consumed(
+    /// Remote request for an EAP-AKA' vector that will be generated on this network.
+    /// Checks for proper authentication.
+    async fn get_eap_aka_prime_vector(
         &self,
-        request: tonic::Request<ReportHomeAuthConsumedReq>,
-    ) -> Result<tonic::Response<ReportHomeAuthConsumedResp>, tonic::Status> {
+        request: tonic::Request<GetHomeEapAkaPrimeVectorReq>,
+    ) -> Result<tonic::Response<GetHomeEapAkaPrimeVectorResp>, tonic::Status> {
         tracing::info!("Request: {:?}", request);
 
         let monitor = tokio_metrics::TaskMonitor::new();
 
         let res = monitor
             .instrument(async move {
-                let content = request.into_inner();
-
-                let message = content.backup_auth_vector_req.clone().ok_or_else(|| {
+                let peer = PeerCertificates::from_request(&request);
+                let message = request.into_inner().message.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
+                peer.verify_status(&self.context, &message.signer_id)?;
+
                 let verify_result = signing::verify_message(&self.context, &message)
                     .await
                     .or_else(|e| {
@@ -142,10 +154,63 @@ impl HomeNetwork for HomeNetworkHandler {
                         ))
                     })?;
 
+                match HomeNetworkHandler::get_eap_aka_prime_vector_hlp(
+                    self.context.clone(),
+                    verify_result,
+                )
+                .await
+                {
+                    Ok(result) => Ok(result),
+                    Err(e) => {
+                        tracing::warn!("Home network get EAP-AKA' vector failed: {}", e);
+
+                        Err(tonic::Status::new(
+                            tonic::Code::Aborted,
+                            format!("Error while handling request: {}", e),
+                        ))
+                    }
+                }
+            })
+            .await;
+
+        self.context
+            .metrics_context
+            .record_metrics("home_network::get_eap_aka_prime_vector", monitor)
+            .await;
+        res
+    }
+
+    /// Remote request to report an auth vector as used.
+    /// Sends a replacement vector in return.
+    async fn report_auth_consumed(
+        &self,
+        request: tonic::Request<ReportHomeAuthConsumedReq>,
+    ) -> Result<tonic::Response<ReportHomeAuthConsumedResp>, tonic::Status> {
+        tracing::info!("Request: {:?}", request);
+
+        let monitor = tokio_metrics::TaskMonitor::new();
+
+        let res = monitor
+            .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
+                let content = request.into_inner();
+
+                peer.verify_status(&self.context, &content.backup_network_id)?;
+
+                let message = content.backup_auth_vector_req.clone().ok_or_else(|| {
+                    tonic::Status::new(tonic::Code::NotFound, "No message received")
+                })?;
+
+                let payload =
+                    home::audit_auth_report(&self.context, &content.backup_network_id, &message)
+                        .await
+                        .map_err(HomeNetworkHandler::audit_status)?;
+
                 match HomeNetworkHandler::report_auth_consumed_hlp(
                     self.context.clone(),
                     content,
-                    verify_result,
+                    &message.encode_to_vec(),
+                    payload,
                 )
                 .await
                 {
@@ -177,25 +242,28 @@ impl HomeNetwork for HomeNetworkHandler {
 
         let res = monitor
             .instrument(async move {
+                let peer = PeerCertificates::from_request(&request);
                 let content = request.into_inner();
 
+                peer.verify_status(&self.context, &content.backup_network_id)?;
+
                 let message = content.get_key_share_req.ok_or_else(|| {
                     tonic::Status::new(tonic::Code::NotFound, "No message received")
                 })?;
 
-                let verify_result = signing::verify_message(&self.context, &message)
-                    .await
-                    .or_else(|e| {
-                        Err(tonic::Status::new(
-                            tonic::Code::Unauthenticated,
-                            format!("Failed to verify message: {}", e),
-                        ))
-                    })?;
+                let payload = home::audit_key_share_report(
+                    &self.context,
+                    &content.backup_network_id,
+                    &message,
+                )
+                .await
+                .map_err(HomeNetworkHandler::audit_status)?;
 
                 match HomeNetworkHandler::report_key_share_consumed_hlp(
                     self.context.clone(),
                     &content.backup_network_id,
-                    verify_result,
+                    &message.encode_to_vec(),
+                    payload,
                 )
                 .await
                 {
@@ -217,6 +285,22 @@ impl HomeNetwork for HomeNetworkHandler {
 }
 
 impl HomeNetworkHandler {
+    /// Maps an error from auditing a report to a status.
+    /// Only a failed audit is blamed on the reporting network, while
+    /// errors of this network (such as a database error) are internal.
+    fn audit_status(error: DauthError) -> tonic::Status {
+        match error {
+            DauthError::InvalidMessageError(_) => tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                format!("Failed to verify message: {}", error),
+            ),
+            error => tonic::Status::new(
+                tonic::Code::Internal,
+                format!("Error while auditing report: {}", error),
+            ),
+        }
+    }
+
     async fn get_auth_vector_hlp(
         context: Arc<DauthContext>,
         verify_result: SignPayloadType,
@@ -225,18 +309,19 @@ impl HomeNetworkHandler {
             let user_id = std::str::from_utf8(payload.user_id.as_slice())?.to_string();
             let serving_network_id = payload.serving_network_id;
 
-            let av_result =
-                home::get_auth_vector(context.clone(), &user_id, &serving_network_id).await?;
+            let av_result = home::get_auth_vector(
+                context.clone(),
+                &user_id,
+                UserIdKind::from_i32(payload.user_id_type).unwrap_or(UserIdKind::Unknown),
+                &serving_network_id,
+                payload.serving_plmn.map(Plmn::from),
+                payload.resync_info.as_ref(),
+            )
+            .await?;
 
             let payload = delegated_auth_vector5_g::Payload {
                 serving_network_id: context.local_context.id.clone(),
-                v: Some(AuthVector5G {
-                    rand: av_result.rand.to_vec(),
-                    xres_star_hash: av_result.xres_star_hash.to_vec(),
-                    autn: av_result.autn.to_vec(),
-                    seqnum: av_result.seqnum,
-                    xres_hash: av_result.xres_hash.to_vec(),
-                }),
+                v: Some(av_result.to_av5_g()),
             };
 
             Ok(tonic::Response::new(GetHomeAuthVectorResp {
@@ -255,6 +340,45 @@ impl HomeNetworkHandler {
         }
     }
 
+    async fn get_eap_aka_prime_vector_hlp(
+        context: Arc<DauthContext>,
+        verify_result: SignPayloadType,
+    ) -> Result<tonic::Response<GetHomeEapAkaPrimeVectorResp>, DauthError> {
+        if let SignPayloadType::GetHomeEapAkaPrimeVectorReq(payload) = verify_result {
+            let user_id = std::str::from_utf8(payload.user_id.as_slice())?.to_string();
+
+            let vector_res = home::get_eap_aka_prime_vector(
+                context.clone(),
+                &user_id,
+                UserIdKind::from_i32(payload.user_id_type).unwrap_or(UserIdKind::Unknown),
+                &payload.serving_network_id,
+                &payload.network_name,
+                payload.serving_plmn.map(Plmn::from),
+                payload.resync_info.as_ref(),
+            )
+            .await?;
+
+            let payload = delegated_auth_vector_eap_aka_prime::Payload {
+                serving_network_id: context.local_context.id.clone(),
+                v: Some(vector_res.to_eap_aka_prime()),
+            };
+
+            Ok(tonic::Response::new(GetHomeEapAkaPrimeVectorResp {
+                vector: Some(DelegatedAuthVectorEapAkaPrime {
+                    message: Some(signing::sign_message(
+                        context,
+                        signing::SignPayloadType::DelegatedAuthVectorEapAkaPrime(payload),
+                    )),
+                }),
+            }))
+        } else {
+            Err(DauthError::InvalidMessageError(format!(
+                "Incorrect message type: {:?}",
+                verify_result
+            )))
+        }
+    }
+
     async fn get_confirm_key_hlp(
         context: Arc<DauthContext>,
         verify_result: SignPayloadType,
@@ -273,6 +397,23 @@ impl HomeNetworkHandler {
                     res.try_into()
                         .or(Err(DauthError::DataError("brokenRes".to_string())))?,
                 ),
+                Preimage::EapRes(res) => {
+                    let keys = home::get_eap_aka_prime_keys(
+                        context.clone(),
+                        res.try_into()
+                            .or(Err(DauthError::DataError("brokenEapRes".to_string())))?,
+                    )
+                    .await?;
+
+                    return Ok(tonic::Response::new(GetHomeConfirmKeyResp {
+                        key: Some(get_home_confirm_key_resp::Key::EapAkaPrime(
+                            EapAkaPrimeKeys {
+                                msk: keys.msk.to_vec(),
+                                kseaf: keys.kseaf.to_vec(),
+                            },
+                        )),
+                    }));
+                }
             };
 
             let key = match home::get_confirm_key(context.clone(), combined_res).await? {
@@ -294,76 +435,63 @@ impl HomeNetworkHandler {
     async fn report_auth_consumed_hlp(
         context: Arc<DauthContext>,
         content: ReportHomeAuthConsumedReq,
-        verify_result: SignPayloadType,
+        signed_request: &[u8],
+        payload: get_backup_auth_vector_req::Payload,
     ) -> Result<tonic::Response<ReportHomeAuthConsumedResp>, DauthError> {
-        // TODO: Check the payload further
-        if let SignPayloadType::GetBackupAuthVectorReq(_payload) = verify_result {
-            let core_response = home::report_auth_consumed(
-                context.clone(),
-                &content.backup_network_id,
-                content.xres_star_hash[..].try_into()?,
-            )
-            .await?;
-
-            match core_response {
-                Some(response) => Ok(tonic::Response::new(ReportHomeAuthConsumedResp {
-                    vector: Some(utilities::build_delegated_vector(
-                        context,
-                        &response,
-                        &content.backup_network_id,
-                    )),
-                })),
-                None => Ok(tonic::Response::new(ReportHomeAuthConsumedResp {
-                    vector: None,
-                })),
-            }
-        } else {
-            Err(DauthError::InvalidMessageError(format!(
-                "Incorrect message type: {:?}",
-                verify_result
-            )))
+        let core_response = home::report_auth_consumed(
+            context.clone(),
+            &content.backup_network_id,
+            content.xres_star_hash[..].try_into()?,
+            &payload,
+            signed_request,
+        )
+        .await?;
+
+        match core_response {
+            Some(response) => Ok(tonic::Response::new(ReportHomeAuthConsumedResp {
+                vector: Some(utilities::build_delegated_vector(
+                    context,
+                    &response,
+                    &content.backup_network_id,
+                )),
+            })),
+            None => Ok(tonic::Response::new(ReportHomeAuthConsumedResp {
+                vector: None,
+            })),
         }
     }
 
     async fn report_key_share_consumed_hlp(
         context: Arc<DauthContext>,
         backup_network_id: &str,
-        verify_result: SignPayloadType,
+        signed_request: &[u8],
+        payload: get_key_share_req::Payload,
     ) -> Result<tonic::Response<ReportHomeKeyShareConsumedResp>, DauthError> {
-        // TODO: Check the payload further
-        if let SignPayloadType::GetKeyShareReq(payload) = verify_result {
-            let hash = match payload
-                .hash
-                .ok_or(DauthError::InvalidMessageError("Missing hash".to_string()))?
-            {
-                get_key_share_req::payload::Hash::XresHash(h) => {
-                    XResHashKind::XResHash(h.as_slice().try_into()?)
-                }
-                get_key_share_req::payload::Hash::XresStarHash(h) => {
-                    XResHashKind::XResStarHash(h.as_slice().try_into()?)
-                }
-            };
-
-            let preimage = match payload.preimage.ok_or(DauthError::InvalidMessageError(
-                "Missing preimage".to_string(),
-            ))? {
-                get_key_share_req::payload::Preimage::Res(r) => {
-                    ResKind::Res(r.as_slice().try_into()?)
-                }
-                get_key_share_req::payload::Preimage::ResStar(r) => {
-                    ResKind::ResStar(r.as_slice().try_into()?)
-                }
-            };
+        let hash = match payload
+            .hash
+            .ok_or(DauthError::InvalidMessageError("Missing hash".to_string()))?
+        {
+            get_key_share_req::payload::Hash::XresHash(h) => {
+                XResHashKind::XResHash(h.as_slice().try_into()?)
+            }
+            get_key_share_req::payload::Hash::XresStarHash(h) => {
+                XResHashKind::XResStarHash(h.as_slice().try_into()?)
+            }
+        };
+
+        let preimage = match payload.preimage.ok_or(DauthError::InvalidMessageError(
+            "Missing preimage".to_string(),
+        ))? {
+            get_key_share_req::payload::Preimage::Res(r) => ResKind::Res(r.as_slice().try_into()?),
+            get_key_share_req::payload::Preimage::ResStar(r) => {
+                ResKind::ResStar(r.as_slice().try_into()?)
+            }
+        };
 
-            home::report_key_share_used(context, &preimage, &hash, backup_network_id).await?;
-            Ok(tonic::Response::new(ReportHomeKeyShareConsumedResp {
-                share: None, // TODO: requires extra state and generation cases
-            }))
-        } else {
-            Err(DauthError::InvalidMessageError(format!(
-                "Incorrect message type: {:?}",
-                verify_result
-            )))
-        }
+        home::report_key_share_used(context, &preimage, &hash, backup_network_id, signed_request)
+            .await?;
+        Ok(tonic::Response::new(ReportHomeKeyShareConsumedResp {
+            share: None, // TODO: requires extra state and generation cases
+        }))
     }
 }
@@ -4,9 +4,13 @@ use crate::data::combined_res::ResKind;
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
 use crate::data::keys::KeyKind;
+use crate::rpc::dauth::common::UserIdKind;
 use crate::rpc::dauth::local::local_authentication_server::LocalAuthentication;
-use crate::rpc::dauth::local::{aka_confirm_req, aka_confirm_resp};
+use crate::rpc::dauth::local::{aka_confirm_req, aka_confirm_resp, eap_aka_prime_confirm_resp};
 use crate::rpc::dauth::local::{AkaConfirmReq, AkaConfirmResp, AkaVectorReq, AkaVectorResp};
+use crate::rpc::dauth::local::{
+    EapAkaPrimeConfirmReq, EapAkaPrimeConfirmResp, EapAkaPrimeVectorReq, EapAkaPrimeVectorResp,
+};
 use crate::services::local;
 
 pub struct LocalAuthenticationHandler {
@@ -38,17 +42,37 @@ impl LocalAuthentication for LocalAuthenticationHandler {
                     }
                 }
 
-                match local::get_auth_vector(
-                    self.context.clone(),
-                    &user_id,
-                    &self.context.local_context.id,
-                    content.resync_info.is_some(),
-                )
-                .await
-                {
+                let user_id_kind =
+                    UserIdKind::from_i32(content.user_id_type).unwrap_or(UserIdKind::Unknown);
+
+                let av_result = match user_id_kind {
+                    UserIdKind::Suci => {
+                        local::get_auth_vector_by_suci(
+                            self.context.clone(),
+                            &user_id,
+                            content.resync_info,
+                        )
+                        .await
+                    }
+                    _ => {
+                        local::get_auth_vector(
+                            self.context.clone(),
+                            &user_id,
+                            &self.context.local_context.id,
+                            content.resync_info,
+                        )
+                        .await
+                    }
+                };
+
+                match av_result {
                     Ok(av_result) => {
                         tracing::info!("Returning result: {:?}", av_result);
-                        Ok(tonic::Response::new(av_result.to_resp()))
+                        if content.eps {
+                            Ok(tonic::Response::new(av_result.to_eps_resp(user_id_kind)))
+                        } else {
+                            Ok(tonic::Response::new(av_result.to_resp(user_id_kind)))
+                        }
                     }
                     Err(e) => {
                         tracing::error!("Error while handling request: {}", e);
@@ -102,6 +126,90 @@ impl LocalAuthentication for LocalAuthenticationHandler {
             .await;
         res
     }
+    /// Local request for an EAP-AKA' vector that will be used on this network.
+    /// No authentication is done.
+    #[tracing::instrument(skip_all)]
+    async fn get_eap_aka_prime_vector(
+        &self,
+        request: tonic::Request<EapAkaPrimeVectorReq>,
+    ) -> Result<tonic::Response<EapAkaPrimeVectorResp>, tonic::Status> {
+        tracing::debug!(?request, "Request received");
+
+        let monitor = tokio_metrics::TaskMonitor::new();
+
+        let res = monitor
+            .instrument(async move {
+                let content = request.into_inner();
+                let user_id = std::str::from_utf8(content.user_id.as_slice())
+                    .or_else(|e| {
+                        tracing::error!("Error while handling request: {}", e);
+                        Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
+                    })?
+                    .to_string();
+
+                let user_id_kind =
+                    UserIdKind::from_i32(content.user_id_type).unwrap_or(UserIdKind::Unknown);
+
+                match local::get_eap_aka_prime_vector(
+                    self.context.clone(),
+                    &user_id,
+                    user_id_kind,
+                    &content.network_name,
+                    content.resync_info,
+                )
+                .await
+                {
+                    Ok(vector_res) => {
+                        tracing::info!("Returning result: {:?}", vector_res);
+                        Ok(tonic::Response::new(vector_res.to_resp(user_id_kind)))
+                    }
+                    Err(e) => {
+                        tracing::error!("Error while handling request: {}", e);
+                        Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
+                    }
+                }
+            })
+            .await;
+
+        self.context
+            .metrics_context
+            .record_metrics("local::get_eap_aka_prime_vector", monitor)
+            .await;
+        res
+    }
+
+    /// Local request to complete the EAP-AKA' process for a vector.
+    /// No authentication is done.
+    #[tracing::instrument(skip_all)]
+    async fn confirm_eap_aka_prime(
+        &self,
+        request: tonic::Request<EapAkaPrimeConfirmReq>,
+    ) -> Result<tonic::Response<EapAkaPrimeConfirmResp>, tonic::Status> {
+        tracing::debug!(?request, "Request received");
+
+        let monitor = tokio_metrics::TaskMonitor::new();
+
+        let res = monitor
+            .instrument(async move {
+                match self.confirm_eap_aka_prime_hlp(request.into_inner()).await {
+                    Ok(response_payload) => {
+                        tracing::info!("Returning result: {:?}", response_payload);
+                        Ok(tonic::Response::new(response_payload))
+                    }
+                    Err(e) => {
+                        tracing::error!("Error while handling request: {}", e);
+                        Err(tonic::Status::new(tonic::Code::NotFound, e.to_string()))
+                    }
+                }
+            })
+            .await;
+
+        self.context
+            .metrics_context
+            .record_metrics("local::confirm_eap_aka_prime", monitor)
+            .await;
+        res
+    }
 }
 
 impl LocalAuthenticationHandler {
@@ -130,10 +238,43 @@ impl LocalAuthenticationHandler {
             }
         };
 
-        let key = match local::confirm_auth(self.context.clone(), &user_id, res).await? {
+        let key = match UserIdKind::from_i32(payload.user_id_type) {
+            Some(UserIdKind::Suci) => {
+                local::confirm_auth_by_suci(self.context.clone(), &user_id, res).await?
+            }
+            _ => local::confirm_auth(self.context.clone(), &user_id, res).await?,
+        };
+
+        let key = match key {
             KeyKind::Kasme(k) => aka_confirm_resp::Key::Kasme(k.to_vec()),
             KeyKind::Kseaf(k) => aka_confirm_resp::Key::Kseaf(k.to_vec()),
         };
         Ok(key)
     }
+
+    async fn confirm_eap_aka_prime_hlp(
+        &self,
+        payload: EapAkaPrimeConfirmReq,
+    ) -> Result<EapAkaPrimeConfirmResp, DauthError> {
+        let user_id = std::str::from_utf8(payload.user_id.as_slice())?.to_string();
+
+        let res = payload
+            .res
+            .try_into()
+            .or(Err(DauthError::DataError("brokenRes".to_string())))?;
+
+        let keys = local::confirm_eap_aka_prime(
+            self.context.clone(),
+            &user_id,
+            UserIdKind::from_i32(payload.user_id_type).unwrap_or(UserIdKind::Unknown),
+            res,
+        )
+        .await?;
+
+        Ok(EapAkaPrimeConfirmResp {
+            error: eap_aka_prime_confirm_resp::ErrorKind::NoError as i32,
+            msk: keys.msk.to_vec(),
+            kseaf: keys.kseaf.to_vec(),
+        })
+    }
 }
@@ -1,11 +1,16 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::data::config::{BackupConfig, UserInfoConfig};
+use crate::data::config::{BackupConfig, MccRangeConfig, ServingPolicyConfig, UserInfoConfig};
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
+use crate::data::quota::{BackupQuota, BackupUsage};
 use crate::management;
 use crate::rpc::dauth::management::management_server::Management;
-use crate::rpc::dauth::management::{AddUserReq, CommandResp, RemoveUserReq};
+use crate::rpc::dauth::management::{
+    get_backup_usage_resp, get_reputation_resp, AddUserReq, CommandResp, GetBackupUsageReq,
+    GetBackupUsageResp, GetReputationReq, GetReputationResp, RemoveUserReq, RotateSigningKeyReq,
+};
 
 pub struct ManagementHandler {
     pub context: Arc<DauthContext>,
@@ -45,14 +50,91 @@ impl Management for ManagementHandler {
     ) -> Result<tonic::Response<CommandResp>, tonic::Status> {
         tracing::info!(?request, "Remove user request");
 
-        // TODO: Add support for removing users.
-        // We can remove users locally, but there is not a way to remove users
-        // from the directory service.
+        match self
+            .remove_user_hlp(self.context.clone(), request.into_inner())
+            .await
+        {
+            Ok(()) => Ok(tonic::Response::new(CommandResp {
+                successful: true,
+                info: "".to_string(),
+            })),
+            Err(error) => {
+                tracing::error!(?error, "Failed to remove user");
+                Ok(tonic::Response::new(CommandResp {
+                    successful: false,
+                    info: format!("Failed to remove user: {:?}", error),
+                }))
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn rotate_signing_key(
+        &self,
+        request: tonic::Request<RotateSigningKeyReq>,
+    ) -> Result<tonic::Response<CommandResp>, tonic::Status> {
+        tracing::info!(?request, "Rotate signing key request");
 
-        Ok(tonic::Response::new(CommandResp {
-            successful: false,
-            info: "Removing users is not supported yet".to_string(),
-        }))
+        match self
+            .rotate_signing_key_hlp(self.context.clone(), request.into_inner())
+            .await
+        {
+            Ok(()) => Ok(tonic::Response::new(CommandResp {
+                successful: true,
+                info: "".to_string(),
+            })),
+            Err(error) => {
+                tracing::error!(?error, "Failed to rotate signing key");
+                Ok(tonic::Response::new(CommandResp {
+                    successful: false,
+                    info: format!("Failed to rotate signing key: {:?}", error),
+                }))
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_reputation(
+        &self,
+        request: tonic::Request<GetReputationReq>,
+    ) -> Result<tonic::Response<GetReputationResp>, tonic::Status> {
+        tracing::info!(?request, "Get reputation request");
+
+        match self
+            .get_reputation_hlp(self.context.clone(), request.into_inner())
+            .await
+        {
+            Ok(response) => Ok(tonic::Response::new(response)),
+            Err(error) => {
+                tracing::error!(?error, "Failed to get reputation");
+                Err(tonic::Status::new(
+                    tonic::Code::Internal,
+                    format!("Failed to get reputation: {:?}", error),
+                ))
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_backup_usage(
+        &self,
+        request: tonic::Request<GetBackupUsageReq>,
+    ) -> Result<tonic::Response<GetBackupUsageResp>, tonic::Status> {
+        tracing::info!(?request, "Get backup usage request");
+
+        match self
+            .get_backup_usage_hlp(self.context.clone(), request.into_inner())
+            .await
+        {
+            Ok(response) => Ok(tonic::Response::new(response)),
+            Err(error) => {
+                tracing::error!(?error, "Failed to get backup usage");
+                Err(tonic::Status::new(
+                    tonic::Code::Internal,
+                    format!("Failed to get backup usage: {:?}", error),
+                ))
+            }
+        }
     }
 }
 
@@ -74,13 +156,162 @@ impl ManagementHandler {
         let user_info = UserInfoConfig {
             user_id: add_user_req.user_id,
             k: add_user_req.k,
-            opc: add_user_req.opc,
+            opc: if add_user_req.opc.is_empty() {
+                None
+            } else {
+                Some(add_user_req.opc)
+            },
+            op: if add_user_req.op.is_empty() {
+                None
+            } else {
+                Some(add_user_req.op)
+            },
+            algorithm: if add_user_req.algorithm.is_empty() {
+                None
+            } else {
+                Some(add_user_req.algorithm)
+            },
             sqn_max: add_user_req.sqn_max,
             backups,
+            auto_backups: if add_user_req.auto_backups > 0 {
+                Some(add_user_req.auto_backups)
+            } else {
+                None
+            },
+            key_threshold: if add_user_req.key_threshold > 0 {
+                Some(add_user_req.key_threshold)
+            } else {
+                None
+            },
+            serving_policy: add_user_req
+                .serving_policy
+                .map(|policy| ServingPolicyConfig {
+                    allowed_network_ids: Some(policy.allowed_network_ids),
+                    denied_network_ids: Some(policy.denied_network_ids),
+                    allowed_mcc_ranges: Some(
+                        policy
+                            .allowed_mcc_ranges
+                            .into_iter()
+                            .map(|range| MccRangeConfig {
+                                first: range.first,
+                                last: range.last,
+                            })
+                            .collect(),
+                    ),
+                }),
         };
 
         management::add_user(context.clone(), &user_info).await?;
 
         Ok(())
     }
+
+    async fn remove_user_hlp(
+        &self,
+        context: Arc<DauthContext>,
+        remove_user_req: RemoveUserReq,
+    ) -> Result<(), DauthError> {
+        management::remove_user(context.clone(), &remove_user_req.user_id).await?;
+
+        Ok(())
+    }
+
+    async fn rotate_signing_key_hlp(
+        &self,
+        context: Arc<DauthContext>,
+        rotate_signing_key_req: RotateSigningKeyReq,
+    ) -> Result<(), DauthError> {
+        if rotate_signing_key_req.rollover_window < 0 {
+            return Err(DauthError::InvalidMessageError(
+                "Rollover window must not be negative".to_string(),
+            ));
+        }
+
+        management::rotate_signing_key(
+            context.clone(),
+            Duration::from_secs(rotate_signing_key_req.rollover_window as u64),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_reputation_hlp(
+        &self,
+        context: Arc<DauthContext>,
+        get_reputation_req: GetReputationReq,
+    ) -> Result<GetReputationResp, DauthError> {
+        let network_id = if get_reputation_req.network_id.is_empty() {
+            None
+        } else {
+            Some(get_reputation_req.network_id.as_str())
+        };
+
+        let networks = management::get_reputation(context.clone(), network_id)
+            .await?
+            .into_iter()
+            .map(
+                |(network_id, reputation, records)| get_reputation_resp::NetworkReputation {
+                    network_id,
+                    reputation,
+                    misbehaviour: records
+                        .into_iter()
+                        .map(|record| get_reputation_resp::Misbehaviour {
+                            kind: record.kind.as_str().to_string(),
+                            detail: record.detail,
+                            evidence: record.evidence,
+                            timestamp: record.timestamp,
+                        })
+                        .collect(),
+                },
+            )
+            .collect();
+
+        Ok(GetReputationResp { networks })
+    }
+
+    async fn get_backup_usage_hlp(
+        &self,
+        context: Arc<DauthContext>,
+        get_backup_usage_req: GetBackupUsageReq,
+    ) -> Result<GetBackupUsageResp, DauthError> {
+        let home_network_id = if get_backup_usage_req.home_network_id.is_empty() {
+            None
+        } else {
+            Some(get_backup_usage_req.home_network_id.as_str())
+        };
+
+        let home_networks = management::get_backup_usage(context.clone(), home_network_id)
+            .await?
+            .into_iter()
+            .map(
+                |(home_network_id, usage, quota)| get_backup_usage_resp::HomeNetworkUsage {
+                    home_network_id,
+                    usage: Some(to_usage_message(&usage)),
+                    quota: Some(to_quota_message(&quota)),
+                },
+            )
+            .collect();
+
+        Ok(GetBackupUsageResp { home_networks })
+    }
+}
+
+fn to_usage_message(usage: &BackupUsage) -> get_backup_usage_resp::Usage {
+    get_backup_usage_resp::Usage {
+        users: usage.users,
+        auth_vectors: usage.auth_vectors,
+        key_shares: usage.key_shares,
+        flood_vectors: usage.flood_vectors,
+    }
+}
+
+/// Unset limits are sent as -1.
+fn to_quota_message(quota: &BackupQuota) -> get_backup_usage_resp::Usage {
+    get_backup_usage_resp::Usage {
+        users: quota.max_users.unwrap_or(-1),
+        auth_vectors: quota.max_auth_vectors.unwrap_or(-1),
+        key_shares: quota.max_key_shares.unwrap_or(-1),
+        flood_vectors: quota.max_flood_vectors.unwrap_or(-1),
+    }
 }
@@ -1,6 +1,7 @@
 pub mod clients;
 pub mod handlers;
 pub mod server;
+pub mod tls;
 pub mod utilities;
 
 pub mod dauth {
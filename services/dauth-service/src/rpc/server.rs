@@ -7,6 +7,7 @@ use crate::rpc::handlers::backup_network::BackupNetworkHandler;
 use crate::rpc::handlers::home_network::HomeNetworkHandler;
 use crate::rpc::handlers::local_authentication::LocalAuthenticationHandler;
 use crate::rpc::handlers::management::ManagementHandler;
+use crate::rpc::tls;
 
 use crate::rpc::dauth::local::local_authentication_server::LocalAuthenticationServer;
 use crate::rpc::dauth::management::management_server::ManagementServer;
@@ -21,20 +22,25 @@ pub async fn start_servers(context: Arc<DauthContext>) {
         context.rpc_context.host_addr
     );
     let host_ip: std::net::SocketAddr = context.rpc_context.host_addr.parse().unwrap();
+
+    // With mutual TLS, only holders of a certificate from the network CA
+    // can connect. The local server remains plain, so management is only
+    // served there, where other networks cannot reach it.
+    let mut external_server = Server::builder();
+    if let Some(tls) = &context.rpc_context.tls {
+        external_server = external_server
+            .tls_config(tls::server_config(tls))
+            .expect("Invalid TLS config");
+    }
+
     let external_server_join_handle = tokio::spawn(
-        Server::builder()
+        external_server
             .add_service(HomeNetworkServer::new(HomeNetworkHandler {
                 context: context.clone(),
             }))
             .add_service(BackupNetworkServer::new(BackupNetworkHandler {
                 context: context.clone(),
             }))
-
-            // WARNING: POTENTIALLY DANGEROUS!
-            // Non-local sources can modify users using this interface.
-            .add_service(ManagementServer::new(ManagementHandler {
-                context: context.clone(),
-            }))
             .serve(host_ip),
     );
 
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, ServerTlsConfig};
+
+use crate::data::context::{DauthContext, TlsContext};
+use crate::data::error::DauthError;
+
+/// Returns the config of the remote-facing server, which requires
+/// clients to present a certificate issued by the network CA.
+pub fn server_config(tls: &TlsContext) -> ServerTlsConfig {
+    ServerTlsConfig::new()
+        .identity(tls.identity.clone())
+        .client_ca_root(tls.ca_certificate.clone())
+}
+
+/// Returns an endpoint to the dAuth network at the address.
+/// With TLS, the network must present a certificate for the
+/// network id it was looked up by.
+pub fn network_endpoint(
+    context: &Arc<DauthContext>,
+    network_id: &str,
+    address: &str,
+) -> Result<Endpoint, DauthError> {
+    build_endpoint(
+        address,
+        context
+            .rpc_context
+            .tls
+            .as_ref()
+            .map(|tls| (tls, network_id)),
+    )
+}
+
+/// Returns an endpoint to the directory service.
+/// With TLS, the directory must present a certificate
+/// for the configured directory domain.
+pub fn directory_endpoint(context: &Arc<DauthContext>) -> Result<Endpoint, DauthError> {
+    build_endpoint(
+        &context.rpc_context.directory_addr,
+        context
+            .rpc_context
+            .tls
+            .as_ref()
+            .map(|tls| (tls, tls.directory_domain.as_str())),
+    )
+}
+
+fn build_endpoint(address: &str, tls: Option<(&TlsContext, &str)>) -> Result<Endpoint, DauthError> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let endpoint = Endpoint::from_shared(format!("{}://{}", scheme, address))
+        .map_err(|e| DauthError::ClientError(format!("Invalid address {}: {}", address, e)))?;
+
+    match tls {
+        Some((tls, domain)) => Ok(endpoint.tls_config(
+            ClientTlsConfig::new()
+                .domain_name(domain)
+                .ca_certificate(tls.ca_certificate.clone())
+                .identity(tls.identity.clone()),
+        )?),
+        None => Ok(endpoint),
+    }
+}
+
+/// Certificates presented by the client of a request, kept
+/// to check the identity of the client once the request
+/// has been consumed.
+pub struct PeerCertificates(Option<Arc<Vec<Certificate>>>);
+
+impl PeerCertificates {
+    pub fn from_request<T>(request: &tonic::Request<T>) -> Self {
+        PeerCertificates(request.peer_certs())
+    }
+
+    /// Checks that the client holds a certificate for the network id.
+    /// Always passes without TLS, where requests are only
+    /// authenticated by their signatures.
+    pub fn verify(&self, context: &DauthContext, network_id: &str) -> Result<(), DauthError> {
+        if context.rpc_context.tls.is_none() {
+            return Ok(());
+        }
+
+        let certificate = self
+            .0
+            .as_ref()
+            .and_then(|certificates| certificates.first().cloned())
+            .ok_or_else(|| {
+                DauthError::PeerIdentityError("No client certificate presented".to_string())
+            })?;
+
+        tls_identity::verify_certificate_name(certificate.get_ref(), network_id)
+            .map_err(DauthError::PeerIdentityError)
+    }
+
+    /// Checks the client as in `verify`, failing with the
+    /// unauthenticated status returned by request handlers.
+    pub fn verify_status(
+        &self,
+        context: &DauthContext,
+        network_id: &str,
+    ) -> Result<(), tonic::Status> {
+        self.verify(context, network_id).map_err(|e| {
+            tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                format!("Failed to verify peer: {}", e),
+            )
+        })
+    }
+}
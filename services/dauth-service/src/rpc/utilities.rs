@@ -1,13 +1,12 @@
 use std::sync::Arc;
 
 use crate::data::{
-    context::DauthContext, error::DauthError, keys, signing, signing::SignPayloadType,
-    vector::AuthVectorRes,
+    context::DauthContext, error::DauthError, keys, policy::ServingPolicy, signing,
+    signing::SignPayloadType, vector::AuthVectorRes,
 };
-use crate::rpc::dauth::common::AuthVector5G;
 use crate::rpc::dauth::remote::{
-    delegated_auth_vector5_g, delegated_confirmation_share, DelegatedAuthVector5G,
-    DelegatedConfirmationShare,
+    delegated_auth_vector5_g, delegated_confirmation_share, delegated_serving_network_policy,
+    DelegatedAuthVector5G, DelegatedConfirmationShare, DelegatedServingNetworkPolicy,
 };
 
 pub fn build_delegated_vector(
@@ -17,13 +16,7 @@ pub fn build_delegated_vector(
 ) -> DelegatedAuthVector5G {
     let payload = delegated_auth_vector5_g::Payload {
         serving_network_id: serving_network_id.to_string(),
-        v: Some(AuthVector5G {
-            rand: vector.rand.to_vec(),
-            xres_star_hash: vector.xres_star_hash.to_vec(),
-            autn: vector.autn.to_vec(),
-            seqnum: vector.seqnum,
-            xres_hash: vector.xres_hash.to_vec(),
-        }),
+        v: Some(vector.to_av5_g()),
     };
 
     DelegatedAuthVector5G {
@@ -43,6 +36,10 @@ pub fn build_delegated_share(
         xres_hash: share.xres_hash.to_vec(),
         kasme_confirmation_share: share.kasme_share.to_vec(),
         kseaf_confirmation_share: share.kseaf_share.to_vec(),
+        kseaf_commitment: share.kseaf_commitment.to_vec(),
+        kasme_commitment: share.kasme_commitment.to_vec(),
+        threshold: share.threshold().into(),
+        epoch: share.epoch,
     };
 
     DelegatedConfirmationShare {
@@ -53,6 +50,24 @@ pub fn build_delegated_share(
     }
 }
 
+pub fn build_delegated_policy(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    policy: &ServingPolicy,
+) -> DelegatedServingNetworkPolicy {
+    let payload = delegated_serving_network_policy::Payload {
+        user_id: user_id.as_bytes().to_vec(),
+        policy: Some(policy.to_serving_network_policy()),
+    };
+
+    DelegatedServingNetworkPolicy {
+        message: Some(signing::sign_message(
+            context,
+            SignPayloadType::DelegatedServingNetworkPolicy(payload),
+        )),
+    }
+}
+
 pub async fn handle_delegated_vector(
     context: &Arc<DauthContext>,
     dvector: DelegatedAuthVector5G,
@@ -81,6 +96,9 @@ pub async fn handle_delegated_vector(
     }
 }
 
+/// Verifies the share's signature and checks both of its
+/// shares against the signed commitments, which must both be
+/// for the signed threshold.
 pub async fn handle_key_share(
     context: Arc<DauthContext>,
     dshare: DelegatedConfirmationShare,
@@ -94,12 +112,23 @@ pub async fn handle_key_share(
     .await?;
 
     if let SignPayloadType::DelegatedConfirmationShare(payload) = verify_result {
-        Ok(keys::CombinedKeyShare {
+        let key_share = keys::CombinedKeyShare {
             xres_star_hash: payload.xres_star_hash.as_slice().try_into()?,
             xres_hash: payload.xres_hash.as_slice().try_into()?,
             kasme_share: payload.kasme_confirmation_share.as_slice().try_into()?,
             kseaf_share: payload.kseaf_confirmation_share.as_slice().try_into()?,
-        })
+            kseaf_commitment: payload.kseaf_commitment.as_slice().try_into()?,
+            kasme_commitment: payload.kasme_commitment.as_slice().try_into()?,
+            epoch: payload.epoch,
+        };
+        key_share.verify()?;
+        key_share
+            .kseaf_commitment
+            .check_threshold(payload.threshold)?;
+        key_share
+            .kasme_commitment
+            .check_threshold(payload.threshold)?;
+        Ok(key_share)
     } else {
         Err(DauthError::InvalidMessageError(format!(
             "Incorrect message type: {:?}",
@@ -107,3 +136,56 @@ pub async fn handle_key_share(
         )))
     }
 }
+
+/// Verifies that the policy was signed by the user's home network
+/// and is meant for the user.
+pub async fn handle_delegated_policy(
+    context: &Arc<DauthContext>,
+    dpolicy: DelegatedServingNetworkPolicy,
+    user_id: &str,
+    home_network_id: &str,
+) -> Result<ServingPolicy, DauthError> {
+    let message = dpolicy.message.ok_or(DauthError::InvalidMessageError(
+        "Missing content".to_string(),
+    ))?;
+
+    if message.signer_id != home_network_id {
+        return Err(DauthError::InvalidMessageError(format!(
+            "Policy not signed by home network: {}",
+            message.signer_id
+        )));
+    }
+
+    let verify_result = signing::verify_message(context, &message).await?;
+
+    if let SignPayloadType::DelegatedServingNetworkPolicy(payload) = verify_result {
+        if payload.user_id != user_id.as_bytes() {
+            return Err(DauthError::InvalidMessageError(
+                "Policy for a different user".to_string(),
+            ));
+        }
+
+        payload
+            .policy
+            .ok_or(DauthError::InvalidMessageError(
+                "Missing content".to_string(),
+            ))?
+            .try_into()
+    } else {
+        Err(DauthError::InvalidMessageError(format!(
+            "Incorrect message type: {:?}",
+            verify_result
+        )))
+    }
+}
+
+/// Returns the status code of a failed request, so that a request
+/// refused for exceeding a quota or from the wrong peer can be
+/// told apart from a failure.
+pub fn status_code(error: &DauthError) -> tonic::Code {
+    match error {
+        DauthError::QuotaExceededError(_) => tonic::Code::ResourceExhausted,
+        DauthError::PeerIdentityError(_) => tonic::Code::Unauthenticated,
+        _ => tonic::Code::Aborted,
+    }
+}
@@ -1,10 +1,22 @@
 use std::sync::Arc;
 
-use crate::data::{context::DauthContext, error::DauthError, keys, vector::AuthVectorRes};
+use crate::common;
+use crate::data::{
+    context::DauthContext, error::DauthError, keys, policy::ServingPolicy, quota::BackupUsage,
+    vector::AuthVectorRes,
+};
 use crate::database;
 
 /// Confirms backup enrollment of this node for the provided user.
 /// Optionally stores a set of auth vectors and key shares for the user.
+/// Each key share is stored with its encoded signed form.
+/// A key share of a vector already held for the user replaces the
+/// held share, since the home network splits the keys of existing
+/// vectors again when the user's backup networks change.
+/// Replaces the user's serving policy, removing it if none is provided.
+/// Nothing is stored if the vectors and new key shares would exceed
+/// the quota of the user's home network, or if any of it fails to be
+/// stored, since the check and all changes are in one transaction.
 #[tracing::instrument(
     skip(context, auth_vectors, key_shares),
     name = "backup::enroll_backup_commit"
@@ -13,32 +25,63 @@ pub async fn enroll_backup_commit(
     context: Arc<DauthContext>,
     user_id: &str,
     auth_vectors: Vec<AuthVectorRes>,
-    key_shares: Vec<keys::CombinedKeyShare>,
+    key_shares: Vec<(keys::CombinedKeyShare, Vec<u8>)>,
+    serving_policy: Option<ServingPolicy>,
 ) -> Result<(), DauthError> {
     tracing::info!("Committing backup enrollment");
 
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let home_network_id = database::backup_users::get(&mut transaction, user_id).await?;
+    let mut new_key_shares = 0;
+    for (share, _) in &key_shares {
+        if database::key_shares::find_user_id(&mut transaction, &share.xres_star_hash)
+            .await?
+            .is_none()
+        {
+            new_key_shares += 1;
+        }
+    }
+    common::quotas::check(
+        &context,
+        &mut transaction,
+        &home_network_id,
+        &BackupUsage {
+            auth_vectors: auth_vectors.len() as i64,
+            key_shares: new_key_shares,
+            ..BackupUsage::default()
+        },
+    )
+    .await?;
+
+    tracing::debug!("Storing serving policy: {:?}", serving_policy);
+
+    match &serving_policy {
+        Some(serving_policy) => {
+            database::serving_policies::upsert(&mut transaction, user_id, serving_policy).await?
+        }
+        None => database::serving_policies::remove(&mut transaction, user_id).await?,
+    }
+
     tracing::debug!("Storing auth vectors: {:?}", auth_vectors);
 
-    let mut transaction = context.local_context.database_pool.begin().await?;
     for av in auth_vectors {
-        database::auth_vectors::add(
-            &mut transaction,
-            &av.user_id,
-            av.seqnum,
-            &av.xres_star_hash,
-            &av.xres_hash,
-            &av.autn,
-            &av.rand.as_array(),
-        )
-        .await?;
+        database::auth_vectors::add(&mut transaction, &av).await?;
     }
-    transaction.commit().await?;
 
     tracing::debug!("Storing key shares: {:?}", key_shares);
 
-    let mut transaction = context.local_context.database_pool.begin().await?;
-    for share in key_shares {
-        database::key_shares::add(&mut transaction, user_id, &share).await?;
+    for (share, signed_share) in key_shares {
+        if let Some(holder_id) =
+            database::key_shares::find_user_id(&mut transaction, &share.xres_star_hash).await?
+        {
+            if holder_id != user_id {
+                return Err(DauthError::InvalidMessageError(
+                    "Key share held for another user".to_string(),
+                ));
+            }
+            database::key_shares::remove(&mut transaction, &share.xres_star_hash).await?;
+        }
+        database::key_shares::add(&mut transaction, user_id, &share, &signed_share).await?;
     }
     transaction.commit().await?;
 
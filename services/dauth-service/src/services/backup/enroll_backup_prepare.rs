@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
-use crate::data::{context::DauthContext, error::DauthError};
+use crate::common;
+use crate::data::{context::DauthContext, error::DauthError, quota::BackupUsage};
 use crate::database;
 
 /// Sets the provided user id as a being backed up by this network.
+/// Refused if a new user would exceed the home network's quota.
 #[tracing::instrument(skip(context), name = "backup::enroll_backup_prepare")]
 pub async fn enroll_backup_prepare(
     context: Arc<DauthContext>,
@@ -13,6 +15,22 @@ pub async fn enroll_backup_prepare(
     tracing::info!("Preparing backup enrollment");
 
     let mut transaction = context.local_context.database_pool.begin().await?;
+    let is_new_user = match database::backup_users::get(&mut transaction, user_id).await {
+        Ok(existing_home_network_id) => existing_home_network_id != home_network_id,
+        Err(_) => true,
+    };
+    if is_new_user {
+        common::quotas::check(
+            &context,
+            &mut transaction,
+            home_network_id,
+            &BackupUsage {
+                users: 1,
+                ..BackupUsage::default()
+            },
+        )
+        .await?;
+    }
     database::backup_users::add(&mut transaction, user_id, home_network_id).await?;
     transaction.commit().await?;
 
@@ -1,9 +1,13 @@
 use std::sync::Arc;
 
-use crate::data::{context::DauthContext, error::DauthError, vector::AuthVectorRes};
+use crate::common;
+use crate::data::{
+    context::DauthContext, error::DauthError, quota::BackupUsage, vector::AuthVectorRes,
+};
 use crate::database;
 
 // Store a new flood vector that will be used before any normal auth vectors.
+// Refused if it would exceed the quota of the user's home network.
 #[tracing::instrument(skip(context), name = "backup::flood_vector")]
 pub async fn flood_vector(
     context: Arc<DauthContext>,
@@ -12,16 +16,18 @@ pub async fn flood_vector(
     tracing::info!("Storing new flood vector");
 
     let mut transaction = context.local_context.database_pool.begin().await?;
-    database::flood_vectors::add(
+    let home_network_id = database::backup_users::get(&mut transaction, &av_result.user_id).await?;
+    common::quotas::check(
+        &context,
         &mut transaction,
-        &av_result.user_id,
-        av_result.seqnum,
-        &av_result.xres_star_hash,
-        &&av_result.xres_hash,
-        &av_result.autn,
-        &av_result.rand.as_array(),
+        &home_network_id,
+        &BackupUsage {
+            flood_vectors: 1,
+            ..BackupUsage::default()
+        },
     )
     .await?;
+    database::flood_vectors::add(&mut transaction, av_result).await?;
     transaction.commit().await?;
 
     Ok(())
@@ -10,6 +10,8 @@ use crate::database;
 /// Gets the next backup auth vector, checking for any available flood
 /// vectors first. If there are no flood vectors, returns the auth vector
 /// with the lowest seqnum.
+/// Only vectors bound to the requested serving PLMN are used, since the
+/// xres* and Kseaf of any other vector would not match the UE's.
 #[tracing::instrument(skip(context), name = "backup::get_auth_vector")]
 pub async fn get_auth_vector(
     context: Arc<DauthContext>,
@@ -21,10 +23,15 @@ pub async fn get_auth_vector(
     let mut transaction = context.local_context.database_pool.begin().await?;
 
     // Check for a flood vector first
+    let flood_res = database::flood_vectors::get_first_by_plmn(
+        &mut transaction,
+        &av_request.user_id,
+        &av_request.plmn,
+    )
+    .await?;
+
     let vector;
-    if let Some(flood_res) =
-        database::flood_vectors::get_first(&mut transaction, &av_request.user_id).await?
-    {
+    if let Some(flood_res) = flood_res {
         vector = flood_res;
 
         database::flood_vectors::mark_sent(&mut transaction, &vector.user_id, vector.seqnum)
@@ -33,7 +40,12 @@ pub async fn get_auth_vector(
 
         tracing::info!("Flood vector found: {:?}", vector);
     } else {
-        vector = database::auth_vectors::get_first(&mut transaction, &av_request.user_id).await?;
+        vector = database::auth_vectors::get_first_by_plmn(
+            &mut transaction,
+            &av_request.user_id,
+            &av_request.plmn,
+        )
+        .await?;
 
         database::auth_vectors::mark_sent(&mut transaction, &vector.user_id, vector.seqnum).await?;
         // database::auth_vectors::remove(&mut transaction, &vector.user_id, &vector.xres_star_hash).await?;
@@ -1,19 +1,22 @@
 use std::sync::Arc;
 
 use crate::data::combined_res::XResHashKind;
-use crate::data::{context::DauthContext, error::DauthError, keys};
+use crate::data::{context::DauthContext, error::DauthError, vector::Plmn};
 use crate::database;
 
-/// Returns a key share value corresponding to the xres hash or
-/// xres* hash, depending on the authentication type (5G or 4G/EPS).
+/// Returns the encoded signed key share corresponding to the xres hash
+/// or xres* hash, depending on the authentication type (5G or 4G/EPS).
 /// The key share should correspond to an existing auth vector that
 /// was previously sent.
+/// Fails if the user's serving policy does not allow the serving network.
 #[tracing::instrument(skip(context), name = "backup::get_key_share")]
 pub async fn get_key_share(
     context: Arc<DauthContext>,
     combined_hash: &XResHashKind,
+    serving_network_id: &str,
+    serving_plmn: Option<&Plmn>,
     signed_request_bytes: &Vec<u8>,
-) -> Result<keys::CombinedKeyShare, DauthError> {
+) -> Result<Vec<u8>, DauthError> {
     tracing::info!("Getting backup key share");
 
     // TODO(matt9j) Should get the rand as part of the key share to validate the hashes...
@@ -30,6 +33,12 @@ pub async fn get_key_share(
 
     let user_id =
         database::key_shares::get_user_id(&mut transaction, &key_share.xres_star_hash).await?;
+    let signed_share =
+        database::key_shares::get_signed_share(&mut transaction, &key_share.xres_star_hash).await?;
+
+    if let Some(policy) = database::serving_policies::get(&mut transaction, &user_id).await? {
+        policy.check(serving_network_id, serving_plmn)?;
+    }
 
     // Remove the auth vectors at the point we have confirmed they were used.
     database::flood_vectors::remove(&mut transaction, &user_id, &key_share.xres_star_hash).await?;
@@ -45,5 +54,5 @@ pub async fn get_key_share(
 
     transaction.commit().await?;
 
-    Ok(key_share)
+    Ok(signed_share)
 }
@@ -5,19 +5,43 @@ use crate::database;
 
 /// Adds a new key share, replacing the indicated old key share
 /// if it hasn't already been used and removed.
-#[tracing::instrument(skip(context), name = "backup::replace_key_share")]
+/// The new key share is stored with its encoded signed form.
+/// A refreshed key share replaces the old share of its own vector,
+/// and is swapped in the same transaction. It must be of a newer epoch,
+/// so a delayed refresh never brings back an older split. Receiving the
+/// current share again is not an error, so refreshes can be retried.
+#[tracing::instrument(skip(context, signed_share), name = "backup::replace_key_share")]
 pub async fn replace_key_share(
     context: Arc<DauthContext>,
     old_xres_star_hash: &auth_vector::types::XResStarHash,
     new_key_share: &keys::CombinedKeyShare,
+    signed_share: &[u8],
 ) -> Result<(), DauthError> {
     tracing::info!("Replacing key share");
 
     let mut transaction = context.local_context.database_pool.begin().await?;
 
     let user_id = database::key_shares::get_user_id(&mut transaction, old_xres_star_hash).await?;
+
+    if new_key_share.xres_star_hash == *old_xres_star_hash {
+        let old_key_share =
+            database::key_shares::get_from_xres_star_hash(&mut transaction, old_xres_star_hash)
+                .await?;
+        if old_key_share == *new_key_share {
+            transaction.rollback().await?;
+            return Ok(());
+        }
+        if new_key_share.epoch <= old_key_share.epoch {
+            transaction.rollback().await?;
+            return Err(DauthError::InvalidShareError(format!(
+                "Share of epoch {} does not replace share of epoch {}",
+                new_key_share.epoch, old_key_share.epoch
+            )));
+        }
+    }
+
     database::key_shares::remove(&mut transaction, old_xres_star_hash).await?;
-    database::key_shares::add(&mut transaction, &user_id, new_key_share).await?;
+    database::key_shares::add(&mut transaction, &user_id, new_key_share, signed_share).await?;
 
     transaction.commit().await?;
 
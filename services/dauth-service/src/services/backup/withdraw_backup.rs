@@ -4,7 +4,7 @@ use crate::data::{context::DauthContext, error::DauthError};
 use crate::database;
 
 /// Removes the user from being backup up on this network.
-/// Also removes all related auth vectors.
+/// Also removes all related auth and flood vectors, and the serving policy.
 #[tracing::instrument(skip(context), name = "backup::withdraw_backup")]
 pub async fn withdraw_backup(
     context: Arc<DauthContext>,
@@ -25,6 +25,8 @@ pub async fn withdraw_backup(
         transaction = context.local_context.database_pool.begin().await?;
         database::backup_users::remove(&mut transaction, user_id, home_network_id).await?;
         database::auth_vectors::remove_all(&mut transaction, user_id).await?;
+        database::flood_vectors::remove_all(&mut transaction, user_id).await?;
+        database::serving_policies::remove(&mut transaction, user_id).await?;
         transaction.commit().await?;
 
         Ok(())
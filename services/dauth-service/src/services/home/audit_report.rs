@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use prost::Message;
+
+use crate::common;
+use crate::data::{
+    context::DauthContext,
+    error::DauthError,
+    misbehaviour::MisbehaviourKind,
+    signing::{self, SignPayloadType},
+};
+use crate::rpc::dauth::remote::{get_backup_auth_vector_req, get_key_share_req, SignedMessage};
+
+/// Audits the serving network request embedded in a report that a
+/// vector was consumed. The request must carry a valid signature of
+/// the serving network it names.
+/// The request does not name the reported vector, which is instead
+/// checked against the serving network once it uses the vector's
+/// key shares (see `report_key_share_used`).
+/// A report that fails the audit is recorded as misbehaviour of the
+/// reporting backup network.
+#[tracing::instrument(skip(context, message), name = "home::audit_auth_report")]
+pub async fn audit_auth_report(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    message: &SignedMessage,
+) -> Result<get_backup_auth_vector_req::Payload, DauthError> {
+    let result = match verify_report(context, message).await {
+        Ok(SignPayloadType::GetBackupAuthVectorReq(payload)) => {
+            check_signer(message, &payload.serving_network_id).map(|()| payload)
+        }
+        Ok(_) => Err(DauthError::InvalidMessageError(
+            "Report does not contain a vector request".to_string(),
+        )),
+        Err(e) => Err(e),
+    };
+
+    flag_failed_audit(context, backup_network_id, message, result).await
+}
+
+/// Audits the serving network request embedded in a report that a
+/// key share was used. The request must carry a valid signature of
+/// the serving network it names.
+/// A report that fails the audit is recorded as misbehaviour of the
+/// reporting backup network.
+#[tracing::instrument(skip(context, message), name = "home::audit_key_share_report")]
+pub async fn audit_key_share_report(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    message: &SignedMessage,
+) -> Result<get_key_share_req::Payload, DauthError> {
+    let result = match verify_report(context, message).await {
+        Ok(SignPayloadType::GetKeyShareReq(payload)) => {
+            check_signer(message, &payload.serving_network_id).map(|()| payload)
+        }
+        Ok(_) => Err(DauthError::InvalidMessageError(
+            "Report does not contain a key share request".to_string(),
+        )),
+        Err(e) => Err(e),
+    };
+
+    flag_failed_audit(context, backup_network_id, message, result).await
+}
+
+/// Verifies the signature of a reported request.
+/// Forged or malformed requests are reported as invalid messages,
+/// while other errors (such as a failed directory lookup) are not
+/// attributed to the reporting network.
+/// A request signed with the serving network's previous key is not
+/// attributed either, as an honest backup may report it after the
+/// key's rollover window ended.
+async fn verify_report(
+    context: &Arc<DauthContext>,
+    message: &SignedMessage,
+) -> Result<SignPayloadType, DauthError> {
+    match signing::verify_message_signature(context, message).await {
+        Err(DauthError::SigningError(e)) => {
+            if signing::signed_by_previous_key(context, message).await? {
+                Err(DauthError::SigningError(e))
+            } else {
+                Err(DauthError::InvalidMessageError(format!(
+                    "Invalid request signature: {}",
+                    e
+                )))
+            }
+        }
+        Err(DauthError::DecodeError(e)) => Err(DauthError::InvalidMessageError(format!(
+            "Malformed request: {}",
+            e
+        ))),
+        result => result,
+    }
+}
+
+/// Checks that a request was signed by the serving network it names.
+fn check_signer(message: &SignedMessage, serving_network_id: &str) -> Result<(), DauthError> {
+    if message.signer_id == serving_network_id {
+        Ok(())
+    } else {
+        Err(DauthError::InvalidMessageError(format!(
+            "Request for {} signed by {}",
+            serving_network_id, message.signer_id
+        )))
+    }
+}
+
+async fn flag_failed_audit<T>(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    message: &SignedMessage,
+    result: Result<T, DauthError>,
+) -> Result<T, DauthError> {
+    if let Err(DauthError::InvalidMessageError(reason)) = &result {
+        common::misbehaviour::record(
+            context,
+            backup_network_id,
+            MisbehaviourKind::UnprovenConsumption,
+            reason,
+            message.encode_to_vec(),
+        )
+        .await;
+    }
+
+    result
+}
@@ -1,19 +1,43 @@
 use std::sync::Arc;
 
 use crate::common;
-use crate::data::{context::DauthContext, error::DauthError, vector::AuthVectorRes};
+use crate::data::{
+    context::DauthContext,
+    error::DauthError,
+    suci::Suci,
+    vector::{AuthVectorRes, Plmn},
+};
+use crate::rpc::dauth::common::{AkaResyncInfo, UserIdKind};
 
 /// Generates an auth vector that will be verified locally.
 /// Stores the kseaf directly, without key shares.
+/// Resynchronizes the user's sqn first if resync info is provided.
+/// A SUCI is de-concealed here, and the SUPI is never returned.
+/// The keys are bound to the serving network PLMN if provided,
+/// or else to this network's PLMN.
 #[tracing::instrument(skip(context), name = "home::get_auth_vector")]
 pub async fn get_auth_vector(
     context: Arc<DauthContext>,
     user_id: &str,
+    user_id_kind: UserIdKind,
     serving_network_id: &str,
+    serving_plmn: Option<Plmn>,
+    resync_info: Option<&AkaResyncInfo>,
 ) -> Result<AuthVectorRes, DauthError> {
     tracing::info!("Generating new vector for requesting network");
 
     // TODO: Additional checking to verify requesting network
 
-    common::auth_vectors::generate_local_vector(context, user_id).await
+    let supi = match user_id_kind {
+        UserIdKind::Suci => Suci::try_from(user_id)?.deconceal(&context.local_context.suci_keys)?,
+        _ => user_id.to_string(),
+    };
+
+    let plmn = serving_plmn.unwrap_or_else(|| context.local_context.plmn());
+
+    let mut auth_vector_res =
+        common::auth_vectors::generate_local_vector(context, &supi, &plmn, resync_info).await?;
+    auth_vector_res.user_id = user_id.to_string();
+
+    Ok(auth_vector_res)
 }
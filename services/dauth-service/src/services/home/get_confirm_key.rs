@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+use auth_vector::types::Res;
+
 use crate::common;
 use crate::data::{combined_res::ResKind, context::DauthContext, error::DauthError, keys};
 
@@ -19,3 +21,14 @@ pub async fn get_confirm_key(
         )),
     }
 }
+
+/// Gets the session keys of an EAP-AKA' vector from this network.
+#[tracing::instrument(skip(context), name = "home::get_eap_aka_prime_keys")]
+pub async fn get_eap_aka_prime_keys(
+    context: Arc<DauthContext>,
+    res: Res,
+) -> Result<keys::EapAkaPrimeSessionKeys, DauthError> {
+    tracing::info!("Getting EAP-AKA' keys for remote authentication");
+
+    common::confirm_keys::get_confirm_key_eap_res(context, res).await
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use crate::common;
+use crate::data::{
+    context::DauthContext,
+    error::DauthError,
+    suci::Suci,
+    vector::{EapAkaPrimeVectorRes, Plmn},
+};
+use crate::rpc::dauth::common::{AkaResyncInfo, UserIdKind};
+
+/// Generates an EAP-AKA' vector that will be verified locally.
+/// Stores the session keys directly, without key shares.
+/// The keys are bound to the SUPI, even if the request used a SUCI.
+/// The anchor key is bound to the serving network PLMN if provided,
+/// or else to this network's PLMN.
+#[tracing::instrument(skip(context), name = "home::get_eap_aka_prime_vector")]
+pub async fn get_eap_aka_prime_vector(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    user_id_kind: UserIdKind,
+    serving_network_id: &str,
+    network_name: &str,
+    serving_plmn: Option<Plmn>,
+    resync_info: Option<&AkaResyncInfo>,
+) -> Result<EapAkaPrimeVectorRes, DauthError> {
+    tracing::info!("Generating new EAP-AKA' vector for requesting network");
+
+    let supi = match user_id_kind {
+        UserIdKind::Suci => Suci::try_from(user_id)?.deconceal(&context.local_context.suci_keys)?,
+        _ => user_id.to_string(),
+    };
+
+    let plmn = serving_plmn.unwrap_or_else(|| context.local_context.plmn());
+
+    let mut vector_res = common::auth_vectors::generate_local_eap_aka_prime_vector(
+        context,
+        &supi,
+        network_name,
+        &plmn,
+        resync_info,
+    )
+    .await?;
+    vector_res.user_id = user_id.to_string();
+
+    Ok(vector_res)
+}
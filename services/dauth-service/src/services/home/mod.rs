@@ -1,10 +1,14 @@
+mod audit_report;
 mod get_auth_vector;
 mod get_confirm_key;
+mod get_eap_aka_prime_vector;
 mod report_auth_consumed;
 mod report_key_share_used;
 
 /* Public access functions */
+pub use audit_report::{audit_auth_report, audit_key_share_report};
 pub use get_auth_vector::get_auth_vector;
-pub use get_confirm_key::get_confirm_key;
+pub use get_confirm_key::{get_confirm_key, get_eap_aka_prime_keys};
+pub use get_eap_aka_prime_vector::get_eap_aka_prime_vector;
 pub use report_auth_consumed::report_auth_consumed;
 pub use report_key_share_used::report_key_share_used;
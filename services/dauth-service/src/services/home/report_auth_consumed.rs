@@ -1,24 +1,52 @@
 use std::sync::Arc;
 
 use auth_vector::data::AuthVectorData;
+use prost::Message;
 use sqlx::{Sqlite, Transaction};
 
 use crate::common;
-use crate::data::{context::DauthContext, error::DauthError, keys, vector::AuthVectorRes};
+use crate::data::{
+    context::DauthContext,
+    error::DauthError,
+    keys,
+    misbehaviour::MisbehaviourKind,
+    vector::{AuthVectorRes, DelegatedVector, Plmn},
+};
 use crate::database;
 use crate::rpc::clients;
+use crate::rpc::dauth::remote::{get_backup_auth_vector_req, SignedMessage, SignedMessageSet};
 
 /// Handles a report that a vector was consumed by a backup network.
 /// Returns a new vector to replace the used vector.
 /// Sends new key shares to all other backup networks for the same user.
-#[tracing::instrument(skip(context), name = "home::report_auth_consumed")]
+/// The signed serving network request is kept as proof of the report,
+/// and is recorded as evidence if the report shows misbehaviour.
+/// The request must already have passed `audit_auth_report`.
+#[tracing::instrument(
+    skip(context, request, signed_request),
+    name = "home::report_auth_consumed"
+)]
 pub async fn report_auth_consumed(
     context: Arc<DauthContext>,
     backup_network_id: &str,
     old_xres_star_hash: &auth_vector::types::XResStarHash,
+    request: &get_backup_auth_vector_req::Payload,
+    signed_request: &[u8],
 ) -> Result<Option<AuthVectorRes>, DauthError> {
     tracing::info!("Auth vector reported used by backup network");
 
+    if !check_report(
+        &context,
+        backup_network_id,
+        old_xres_star_hash,
+        request,
+        signed_request,
+    )
+    .await?
+    {
+        return Ok(None);
+    }
+
     // A lot of operations are done on this transaction, but this is
     // the best way to ensure that changes are reverted on error.
     let mut transaction = context.local_context.database_pool.begin().await?;
@@ -28,10 +56,11 @@ pub async fn report_auth_consumed(
         &mut transaction,
         backup_network_id,
         old_xres_star_hash,
+        signed_request,
     )
     .await?
     {
-        Some((user_id, auth_vector_data, seqnum)) => {
+        Some((user_id, auth_vector_data, seqnum, plmn)) => {
             tracing::debug!(
                 ?user_id,
                 ?auth_vector_data,
@@ -43,8 +72,10 @@ pub async fn report_auth_consumed(
             send_key_shares(
                 context.clone(),
                 &mut transaction,
+                &user_id,
                 &backup_networks,
                 &auth_vector_data,
+                &plmn,
                 old_xres_star_hash,
             )
             .await?;
@@ -64,6 +95,7 @@ pub async fn report_auth_consumed(
                 autn: auth_vector_data.autn,
                 xres_star_hash: auth_vector_data.xres_star_hash,
                 xres_hash: auth_vector_data.xres_hash,
+                plmn,
             }))
         }
         None => {
@@ -73,18 +105,108 @@ pub async fn report_auth_consumed(
     }
 }
 
+/// Checks the report against the state of the vector before it is replaced.
+/// Returns whether the vector is still outstanding and may be replaced.
+/// The request must name the user and serving PLMN of the vector, and
+/// must not have been reported for any other vector. A report from a
+/// network that does not own the vector claims a vector it was never
+/// given. For a vector that was already replaced, a request signed by a
+/// different serving network than the original shows that the backup
+/// network issued the vector more than once.
+/// Any of these is recorded as misbehaviour.
+async fn check_report(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    old_xres_star_hash: &auth_vector::types::XResStarHash,
+    request: &get_backup_auth_vector_req::Payload,
+    signed_request: &[u8],
+) -> Result<bool, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let state = database::vector_state::get(&mut transaction, old_xres_star_hash).await?;
+    let consumed = database::consumed_vectors::get(&mut transaction, old_xres_star_hash).await?;
+    let request_reported =
+        database::consumed_vectors::is_request_reported(&mut transaction, signed_request).await?;
+    transaction.commit().await?;
+
+    if let Some((owning_network_id, user_id, plmn)) = state {
+        let failure = if owning_network_id != backup_network_id {
+            "Reported vector owned by another network"
+        } else if request.user_id != user_id.as_bytes() {
+            "Reported request names another user"
+        } else if request.serving_plmn.clone().map(Plmn::from) != Some(plmn) {
+            "Reported request names another serving plmn"
+        } else if request_reported {
+            "Reported request already reported for another vector"
+        } else {
+            return Ok(true);
+        };
+
+        common::misbehaviour::record(
+            context,
+            backup_network_id,
+            MisbehaviourKind::UnprovenConsumption,
+            failure,
+            signed_request.to_vec(),
+        )
+        .await;
+        return Err(DauthError::InvalidMessageError(failure.to_string()));
+    }
+
+    match consumed {
+        Some((reporting_network_id, _)) if reporting_network_id != backup_network_id => {
+            let failure = "Reported vector consumed by another network";
+            common::misbehaviour::record(
+                context,
+                backup_network_id,
+                MisbehaviourKind::UnprovenConsumption,
+                failure,
+                signed_request.to_vec(),
+            )
+            .await;
+            Err(DauthError::InvalidMessageError(failure.to_string()))
+        }
+        Some((_, original_request))
+            if SignedMessage::decode(&original_request[..])?.signer_id
+                != SignedMessage::decode(signed_request)?.signer_id =>
+        {
+            // Both requests are needed to show the vector went to two networks
+            let evidence = SignedMessageSet {
+                messages: vec![
+                    SignedMessage::decode(&original_request[..])?,
+                    SignedMessage::decode(signed_request)?,
+                ],
+            };
+            common::misbehaviour::record(
+                context,
+                backup_network_id,
+                MisbehaviourKind::OverIssuedVector,
+                "Reported vector issued to more than one serving network",
+                evidence.encode_to_vec(),
+            )
+            .await;
+            Ok(false)
+        }
+        _ => {
+            tracing::warn!("No local state for vector, likely was already reported and replaced");
+            Ok(false)
+        }
+    }
+}
+
 /// Attempts to build a new auth vector based on previous stored state.
 /// If the state doesn't exist, this is considered a non-error and likely
 /// occurs with repeated requests to report the same auth vector as used.
-/// If the state does exists, a replacement auth vector is generated.
+/// If the state does exists, a replacement auth vector is generated,
+/// bound to the same serving network PLMN as the used vector.
 async fn replace_auth_vector_from_state(
     context: Arc<DauthContext>,
     transaction: &mut Transaction<'_, Sqlite>,
     backup_network_id: &str,
     old_xres_star_hash: &auth_vector::types::XResStarHash,
-) -> Result<Option<(String, AuthVectorData, i64)>, DauthError> {
+    signed_request: &[u8],
+) -> Result<Option<(String, AuthVectorData, i64, Plmn)>, DauthError> {
     match database::vector_state::get(transaction, old_xres_star_hash).await? {
-        Some((owning_network_id, user_id)) => {
+        Some((owning_network_id, user_id, plmn)) => {
             tracing::debug!(
                 ?owning_network_id,
                 ?user_id,
@@ -96,6 +218,15 @@ async fn replace_auth_vector_from_state(
             }
 
             database::vector_state::remove(transaction, old_xres_star_hash).await?;
+            database::delegated_vectors::remove(transaction, old_xres_star_hash).await?;
+            database::consumed_vectors::add(
+                transaction,
+                old_xres_star_hash,
+                &user_id,
+                backup_network_id,
+                signed_request,
+            )
+            .await?;
 
             let seqnum_slice =
                 database::backup_networks::get_slice(transaction, &user_id, backup_network_id)
@@ -106,6 +237,7 @@ async fn replace_auth_vector_from_state(
                 transaction,
                 &user_id,
                 seqnum_slice,
+                &plmn,
             )
             .await?;
 
@@ -114,10 +246,11 @@ async fn replace_auth_vector_from_state(
                 &auth_vector_data.xres_star_hash,
                 &user_id,
                 backup_network_id,
+                &plmn,
             )
             .await?;
 
-            Ok(Some((user_id, auth_vector_data, seqnum)))
+            Ok(Some((user_id, auth_vector_data, seqnum, plmn)))
         }
         None => Ok(None),
     }
@@ -125,30 +258,35 @@ async fn replace_auth_vector_from_state(
 
 /// Builds a set of key shares for the provided backup networks.
 /// Creates tasks to send shares (makes no network calls).
+/// Keeps the vector and the holder of each share, so that the shares
+/// can be refreshed. Fails if there are fewer backup networks than the
+/// key threshold of the user.
 async fn send_key_shares(
     context: Arc<DauthContext>,
     transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
     backup_networks: &Vec<String>,
     auth_vector_data: &AuthVectorData,
+    plmn: &Plmn,
     old_xres_star_hash: &auth_vector::types::XResStarHash,
 ) -> Result<(), DauthError> {
-    let mut kseaf_key_shares = keys::create_shares_from_kseaf(
+    let num_shares = keys::share_count(backup_networks.len())?;
+    let threshold = context.backup_context.key_threshold(
+        database::user_infos::get_key_threshold(transaction, &user_id.to_string()).await?,
+        num_shares,
+    )?;
+
+    let (mut kseaf_key_shares, kseaf_commitment) = keys::create_shares_from_kseaf(
         &auth_vector_data.kseaf,
-        backup_networks.len() as u8,
-        std::cmp::min(
-            context.backup_context.backup_key_threshold,
-            backup_networks.len() as u8,
-        ),
+        num_shares,
+        threshold,
         &mut rand_0_8::thread_rng(),
     )?;
 
-    let mut kasme_key_shares = keys::create_shares_from_kasme(
+    let (mut kasme_key_shares, kasme_commitment) = keys::create_shares_from_kasme(
         &auth_vector_data.kasme,
-        backup_networks.len() as u8,
-        std::cmp::min(
-            context.backup_context.backup_key_threshold,
-            backup_networks.len() as u8,
-        ),
+        num_shares,
+        threshold,
         &mut rand_0_8::thread_rng(),
     )?;
 
@@ -162,14 +300,42 @@ async fn send_key_shares(
         database::tasks::replace_key_shares::add(
             transaction,
             &backup_network_id,
+            old_xres_star_hash,
+            &keys::CombinedKeyShare {
+                xres_star_hash: auth_vector_data.xres_star_hash,
+                xres_hash: auth_vector_data.xres_hash,
+                kseaf_share,
+                kasme_share,
+                kseaf_commitment: kseaf_commitment.clone(),
+                kasme_commitment: kasme_commitment.clone(),
+                epoch: 0,
+            },
+        )
+        .await?;
+        database::key_share_state::add(
+            transaction,
             &auth_vector_data.xres_star_hash,
             &auth_vector_data.xres_hash,
-            old_xres_star_hash,
-            &kseaf_share,
-            &kasme_share,
+            backup_network_id,
+            user_id,
+            &auth_vector_data.rand.as_array(),
         )
         .await?;
     }
 
+    database::delegated_vectors::add(
+        transaction,
+        user_id,
+        &DelegatedVector {
+            xres_star_hash: auth_vector_data.xres_star_hash,
+            xres_hash: auth_vector_data.xres_hash,
+            rand: auth_vector_data.rand,
+            autn: auth_vector_data.autn,
+            plmn: plmn.clone(),
+            epoch: 0,
+        },
+    )
+    .await?;
+
     Ok(())
 }
@@ -1,20 +1,30 @@
 use std::sync::Arc;
 
+use prost::Message;
+
+use crate::common;
 use crate::data::{
     combined_res::ResKind, combined_res::XResHashKind, context::DauthContext, error::DauthError,
+    misbehaviour::MisbehaviourKind,
 };
 use crate::database;
+use crate::rpc::dauth::remote::{SignedMessage, SignedMessageSet};
 
 /// Handles a key share that was generated by this network and used
 /// by a backup network. Validates the provided response by hashing
 /// it and checking it with the original hash that was stored when
 /// the vector was generated.
-#[tracing::instrument(skip(context), name = "home::report_key_share_used")]
+/// A response that fails validation is recorded as misbehaviour of the
+/// backup network, with the signed request as evidence.
+/// A valid response also proves which serving network used the vector,
+/// which is checked against the report of the vector's consumption.
+#[tracing::instrument(skip(context, signed_request), name = "home::report_key_share_used")]
 pub async fn report_key_share_used(
     context: Arc<DauthContext>,
     response: &ResKind,
     xresponse_hash: &XResHashKind,
     backup_network_id: &str,
+    signed_request: &[u8],
 ) -> Result<(), DauthError> {
     tracing::info!("Handling reported used key share by backup network");
 
@@ -45,7 +55,12 @@ pub async fn report_key_share_used(
         match xresponse_hash {
             XResHashKind::XResStarHash(xres_star_hash) => {
                 if let ResKind::ResStar(res_star) = response {
-                    validate_xres_star_hash(xres_star_hash, res_star, &state.rand)?;
+                    if let Err(e) = validate_xres_star_hash(xres_star_hash, res_star, &state.rand) {
+                        transaction.rollback().await?;
+                        record_unproven_consumption(&context, backup_network_id, signed_request)
+                            .await;
+                        return Err(e);
+                    }
                     database::key_share_state::remove_by_xres_star_hash(
                         &mut transaction,
                         xres_star_hash,
@@ -60,7 +75,12 @@ pub async fn report_key_share_used(
             }
             XResHashKind::XResHash(xres_hash) => {
                 if let ResKind::Res(res) = response {
-                    validate_xres_hash(xres_hash, res, &state.rand)?;
+                    if let Err(e) = validate_xres_hash(xres_hash, res, &state.rand) {
+                        transaction.rollback().await?;
+                        record_unproven_consumption(&context, backup_network_id, signed_request)
+                            .await;
+                        return Err(e);
+                    }
                     database::key_share_state::remove_by_xres_hash(
                         &mut transaction,
                         xres_hash,
@@ -76,6 +96,10 @@ pub async fn report_key_share_used(
         };
 
         transaction.commit().await?;
+
+        if let XResHashKind::XResStarHash(xres_star_hash) = xresponse_hash {
+            check_reported_consumer(&context, xres_star_hash, signed_request).await?;
+        }
     } else {
         tracing::warn!("Key share reported used with no corresponding share state");
     }
@@ -83,6 +107,60 @@ pub async fn report_key_share_used(
     Ok(())
 }
 
+/// Checks that a vector was reported consumed with a request of the
+/// serving network that used it.
+/// The serving network request in a consumption report does not name
+/// the vector, so the reported xres* hash is only bound to it once the
+/// vector is used. A key share request with a valid res* signed by
+/// another serving network shows the owner of the vector reported it
+/// for the wrong request, or issued it more than once.
+async fn check_reported_consumer(
+    context: &Arc<DauthContext>,
+    xres_star_hash: &auth_vector::types::XResStarHash,
+    signed_request: &[u8],
+) -> Result<(), DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let consumed = database::consumed_vectors::get(&mut transaction, xres_star_hash).await?;
+    transaction.commit().await?;
+
+    if let Some((reporting_network_id, reported_request)) = consumed {
+        let reported_request = SignedMessage::decode(&reported_request[..])?;
+        let used_request = SignedMessage::decode(signed_request)?;
+
+        if reported_request.signer_id != used_request.signer_id {
+            let evidence = SignedMessageSet {
+                messages: vec![reported_request, used_request],
+            };
+            common::misbehaviour::record(
+                context,
+                &reporting_network_id,
+                MisbehaviourKind::UnprovenConsumption,
+                "Reported vector used by another serving network",
+                evidence.encode_to_vec(),
+            )
+            .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records a key share reported used without a valid response.
+async fn record_unproven_consumption(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    signed_request: &[u8],
+) {
+    common::misbehaviour::record(
+        context,
+        backup_network_id,
+        MisbehaviourKind::UnprovenConsumption,
+        "Reported key share used with an invalid response",
+        signed_request.to_vec(),
+    )
+    .await;
+}
+
 /// Confirms res* is a valid preimage of xres* hash.
 fn validate_xres_star_hash(
     xres_star_hash: &auth_vector::types::XResStarHash,
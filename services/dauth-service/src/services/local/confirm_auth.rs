@@ -9,7 +9,9 @@ use crate::common;
 use crate::data::state::AuthState;
 use crate::data::{
     combined_res::ResKind, context::DauthContext, error::DauthError, keys, state::AuthSource,
+    suci::Suci, utilities,
 };
+use crate::database;
 use crate::rpc::clients;
 
 /// Attempts to find the Kseaf value for the network.
@@ -30,49 +32,132 @@ pub async fn confirm_auth(
 
     if home_network_id == context.local_context.id {
         tracing::debug!(?user_id, "User owned by this network");
-        return match combined_res {
-            ResKind::ResStar(res_star) => Ok(keys::KeyKind::Kseaf(
-                common::confirm_keys::get_confirm_key_res_star(context.clone(), res_star).await?,
-            )),
-            ResKind::Res(res) => Ok(keys::KeyKind::Kasme(
-                common::confirm_keys::get_confirm_key_res(context.clone(), res).await?,
-            )),
-        };
+        confirm_local(context, combined_res).await
     } else {
         tracing::debug!(?user_id, ?home_network_id, "User owned by other network");
+        confirm_remote(
+            context,
+            user_id,
+            combined_res,
+            &home_network_id,
+            &backup_network_ids,
+        )
+        .await
+    }
+}
 
-        let (address, _) = clients::directory::lookup_network(&context, &home_network_id).await?;
+/// Attempts to find the Kseaf value for a user identified by SUCI.
+/// The SUCI is routed to its home network, as when the vector was
+/// requested. Backup networks are not used.
+#[tracing::instrument(skip(context), name = "local::confirm_auth_by_suci")]
+pub async fn confirm_auth_by_suci(
+    context: Arc<DauthContext>,
+    suci: &str,
+    combined_res: ResKind,
+) -> Result<keys::KeyKind, DauthError> {
+    tracing::info!("Confirming local authentication by SUCI");
 
-        let state;
-        {
-            let mut map = context.backup_context.auth_states.lock().await;
-            state = map.remove(user_id).ok_or(DauthError::NotFoundError(
-                "Could not find state for auth transaction".to_string(),
-            ))?;
-            // drop mutex guard
-        }
+    let home_network_id = Suci::try_from(suci)?.home_network_id(&context.local_context)?;
 
-        let key = match combined_res {
-            ResKind::Res(res) => {
-                confirm_authentication_eps(&context, &res, &state, &backup_network_ids, &address)
-                    .await?
-            }
-            ResKind::ResStar(res_star) => {
-                confirm_authentication_5g(
-                    &context,
-                    &res_star,
-                    &state,
-                    &backup_network_ids,
-                    &address,
-                )
-                .await?
-            }
-        };
+    if home_network_id == context.local_context.id {
+        confirm_local(context, combined_res).await
+    } else {
+        confirm_remote(context, suci, combined_res, &home_network_id, &Vec::new()).await
+    }
+}
 
-        Ok(key)
+/// Gets the key of a vector generated on this network.
+async fn confirm_local(
+    context: Arc<DauthContext>,
+    combined_res: ResKind,
+) -> Result<keys::KeyKind, DauthError> {
+    match combined_res {
+        ResKind::ResStar(res_star) => Ok(keys::KeyKind::Kseaf(
+            common::confirm_keys::get_confirm_key_res_star(context.clone(), res_star).await?,
+        )),
+        ResKind::Res(res) => Ok(keys::KeyKind::Kasme(
+            common::confirm_keys::get_confirm_key_res(context.clone(), res).await?,
+        )),
     }
 }
 
+/// Gets the key of a vector issued by the home or a backup network.
+async fn confirm_remote(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    combined_res: ResKind,
+    home_network_id: &str,
+    backup_network_ids: &Vec<String>,
+) -> Result<keys::KeyKind, DauthError> {
+    let (address, _) = clients::directory::lookup_network(&context, home_network_id).await?;
+
+    let state = find_auth_state(&context, user_id, &combined_res).await?;
+
+    let key = match combined_res {
+        ResKind::Res(res) => {
+            confirm_authentication_eps(
+                &context,
+                &res,
+                &state,
+                home_network_id,
+                backup_network_ids,
+                &address,
+            )
+            .await?
+        }
+        ResKind::ResStar(res_star) => {
+            confirm_authentication_5g(
+                &context,
+                &res_star,
+                &state,
+                home_network_id,
+                backup_network_ids,
+                &address,
+            )
+            .await?
+        }
+    };
+
+    // The challenge is only used up once its key is recovered,
+    // so that a failed confirmation can be retried
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    database::auth_states::remove(&mut transaction, &state.xres_star_hash).await?;
+    transaction.commit().await?;
+
+    Ok(key)
+}
+
+/// Finds the stored state of the challenge answered by the res.
+/// A user may have several outstanding challenges, so the state is matched
+/// by recomputing the expected hash from each stored rand.
+async fn find_auth_state(
+    context: &Arc<DauthContext>,
+    user_id: &str,
+    combined_res: &ResKind,
+) -> Result<AuthState, DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    let state = database::auth_states::get_all_by_user(
+        &mut transaction,
+        user_id,
+        utilities::unix_timestamp(),
+    )
+    .await?
+    .into_iter()
+    .find(|state| match combined_res {
+        ResKind::ResStar(res_star) => {
+            auth_vector::types::gen_xres_star_hash(&state.rand, res_star) == state.xres_star_hash
+        }
+        ResKind::Res(res) => auth_vector::types::gen_xres_hash(&state.rand, res) == state.xres_hash,
+    })
+    .ok_or(DauthError::NotFoundError(
+        "Could not find state for auth transaction".to_string(),
+    ))?;
+    transaction.commit().await?;
+
+    Ok(state)
+}
+
 /// Confirm authentication for a 5G request.
 /// Checks local state to determine if the auth vector was given
 /// by the user's home network or a backup network.
@@ -80,6 +165,7 @@ async fn confirm_authentication_5g(
     context: &Arc<DauthContext>,
     res_star: &ResStar,
     state: &AuthState,
+    home_network_id: &str,
     backup_network_ids: &Vec<String>,
     address: &str,
 ) -> Result<keys::KeyKind, DauthError> {
@@ -93,6 +179,7 @@ async fn confirm_authentication_5g(
                 context.clone(),
                 &res_star,
                 &xres_star_hash,
+                home_network_id,
                 &address,
             )
             .await?
@@ -100,48 +187,24 @@ async fn confirm_authentication_5g(
         AuthSource::BackupNetwork => {
             tracing::info!("Auth started from backup network");
 
-            let mut key_shares = Vec::with_capacity(backup_network_ids.len());
             let mut request_set = tokio::task::JoinSet::new();
 
-            let share_threshold: u8 = std::cmp::min(
-                context.backup_context.backup_key_threshold,
-                backup_network_ids.len() as u8,
-            );
-
             for backup_network_id in backup_network_ids {
                 request_set.spawn(kseaf_key_share_from_network_id(
                     context.clone(),
                     xres_star_hash.clone(),
                     res_star.clone(),
+                    home_network_id.to_string(),
                     backup_network_id.to_string(),
                 ));
             }
 
-            while let Some(response_result) = request_set.join_next().await {
-                match response_result {
-                    Ok(key_share) => match key_share {
-                        Ok(share) => {
-                            key_shares.push(share);
-                            if key_shares.len() >= share_threshold.into() {
-                                break;
-                            }
-                        }
-                        Err(error) => tracing::debug!(?error, "Failed to get key share"),
-                    },
-                    Err(error) => tracing::debug!(?error, "Failed to get key share"),
-                }
-            }
-
-            if key_shares.len() < share_threshold.into() {
-                tracing::error!(
-                    "Insufficient valid responses, {} of {} needed to compute kseaf, auth cannot proceed",
-                    key_shares.len(),
-                    share_threshold
-                );
-                return Err(DauthError::ShamirShareError());
-            }
-
-            keys::recover_kseaf_from_shares(&key_shares, share_threshold)?
+            recover_key_from_backups(
+                request_set,
+                backup_network_ids.len(),
+                keys::recover_kseaf_with_commitment,
+            )
+            .await?
         }
     };
 
@@ -155,6 +218,7 @@ async fn confirm_authentication_eps(
     context: &Arc<DauthContext>,
     res: &Res,
     state: &AuthState,
+    home_network_id: &str,
     backup_network_ids: &Vec<String>,
     address: &str,
 ) -> Result<keys::KeyKind, DauthError> {
@@ -168,6 +232,7 @@ async fn confirm_authentication_eps(
                 context.clone(),
                 &res,
                 &xres_hash,
+                home_network_id,
                 &address,
             )
             .await?
@@ -175,60 +240,112 @@ async fn confirm_authentication_eps(
         AuthSource::BackupNetwork => {
             tracing::info!("Auth started from backup network");
 
-            let mut key_shares = Vec::with_capacity(backup_network_ids.len());
             let mut request_set = tokio::task::JoinSet::new();
 
-            let share_threshold: u8 = std::cmp::min(
-                context.backup_context.backup_key_threshold,
-                backup_network_ids.len() as u8,
-            );
-
             for backup_network_id in backup_network_ids {
                 request_set.spawn(kasme_key_share_from_network_id(
                     context.clone(),
                     xres_hash.clone(),
                     res.clone(),
+                    home_network_id.to_string(),
                     backup_network_id.to_string(),
                 ));
             }
 
-            while let Some(response_result) = request_set.join_next().await {
-                match response_result {
-                    Ok(key_share) => match key_share {
-                        Ok(share) => {
-                            key_shares.push(share);
-                            if key_shares.len() >= share_threshold.into() {
-                                break;
-                            }
-                        }
-                        Err(error) => tracing::debug!(?error, "Failed to get key share"),
-                    },
-                    Err(error) => tracing::debug!(?error, "Failed to get key share"),
-                }
+            recover_key_from_backups(
+                request_set,
+                backup_network_ids.len(),
+                keys::recover_kasme_with_commitment,
+            )
+            .await?
+        }
+    };
+
+    Ok(keys::KeyKind::Kasme(key))
+}
+
+/// Collects key shares from backup networks until enough of them
+/// recover the key committed to by the home network.
+/// Shares are grouped by epoch and commitment, so that shares split
+/// before and after a refresh are never combined, and a stale or
+/// conflicting share set cannot prevent recovery from the honest backups.
+/// Each share was verified against its commitment when received, so
+/// any threshold of the shares in a group recover the key.
+/// The shares needed come from the vector's own threshold, signed by
+/// the home network with each share, and not from local config.
+async fn recover_key_from_backups<S, K>(
+    mut request_set: tokio::task::JoinSet<Result<(S, u32, keys::KeyCommitment), DauthError>>,
+    num_requests: usize,
+    recover: fn(&Vec<S>, &keys::KeyCommitment) -> Result<K, DauthError>,
+) -> Result<K, DauthError>
+where
+    S: Send + 'static,
+{
+    let mut share_groups: Vec<(u32, keys::KeyCommitment, Vec<S>)> = Vec::new();
+
+    while let Some(response_result) = request_set.join_next().await {
+        let (share, epoch, commitment) = match response_result {
+            Ok(Ok(response)) => response,
+            Ok(Err(error)) => {
+                tracing::debug!(?error, "Failed to get key share");
+                continue;
+            }
+            Err(error) => {
+                tracing::debug!(?error, "Failed to get key share");
+                continue;
             }
+        };
 
-            if key_shares.len() < share_threshold.into() {
-                tracing::error!(
-                    "Insufficient valid responses, {} of {} needed to compute kasme, auth cannot proceed",
-                    key_shares.len(),
-                    share_threshold
-                );
-                return Err(DauthError::ShamirShareError());
+        let group = match share_groups
+            .iter()
+            .position(|(group_epoch, group_commitment, _)| {
+                *group_epoch == epoch && *group_commitment == commitment
+            }) {
+            Some(index) => &mut share_groups[index],
+            None => {
+                if !share_groups.is_empty() {
+                    tracing::warn!(
+                        ?epoch,
+                        "Backup networks returned shares of conflicting epochs or commitments"
+                    );
+                }
+                share_groups.push((epoch, commitment, Vec::new()));
+                share_groups.last_mut().unwrap()
             }
+        };
+        group.2.push(share);
 
-            keys::recover_kasme_from_shares(&key_shares, share_threshold)?
+        let (_, commitment, key_shares) = group;
+        if key_shares.len() >= commitment.threshold().into() {
+            match recover(key_shares, commitment) {
+                Ok(key) => return Ok(key),
+                Err(error) => tracing::warn!(
+                    ?error,
+                    "Failed to recover key from {} shares, waiting for more",
+                    key_shares.len()
+                ),
+            }
         }
-    };
+    }
 
-    Ok(keys::KeyKind::Kasme(key))
+    tracing::error!(
+        "Insufficient valid responses, {} of {} backups returned a share, auth cannot proceed",
+        share_groups
+            .iter()
+            .map(|(_, _, key_shares)| key_shares.len())
+            .sum::<usize>(),
+        num_requests
+    );
+    Err(DauthError::ShamirShareError())
 }
 
 async fn kseaf_key_share_from_network_id(
     context: Arc<DauthContext>,
     xres_star_hash: XResStarHash,
     res_star: ResStar,
+    home_network_id: String,
     backup_network_id: String,
-) -> Result<keys::KseafShare, DauthError> {
+) -> Result<(keys::KseafShare, u32, keys::KeyCommitment), DauthError> {
     let (backup_address, _) =
         clients::directory::lookup_network(&context, &backup_network_id).await?;
 
@@ -236,6 +353,8 @@ async fn kseaf_key_share_from_network_id(
         context.clone(),
         xres_star_hash.clone(),
         res_star.clone(),
+        home_network_id,
+        backup_network_id,
         backup_address.to_string(),
     )
     .await
@@ -245,10 +364,19 @@ async fn kasme_key_share_from_network_id(
     context: Arc<DauthContext>,
     xres_hash: XResHash,
     res: Res,
+    home_network_id: String,
     backup_network_id: String,
-) -> Result<keys::KasmeShare, DauthError> {
+) -> Result<(keys::KasmeShare, u32, keys::KeyCommitment), DauthError> {
     let (backup_address, _) =
         clients::directory::lookup_network(&context, &backup_network_id).await?;
 
-    clients::backup_network::get_kasme_key_share(context, xres_hash, res, backup_address).await
+    clients::backup_network::get_kasme_key_share(
+        context,
+        xres_hash,
+        res,
+        home_network_id,
+        backup_network_id,
+        backup_address,
+    )
+    .await
 }
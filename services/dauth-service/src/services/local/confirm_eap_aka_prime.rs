@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use auth_vector::types::Res;
+
+use crate::common;
+use crate::data::{context::DauthContext, error::DauthError, keys::EapAkaPrimeSessionKeys};
+use crate::rpc::clients;
+use crate::rpc::dauth::common::UserIdKind;
+
+use super::get_eap_aka_prime_vector::lookup_home_network_id;
+
+/// Attempts to find the EAP-AKA' session keys for the network.
+/// Gets the keys from the database if the vector was generated on this
+/// network, otherwise from the home network, which checks the res.
+#[tracing::instrument(skip(context), name = "local::confirm_eap_aka_prime")]
+pub async fn confirm_eap_aka_prime(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    user_id_kind: UserIdKind,
+    res: Res,
+) -> Result<EapAkaPrimeSessionKeys, DauthError> {
+    tracing::info!("Confirming local EAP-AKA' authentication");
+
+    let home_network_id = lookup_home_network_id(&context, user_id, user_id_kind).await?;
+
+    if home_network_id == context.local_context.id {
+        common::confirm_keys::get_confirm_key_eap_res(context, res).await
+    } else {
+        let (address, _) = clients::directory::lookup_network(&context, &home_network_id).await?;
+
+        clients::home_network::get_confirm_key_eap_aka_prime(
+            context.clone(),
+            &res,
+            &home_network_id,
+            &address,
+        )
+        .await
+    }
+}
@@ -1,36 +1,54 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use auth_vector::{self, types::XResStarHash};
+use auth_vector::{
+    self,
+    types::{Auts, Rand, XResStarHash},
+};
 
 use crate::common;
 use crate::data::{
     context::DauthContext,
     error::DauthError,
     state::{AuthSource, AuthState},
-    vector::AuthVectorRes,
+    suci::Suci,
+    utilities,
+    vector::{AuthVectorRes, Plmn},
 };
+use crate::database;
 use crate::rpc::clients;
+use crate::rpc::dauth::common::{AkaResyncInfo, UserIdKind};
 
 /// Attempts to get a vector in the following order of checks:
 /// 1. Generate the vector locally if this is the home network
 /// 2. Lookup the home network of the user and request a vector
 /// 3. Request a vector from all backup networks
 /// Stores auth state for 2 and 3.
+/// Resync info from the UE is used to resynchronize the sqn for 1 and 2.
+/// The xres* and keys of every vector are bound to this network's PLMN,
+/// so that they match what the UE derives from the serving network name.
 #[tracing::instrument(skip(context), name = "local::get_auth_vector")]
 pub async fn get_auth_vector(
     context: Arc<DauthContext>,
     user_id: &str,
     network_id: &str,
-    is_resync_attempt: bool,
+    resync_info: Option<AkaResyncInfo>,
 ) -> Result<AuthVectorRes, DauthError> {
     tracing::info!("Getting auth vector for local authentication");
 
     let (home_network_id, backup_network_ids) =
         clients::directory::lookup_user(&context, user_id).await?;
+    let serving_plmn = context.local_context.plmn();
 
     if home_network_id == context.local_context.id {
-        match common::auth_vectors::generate_local_vector(context.clone(), user_id).await {
+        match common::auth_vectors::generate_local_vector(
+            context.clone(),
+            user_id,
+            &serving_plmn,
+            resync_info.as_ref(),
+        )
+        .await
+        {
             Ok(auth_vector_res) => {
                 tracing::debug!(?user_id, "Successfully generated an auth vector locally");
                 return Ok(auth_vector_res);
@@ -42,7 +60,16 @@ pub async fn get_auth_vector(
 
         tracing::error!(?user_id, "Failed to generate auth vector for local user");
     } else {
-        match attempt_home_network_request(context.clone(), user_id, &home_network_id).await {
+        match attempt_home_network_request(
+            context.clone(),
+            user_id,
+            UserIdKind::Supi,
+            &home_network_id,
+            resync_info.clone(),
+            &serving_plmn,
+        )
+        .await
+        {
             Ok(auth_vector_res) => {
                 tracing::debug!(
                     ?user_id,
@@ -59,7 +86,8 @@ pub async fn get_auth_vector(
             context.clone(),
             user_id,
             &backup_network_ids,
-            is_resync_attempt,
+            resync_info.as_ref(),
+            &serving_plmn,
         )
         .await
         {
@@ -86,13 +114,58 @@ pub async fn get_auth_vector(
     ))
 }
 
+/// Gets a vector for a user identified by SUCI.
+/// The SUCI is routed to its home network, which is the only network
+/// able to de-conceal it. Backup networks only know users by SUPI,
+/// so they are not used.
+/// Stores auth state under the SUCI if the home network is remote.
+#[tracing::instrument(skip(context), name = "local::get_auth_vector_by_suci")]
+pub async fn get_auth_vector_by_suci(
+    context: Arc<DauthContext>,
+    suci: &str,
+    resync_info: Option<AkaResyncInfo>,
+) -> Result<AuthVectorRes, DauthError> {
+    tracing::info!("Getting auth vector by SUCI for local authentication");
+
+    let parsed_suci = Suci::try_from(suci)?;
+    let home_network_id = parsed_suci.home_network_id(&context.local_context)?;
+
+    if home_network_id == context.local_context.id {
+        let supi = parsed_suci.deconceal(&context.local_context.suci_keys)?;
+
+        let mut auth_vector_res = common::auth_vectors::generate_local_vector(
+            context.clone(),
+            &supi,
+            &context.local_context.plmn(),
+            resync_info.as_ref(),
+        )
+        .await?;
+        auth_vector_res.user_id = suci.to_string();
+
+        Ok(auth_vector_res)
+    } else {
+        attempt_home_network_request(
+            context.clone(),
+            suci,
+            UserIdKind::Suci,
+            &home_network_id,
+            resync_info,
+            &context.local_context.plmn(),
+        )
+        .await
+    }
+}
+
 /// Attempts to ask the user's home network for a vector.
 /// If successful, returns resulting vector.
 /// Otherwise, returns None.
 async fn attempt_home_network_request(
     context: Arc<DauthContext>,
     user_id: &str,
+    user_id_kind: UserIdKind,
     home_network_id: &str,
+    resync_info: Option<AkaResyncInfo>,
+    serving_plmn: &Plmn,
 ) -> Result<AuthVectorRes, DauthError> {
     tracing::info!(
         ?user_id,
@@ -105,20 +178,17 @@ async fn attempt_home_network_request(
     match clients::home_network::get_auth_vector(
         context.clone(),
         user_id,
+        user_id_kind,
+        home_network_id,
         &home_address,
+        resync_info,
+        serving_plmn,
         Duration::from_millis(100),
     )
     .await
     {
         Ok(vector) => {
-            context.backup_context.auth_states.lock().await.insert(
-                user_id.to_string(),
-                AuthState {
-                    rand: vector.rand.clone(),
-                    source: AuthSource::HomeNetwork,
-                    xres_star_hash: vector.xres_star_hash.clone(),
-                },
-            );
+            store_auth_state(&context, user_id, &vector, AuthSource::HomeNetwork).await?;
             Ok(vector)
         }
         Err(e) => Err(e),
@@ -132,7 +202,8 @@ async fn attempt_backup_network_request(
     context: Arc<DauthContext>,
     user_id: &str,
     backup_network_ids: &Vec<String>,
-    is_resync_attempt: bool,
+    resync_info: Option<&AkaResyncInfo>,
+    serving_plmn: &Plmn,
 ) -> Result<AuthVectorRes, DauthError> {
     tracing::info!(
         ?user_id,
@@ -144,15 +215,24 @@ async fn attempt_backup_network_request(
     // previously sent a tuple.
     let mut resync_xres_star_hash: Option<XResStarHash> = None;
 
-    // Only attempt to look up the prior used vector if this is a resync
-    if is_resync_attempt {
-        resync_xres_star_hash = context
-            .backup_context
-            .auth_states
-            .lock()
-            .await
-            .get(user_id)
-            .and_then(|state| Some(state.xres_star_hash));
+    // Only attempt to look up the prior used vector if this is a resync.
+    // Backups cannot verify the AUTS, but its rand identifies the vector
+    // the UE rejected.
+    if let Some(resync_info) = resync_info {
+        Auts::try_from(&resync_info.auts[..])?;
+        let resync_rand: Rand = resync_info.rand[..].try_into()?;
+
+        let mut transaction = context.local_context.database_pool.begin().await?;
+        resync_xres_star_hash = database::auth_states::get_all_by_user(
+            &mut transaction,
+            user_id,
+            utilities::unix_timestamp(),
+        )
+        .await?
+        .into_iter()
+        .find(|state| state.rand == resync_rand)
+        .map(|state| state.xres_star_hash);
+        transaction.commit().await?;
     }
 
     // Attempt to lookup an auth vector from the backup networks in parallel.
@@ -164,6 +244,7 @@ async fn attempt_backup_network_request(
             user_id.to_string(),
             backup_network_id.to_string(),
             resync_xres_star_hash,
+            serving_plmn.clone(),
         ));
     }
 
@@ -171,14 +252,13 @@ async fn attempt_backup_network_request(
         match response_result {
             Ok(response) => match response {
                 Ok(auth_vector_result) => {
-                    context.backup_context.auth_states.lock().await.insert(
-                        user_id.to_string(),
-                        AuthState {
-                            rand: auth_vector_result.rand.clone(),
-                            source: AuthSource::BackupNetwork,
-                            xres_star_hash: auth_vector_result.xres_star_hash.clone(),
-                        },
-                    );
+                    store_auth_state(
+                        &context,
+                        user_id,
+                        &auth_vector_result,
+                        AuthSource::BackupNetwork,
+                    )
+                    .await?;
                     return Ok(auth_vector_result);
                 }
                 Err(e) => tracing::debug!("Failed to get auth from backup: {}", e),
@@ -192,20 +272,62 @@ async fn attempt_backup_network_request(
     ))
 }
 
+/// Stores the state of an issued challenge until it is confirmed or expires.
+/// Challenges are keyed by xres* hash, so several may be outstanding per user.
+async fn store_auth_state(
+    context: &Arc<DauthContext>,
+    user_id: &str,
+    vector: &AuthVectorRes,
+    source: AuthSource,
+) -> Result<(), DauthError> {
+    let now = utilities::unix_timestamp();
+    let expiration = now + context.backup_context.auth_state_lifetime.as_secs() as i64;
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    database::auth_states::remove_expired(&mut transaction, now).await?;
+    database::auth_states::add(
+        &mut transaction,
+        user_id,
+        &AuthState {
+            rand: vector.rand,
+            source,
+            xres_star_hash: vector.xres_star_hash,
+            xres_hash: vector.xres_hash,
+        },
+        expiration,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Requests a vector from a backup network.
+/// Backup networks below the minimum reputation are not used.
 async fn get_auth_vector_from_network_id(
     context: Arc<DauthContext>,
     user_id: String,
     backup_network_id: String,
     resync_xres_star_hash: Option<XResStarHash>,
+    serving_plmn: Plmn,
 ) -> Result<AuthVectorRes, DauthError> {
+    if !common::misbehaviour::is_reputable(&context, &backup_network_id).await? {
+        return Err(DauthError::PolicyError(format!(
+            "Backup network {} is below minimum reputation",
+            backup_network_id
+        )));
+    }
+
     let (backup_address, _) =
         clients::directory::lookup_network(&context, &backup_network_id).await?;
 
     clients::backup_network::get_auth_vector(
         context.clone(),
         &user_id,
+        &backup_network_id,
         &backup_address,
         resync_xres_star_hash,
+        &serving_plmn,
     )
     .await
 }
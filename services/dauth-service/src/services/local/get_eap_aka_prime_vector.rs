@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common;
+use crate::data::{
+    context::DauthContext, error::DauthError, suci::Suci, vector::EapAkaPrimeVectorRes,
+};
+use crate::rpc::clients;
+use crate::rpc::dauth::common::{AkaResyncInfo, UserIdKind};
+
+/// Gets an EAP-AKA' vector bound to the access network name.
+/// Generates the vector locally if this is the home network,
+/// otherwise requests it from the home network.
+/// Backup networks only hold 5G and EPS vectors, so they are not used.
+#[tracing::instrument(skip(context), name = "local::get_eap_aka_prime_vector")]
+pub async fn get_eap_aka_prime_vector(
+    context: Arc<DauthContext>,
+    user_id: &str,
+    user_id_kind: UserIdKind,
+    network_name: &str,
+    resync_info: Option<AkaResyncInfo>,
+) -> Result<EapAkaPrimeVectorRes, DauthError> {
+    tracing::info!("Getting EAP-AKA' vector for local authentication");
+
+    let home_network_id = lookup_home_network_id(&context, user_id, user_id_kind).await?;
+
+    if home_network_id == context.local_context.id {
+        let supi = match user_id_kind {
+            UserIdKind::Suci => {
+                Suci::try_from(user_id)?.deconceal(&context.local_context.suci_keys)?
+            }
+            _ => user_id.to_string(),
+        };
+
+        let mut vector_res = common::auth_vectors::generate_local_eap_aka_prime_vector(
+            context.clone(),
+            &supi,
+            network_name,
+            &context.local_context.plmn(),
+            resync_info.as_ref(),
+        )
+        .await?;
+        vector_res.user_id = user_id.to_string();
+
+        Ok(vector_res)
+    } else {
+        let (home_address, _) =
+            clients::directory::lookup_network(&context, &home_network_id).await?;
+
+        clients::home_network::get_eap_aka_prime_vector(
+            context.clone(),
+            user_id,
+            user_id_kind,
+            network_name,
+            &home_network_id,
+            &home_address,
+            resync_info,
+            Duration::from_millis(100),
+        )
+        .await
+    }
+}
+
+/// Finds the home network of a user.
+/// A SUCI is routed by its PLMN, otherwise the directory is used.
+pub(super) async fn lookup_home_network_id(
+    context: &Arc<DauthContext>,
+    user_id: &str,
+    user_id_kind: UserIdKind,
+) -> Result<String, DauthError> {
+    match user_id_kind {
+        UserIdKind::Suci => Suci::try_from(user_id)?.home_network_id(&context.local_context),
+        _ => Ok(clients::directory::lookup_user(context, user_id).await?.0),
+    }
+}
@@ -1,6 +1,10 @@
 mod confirm_auth;
+mod confirm_eap_aka_prime;
 mod get_auth_vector;
+mod get_eap_aka_prime_vector;
 
 /* Public access functions */
-pub use confirm_auth::confirm_auth;
-pub use get_auth_vector::get_auth_vector;
+pub use confirm_auth::{confirm_auth, confirm_auth_by_suci};
+pub use confirm_eap_aka_prime::confirm_eap_aka_prime;
+pub use get_auth_vector::{get_auth_vector, get_auth_vector_by_suci};
+pub use get_eap_aka_prime_vector::get_eap_aka_prime_vector;
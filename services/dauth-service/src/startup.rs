@@ -9,16 +9,26 @@ use std::{
 use ed25519_dalek::Keypair;
 use rand::rngs::OsRng;
 use serde_yaml;
+use tonic::transport::{Certificate, Identity};
 
 use crate::database;
 use crate::{
     data::{
-        config::DauthConfig,
+        config::{
+            BackupPlacementConfig, BackupQuotaConfig, DauthConfig, PlmnConfig, SuciKeyConfig,
+            SuciRouteConfig, TlsConfig,
+        },
         context::{
             BackupContext, DauthContext, LocalContext, MetricsContext, RpcContext, TasksContext,
+            TlsContext,
         },
         error::DauthError,
         keys,
+        placement::PlacementPolicy,
+        quota::{BackupQuota, BackupQuotas},
+        suci::{self, ProtectionScheme, SuciKey},
+        utilities,
+        vector::Plmn,
     },
     management,
 };
@@ -42,24 +52,50 @@ pub fn build_config_from_file(yaml_path: PathBuf) -> Result<DauthConfig, DauthEr
 pub async fn build_context(config: DauthConfig) -> Result<Arc<DauthContext>, DauthError> {
     let keys = generate_keys(&config.ed25519_keyfile_path);
     let pool = database::general::database_init(&config.database_path).await?;
+    let suci_keys = build_suci_keys(config.suci_keys.unwrap_or_default())?;
+    let suci_routes = build_suci_routes(config.suci_routes.unwrap_or_default());
+    let serving_plmns = build_serving_plmns(
+        config.serving_plmns.unwrap_or_default(),
+        &config.mcc,
+        &config.mnc,
+    );
+    let min_backup_reputation = config.min_backup_reputation.unwrap_or(0.0);
+    let placement_policy = build_placement_policy(config.backup_placement, min_backup_reputation);
+    let backup_quotas = build_backup_quotas(config.backup_quotas.unwrap_or_default())?;
+    let tls = config.tls.map(build_tls).transpose()?;
+    let backup_key_threshold = build_key_threshold(config.backup_key_threshold)?;
 
     let context = Arc::new(DauthContext {
         local_context: LocalContext {
             id: config.id,
             database_pool: pool,
-            signing_keys: keys,
+            signing_keys: std::sync::RwLock::new(keys),
+            keyfile_path: config.ed25519_keyfile_path,
             num_sqn_slices: config.num_sqn_slices,
             max_backup_vectors: config.max_backup_vectors,
             mcc: config.mcc,
             mnc: config.mnc,
+            op: config.op,
+            suci_keys,
+            suci_routes,
+            region: config.region.unwrap_or_default(),
+            operator_group: config.operator_group.unwrap_or_default(),
         },
         backup_context: BackupContext {
-            backup_key_threshold: config
-                .backup_key_threshold
-                .unwrap_or(keys::TEMPORARY_CONSTANT_THRESHOLD as i64)
-                as u8,
-            auth_states: tokio::sync::Mutex::new(HashMap::new()),
+            backup_key_threshold,
+            min_backup_reputation,
+            reputation_half_life: Duration::from_secs_f64(
+                config.reputation_half_life.unwrap_or(604800.0),
+            ),
+            serving_plmns,
+            auth_state_lifetime: Duration::from_secs_f64(
+                config.auth_state_lifetime.unwrap_or(300.0),
+            ),
+            backup_capacity: config.backup_capacity.unwrap_or(0),
+            placement_policy,
+            backup_quotas,
             directory_network_cache: tokio::sync::Mutex::new(HashMap::new()),
+            directory_key_refreshes: tokio::sync::Mutex::new(HashMap::new()),
             directory_user_cache: tokio::sync::Mutex::new(HashMap::new()),
         },
         rpc_context: RpcContext {
@@ -73,6 +109,12 @@ pub async fn build_context(config: DauthConfig) -> Result<Arc<DauthContext>, Dau
             directory_client: tokio::sync::Mutex::new(None),
             known_offline_networks: tokio::sync::Mutex::new(HashMap::new()),
             failed_connection_retry_cooldown: Duration::from_secs(30),
+            replay_window: Duration::from_secs_f64(config.replay_window.unwrap_or(30.0)),
+            max_replay_cache_size: config.max_replay_cache_size.unwrap_or(100000),
+            max_replay_cache_size_per_signer: config
+                .max_replay_cache_size_per_signer
+                .unwrap_or(10000),
+            tls,
         },
         tasks_context: TasksContext {
             start_time: Instant::now(),
@@ -82,6 +124,10 @@ pub async fn build_context(config: DauthConfig) -> Result<Arc<DauthContext>, Dau
             replace_key_share_delay: Duration::from_secs_f64(10.0),
             metrics_report_interval: Duration::from_secs_f64(10.0),
             metrics_last_report: tokio::sync::Mutex::new(Instant::now()),
+            key_share_refresh_interval: config
+                .key_share_refresh_interval
+                .map(Duration::from_secs_f64),
+            key_shares_last_refresh: tokio::sync::Mutex::new(Instant::now()),
         },
         metrics_context: MetricsContext {
             max_recorded_metrics: config.max_recorded_metrics.unwrap_or(100) as usize,
@@ -97,6 +143,153 @@ pub async fn build_context(config: DauthConfig) -> Result<Arc<DauthContext>, Dau
     Ok(context)
 }
 
+fn build_suci_keys(configs: Vec<SuciKeyConfig>) -> Result<HashMap<u8, SuciKey>, DauthError> {
+    let mut suci_keys = HashMap::new();
+
+    for config in configs {
+        let key = SuciKey::new(
+            ProtectionScheme::from_profile(&config.profile)?,
+            &utilities::convert_hex_string_to_byte_vec_with_length(
+                &config.private_key,
+                suci::PRIVATE_KEY_LENGTH,
+            )?,
+        )?;
+
+        tracing::info!(
+            key_id = config.key_id,
+            public_key = %hex::encode(key.public_key()?),
+            "Loaded SUCI key"
+        );
+
+        if suci_keys.insert(config.key_id, key).is_some() {
+            return Err(DauthError::ConfigError(format!(
+                "Duplicate SUCI key id: {}",
+                config.key_id
+            )));
+        }
+    }
+    Ok(suci_keys)
+}
+
+fn build_suci_routes(configs: Vec<SuciRouteConfig>) -> HashMap<String, String> {
+    configs
+        .into_iter()
+        .map(|config| {
+            (
+                suci::route_key(
+                    &config.mcc,
+                    &config.mnc,
+                    config.routing_indicator.as_deref(),
+                ),
+                config.network_id,
+            )
+        })
+        .collect()
+}
+
+/// Backup vectors are bound to this network's own PLMN
+/// if no serving PLMNs are configured.
+fn build_serving_plmns(configs: Vec<PlmnConfig>, mcc: &str, mnc: &str) -> Vec<Plmn> {
+    if configs.is_empty() {
+        return vec![Plmn {
+            mcc: mcc.to_string(),
+            mnc: mnc.to_string(),
+        }];
+    }
+
+    configs
+        .into_iter()
+        .map(|config| Plmn {
+            mcc: config.mcc,
+            mnc: config.mnc,
+        })
+        .collect()
+}
+
+/// Automatically selected backups must also meet the
+/// minimum backup reputation.
+fn build_placement_policy(
+    config: Option<BackupPlacementConfig>,
+    min_reputation: f64,
+) -> PlacementPolicy {
+    match config {
+        Some(config) => PlacementPolicy {
+            distinct_operator_groups: config.distinct_operator_groups.unwrap_or(false),
+            distinct_regions: config.distinct_regions.unwrap_or(false),
+            allowed_regions: config.allowed_regions.unwrap_or_default(),
+            min_reputation,
+        },
+        None => PlacementPolicy {
+            min_reputation,
+            ..PlacementPolicy::default()
+        },
+    }
+}
+
+/// Home networks without their own quota share the default
+/// quota, which has no limits if not configured.
+fn build_backup_quotas(configs: Vec<BackupQuotaConfig>) -> Result<BackupQuotas, DauthError> {
+    let mut default = None;
+    let mut home_networks = HashMap::new();
+
+    for config in configs {
+        let quota = BackupQuota {
+            max_users: config.max_users,
+            max_auth_vectors: config.max_auth_vectors,
+            max_key_shares: config.max_key_shares,
+            max_flood_vectors: config.max_flood_vectors,
+        };
+
+        let previous = match &config.home_network_id {
+            Some(home_network_id) => home_networks.insert(home_network_id.clone(), quota),
+            None => default.replace(quota),
+        };
+        if previous.is_some() {
+            return Err(DauthError::ConfigError(format!(
+                "Duplicate backup quota for home network: {}",
+                config.home_network_id.as_deref().unwrap_or("default")
+            )));
+        }
+    }
+
+    Ok(BackupQuotas {
+        default: default.unwrap_or_default(),
+        home_networks,
+    })
+}
+
+/// The threshold must fit the number of shares a key can be split into.
+fn build_key_threshold(key_threshold: Option<i64>) -> Result<u8, DauthError> {
+    match key_threshold {
+        Some(key_threshold) => match u8::try_from(key_threshold) {
+            Ok(key_threshold) if key_threshold > 0 && key_threshold <= keys::MAX_SHARE_COUNT => {
+                Ok(key_threshold)
+            }
+            _ => Err(DauthError::ConfigError(format!(
+                "Invalid backup key threshold: {}",
+                key_threshold
+            ))),
+        },
+        None => Ok(keys::DEFAULT_KEY_THRESHOLD),
+    }
+}
+
+/// Certificates are only loaded at startup, so renewed
+/// certificates take effect on restart.
+fn build_tls(config: TlsConfig) -> Result<TlsContext, DauthError> {
+    let read = |path: &str| {
+        fs::read(path).map_err(|e| {
+            DauthError::ConfigError(format!("Failed to read TLS file '{}': {}", path, e))
+        })
+    };
+
+    Ok(TlsContext {
+        identity: Identity::from_pem(read(&config.cert_path)?, read(&config.key_path)?),
+        ca_certificate: Certificate::from_pem(read(&config.ca_cert_path)?),
+        directory_domain: config.directory_domain,
+    })
+}
+
 fn generate_keys(keyfile_path: &String) -> Keypair {
     match fs::read(keyfile_path) {
         Ok(keypair_bytes) => match Keypair::from_bytes(&keypair_bytes) {
@@ -1,7 +1,10 @@
 mod metrics;
+mod refresh_key_shares;
 mod register;
 mod replace_key_shares;
 mod report_auth_vectors;
 mod report_key_shares;
 pub mod task_manager;
 mod update_users;
+mod withdraw_backups;
+mod withdraw_shares;
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use auth_vector::data::AuthVectorData;
+
+use crate::common;
+use crate::data::context::DauthContext;
+use crate::data::error::DauthError;
+use crate::data::keys;
+use crate::database;
+use crate::rpc::clients;
+
+/// Runs the refresh key shares task.
+/// Queues all users with delegated vectors once the refresh interval
+/// has passed, then refreshes the key shares of each queued user.
+/// The refreshed shares are sent by the replace key shares task.
+pub async fn run_task(context: Arc<DauthContext>) -> Result<(), DauthError> {
+    queue_periodic_refresh(&context).await?;
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let user_ids = database::tasks::refresh_key_shares::get_user_ids(&mut transaction).await?;
+    transaction.commit().await?;
+
+    if user_ids.is_empty() {
+        tracing::debug!("Nothing to do for refresh key shares task");
+    } else {
+        tracing::info!("Found {} key share refresh(es) pending", user_ids.len());
+
+        for user_id in user_ids {
+            if let Err(e) = refresh_key_shares(context.clone(), &user_id).await {
+                tracing::warn!(?e, ?user_id, "Failed to refresh key shares");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn queue_periodic_refresh(context: &Arc<DauthContext>) -> Result<(), DauthError> {
+    let refresh_interval = match context.tasks_context.key_share_refresh_interval {
+        Some(refresh_interval) => refresh_interval,
+        None => return Ok(()),
+    };
+
+    let mut last_refresh = context.tasks_context.key_shares_last_refresh.lock().await;
+    if last_refresh.elapsed() > refresh_interval {
+        let mut transaction = context.local_context.database_pool.begin().await?;
+        for user_id in database::delegated_vectors::get_user_ids(&mut transaction).await? {
+            database::tasks::refresh_key_shares::add(&mut transaction, &user_id).await?;
+        }
+        transaction.commit().await?;
+
+        *last_refresh = Instant::now();
+    }
+    Ok(())
+}
+
+/// Splits the keys of each delegated vector of the user again, with
+/// new random polynomials, between the backup networks that hold its
+/// shares. The keys are derived again from the user's K, and do not
+/// change, but old shares no longer combine with new shares, so shares
+/// must be collected within one refresh to be of use.
+/// Each split is of a new epoch, which is kept with the vector in the
+/// same transaction that queues its replacements, and signed with each
+/// share. Backup networks only replace a share with one of a newer
+/// epoch, and serving networks never combine shares of two epochs, so
+/// backups holding shares of either epoch during a refresh are safe.
+/// Holders that are no longer backups of the user get no new share.
+/// A vector with fewer remaining holders than the key threshold is not
+/// refreshed, since that would lower its threshold.
+/// All replacements for the user are queued in one transaction, and
+/// each backup network swaps its share in one transaction on receipt.
+async fn refresh_key_shares(context: Arc<DauthContext>, user_id: &str) -> Result<(), DauthError> {
+    let (_, backup_networks) = clients::directory::lookup_user(&context, user_id).await?;
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+
+    let key_threshold =
+        database::user_infos::get_key_threshold(&mut transaction, &user_id.to_string()).await?;
+    let delegated_vectors =
+        database::delegated_vectors::get_all_by_user(&mut transaction, user_id).await?;
+    for delegated in &delegated_vectors {
+        let holders: Vec<String> =
+            database::key_share_state::get_holders(&mut transaction, &delegated.xres_star_hash)
+                .await?
+                .into_iter()
+                .filter(|holder| backup_networks.contains(holder))
+                .collect();
+
+        if holders.is_empty() {
+            continue;
+        }
+
+        let (num_shares, threshold) =
+            match keys::share_count(holders.len()).and_then(|num_shares| {
+                context
+                    .backup_context
+                    .key_threshold(key_threshold, num_shares)
+                    .map(|threshold| (num_shares, threshold))
+            }) {
+                Ok(counts) => counts,
+                Err(e) => {
+                    tracing::warn!(?e, ?user_id, "Not refreshing key shares of vector");
+                    continue;
+                }
+            };
+
+        let vector =
+            common::auth_vectors::rebuild_delegated_vector(&mut transaction, user_id, delegated)
+                .await?;
+        let epoch = delegated.epoch + 1;
+        let key_shares = split_keys(&vector, epoch, num_shares, threshold)?;
+        for (backup_network_id, key_share) in std::iter::zip(&holders, &key_shares) {
+            database::tasks::replace_key_shares::add_refresh(
+                &mut transaction,
+                backup_network_id,
+                key_share,
+            )
+            .await?;
+        }
+        database::delegated_vectors::set_epoch(&mut transaction, &delegated.xres_star_hash, epoch)
+            .await?;
+    }
+
+    database::tasks::refresh_key_shares::remove(&mut transaction, user_id).await?;
+    transaction.commit().await?;
+
+    tracing::info!(
+        ?user_id,
+        "Refreshed key shares of {} vector(s)",
+        delegated_vectors.len()
+    );
+    Ok(())
+}
+
+/// Splits the keys of a vector into a new set of combined key shares
+/// of the given epoch.
+pub fn split_keys(
+    vector: &AuthVectorData,
+    epoch: u32,
+    num_shares: u8,
+    threshold: u8,
+) -> Result<Vec<keys::CombinedKeyShare>, DauthError> {
+    let mut rng = rand_0_8::thread_rng();
+
+    let (kseaf_shares, kseaf_commitment) =
+        keys::create_shares_from_kseaf(&vector.kseaf, num_shares, threshold, &mut rng)?;
+    let (kasme_shares, kasme_commitment) =
+        keys::create_shares_from_kasme(&vector.kasme, num_shares, threshold, &mut rng)?;
+
+    Ok(std::iter::zip(kseaf_shares, kasme_shares)
+        .map(|(kseaf_share, kasme_share)| keys::CombinedKeyShare {
+            xres_star_hash: vector.xres_star_hash,
+            xres_hash: vector.xres_hash,
+            kseaf_share,
+            kasme_share,
+            kseaf_commitment: kseaf_commitment.clone(),
+            kasme_commitment: kasme_commitment.clone(),
+            epoch,
+        })
+        .collect())
+}
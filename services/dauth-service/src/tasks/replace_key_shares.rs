@@ -30,10 +30,9 @@ pub async fn run_task(context: Arc<DauthContext>) -> Result<(), DauthError> {
                 Ok(task_res) => match task_res {
                     Ok(replace) => {
                         let mut transaction = context.local_context.database_pool.begin().await?;
-                        database::tasks::replace_key_shares::remove(
+                        database::tasks::replace_key_shares::remove_sent(
                             &mut transaction,
-                            &replace.backup_network_id,
-                            &replace.xres_star_hash,
+                            &replace,
                         )
                         .await?;
                         transaction.commit().await?;
@@ -72,7 +72,8 @@ async fn report_to_network(
 
     let (home_net_address, _) = clients::directory::lookup_network(&context, &network_id).await?;
 
-    let mut client = clients::home_network::get_client(context.clone(), &home_net_address).await?;
+    let mut client =
+        clients::home_network::get_client(context.clone(), &network_id, &home_net_address).await?;
 
     // Run reports to a single network serially for now. This could be
     // parallelized in the future, or we could build an aggregate API.
@@ -101,16 +102,7 @@ async fn report_to_network(
 
             let mut transaction = context.local_context.database_pool.begin().await?;
 
-            database::auth_vectors::add(
-                &mut transaction,
-                &av_result.user_id,
-                av_result.seqnum,
-                &av_result.xres_star_hash,
-                &av_result.xres_hash,
-                &av_result.autn,
-                &av_result.rand.as_array(),
-            )
-            .await?;
+            database::auth_vectors::add(&mut transaction, &av_result).await?;
 
             transaction.commit().await?;
         }
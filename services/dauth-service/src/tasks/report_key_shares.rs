@@ -73,7 +73,8 @@ async fn report_to_network(
     tracing::info!(?network_id, "Reporting key share(s) used to home network");
     let (home_net_address, _) = clients::directory::lookup_network(&context, &network_id).await?;
 
-    let mut client = clients::home_network::get_client(context.clone(), &home_net_address).await?;
+    let mut client =
+        clients::home_network::get_client(context.clone(), &network_id, &home_net_address).await?;
 
     // Run reports to a single network serially for now. This could be
     // parallelized in the future, or we could build an aggregate API.
@@ -39,12 +39,21 @@ async fn run(context: Arc<DauthContext>) -> Result<(), DauthError> {
             tasks.push(tokio::spawn(tasks::replace_key_shares::run_task(
                 context.clone(),
             )));
+            tasks.push(tokio::spawn(tasks::refresh_key_shares::run_task(
+                context.clone(),
+            )));
             tasks.push(tokio::spawn(tasks::report_auth_vectors::run_task(
                 context.clone(),
             )));
             tasks.push(tokio::spawn(tasks::report_key_shares::run_task(
                 context.clone(),
             )));
+            tasks.push(tokio::spawn(tasks::withdraw_backups::run_task(
+                context.clone(),
+            )));
+            tasks.push(tokio::spawn(tasks::withdraw_shares::run_task(
+                context.clone(),
+            )));
             tasks.push(tokio::spawn(tasks::metrics::run_task(context.clone())));
 
             for task in tasks {
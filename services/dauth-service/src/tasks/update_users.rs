@@ -4,15 +4,18 @@ use std::io::prelude::*;
 use std::sync::Arc;
 
 use auth_vector::data::AuthVectorData;
-use auth_vector::types::Rand;
+use auth_vector::types::{Rand, XResStarHash};
+use sqlx::{Sqlite, Transaction};
 
 use crate::common;
 use crate::data::context::DauthContext;
 use crate::data::error::DauthError;
 use crate::data::keys;
-use crate::data::vector::AuthVectorRes;
+use crate::data::placement;
+use crate::data::vector::{AuthVectorRes, DelegatedVector, Plmn};
 use crate::database;
 use crate::rpc::clients::{backup_network, directory};
+use crate::tasks::refresh_key_shares;
 
 /// Runs the update user task.
 /// Iterates through user in the user update table.
@@ -57,15 +60,28 @@ struct CreatedVector {
     pub backup_network_id: String,
     pub seqnum: i64,
     pub vector: AuthVectorData,
+    pub plmn: Plmn,
 }
 
-/// Adds the user and its backup networks to the directory service.
+/// Selects any automatic backups of the user, then adds the user
+/// and its backup networks to the directory service.
 /// Then, enrolls each of the backup networks.
+/// Backup networks below the minimum reputation are not enrolled.
+/// Keeps the keys of the new vectors so their shares can be refreshed.
+/// If the backup networks changed, reconciles them with the previous set.
+/// Removed networks are withdrawn along with the vectors they held, and
+/// the keys of all other vectors are split again between the new set.
+/// Withdrawals are queued in the same transaction as the local removal,
+/// and sent by the withdraw tasks until they succeed.
 async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Result<(), DauthError> {
     let user_id = &user_id;
 
-    // T1: Get all backups for the user
+    select_backups(&context, user_id).await?;
+
+    // T1: Get all backups for the user, the previous backups and the key threshold
     let user_data: Vec<(String, i64)>;
+    let previous_backups: Vec<String>;
+    let key_threshold: Option<u8>;
     {
         let mut transaction = context.local_context.database_pool.begin().await.unwrap();
         user_data = database::tasks::update_users::get_user_data(&mut transaction, &user_id)
@@ -73,12 +89,25 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
             .into_iter()
             .filter(|v| v.0 != context.local_context.id)
             .collect();
+        previous_backups =
+            database::backup_networks::get_all_by_user(&mut transaction, user_id).await?;
+        key_threshold = database::user_infos::get_key_threshold(&mut transaction, user_id).await?;
         transaction.commit().await.or_else(|e| {
             tracing::error!(?e, "Failed to commit get user data");
             Err(e)
         })?; // T1 end
     }
 
+    let mut reputable_user_data = Vec::with_capacity(user_data.len());
+    for (backup_network_id, sqn_slice) in user_data {
+        if common::misbehaviour::is_reputable(&context, &backup_network_id).await? {
+            reputable_user_data.push((backup_network_id, sqn_slice));
+        } else {
+            tracing::warn!(?backup_network_id, ?user_id, "Not enrolling backup network");
+        }
+    }
+    let user_data = reputable_user_data;
+
     let mut backup_network_ids = Vec::new();
     let mut vectors_map = HashMap::new();
     let mut shares_map = HashMap::new();
@@ -88,6 +117,24 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
         vectors_map.insert(backup_network_id.clone(), Vec::new());
         shares_map.insert(backup_network_id.clone(), Vec::new());
     }
+    let num_shares = keys::share_count(backup_network_ids.len())?;
+    let threshold = if backup_network_ids.is_empty() {
+        0
+    } else {
+        context
+            .backup_context
+            .key_threshold(key_threshold, num_shares)?
+    };
+
+    let removed_backups: Vec<String> = previous_backups
+        .iter()
+        .filter(|backup_network_id| !backup_network_ids.contains(backup_network_id))
+        .cloned()
+        .collect();
+    let backups_changed = !removed_backups.is_empty()
+        || backup_network_ids
+            .iter()
+            .any(|backup_network_id| !previous_backups.contains(backup_network_id));
 
     directory::upsert_user(context.clone(), &user_id, backup_network_ids.clone()).await?;
 
@@ -123,15 +170,17 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
             );
 
             // for each vector (up to max), build a new vector and set of key shares
-            for _ in 0..std::cmp::max(
-                0,
-                context.local_context.max_backup_vectors - num_existing_vectors,
-            ) {
+            // vectors are spread across the serving PLMNs their keys are bound to
+            let serving_plmns = &context.backup_context.serving_plmns;
+            for index in num_existing_vectors..context.local_context.max_backup_vectors {
+                let plmn = &serving_plmns[index as usize % serving_plmns.len()];
+
                 let (vector, seqnum) = common::auth_vectors::build_auth_vector(
                     context.clone(),
                     &mut transaction,
                     user_id,
                     *sqn_slice,
+                    plmn,
                 )
                 .await?;
 
@@ -139,6 +188,7 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
                     backup_network_id: backup_network_id.clone(),
                     seqnum: seqnum,
                     vector: vector,
+                    plmn: plmn.clone(),
                 });
             }
         }
@@ -146,7 +196,27 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
         transaction.commit().await?; // T2 end
     }
 
+    let reconciliation = if backups_changed {
+        tracing::info!(?user_id, ?removed_backups, "Backup networks changed");
+        // T3: Collect vectors to withdraw, and split the others again
+        let mut transaction = context.local_context.database_pool.begin().await?;
+        let reconciliation = reconcile_backups(
+            &mut transaction,
+            user_id,
+            &backup_network_ids,
+            threshold,
+            &removed_backups,
+            &mut shares_map,
+        )
+        .await?;
+        transaction.commit().await?; // T3 end
+        reconciliation
+    } else {
+        Reconciliation::default()
+    };
+
     /* create vectors and shares */
+    let mut delegated_vectors = Vec::with_capacity(update_tasks.len());
     for task in update_tasks {
         let vector = task.vector;
         let backup_network_id = &task.backup_network_id;
@@ -159,25 +229,11 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
         );
         let mut rng = rand_0_8::thread_rng();
 
-        let kseaf_shares = keys::create_shares_from_kseaf(
-            &vector.kseaf,
-            backup_network_ids.len() as u8,
-            std::cmp::min(
-                context.backup_context.backup_key_threshold,
-                backup_network_ids.len() as u8,
-            ),
-            &mut rng,
-        )?;
+        let (kseaf_shares, kseaf_commitment) =
+            keys::create_shares_from_kseaf(&vector.kseaf, num_shares, threshold, &mut rng)?;
 
-        let kasme_shares = keys::create_shares_from_kasme(
-            &vector.kasme,
-            backup_network_ids.len() as u8,
-            std::cmp::min(
-                context.backup_context.backup_key_threshold,
-                backup_network_ids.len() as u8,
-            ),
-            &mut rng,
-        )?;
+        let (kasme_shares, kasme_commitment) =
+            keys::create_shares_from_kasme(&vector.kasme, num_shares, threshold, &mut rng)?;
 
         let mut shares: Vec<(keys::CombinedKeyShare, Rand)> = Vec::new();
 
@@ -188,6 +244,9 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
                     xres_hash: xres_hash,
                     kasme_share: kasme_share,
                     kseaf_share: kseaf_share,
+                    kseaf_commitment: kseaf_commitment.clone(),
+                    kasme_commitment: kasme_commitment.clone(),
+                    epoch: 0,
                 }
                 .to_owned(),
                 rand,
@@ -204,6 +263,7 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
                 autn: vector.autn,
                 xres_star_hash: vector.xres_star_hash,
                 xres_hash: vector.xres_hash,
+                plmn: task.plmn.clone(),
             });
 
         for other_id in &backup_network_ids {
@@ -218,9 +278,25 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
         if !shares.is_empty() {
             tracing::warn!("{} unused share(s) after share generation", shares.len())
         }
+
+        delegated_vectors.push(DelegatedVector {
+            xres_star_hash,
+            xres_hash,
+            rand,
+            autn: vector.autn,
+            plmn: task.plmn,
+            epoch: 0,
+        });
     }
 
     /* enroll backups */
+    let serving_policy = {
+        let mut transaction = context.local_context.database_pool.begin().await?;
+        let serving_policy = database::serving_policies::get(&mut transaction, user_id).await?;
+        transaction.commit().await?;
+        serving_policy
+    };
+
     for (backup_network_id, _) in &user_data {
         let (address, _) = directory::lookup_network(&context, &backup_network_id)
             .await
@@ -267,6 +343,8 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
             user_id,
             &vectors,
             &key_shares,
+            threshold,
+            serving_policy.as_ref(),
             &address,
         )
         .await
@@ -313,6 +391,7 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
                     &vector.xres_star_hash,
                     user_id,
                     backup_network_id,
+                    &vector.plmn,
                 )
                 .await?;
             }
@@ -328,6 +407,66 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
                 .await?;
             }
         }
+        for delegated in &delegated_vectors {
+            database::delegated_vectors::add(&mut transaction, user_id, delegated).await?;
+        }
+
+        for (backup_network_id, key_share) in &reconciliation.refreshed_shares {
+            database::tasks::replace_key_shares::add_refresh(
+                &mut transaction,
+                backup_network_id,
+                key_share,
+            )
+            .await?;
+        }
+        for (xres_star_hash, epoch) in &reconciliation.epochs {
+            database::delegated_vectors::set_epoch(&mut transaction, xres_star_hash, *epoch)
+                .await?;
+        }
+        // Withdrawals are queued with the local removal, and retried until sent
+        for (xres_star_hash, holders) in &reconciliation.withdrawn_vectors {
+            for backup_network_id in holders {
+                database::key_share_state::remove_by_xres_star_hash(
+                    &mut transaction,
+                    xres_star_hash,
+                    backup_network_id,
+                )
+                .await?;
+                database::tasks::withdraw_shares::add(
+                    &mut transaction,
+                    backup_network_id,
+                    xres_star_hash,
+                )
+                .await?;
+            }
+            database::tasks::replace_key_shares::remove_by_xres_star_hash(
+                &mut transaction,
+                xres_star_hash,
+            )
+            .await?;
+            database::delegated_vectors::remove(&mut transaction, xres_star_hash).await?;
+            database::vector_state::remove(&mut transaction, xres_star_hash).await?;
+        }
+        for (backup_network_id, xres_star_hashes) in &reconciliation.removed_shares {
+            for xres_star_hash in xres_star_hashes {
+                database::key_share_state::remove_by_xres_star_hash(
+                    &mut transaction,
+                    xres_star_hash,
+                    backup_network_id,
+                )
+                .await?;
+                database::tasks::withdraw_shares::add(
+                    &mut transaction,
+                    backup_network_id,
+                    xres_star_hash,
+                )
+                .await?;
+            }
+            // Also releases the backup's sqn slice for selection
+            database::backup_networks::remove(&mut transaction, user_id, backup_network_id).await?;
+            database::tasks::withdraw_backups::add(&mut transaction, user_id, backup_network_id)
+                .await?;
+        }
         transaction.commit().await?; // T4 end
     }
 
@@ -338,3 +477,519 @@ async fn handle_user_update(context: Arc<DauthContext>, user_id: String) -> Resu
 
     Ok(())
 }
+
+/// Changes made to reconcile the backup networks of a user
+/// with its previous backup networks.
+#[derive(Default)]
+struct Reconciliation {
+    /// Key shares replacing those held by continuing backup networks
+    refreshed_shares: Vec<(String, keys::CombinedKeyShare)>,
+    /// New epochs of the vectors whose keys were split again
+    epochs: Vec<(XResStarHash, u32)>,
+    /// Vectors held by removed backup networks, with their share holders
+    withdrawn_vectors: Vec<(XResStarHash, Vec<String>)>,
+    /// Removed backup networks, with the key shares they held
+    removed_shares: Vec<(String, Vec<XResStarHash>)>,
+}
+
+/// Finds the vectors held by removed backup networks, which are withdrawn.
+/// The keys of every other vector of the user are derived again and
+/// split between the current backup networks in a new epoch, so that
+/// the number of shares and the threshold match the new set.
+/// Networks that already hold a share of a vector have it replaced,
+/// and other networks are sent their share when enrolled.
+/// Pending withdrawals from networks that are backups again are dropped,
+/// so they are not sent after the network is enrolled again.
+async fn reconcile_backups(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+    backup_network_ids: &[String],
+    threshold: u8,
+    removed_backups: &[String],
+    shares_map: &mut HashMap<String, Vec<(keys::CombinedKeyShare, Rand)>>,
+) -> Result<Reconciliation, DauthError> {
+    let mut reconciliation = Reconciliation::default();
+
+    for backup_network_id in removed_backups {
+        for xres_star_hash in
+            database::vector_state::get_all_by_id(transaction, user_id, backup_network_id).await?
+        {
+            let xres_star_hash: XResStarHash = xres_star_hash.as_slice().try_into()?;
+            let holders =
+                database::key_share_state::get_holders(transaction, &xres_star_hash).await?;
+            reconciliation
+                .withdrawn_vectors
+                .push((xres_star_hash, holders));
+        }
+
+        let xres_star_hashes =
+            database::key_share_state::get_all_by_id(transaction, user_id, backup_network_id)
+                .await?;
+        reconciliation
+            .removed_shares
+            .push((backup_network_id.clone(), xres_star_hashes));
+    }
+
+    for backup_network_id in backup_network_ids {
+        database::tasks::withdraw_backups::remove(transaction, user_id, backup_network_id).await?;
+    }
+
+    if backup_network_ids.is_empty() {
+        return Ok(reconciliation);
+    }
+
+    for delegated in database::delegated_vectors::get_all_by_user(transaction, user_id).await? {
+        if reconciliation
+            .withdrawn_vectors
+            .iter()
+            .any(|(xres_star_hash, _)| xres_star_hash == &delegated.xres_star_hash)
+        {
+            continue;
+        }
+
+        let holders =
+            database::key_share_state::get_holders(transaction, &delegated.xres_star_hash).await?;
+        if holders.is_empty() {
+            continue;
+        }
+
+        let vector =
+            common::auth_vectors::rebuild_delegated_vector(transaction, user_id, &delegated)
+                .await?;
+        let epoch = delegated.epoch + 1;
+        let key_shares = refresh_key_shares::split_keys(
+            &vector,
+            epoch,
+            keys::share_count(backup_network_ids.len())?,
+            threshold,
+        )?;
+        reconciliation
+            .epochs
+            .push((delegated.xres_star_hash, epoch));
+        for (backup_network_id, key_share) in std::iter::zip(backup_network_ids, key_shares) {
+            if holders.contains(backup_network_id) {
+                reconciliation
+                    .refreshed_shares
+                    .push((backup_network_id.clone(), key_share));
+            } else {
+                database::tasks::withdraw_shares::remove(
+                    transaction,
+                    backup_network_id,
+                    &delegated.xres_star_hash,
+                )
+                .await?;
+                shares_map
+                    .get_mut(backup_network_id)
+                    .ok_or(DauthError::DataError("Shares map error".to_string()))?
+                    .push((key_share, delegated.rand));
+            }
+        }
+    }
+
+    Ok(reconciliation)
+}
+
+/// Selects backup networks from the directory for a user with
+/// a pending automatic selection, and adds them to the user update.
+/// Networks that already back up the user outside of its configured
+/// backups are kept first, and keep their sqn slices.
+/// Newly selected networks are given sqn slices that no current or
+/// configured backup uses. A slice is released when its backup is
+/// removed by the user update, and when given to a new backup continues
+/// from the highest sqn issued in it, so no sqn is issued twice.
+async fn select_backups(context: &Arc<DauthContext>, user_id: &str) -> Result<(), DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let (num_backups, sqn_max) =
+        match database::tasks::select_backups::get(&mut transaction, user_id).await? {
+            Some(selection) => selection,
+            None => {
+                transaction.commit().await?;
+                return Ok(());
+            }
+        };
+
+    let mut configured = Vec::new();
+    let mut used_slices = vec![0];
+    for (backup_network_id, sqn_slice) in
+        database::tasks::update_users::get_user_data(&mut transaction, user_id).await?
+    {
+        configured.push(backup_network_id);
+        used_slices.push(sqn_slice);
+    }
+
+    let mut previous = Vec::new();
+    for backup_network_id in
+        database::backup_networks::get_all_by_user(&mut transaction, user_id).await?
+    {
+        let sqn_slice =
+            database::backup_networks::get_slice(&mut transaction, user_id, &backup_network_id)
+                .await?;
+        used_slices.push(sqn_slice);
+        if !configured.contains(&backup_network_id) {
+            previous.push((backup_network_id, sqn_slice));
+        }
+    }
+
+    let existing_slices =
+        database::user_infos::get_slices(&mut transaction, &user_id.to_string()).await?;
+    let user_info = database::user_infos::get(&mut transaction, &user_id.to_string(), 0).await?;
+    transaction.commit().await?;
+
+    let mut candidates = Vec::new();
+    for mut candidate in directory::list_networks(context).await? {
+        if candidate.network_id != context.local_context.id {
+            candidate.reputation =
+                common::misbehaviour::reputation(context, &candidate.network_id).await?;
+            candidates.push(candidate);
+        }
+    }
+
+    let previous_ids: Vec<String> = previous.iter().map(|(id, _)| id.clone()).collect();
+    let selected = placement::select_backups(
+        &context.backup_context.placement_policy,
+        &candidates,
+        &configured,
+        &previous_ids,
+        num_backups.max(0) as usize,
+    );
+
+    let num_new = selected
+        .iter()
+        .filter(|backup_network_id| !previous_ids.contains(backup_network_id))
+        .count();
+    let mut free_slices =
+        placement::free_sqn_slices(&used_slices, context.local_context.num_sqn_slices, num_new)
+            .into_iter();
+
+    if selected.len() < num_backups as usize {
+        tracing::warn!(
+            ?user_id,
+            "Only {} of {} backup network(s) eligible for selection",
+            selected.len(),
+            num_backups
+        );
+    }
+
+    let num_sqn_slices = context.local_context.num_sqn_slices;
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    for backup_network_id in &selected {
+        let previous_slice = previous
+            .iter()
+            .find(|(id, _)| id == backup_network_id)
+            .map(|(_, sqn_slice)| *sqn_slice);
+
+        let sqn_slice = match previous_slice {
+            Some(sqn_slice) => sqn_slice,
+            None => match free_slices.next() {
+                Some(sqn_slice) => {
+                    let mut slice_sqn_max =
+                        sqn_max - sqn_max.rem_euclid(num_sqn_slices) + sqn_slice;
+                    if existing_slices.contains(&sqn_slice) {
+                        // Released by a removed backup
+                        let released = database::user_infos::get(
+                            &mut transaction,
+                            &user_id.to_string(),
+                            sqn_slice,
+                        )
+                        .await?;
+                        slice_sqn_max = slice_sqn_max.max(released.sqn);
+                    }
+
+                    database::user_infos::upsert(
+                        &mut transaction,
+                        &user_id.to_string(),
+                        &user_info.k,
+                        &user_info.opc,
+                        user_info.algorithm,
+                        slice_sqn_max,
+                        sqn_slice,
+                    )
+                    .await?;
+                    sqn_slice
+                }
+                None => {
+                    tracing::warn!(?user_id, ?backup_network_id, "No free sqn slice for backup");
+                    continue;
+                }
+            },
+        };
+
+        tracing::info!(
+            ?user_id,
+            ?backup_network_id,
+            ?sqn_slice,
+            "Selected backup network"
+        );
+        database::tasks::update_users::add(&mut transaction, user_id, sqn_slice, backup_network_id)
+            .await?;
+    }
+    database::tasks::select_backups::remove(&mut transaction, user_id).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+    use sqlx::{Sqlite, SqlitePool, Transaction};
+    use tempfile::{tempdir, TempDir};
+
+    use auth_vector::data::AuthVectorData;
+    use auth_vector::types::{Algorithm, Rand, Sqn, XResStarHash, K_LENGTH, OPC_LENGTH};
+
+    use crate::data::keys;
+    use crate::data::vector::{DelegatedVector, Plmn};
+    use crate::database::{self, general};
+    use crate::tasks::update_users;
+
+    const USER_ID: &str = "test_user";
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let pool = general::database_init(&path).await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        database::user_infos::upsert(
+            &mut transaction,
+            &USER_ID.to_string(),
+            &[1; K_LENGTH],
+            &[2; OPC_LENGTH],
+            Algorithm::Milenage,
+            32,
+            0,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        (pool, dir)
+    }
+
+    fn plmn() -> Plmn {
+        Plmn {
+            mcc: "901".to_string(),
+            mnc: "70".to_string(),
+        }
+    }
+
+    /// Adds a delegated vector owned by one network, with shares held by others
+    async fn add_vector(
+        transaction: &mut Transaction<'_, Sqlite>,
+        sqn: i64,
+        owner: &str,
+        holders: &[&str],
+    ) -> AuthVectorData {
+        let vector = auth_vector::generate_vector(
+            "901",
+            "70",
+            Algorithm::Milenage,
+            &[1; K_LENGTH],
+            &[2; OPC_LENGTH],
+            &Sqn::try_from(sqn).unwrap(),
+        )
+        .unwrap();
+
+        database::vector_state::add(transaction, &vector.xres_star_hash, USER_ID, owner, &plmn())
+            .await
+            .unwrap();
+        for holder in holders {
+            database::key_share_state::add(
+                transaction,
+                &vector.xres_star_hash,
+                &vector.xres_hash,
+                holder,
+                USER_ID,
+                &vector.rand.as_array(),
+            )
+            .await
+            .unwrap();
+        }
+        database::delegated_vectors::add(
+            transaction,
+            USER_ID,
+            &DelegatedVector {
+                xres_star_hash: vector.xres_star_hash,
+                xres_hash: vector.xres_hash,
+                rand: vector.rand,
+                autn: vector.autn,
+                plmn: plmn(),
+                epoch: 0,
+            },
+        )
+        .await
+        .unwrap();
+
+        vector
+    }
+
+    fn ids(network_ids: &[&str]) -> Vec<String> {
+        network_ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn gen_shares_map(
+        network_ids: &[String],
+    ) -> HashMap<String, Vec<(keys::CombinedKeyShare, Rand)>> {
+        network_ids
+            .iter()
+            .map(|network_id| (network_id.clone(), Vec::new()))
+            .collect()
+    }
+
+    /// Tests that vectors of removed networks are withdrawn, and the keys
+    /// of the others are split again between the new set in a new epoch
+    #[tokio::test]
+    async fn test_reconcile_removed_backup() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        let removed_vector = add_vector(&mut transaction, 33, "b", &["a", "b"]).await;
+        let kept_vector = add_vector(&mut transaction, 65, "a", &["a", "b"]).await;
+
+        let backup_network_ids = ids(&["a", "c"]);
+        let mut shares_map = gen_shares_map(&backup_network_ids);
+        let reconciliation = update_users::reconcile_backups(
+            &mut transaction,
+            USER_ID,
+            &backup_network_ids,
+            2,
+            &ids(&["b"]),
+            &mut shares_map,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(1, reconciliation.withdrawn_vectors.len());
+        let (withdrawn_hash, holders) = &reconciliation.withdrawn_vectors[0];
+        assert_eq!(&removed_vector.xres_star_hash, withdrawn_hash);
+        let mut holders = holders.clone();
+        holders.sort();
+        assert_eq!(ids(&["a", "b"]), holders);
+
+        assert_eq!(1, reconciliation.removed_shares.len());
+        let (removed_id, removed_hashes) = &reconciliation.removed_shares[0];
+        assert_eq!("b", removed_id);
+        let mut removed_hashes = removed_hashes.clone();
+        removed_hashes.sort();
+        let mut expected_hashes: Vec<XResStarHash> =
+            vec![removed_vector.xres_star_hash, kept_vector.xres_star_hash];
+        expected_hashes.sort();
+        assert_eq!(expected_hashes, removed_hashes);
+
+        assert_eq!(vec![(kept_vector.xres_star_hash, 1)], reconciliation.epochs);
+
+        // The holder gets a replacement, and the new network a share to enroll
+        assert_eq!(1, reconciliation.refreshed_shares.len());
+        let (refreshed_id, refreshed_share) = &reconciliation.refreshed_shares[0];
+        assert_eq!("a", refreshed_id);
+        assert!(shares_map["a"].is_empty());
+        assert_eq!(1, shares_map["c"].len());
+        let (new_share, rand) = &shares_map["c"][0];
+        assert_eq!(kept_vector.rand, *rand);
+
+        for share in [refreshed_share, new_share] {
+            assert_eq!(kept_vector.xres_star_hash, share.xres_star_hash);
+            assert_eq!(1, share.epoch);
+            share.verify().unwrap();
+        }
+
+        // Both shares recover the unchanged keys
+        assert_eq!(
+            kept_vector.kseaf,
+            keys::recover_kseaf_with_commitment(
+                &vec![refreshed_share.kseaf_share, new_share.kseaf_share],
+                &new_share.kseaf_commitment,
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            kept_vector.kasme,
+            keys::recover_kasme_with_commitment(
+                &vec![refreshed_share.kasme_share, new_share.kasme_share],
+                &new_share.kasme_commitment,
+            )
+            .unwrap()
+        );
+    }
+
+    /// Tests that withdrawals pending for networks that are backups again are dropped
+    #[tokio::test]
+    async fn test_reconcile_drops_pending_withdrawals() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        let vector = add_vector(&mut transaction, 33, "a", &["a"]).await;
+        database::tasks::withdraw_backups::add(&mut transaction, USER_ID, "b")
+            .await
+            .unwrap();
+        database::tasks::withdraw_shares::add(&mut transaction, "b", &vector.xres_star_hash)
+            .await
+            .unwrap();
+
+        let backup_network_ids = ids(&["a", "b"]);
+        let mut shares_map = gen_shares_map(&backup_network_ids);
+        update_users::reconcile_backups(
+            &mut transaction,
+            USER_ID,
+            &backup_network_ids,
+            1,
+            &[],
+            &mut shares_map,
+        )
+        .await
+        .unwrap();
+
+        assert!(database::tasks::withdraw_backups::get(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(database::tasks::withdraw_shares::get(&mut transaction)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(1, shares_map["b"].len());
+        transaction.commit().await.unwrap();
+    }
+
+    /// Tests that removing every backup withdraws all vectors without splitting
+    #[tokio::test]
+    async fn test_reconcile_no_backups() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        add_vector(&mut transaction, 33, "a", &["a"]).await;
+
+        let mut shares_map = HashMap::new();
+        let reconciliation = update_users::reconcile_backups(
+            &mut transaction,
+            USER_ID,
+            &[],
+            0,
+            &ids(&["a"]),
+            &mut shares_map,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(1, reconciliation.withdrawn_vectors.len());
+        assert_eq!(1, reconciliation.removed_shares.len());
+        assert!(reconciliation.refreshed_shares.is_empty());
+        assert!(reconciliation.epochs.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use crate::data::context::DauthContext;
+use crate::data::error::DauthError;
+use crate::database;
+use crate::rpc::clients;
+
+/// Runs the withdraw backups task.
+/// Withdraws each pending user from the backup network it was removed
+/// from. Withdrawals are queued with the local removal, so a failed
+/// withdrawal stays pending and is retried on the next run.
+pub async fn run_task(context: Arc<DauthContext>) -> Result<(), DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let withdrawals = database::tasks::withdraw_backups::get(&mut transaction).await?;
+    transaction.commit().await?;
+
+    if withdrawals.is_empty() {
+        tracing::debug!("Nothing to do for withdraw backups task");
+    } else {
+        tracing::info!("Found {} backup withdrawal(s) pending", withdrawals.len());
+
+        for (user_id, backup_network_id) in withdrawals {
+            if let Err(e) = withdraw_backup(&context, &user_id, &backup_network_id).await {
+                tracing::warn!(
+                    ?e,
+                    ?user_id,
+                    ?backup_network_id,
+                    "Failed to withdraw backup"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn withdraw_backup(
+    context: &Arc<DauthContext>,
+    user_id: &str,
+    backup_network_id: &str,
+) -> Result<(), DauthError> {
+    let (address, _) = clients::directory::lookup_network(context, backup_network_id).await?;
+
+    clients::backup_network::withdraw_backup(context.clone(), user_id, backup_network_id, &address)
+        .await?;
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    database::tasks::withdraw_backups::remove(&mut transaction, user_id, backup_network_id).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use auth_vector::types::XResStarHash;
+
+use crate::data::context::DauthContext;
+use crate::data::error::DauthError;
+use crate::database;
+use crate::rpc::clients;
+
+/// Runs the withdraw shares task.
+/// Withdraws the pending key shares from each backup network holding
+/// them, in one request per network. Withdrawals are queued with the
+/// local removal, so failed withdrawals stay pending and are retried
+/// on the next run.
+pub async fn run_task(context: Arc<DauthContext>) -> Result<(), DauthError> {
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    let withdrawals = database::tasks::withdraw_shares::get(&mut transaction).await?;
+    transaction.commit().await?;
+
+    if withdrawals.is_empty() {
+        tracing::debug!("Nothing to do for withdraw shares task");
+    } else {
+        tracing::info!("Found {} share withdrawal(s) pending", withdrawals.len());
+
+        let mut shares_per_network: HashMap<String, Vec<XResStarHash>> = HashMap::new();
+        for (backup_network_id, xres_star_hash) in withdrawals {
+            shares_per_network
+                .entry(backup_network_id)
+                .or_default()
+                .push(xres_star_hash);
+        }
+
+        for (backup_network_id, xres_star_hashes) in shares_per_network {
+            if let Err(e) = withdraw_shares(&context, &backup_network_id, xres_star_hashes).await {
+                tracing::warn!(?e, ?backup_network_id, "Failed to withdraw key shares");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn withdraw_shares(
+    context: &Arc<DauthContext>,
+    backup_network_id: &str,
+    xres_star_hashes: Vec<XResStarHash>,
+) -> Result<(), DauthError> {
+    let (address, _) = clients::directory::lookup_network(context, backup_network_id).await?;
+
+    clients::backup_network::withdraw_shares(
+        context.clone(),
+        xres_star_hashes.clone(),
+        backup_network_id,
+        &address,
+    )
+    .await?;
+
+    let mut transaction = context.local_context.database_pool.begin().await?;
+    for xres_star_hash in &xres_star_hashes {
+        database::tasks::withdraw_shares::remove(
+            &mut transaction,
+            backup_network_id,
+            xres_star_hash,
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
+mod test_certificates;
 mod test_core;
 mod test_dauth;
 mod test_directory;
 
+pub use test_certificates::TestCertificates;
 pub use test_core::TestCore;
 pub use test_dauth::TestDauth;
 pub use test_directory::TestDirectory;
@@ -0,0 +1,105 @@
+use std::fs;
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use tempfile::{tempdir, TempDir};
+
+use dauth_service::data::config::TlsConfig;
+use dauth_service::data::error::DauthError;
+use directory_service::data::config::TlsConfig as DirectoryTlsConfig;
+
+/// Domain of the directory certificate issued by the test CA.
+pub const TEST_DIRECTORY_DOMAIN: &str = "test-directory";
+
+/// Test certificate authority that issues certificates for
+/// network ids, written as PEM files to a temp directory.
+pub struct TestCertificates {
+    ca: Certificate,
+    ca_cert_path: String,
+    // Must not be dropped
+    temp_dir: TempDir,
+}
+
+impl TestCertificates {
+    /// Builds a new CA with a freshly generated key.
+    pub fn new() -> Result<Self, DauthError> {
+        let temp_dir = tempdir()?;
+
+        let mut params = CertificateParams::new(Vec::new());
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "dAuth test CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(params).map_err(to_config_error)?;
+
+        let ca_cert_path = Self::write(
+            &temp_dir,
+            "ca.pem",
+            &ca.serialize_pem().map_err(to_config_error)?,
+        )?;
+
+        Ok(Self {
+            ca,
+            ca_cert_path,
+            temp_dir,
+        })
+    }
+
+    /// Returns the TLS config of a network, with a certificate for the name.
+    pub fn dauth_config(&self, name: &str) -> Result<TlsConfig, DauthError> {
+        let (cert_path, key_path) = self.issue(name)?;
+
+        Ok(TlsConfig {
+            cert_path,
+            key_path,
+            ca_cert_path: self.ca_cert_path.clone(),
+            directory_domain: TEST_DIRECTORY_DOMAIN.to_string(),
+        })
+    }
+
+    /// Returns the TLS config of the directory.
+    pub fn directory_config(&self) -> Result<DirectoryTlsConfig, DauthError> {
+        let (cert_path, key_path) = self.issue(TEST_DIRECTORY_DOMAIN)?;
+
+        Ok(DirectoryTlsConfig {
+            cert_path,
+            key_path,
+            ca_cert_path: self.ca_cert_path.clone(),
+        })
+    }
+
+    /// Issues a certificate for the name, returning the
+    /// paths of the certificate and its key.
+    fn issue(&self, name: &str) -> Result<(String, String), DauthError> {
+        let mut params = CertificateParams::new(vec![name.to_string()]);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let certificate = Certificate::from_params(params).map_err(to_config_error)?;
+
+        Ok((
+            Self::write(
+                &self.temp_dir,
+                &format!("{}.pem", name),
+                &certificate
+                    .serialize_pem_with_signer(&self.ca)
+                    .map_err(to_config_error)?,
+            )?,
+            Self::write(
+                &self.temp_dir,
+                &format!("{}.key", name),
+                &certificate.serialize_private_key_pem(),
+            )?,
+        ))
+    }
+
+    fn write(temp_dir: &TempDir, file_name: &str, contents: &str) -> Result<String, DauthError> {
+        let path = temp_dir.path().join(file_name);
+        fs::write(&path, contents)?;
+
+        Ok(String::from(path.to_str().ok_or(
+            DauthError::ConfigError("Failed to generate path".to_string()),
+        )?))
+    }
+}
+
+fn to_config_error(error: rcgen::RcgenError) -> DauthError {
+    DauthError::ConfigError(format!("Failed to generate certificate: {}", error))
+}
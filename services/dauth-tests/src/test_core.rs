@@ -45,19 +45,25 @@ impl TestCore {
         let user_info = UserInfoConfig {
             user_id: "".to_string(), // Not needed
             k: TEST_K.to_string(),
-            opc: TEST_OPC.to_string(),
+            opc: Some(TEST_OPC.to_string()),
+            op: None,
+            algorithm: None,
             sqn_max: 0,      // Not needed
             backups: vec![], // Not needed
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
         };
 
         Ok(generate_vector_with_rand(
             "901",
             "70",
+            user_info.get_algorithm()?,
             &user_info.get_k()?,
-            &user_info.get_opc()?,
+            &user_info.get_opc(None)?,
             &auth_vector.rand[..].try_into()?,
             &Sqn::try_from(auth_vector.seqnum)?,
-        ))
+        )?)
     }
 
     /// Requests an auth vector from the configured dauth instance.
@@ -71,6 +77,7 @@ impl TestCore {
             .get_auth_vector(Request::new(AkaVectorReq {
                 user_id_type: UserIdKind::Supi as i32,
                 user_id: user_id.as_bytes().to_vec(),
+                eps: false,
                 resync_info: None,
             }))
             .await?
@@ -5,7 +5,7 @@ use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use tempfile::{tempdir, TempDir};
 
-use dauth_service::data::config::{DauthConfig, UserInfoConfig};
+use dauth_service::data::config::{DauthConfig, TlsConfig, UserInfoConfig};
 use dauth_service::data::context::DauthContext;
 use tokio::task::JoinHandle;
 
@@ -25,6 +25,17 @@ impl TestDauth {
     /// Builds a new test object with the provided id and host,
     /// but otherwise uses a default configuration.
     pub async fn new(id: &str, host: &str, dir_host: &str) -> Result<Self, DauthError> {
+        Self::new_with_tls(id, host, dir_host, None).await
+    }
+
+    /// Builds a new test object with the provided id and host, using
+    /// mutual TLS if configured, but otherwise uses a default configuration.
+    pub async fn new_with_tls(
+        id: &str,
+        host: &str,
+        dir_host: &str,
+        tls: Option<TlsConfig>,
+    ) -> Result<Self, DauthError> {
         let rand_dir: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
         let temp_dir = tempdir()?;
         let ed25519_keyfile_path = String::from(
@@ -64,6 +75,23 @@ impl TestDauth {
             mnc: "70".to_string(),
             max_recorded_metrics: Some(1),
             backup_key_threshold: Some(1),
+            min_backup_reputation: None,
+            reputation_half_life: None,
+            key_share_refresh_interval: None,
+            replay_window: None,
+            max_replay_cache_size: None,
+            max_replay_cache_size_per_signer: None,
+            auth_state_lifetime: None,
+            op: None,
+            suci_keys: None,
+            suci_routes: None,
+            serving_plmns: None,
+            region: None,
+            operator_group: None,
+            backup_capacity: None,
+            backup_placement: None,
+            backup_quotas: None,
+            tls,
         };
 
         let context = dauth_service::startup::build_context(config).await?;
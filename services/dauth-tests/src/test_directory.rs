@@ -5,7 +5,7 @@ use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use tempfile::{tempdir, TempDir};
 
-use directory_service::data::config::DirectoryConfig;
+use directory_service::data::config::{DirectoryConfig, TlsConfig};
 use directory_service::data::context::DirectoryContext;
 use tokio::task::JoinHandle;
 
@@ -25,6 +25,12 @@ impl TestDirectory {
     /// Builds a new test object with the provided host,
     /// but otherwise uses a default configuration.
     pub async fn new(host: &str) -> Result<Self, DirectoryError> {
+        Self::new_with_tls(host, None).await
+    }
+
+    /// Builds a new test object with the provided host, using mutual
+    /// TLS if configured, but otherwise uses a default configuration.
+    pub async fn new_with_tls(host: &str, tls: Option<TlsConfig>) -> Result<Self, DirectoryError> {
         let rand_dir: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
         let temp_dir = tempdir().or_else(|e| {
             Err(DirectoryError::ConfigError(format!(
@@ -46,6 +52,8 @@ impl TestDirectory {
         let config = DirectoryConfig {
             host_address: format!("{}:8900", host),
             database_path,
+            tls,
+            max_rollover_window: None,
         };
 
         let context = directory_service::startup::build_context(config).await?;
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use dauth_service::data::signing::{self, SignPayloadType};
+use dauth_service::database;
+use dauth_service::management;
+use dauth_service::rpc::dauth::remote::{
+    delegated_auth_vector5_g, get_key_share_req, withdraw_shares_req,
+};
+use dauth_service::services::home;
+use dauth_tests::{TestDauth, TestDirectory};
+
+#[tokio::test]
+async fn test_rotated_key_after_rollover_window() {
+    let signer = TestDauth::new("test-rotation-signer", "127.0.0.20", "127.0.0.20")
+        .await
+        .unwrap();
+    let verifier = TestDauth::new("test-rotation-verifier", "127.0.0.21", "127.0.0.20")
+        .await
+        .unwrap();
+    let _dir_context = TestDirectory::new("127.0.0.20").await.unwrap();
+    tokio::time::sleep(Duration::from_secs_f32(1.0)).await;
+
+    // Delegated vector signed before the rotation
+    let message = signing::sign_message(
+        signer.context.clone(),
+        SignPayloadType::DelegatedAuthVector5G(delegated_auth_vector5_g::Payload::default()),
+    );
+    signing::verify_message(&verifier.context, &message)
+        .await
+        .unwrap();
+
+    // Request signed before the rotation
+    let request = signing::sign_message(
+        signer.context.clone(),
+        SignPayloadType::WithdrawSharesReq(withdraw_shares_req::Payload::default()),
+    );
+    signing::verify_message_signature(&verifier.context, &request)
+        .await
+        .unwrap();
+
+    // Serving network request reported by a backup
+    let report = signing::sign_message(
+        signer.context.clone(),
+        SignPayloadType::GetKeyShareReq(get_key_share_req::Payload {
+            serving_network_id: "test-rotation-signer".to_string(),
+            ..Default::default()
+        }),
+    );
+
+    management::rotate_signing_key(signer.context.clone(), Duration::from_secs(2))
+        .await
+        .unwrap();
+
+    // Previous key is accepted during the rollover window
+    signing::verify_message(&verifier.context, &message)
+        .await
+        .unwrap();
+    signing::verify_message_signature(&verifier.context, &request)
+        .await
+        .unwrap();
+
+    let new_message = signing::sign_message(
+        signer.context.clone(),
+        SignPayloadType::DelegatedAuthVector5G(delegated_auth_vector5_g::Payload::default()),
+    );
+    signing::verify_message(&verifier.context, &new_message)
+        .await
+        .unwrap();
+
+    // Requests are rejected once it ends
+    tokio::time::sleep(Duration::from_secs_f32(3.0)).await;
+    assert!(
+        signing::verify_message_signature(&verifier.context, &request)
+            .await
+            .is_err()
+    );
+
+    // A late report of a request signed with the previous key is
+    // rejected, but not recorded as misbehaviour of the backup
+    assert!(
+        home::audit_key_share_report(&verifier.context, "test-rotation-backup", &report)
+            .await
+            .is_err()
+    );
+    let mut transaction = verifier
+        .context
+        .local_context
+        .database_pool
+        .begin()
+        .await
+        .unwrap();
+    assert!(
+        database::misbehaviour::get_all_by_network(&mut transaction, "test-rotation-backup")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    transaction.commit().await.unwrap();
+
+    // But vectors delegated before the rotation are still valid,
+    // since backups hold them until they are used
+    signing::verify_message(&verifier.context, &message)
+        .await
+        .unwrap();
+    signing::verify_message(&verifier.context, &new_message)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use dauth_service::data::config::{BackupConfig, UserInfoConfig};
+use dauth_tests::{TestCertificates, TestDauth, TestDirectory, TEST_K, TEST_OPC};
+
+const NUM_USERS: usize = 5;
+const NUM_BACKUPS: usize = 2;
+
+fn build_user_infos(id: &str, backup_ids: &[String]) -> (Vec<UserInfoConfig>, Vec<String>) {
+    let mut user_infos = Vec::new();
+    let mut user_ids = Vec::new();
+    for num in 0..NUM_USERS {
+        let user_id = format!("user-{}-{}", id, num);
+
+        let mut backup_configs = Vec::new();
+        for (backup_num, backup_id) in backup_ids.iter().enumerate() {
+            backup_configs.push(BackupConfig {
+                backup_id: backup_id.to_owned(),
+                sqn_slice: 1 + backup_num as i64,
+                sqn_max: 33 + backup_num as i64,
+            });
+        }
+
+        user_ids.push(user_id.to_owned());
+        user_infos.push(UserInfoConfig {
+            user_id,
+            k: TEST_K.to_string(),
+            opc: Some(TEST_OPC.to_string()),
+            op: None,
+            algorithm: None,
+            sqn_max: 32,
+            backups: backup_configs,
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
+        });
+    }
+
+    (user_infos, user_ids)
+}
+
+#[tokio::test]
+async fn test_user_with_backups_over_tls() {
+    let certificates = TestCertificates::new().unwrap();
+
+    let id = "test-tls-home";
+    let test_context = TestDauth::new_with_tls(
+        id,
+        "127.0.0.10",
+        "127.0.0.10",
+        Some(certificates.dauth_config(id).unwrap()),
+    )
+    .await
+    .unwrap();
+
+    let mut backups = Vec::new();
+    let mut backup_ids = Vec::new();
+    for backup_num in 0..NUM_BACKUPS {
+        let backup_id = format!("test-tls-backup-{}", backup_num);
+        backups.push(
+            TestDauth::new_with_tls(
+                &backup_id,
+                &format!("127.0.0.{}", 11 + backup_num),
+                "127.0.0.10",
+                Some(certificates.dauth_config(&backup_id).unwrap()),
+            )
+            .await
+            .unwrap(),
+        );
+        backup_ids.push(backup_id);
+    }
+
+    let dir_context =
+        TestDirectory::new_with_tls("127.0.0.10", Some(certificates.directory_config().unwrap()))
+            .await
+            .unwrap();
+
+    let (user_infos, user_ids) = build_user_infos(id, &backup_ids);
+    test_context.add_users(&user_infos).await.unwrap();
+    // TODO: Time value is unstable, need to find a better way
+    tokio::time::sleep(Duration::from_secs_f32(5.0)).await;
+
+    test_context.check_users_exists(&user_ids, 0).await.unwrap();
+    dir_context.check_users_exists(&user_ids).await.unwrap();
+
+    for backup in backups {
+        backup.check_backup_user_exists(&user_ids).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_certificate_bound_to_network_id() {
+    let certificates = TestCertificates::new().unwrap();
+
+    // Holds a valid certificate, but for another network id
+    let id = "test-tls-impostor";
+    let test_context = TestDauth::new_with_tls(
+        id,
+        "127.0.0.15",
+        "127.0.0.15",
+        Some(certificates.dauth_config("test-tls-other").unwrap()),
+    )
+    .await
+    .unwrap();
+
+    let dir_context =
+        TestDirectory::new_with_tls("127.0.0.15", Some(certificates.directory_config().unwrap()))
+            .await
+            .unwrap();
+
+    let (user_infos, user_ids) = build_user_infos(id, &[]);
+    test_context.add_users(&user_infos).await.unwrap();
+    tokio::time::sleep(Duration::from_secs_f32(2.0)).await;
+
+    // Users are only stored locally, since the directory refuses the network
+    test_context.check_users_exists(&user_ids, 0).await.unwrap();
+    assert!(dir_context.check_users_exists(&user_ids).await.is_err());
+}
@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use dauth_service::data::config::UserInfoConfig;
-use dauth_tests::{TestDauth, TestDirectory, TestCore, TEST_K, TEST_OPC};
+use dauth_tests::{TestCore, TestDauth, TestDirectory, TEST_K, TEST_OPC};
 
 const NUM_USERS: usize = 10;
 
@@ -22,9 +22,14 @@ async fn test_home_auth_successful() {
         user_infos.push(UserInfoConfig {
             user_id,
             k: TEST_K.to_string(),
-            opc: TEST_OPC.to_string(),
+            opc: Some(TEST_OPC.to_string()),
+            op: None,
+            algorithm: None,
             sqn_max: 32,
             backups: Vec::new(),
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
         });
     }
 
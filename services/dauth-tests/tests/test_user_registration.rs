@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use dauth_service::data::config::{UserInfoConfig, BackupConfig};
+use dauth_service::data::config::{BackupConfig, UserInfoConfig};
 use dauth_tests::{TestDauth, TestDirectory, TEST_K, TEST_OPC};
 
 const NUM_USERS: usize = 10;
@@ -21,9 +21,14 @@ async fn test_simple_user_registration() {
         user_infos.push(UserInfoConfig {
             user_id,
             k: TEST_K.to_string(),
-            opc: TEST_OPC.to_string(),
+            opc: Some(TEST_OPC.to_string()),
+            op: None,
+            algorithm: None,
             sqn_max: 32,
             backups: Vec::new(),
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
         });
     }
 
@@ -57,7 +62,15 @@ async fn test_user_with_backups() {
     let mut backups = Vec::new();
     for backup_num in 0..NUM_BACKUPS {
         let backup_id = format!("test-backup-{}", backup_num);
-        backups.push(TestDauth::new(&backup_id, &format!("127.0.0.{}", 4+backup_num), "127.0.0.3").await.unwrap());
+        backups.push(
+            TestDauth::new(
+                &backup_id,
+                &format!("127.0.0.{}", 4 + backup_num),
+                "127.0.0.3",
+            )
+            .await
+            .unwrap(),
+        );
     }
 
     let dir_context = TestDirectory::new("127.0.0.3").await.unwrap();
@@ -81,9 +94,14 @@ async fn test_user_with_backups() {
         user_infos.push(UserInfoConfig {
             user_id,
             k: TEST_K.to_string(),
-            opc: TEST_OPC.to_string(),
+            opc: Some(TEST_OPC.to_string()),
+            op: None,
+            algorithm: None,
             sqn_max: 32,
             backups: backup_configs,
+            auto_backups: None,
+            key_threshold: None,
+            serving_policy: None,
         });
     }
 
@@ -97,5 +115,4 @@ async fn test_user_with_backups() {
     for backup in backups {
         backup.check_backup_user_exists(&user_ids).await.unwrap();
     }
-
 }
@@ -5,4 +5,16 @@ use serde::{Deserialize, Serialize};
 pub struct DirectoryConfig {
     pub host_address: String,
     pub database_path: String,
+    pub tls: Option<TlsConfig>,
+    pub max_rollover_window: Option<i64>,
+}
+
+/// Represents the certificates for mutual TLS with dAuth networks.
+/// Network certificates are issued by the CA for the network id,
+/// as a DNS subject alternative name.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub ca_cert_path: String,
 }
@@ -1,8 +1,18 @@
 use sqlx::SqlitePool;
+use tonic::transport::{Certificate, Identity};
 
 /// Maintains all context for the directory service.
 #[derive(Debug)]
 pub struct DirectoryContext {
     pub host_address: String,
     pub database_pool: SqlitePool,
+    pub tls: Option<TlsContext>,
+    pub max_rollover_window: i64,
+}
+
+/// Certificates for mutual TLS with dAuth networks.
+#[derive(Debug)]
+pub struct TlsContext {
+    pub identity: Identity,
+    pub ca_certificate: Certificate,
 }
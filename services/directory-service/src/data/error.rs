@@ -10,6 +10,12 @@ pub enum DirectoryError {
     #[error("Invalid access -- {0}")]
     InvalidAccess(String),
 
+    #[error("Peer identity error -- {0}")]
+    PeerIdentityError(String),
+
     #[error("Config error -- {0}")]
     ConfigError(String),
+
+    #[error("Signing error -- {0}")]
+    SigningError(#[from] ed25519_dalek::SignatureError),
 }
@@ -0,0 +1,19 @@
+/// Placement metadata registered by a network.
+/// Used by home networks to choose diverse backup networks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkMetadata {
+    pub region: String,
+    pub operator_group: String,
+    /// Max number of users the network will back up, 0 for no limit
+    pub backup_capacity: i64,
+}
+
+/// The public key a network replaced in its last key change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreviousKey {
+    pub public_key: Vec<u8>,
+    /// Unix time (seconds) of the key change
+    pub replaced: i64,
+    /// Unix time (seconds) when the rollover window ends
+    pub expiration: i64,
+}
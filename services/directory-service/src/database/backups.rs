@@ -76,6 +76,22 @@ pub async fn remove(
     Ok(())
 }
 
+/// Removes a backup network from all users it backs up.
+pub async fn remove_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    backup_network_id: &str,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "DELETE FROM backups_directory_table
+        WHERE backup_network_id=$1",
+    )
+    .bind(backup_network_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -275,4 +291,65 @@ mod tests {
         .await
         .is_err());
     }
+
+    #[tokio::test]
+    async fn test_remove_network() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            &format!("test_home_network_id"),
+            &format!("test_home_address"),
+            &vec![0],
+        )
+        .await
+        .unwrap();
+        for network in 0..2 {
+            networks::upsert(
+                &mut transaction,
+                &format!("test_network_id_{}", network),
+                &format!("test_network_address_{}", network),
+                &vec![0],
+            )
+            .await
+            .unwrap();
+        }
+        for row in 0..num_rows {
+            users::add(
+                &mut transaction,
+                &format!("test_user_id_{}", row),
+                &format!("test_home_network_id"),
+            )
+            .await
+            .unwrap();
+            for network in 0..2 {
+                backups::add(
+                    &mut transaction,
+                    &format!("test_user_id_{}", row),
+                    &format!("test_network_id_{}", network),
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        backups::remove_network(&mut transaction, &format!("test_network_id_0"))
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            let res = backups::get(&mut transaction, &format!("test_user_id_{}", row))
+                .await
+                .unwrap();
+
+            assert_eq!(res, vec![format!("test_network_id_1")]);
+        }
+        transaction.commit().await.unwrap();
+    }
 }
@@ -2,6 +2,7 @@ use sqlx::sqlite::SqlitePool;
 use sqlx::{Row, Sqlite, Transaction};
 
 use crate::data::error::DirectoryError;
+use crate::data::network::{NetworkMetadata, PreviousKey};
 
 /// Creates the backup networks table if it does not exist already.
 /// Contains all networks that are used as a backup for this network
@@ -10,7 +11,14 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DirectoryError> {
         "CREATE TABLE IF NOT EXISTS networks_directory_table (
             network_id TEXT PRIMARY KEY,
             address TEXT NOT NULL,
-            public_key BLOB NOT NULL
+            public_key BLOB NOT NULL,
+            previous_public_key BLOB,
+            previous_key_replaced INT,
+            previous_key_expiration INT,
+            region TEXT NOT NULL DEFAULT '',
+            operator_group TEXT NOT NULL DEFAULT '',
+            backup_capacity INT NOT NULL DEFAULT 0,
+            registration_timestamp INT NOT NULL DEFAULT 0
         );",
     )
     .execute(pool)
@@ -21,6 +29,7 @@ pub async fn init_table(pool: &SqlitePool) -> Result<(), DirectoryError> {
 /* Queries */
 
 /// Adds a network with its address and public key.
+/// Keeps any previous key info if the network exists.
 pub async fn upsert(
     transaction: &mut Transaction<'_, Sqlite>,
     network_id: &str,
@@ -28,8 +37,11 @@ pub async fn upsert(
     public_key: &Vec<u8>,
 ) -> Result<(), DirectoryError> {
     sqlx::query(
-        "REPLACE INTO networks_directory_table
-        VALUES ($1,$2,$3)",
+        "INSERT INTO networks_directory_table (network_id, address, public_key)
+        VALUES ($1,$2,$3)
+        ON CONFLICT(network_id) DO UPDATE SET
+            address=excluded.address,
+            public_key=excluded.public_key",
     )
     .bind(network_id)
     .bind(address)
@@ -59,6 +71,153 @@ pub async fn get(
     ))
 }
 
+/// Sets the previous public key of a network, when it was
+/// replaced, and when it expires.
+pub async fn set_previous_key(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+    previous_key: &PreviousKey,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "UPDATE networks_directory_table
+        SET previous_public_key=$2, previous_key_replaced=$3, previous_key_expiration=$4
+        WHERE network_id=$1",
+    )
+    .bind(network_id)
+    .bind(&previous_key.public_key)
+    .bind(previous_key.replaced)
+    .bind(previous_key.expiration)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the previous public key of a network, if any.
+pub async fn get_previous_key(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+) -> Result<Option<PreviousKey>, DirectoryError> {
+    let row = sqlx::query(
+        "SELECT * FROM networks_directory_table
+        WHERE network_id=$1;",
+    )
+    .bind(network_id)
+    .fetch_one(transaction)
+    .await?;
+
+    let previous_public_key = row.try_get::<Option<Vec<u8>>, &str>("previous_public_key")?;
+    let previous_key_replaced = row.try_get::<Option<i64>, &str>("previous_key_replaced")?;
+    let previous_key_expiration = row.try_get::<Option<i64>, &str>("previous_key_expiration")?;
+
+    Ok(previous_public_key.map(|public_key| PreviousKey {
+        public_key,
+        replaced: previous_key_replaced.unwrap_or_default(),
+        expiration: previous_key_expiration.unwrap_or_default(),
+    }))
+}
+
+/// Sets the placement metadata of a network.
+pub async fn set_metadata(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+    metadata: &NetworkMetadata,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "UPDATE networks_directory_table
+        SET region=$2, operator_group=$3, backup_capacity=$4
+        WHERE network_id=$1",
+    )
+    .bind(network_id)
+    .bind(&metadata.region)
+    .bind(&metadata.operator_group)
+    .bind(metadata.backup_capacity)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Sets the timestamp of the latest registration of a network.
+pub async fn set_registration_timestamp(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+    registration_timestamp: i64,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "UPDATE networks_directory_table
+        SET registration_timestamp=$2
+        WHERE network_id=$1",
+    )
+    .bind(network_id)
+    .bind(registration_timestamp)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Gets the timestamp of the latest registration of a network.
+pub async fn get_registration_timestamp(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+) -> Result<i64, DirectoryError> {
+    let row = sqlx::query(
+        "SELECT * FROM networks_directory_table
+        WHERE network_id=$1;",
+    )
+    .bind(network_id)
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(row.try_get::<i64, &str>("registration_timestamp")?)
+}
+
+/// Gets the id and placement metadata of all networks,
+/// along with the number of users each network backs up.
+pub async fn get_all(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<Vec<(String, NetworkMetadata, i64)>, DirectoryError> {
+    let rows = sqlx::query(
+        "SELECT network_id, region, operator_group, backup_capacity,
+            (SELECT COUNT(*) FROM backups_directory_table
+                WHERE backup_network_id=network_id) AS backup_count
+        FROM networks_directory_table;",
+    )
+    .fetch_all(transaction)
+    .await?;
+
+    let mut res = Vec::with_capacity(rows.len());
+    for row in rows {
+        res.push((
+            row.try_get::<String, &str>("network_id")?,
+            NetworkMetadata {
+                region: row.try_get::<String, &str>("region")?,
+                operator_group: row.try_get::<String, &str>("operator_group")?,
+                backup_capacity: row.try_get::<i64, &str>("backup_capacity")?,
+            },
+            row.try_get::<i64, &str>("backup_count")?,
+        ))
+    }
+    Ok(res)
+}
+
+/// Removes a network.
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    network_id: &str,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "DELETE FROM networks_directory_table
+        WHERE network_id=$1",
+    )
+    .bind(network_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -68,7 +227,8 @@ mod tests {
     use sqlx::SqlitePool;
     use tempfile::{tempdir, TempDir};
 
-    use crate::database::{general, networks};
+    use crate::data::network::{NetworkMetadata, PreviousKey};
+    use crate::database::{backups, general, networks, users};
 
     fn gen_name() -> String {
         let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
@@ -83,6 +243,8 @@ mod tests {
 
         let pool = general::build_pool(&path).await.unwrap();
         networks::init_table(&pool).await.unwrap();
+        users::init_table(&pool).await.unwrap();
+        backups::init_table(&pool).await.unwrap();
 
         (pool, dir)
     }
@@ -186,4 +348,226 @@ mod tests {
         }
         transaction.commit().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            networks::upsert(
+                &mut transaction,
+                &format!("test_network_id_{}", row),
+                &format!("test_address_{}", row),
+                &vec![row as u8],
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            networks::remove(&mut transaction, &format!("test_network_id_{}", row))
+                .await
+                .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert!(
+                networks::get(&mut transaction, &format!("test_network_id_{}", row))
+                    .await
+                    .is_err()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_previous_key() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            "test_network_0",
+            "test_address_0",
+            &vec![0, 0, 0],
+        )
+        .await
+        .unwrap();
+        assert!(
+            networks::get_previous_key(&mut transaction, "test_network_0")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            "test_network_0",
+            "test_address_0",
+            &vec![1, 1, 1],
+        )
+        .await
+        .unwrap();
+        networks::set_previous_key(
+            &mut transaction,
+            "test_network_0",
+            &PreviousKey {
+                public_key: vec![0, 0, 0],
+                replaced: 40,
+                expiration: 100,
+            },
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        // Previous key is kept through later updates
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            "test_network_0",
+            "test_address_0a",
+            &vec![1, 1, 1],
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let (address, key) = networks::get(&mut transaction, "test_network_0")
+            .await
+            .unwrap();
+        assert_eq!("test_address_0a", address);
+        assert_eq!(vec![1, 1, 1], key);
+        assert_eq!(
+            networks::get_previous_key(&mut transaction, "test_network_0")
+                .await
+                .unwrap(),
+            Some(PreviousKey {
+                public_key: vec![0, 0, 0],
+                replaced: 40,
+                expiration: 100,
+            })
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_registration_timestamp() {
+        let (pool, _dir) = init().await;
+
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            "test_network_0",
+            "test_address_0",
+            &vec![0, 0, 0],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            networks::get_registration_timestamp(&mut transaction, "test_network_0")
+                .await
+                .unwrap(),
+            0
+        );
+
+        networks::set_registration_timestamp(&mut transaction, "test_network_0", 100)
+            .await
+            .unwrap();
+        assert_eq!(
+            networks::get_registration_timestamp(&mut transaction, "test_network_0")
+                .await
+                .unwrap(),
+            100
+        );
+        assert!(
+            networks::get_registration_timestamp(&mut transaction, "test_network_1")
+                .await
+                .is_err()
+        );
+        transaction.commit().await.unwrap();
+    }
+
+    /// Tests that metadata and backup counts are listed for all networks
+    #[tokio::test]
+    async fn test_metadata_get_all() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            let network_id = format!("test_network_id_{}", row);
+            networks::upsert(
+                &mut transaction,
+                &network_id,
+                &format!("test_address_{}", row),
+                &vec![row as u8],
+            )
+            .await
+            .unwrap();
+
+            if row % 2 == 0 {
+                networks::set_metadata(
+                    &mut transaction,
+                    &network_id,
+                    &NetworkMetadata {
+                        region: format!("test_region_{}", row),
+                        operator_group: format!("test_group_{}", row),
+                        backup_capacity: row,
+                    },
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        // Network n backs up n users
+        for user in 0..num_rows {
+            users::add(
+                &mut transaction,
+                &format!("test_user_{}", user),
+                "test_network_id_0",
+            )
+            .await
+            .unwrap();
+            for row in (user + 1)..num_rows {
+                backups::add(
+                    &mut transaction,
+                    &format!("test_user_{}", user),
+                    &format!("test_network_id_{}", row),
+                )
+                .await
+                .unwrap();
+            }
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let mut all = networks::get_all(&mut transaction).await.unwrap();
+        all.sort_by_key(|(_, _, backup_count)| *backup_count);
+        assert_eq!(num_rows as usize, all.len());
+
+        for (row, (network_id, metadata, backup_count)) in all.into_iter().enumerate() {
+            let row = row as i64;
+            assert_eq!(format!("test_network_id_{}", row), network_id);
+            assert_eq!(row, backup_count);
+            if row % 2 == 0 {
+                assert_eq!(format!("test_region_{}", row), metadata.region);
+                assert_eq!(format!("test_group_{}", row), metadata.operator_group);
+                assert_eq!(row, metadata.backup_capacity);
+            } else {
+                assert_eq!(NetworkMetadata::default(), metadata);
+            }
+        }
+        transaction.commit().await.unwrap();
+    }
 }
@@ -54,6 +54,42 @@ pub async fn get(
     Ok(row.try_get::<String, &str>("home_network_id")?)
 }
 
+/// Gets the ids of all users owned by the home network.
+pub async fn get_by_home_network(
+    transaction: &mut Transaction<'_, Sqlite>,
+    home_network_id: &str,
+) -> Result<Vec<String>, DirectoryError> {
+    let rows = sqlx::query(
+        "SELECT * FROM users_directory_table
+        WHERE home_network_id=$1;",
+    )
+    .bind(home_network_id)
+    .fetch_all(transaction)
+    .await?;
+
+    let mut res = Vec::new();
+    for row in rows {
+        res.push(row.try_get::<String, &str>("user_id")?)
+    }
+    Ok(res)
+}
+
+/// Removes a user.
+pub async fn remove(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_id: &str,
+) -> Result<(), DirectoryError> {
+    sqlx::query(
+        "DELETE FROM users_directory_table
+        WHERE user_id=$1",
+    )
+    .bind(user_id)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 /* Testing */
 
 #[cfg(test)]
@@ -169,4 +205,87 @@ mod tests {
         .await
         .is_err());
     }
+
+    #[tokio::test]
+    async fn test_get_by_home_network() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        for network in 0..2 {
+            networks::upsert(
+                &mut transaction,
+                &format!("test_home_network_id_{}", network),
+                &format!("test_home_address_{}", network),
+                &vec![0],
+            )
+            .await
+            .unwrap();
+        }
+        for row in 0..num_rows {
+            users::add(
+                &mut transaction,
+                &format!("test_user_id_{}", row),
+                &format!("test_home_network_id_{}", row % 2),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        let res = users::get_by_home_network(&mut transaction, "test_home_network_id_0")
+            .await
+            .unwrap();
+
+        assert_eq!(res.len(), num_rows / 2);
+        for row in (0..num_rows).step_by(2) {
+            assert!(res.contains(&format!("test_user_id_{}", row)));
+        }
+        transaction.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove() {
+        let (pool, _dir) = init().await;
+        let num_rows = 10;
+
+        let mut transaction = pool.begin().await.unwrap();
+        networks::upsert(
+            &mut transaction,
+            &format!("test_home_network_id"),
+            &format!("test_home_address"),
+            &vec![0],
+        )
+        .await
+        .unwrap();
+        for row in 0..num_rows {
+            users::add(
+                &mut transaction,
+                &format!("test_user_id_{}", row),
+                &format!("test_home_network_id"),
+            )
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            users::remove(&mut transaction, &format!("test_user_id_{}", row))
+                .await
+                .unwrap();
+        }
+        transaction.commit().await.unwrap();
+
+        let mut transaction = pool.begin().await.unwrap();
+        for row in 0..num_rows {
+            assert!(
+                users::get(&mut transaction, &format!("test_user_id_{}", row))
+                    .await
+                    .is_err()
+            );
+        }
+        transaction.commit().await.unwrap();
+    }
 }
@@ -1,47 +1,115 @@
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::data::{context::DirectoryContext, error::DirectoryError};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+
+use crate::data::{
+    context::DirectoryContext,
+    error::DirectoryError,
+    network::{NetworkMetadata, PreviousKey},
+};
 use crate::database;
 
 /*  Manager handles all functionality of the directory service.
  *  Shares a 1:1 relation with the RPC handler.
  */
 
+/// Seconds a signed request remains valid after it was signed.
+const REQUEST_WINDOW: i64 = 60;
+
 /// Registers a network with the directory.
-/// Stores the networks address and public key.
+/// Stores the networks address, public key, and placement metadata.
+/// The payload must be signed by the submitted public key, and
+/// key changes must also be signed by the previously registered key.
+/// On a key change, the previous key is kept with the time of the change
+/// and the end of the rollover window, which may not exceed the
+/// configured maximum.
+/// Registrations must be recent and no older than the stored one,
+/// so a captured registration cannot roll back the network.
 pub async fn register(
     context: Arc<DirectoryContext>,
     network_id: &str,
     address: &str,
     public_key: &Vec<u8>,
+    rollover_window: i64,
+    metadata: &NetworkMetadata,
+    timestamp: i64,
+    payload: &[u8],
+    signature: &[u8],
+    previous_key_signature: &[u8],
 ) -> Result<(), DirectoryError> {
     tracing::info!(
-        "Register called: {:?}-{:?}-{:?}",
+        "Register called: {:?}-{:?}-{:?}-{:?}",
         network_id,
         address,
-        public_key
+        public_key,
+        metadata
     );
 
+    verify_signature(public_key, payload, signature)?;
+    check_timestamp(timestamp)?;
+    let previous_key_expiration = rollover_expiration(&context, rollover_window)?;
+
     let mut transaction = context.database_pool.begin().await?;
+
+    let mut changed_key = None;
+    if let Ok((_, previous_key)) = database::networks::get(&mut transaction, network_id).await {
+        if database::networks::get_registration_timestamp(&mut transaction, network_id).await?
+            > timestamp
+        {
+            return Err(DirectoryError::InvalidAccess(
+                "Registration older than the current registration".to_string(),
+            ));
+        }
+
+        if &previous_key != public_key {
+            verify_signature(&previous_key, payload, previous_key_signature).map_err(|e| {
+                tracing::warn!("Key change not signed by previous key: {:?}", e);
+                DirectoryError::InvalidAccess(
+                    "Key change not signed by previously registered key".to_string(),
+                )
+            })?;
+            changed_key = Some(previous_key);
+        }
+    }
+
     database::networks::upsert(&mut transaction, network_id, address, public_key).await?;
+    database::networks::set_metadata(&mut transaction, network_id, metadata).await?;
+    database::networks::set_registration_timestamp(&mut transaction, network_id, timestamp).await?;
+
+    if let Some(previous_key) = changed_key {
+        database::networks::set_previous_key(
+            &mut transaction,
+            network_id,
+            &PreviousKey {
+                public_key: previous_key,
+                replaced: timestamp,
+                expiration: previous_key_expiration,
+            },
+        )
+        .await?;
+    }
+
     transaction.commit().await?;
 
     Ok(())
 }
 
 /// Looks up a network by id and checks if it has been registered.
-/// Returns the address and public key of the network.
+/// Returns the address and public key of the network, and the
+/// previous public key if the key was changed.
 pub async fn lookup_network(
     context: Arc<DirectoryContext>,
     network_id: &str,
-) -> Result<(String, Vec<u8>), DirectoryError> {
+) -> Result<(String, Vec<u8>, Option<PreviousKey>), DirectoryError> {
     tracing::info!("Lookup network called: {:?}", network_id);
 
     let mut transaction = context.database_pool.begin().await?;
-    let res = database::networks::get(&mut transaction, network_id).await?;
+    let (address, public_key) = database::networks::get(&mut transaction, network_id).await?;
+    let previous_key = database::networks::get_previous_key(&mut transaction, network_id).await?;
     transaction.commit().await?;
 
-    Ok(res)
+    Ok((address, public_key, previous_key))
 }
 
 /// Looks up a user by id.
@@ -100,3 +168,557 @@ pub async fn upsert_user(
     transaction.commit().await?;
     Ok(())
 }
+
+/// Removes the user and its set of backup networks.
+/// The home network must be the owner of the user, and the
+/// payload must be recently signed by its registered key.
+pub async fn delete_user(
+    context: Arc<DirectoryContext>,
+    user_id: &str,
+    home_network_id: &str,
+    timestamp: i64,
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<(), DirectoryError> {
+    tracing::info!("Delete user called: {:?}-{:?}", user_id, home_network_id);
+
+    check_timestamp(timestamp)?;
+
+    let mut transaction = context.database_pool.begin().await?;
+
+    let (_, public_key) = database::networks::get(&mut transaction, home_network_id).await?;
+    verify_signature(&public_key, payload, signature)?;
+
+    if database::users::get(&mut transaction, user_id).await? != home_network_id {
+        return Err(DirectoryError::InvalidAccess(
+            "User owned by another network".to_string(),
+        ));
+    }
+
+    database::backups::remove(&mut transaction, user_id).await?;
+    database::users::remove(&mut transaction, user_id).await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Removes a network from the directory.
+/// Also removes the network as a backup for all users,
+/// and removes all users owned by the network.
+/// The payload must be recently signed by the registered key.
+pub async fn deregister_network(
+    context: Arc<DirectoryContext>,
+    network_id: &str,
+    timestamp: i64,
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<(), DirectoryError> {
+    tracing::info!("Deregister network called: {:?}", network_id);
+
+    check_timestamp(timestamp)?;
+
+    let mut transaction = context.database_pool.begin().await?;
+
+    let (_, public_key) = database::networks::get(&mut transaction, network_id).await?;
+    verify_signature(&public_key, payload, signature)?;
+
+    for user_id in database::users::get_by_home_network(&mut transaction, network_id).await? {
+        database::backups::remove(&mut transaction, &user_id).await?;
+        database::users::remove(&mut transaction, &user_id).await?;
+    }
+
+    database::backups::remove_network(&mut transaction, network_id).await?;
+    database::networks::remove(&mut transaction, network_id).await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Lists all registered networks.
+/// Returns the id and placement metadata of each network,
+/// along with the number of users it currently backs up.
+pub async fn list_networks(
+    context: Arc<DirectoryContext>,
+) -> Result<Vec<(String, NetworkMetadata, i64)>, DirectoryError> {
+    tracing::info!("List networks called");
+
+    let mut transaction = context.database_pool.begin().await?;
+    let networks = database::networks::get_all(&mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(networks)
+}
+
+/// Verifies that the payload was signed by the private key
+/// matching the provided public key.
+fn verify_signature(
+    public_key: &[u8],
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<(), DirectoryError> {
+    let public_key = PublicKey::from_bytes(public_key)?;
+    let signature = Signature::from_bytes(signature)?;
+
+    Ok(public_key.verify(payload, &signature)?)
+}
+
+/// Checks that a signed request is recent enough to be accepted,
+/// which bounds how long a captured request can be replayed.
+fn check_timestamp(timestamp: i64) -> Result<(), DirectoryError> {
+    if (unix_timestamp()? - timestamp).abs() > REQUEST_WINDOW {
+        Err(DirectoryError::InvalidAccess(format!(
+            "Request timestamp {} outside of accepted window",
+            timestamp
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns when a previous key replaced now expires.
+/// Fails if the rollover window exceeds the configured maximum.
+fn rollover_expiration(
+    context: &DirectoryContext,
+    rollover_window: i64,
+) -> Result<i64, DirectoryError> {
+    if rollover_window > context.max_rollover_window {
+        return Err(DirectoryError::InvalidAccess(format!(
+            "Rollover window {} exceeds the maximum of {} seconds",
+            rollover_window, context.max_rollover_window
+        )));
+    }
+
+    unix_timestamp()?
+        .checked_add(rollover_window.max(0))
+        .ok_or_else(|| {
+            DirectoryError::InvalidAccess(format!(
+                "Rollover window {} out of range",
+                rollover_window
+            ))
+        })
+}
+
+fn unix_timestamp() -> Result<i64, DirectoryError> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| DirectoryError::InvalidAccess(format!("Invalid system time: {}", e)))?
+        .as_secs() as i64)
+}
+
+/* Testing */
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ed25519_dalek::{Keypair, Signer};
+    use prost::Message;
+    use rand::distributions::Alphanumeric;
+    use rand::rngs::OsRng;
+    use rand::{thread_rng, Rng};
+    use tempfile::{tempdir, TempDir};
+
+    use crate::data::context::DirectoryContext;
+    use crate::data::network::NetworkMetadata;
+    use crate::database;
+    use crate::manager;
+    use crate::rpc::directory_service::{delete_user_req, deregister_network_req, register_req};
+
+    fn gen_name() -> String {
+        let s: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
+
+        format!("sqlite_{}.db", s)
+    }
+
+    async fn init() -> (Arc<DirectoryContext>, TempDir) {
+        let dir = tempdir().unwrap();
+        let path = String::from(dir.path().join(gen_name()).to_str().unwrap());
+        println!("Building temporary db: {}", path);
+
+        let database_pool = database::general::database_init(&path).await.unwrap();
+
+        (
+            Arc::new(DirectoryContext {
+                host_address: "127.0.0.1:8900".to_string(),
+                database_pool,
+                tls: None,
+                max_rollover_window: 3600,
+            }),
+            dir,
+        )
+    }
+
+    fn register_payload(keys: &Keypair, address: &str, timestamp: i64) -> Vec<u8> {
+        register_req::Payload {
+            network_id: "test_network_id".to_string(),
+            address: address.to_string(),
+            public_key: keys.public.as_bytes().to_vec(),
+            rollover_window: 60,
+            region: "test_region".to_string(),
+            operator_group: "test_group".to_string(),
+            backup_capacity: 10,
+            timestamp,
+        }
+        .encode_to_vec()
+    }
+
+    async fn register_at(
+        context: Arc<DirectoryContext>,
+        keys: &Keypair,
+        previous_keys: Option<&Keypair>,
+        address: &str,
+        timestamp: i64,
+    ) -> Result<(), crate::data::error::DirectoryError> {
+        let payload = register_payload(keys, address, timestamp);
+        let previous_key_signature = match previous_keys {
+            Some(previous_keys) => previous_keys.sign(&payload).to_bytes().to_vec(),
+            None => Vec::new(),
+        };
+
+        manager::register(
+            context,
+            "test_network_id",
+            address,
+            &keys.public.as_bytes().to_vec(),
+            60,
+            &NetworkMetadata {
+                region: "test_region".to_string(),
+                operator_group: "test_group".to_string(),
+                backup_capacity: 10,
+            },
+            timestamp,
+            &payload,
+            &keys.sign(&payload).to_bytes(),
+            &previous_key_signature,
+        )
+        .await
+    }
+
+    async fn register(
+        context: Arc<DirectoryContext>,
+        keys: &Keypair,
+        previous_keys: Option<&Keypair>,
+        address: &str,
+    ) -> Result<(), crate::data::error::DirectoryError> {
+        register_at(
+            context,
+            keys,
+            previous_keys,
+            address,
+            manager::unix_timestamp().unwrap(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+
+        // Same key can update the address
+        register(context.clone(), &keys, None, "test_address_2")
+            .await
+            .unwrap();
+
+        let (address, public_key, previous_key) =
+            manager::lookup_network(context, "test_network_id")
+                .await
+                .unwrap();
+        assert_eq!(address, "test_address_2");
+        assert_eq!(public_key, keys.public.as_bytes().to_vec());
+        assert!(previous_key.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_wrong_key_fails() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let other_keys = Keypair::generate(&mut OsRng {});
+
+        let now = manager::unix_timestamp().unwrap();
+        let payload = register_payload(&keys, "test_address", now);
+
+        assert!(manager::register(
+            context.clone(),
+            "test_network_id",
+            "test_address",
+            &keys.public.as_bytes().to_vec(),
+            60,
+            &NetworkMetadata::default(),
+            now,
+            &payload,
+            &other_keys.sign(&payload).to_bytes(),
+            &[],
+        )
+        .await
+        .is_err());
+
+        assert!(manager::lookup_network(context, "test_network_id")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_register_replay_fails() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let now = manager::unix_timestamp().unwrap();
+
+        register_at(context.clone(), &keys, None, "test_address", now - 10)
+            .await
+            .unwrap();
+        register_at(context.clone(), &keys, None, "test_address_2", now)
+            .await
+            .unwrap();
+
+        // Older registration cannot roll back the address
+        assert!(
+            register_at(context.clone(), &keys, None, "test_address", now - 10)
+                .await
+                .is_err()
+        );
+        // Stale registrations are refused outright
+        assert!(
+            register_at(context.clone(), &keys, None, "test_address_2", now - 3600)
+                .await
+                .is_err()
+        );
+
+        let (address, _, _) = manager::lookup_network(context, "test_network_id")
+            .await
+            .unwrap();
+        assert_eq!(address, "test_address_2");
+    }
+
+    #[tokio::test]
+    async fn test_register_key_change() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let new_keys = Keypair::generate(&mut OsRng {});
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+
+        // Key change without the previous key fails
+        assert!(register(context.clone(), &new_keys, None, "test_address")
+            .await
+            .is_err());
+        assert!(
+            register(context.clone(), &new_keys, Some(&new_keys), "test_address")
+                .await
+                .is_err()
+        );
+
+        let (_, public_key, _) = manager::lookup_network(context.clone(), "test_network_id")
+            .await
+            .unwrap();
+        assert_eq!(public_key, keys.public.as_bytes().to_vec());
+
+        // Key change signed by the previous key succeeds
+        register(context.clone(), &new_keys, Some(&keys), "test_address")
+            .await
+            .unwrap();
+
+        let (_, public_key, previous_key) = manager::lookup_network(context, "test_network_id")
+            .await
+            .unwrap();
+        assert_eq!(public_key, new_keys.public.as_bytes().to_vec());
+
+        let previous_key = previous_key.unwrap();
+        assert_eq!(previous_key.public_key, keys.public.as_bytes().to_vec());
+        assert!(previous_key.replaced <= manager::unix_timestamp().unwrap());
+        assert!(previous_key.expiration >= previous_key.replaced + 60);
+    }
+
+    #[tokio::test]
+    async fn test_register_rollover_window_too_long_fails() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let new_keys = Keypair::generate(&mut OsRng {});
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+
+        for rollover_window in [3601, i64::MAX] {
+            let now = manager::unix_timestamp().unwrap();
+            let payload = register_req::Payload {
+                network_id: "test_network_id".to_string(),
+                address: "test_address".to_string(),
+                public_key: new_keys.public.as_bytes().to_vec(),
+                rollover_window,
+                region: "test_region".to_string(),
+                operator_group: "test_group".to_string(),
+                backup_capacity: 10,
+                timestamp: now,
+            }
+            .encode_to_vec();
+
+            assert!(manager::register(
+                context.clone(),
+                "test_network_id",
+                "test_address",
+                &new_keys.public.as_bytes().to_vec(),
+                rollover_window,
+                &NetworkMetadata::default(),
+                now,
+                &payload,
+                &new_keys.sign(&payload).to_bytes(),
+                &keys.sign(&payload).to_bytes(),
+            )
+            .await
+            .is_err());
+        }
+
+        let (_, public_key, previous_key) = manager::lookup_network(context, "test_network_id")
+            .await
+            .unwrap();
+        assert_eq!(public_key, keys.public.as_bytes().to_vec());
+        assert!(previous_key.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_networks() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+
+        assert!(manager::list_networks(context.clone())
+            .await
+            .unwrap()
+            .is_empty());
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+        manager::upsert_user(
+            context.clone(),
+            "test_user_id",
+            "test_network_id",
+            &vec!["test_network_id".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let networks = manager::list_networks(context).await.unwrap();
+        assert_eq!(
+            networks,
+            vec![(
+                "test_network_id".to_string(),
+                NetworkMetadata {
+                    region: "test_region".to_string(),
+                    operator_group: "test_group".to_string(),
+                    backup_capacity: 10,
+                },
+                1
+            )]
+        );
+    }
+
+    async fn delete_user(
+        context: Arc<DirectoryContext>,
+        keys: &Keypair,
+        timestamp: i64,
+    ) -> Result<(), crate::data::error::DirectoryError> {
+        let payload = delete_user_req::Payload {
+            user_id: "test_user_id".to_string(),
+            home_network_id: "test_network_id".to_string(),
+            timestamp,
+        }
+        .encode_to_vec();
+
+        manager::delete_user(
+            context,
+            "test_user_id",
+            "test_network_id",
+            timestamp,
+            &payload,
+            &keys.sign(&payload).to_bytes(),
+        )
+        .await
+    }
+
+    async fn deregister_network(
+        context: Arc<DirectoryContext>,
+        keys: &Keypair,
+        timestamp: i64,
+    ) -> Result<(), crate::data::error::DirectoryError> {
+        let payload = deregister_network_req::Payload {
+            network_id: "test_network_id".to_string(),
+            timestamp,
+        }
+        .encode_to_vec();
+
+        manager::deregister_network(
+            context,
+            "test_network_id",
+            timestamp,
+            &payload,
+            &keys.sign(&payload).to_bytes(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let other_keys = Keypair::generate(&mut OsRng {});
+        let now = manager::unix_timestamp().unwrap();
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+        manager::upsert_user(context.clone(), "test_user_id", "test_network_id", &vec![])
+            .await
+            .unwrap();
+
+        // Must be signed by the registered key
+        assert!(delete_user(context.clone(), &other_keys, now)
+            .await
+            .is_err());
+        // Must be recent
+        assert!(delete_user(context.clone(), &keys, now - 3600)
+            .await
+            .is_err());
+        manager::lookup_user(context.clone(), "test_user_id")
+            .await
+            .unwrap();
+
+        delete_user(context.clone(), &keys, now).await.unwrap();
+        assert!(manager::lookup_user(context, "test_user_id").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deregister_network() {
+        let (context, _dir) = init().await;
+        let keys = Keypair::generate(&mut OsRng {});
+        let other_keys = Keypair::generate(&mut OsRng {});
+        let now = manager::unix_timestamp().unwrap();
+
+        register(context.clone(), &keys, None, "test_address")
+            .await
+            .unwrap();
+
+        assert!(deregister_network(context.clone(), &other_keys, now)
+            .await
+            .is_err());
+        assert!(deregister_network(context.clone(), &keys, now - 3600)
+            .await
+            .is_err());
+        manager::lookup_network(context.clone(), "test_network_id")
+            .await
+            .unwrap();
+
+        deregister_network(context.clone(), &keys, now)
+            .await
+            .unwrap();
+        assert!(manager::lookup_network(context, "test_network_id")
+            .await
+            .is_err());
+    }
+}
@@ -1,12 +1,18 @@
 use std::sync::Arc;
 
+use prost::Message;
+
 use crate::data::context::DirectoryContext;
+use crate::data::network::NetworkMetadata;
 use crate::manager;
 use crate::rpc::directory_service::directory_server::Directory;
 use crate::rpc::directory_service::{
+    delete_user_req, deregister_network_req, list_networks_resp, register_req, DeleteUserReq,
+    DeleteUserResp, DeregisterNetworkReq, DeregisterNetworkResp, ListNetworksReq, ListNetworksResp,
     LookupUserReq, LookupUserResp, LooukupNetworkReq, LooukupNetworkResp, RegisterReq,
     RegisterResp, UpsertUserReq, UpsertUserResp,
 };
+use crate::rpc::tls::PeerCertificates;
 
 /// Handles all RPC calls to the directory service.
 pub struct DirectoryHandler {
@@ -21,13 +27,43 @@ impl Directory for DirectoryHandler {
     ) -> Result<tonic::Response<RegisterResp>, tonic::Status> {
         tracing::info!("New request: {:?}", request);
 
+        let peer = PeerCertificates::from_request(&request);
         let content = request.into_inner();
 
+        let payload = match register_req::Payload::decode(content.payload.as_slice()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Failed to decode payload: {:?}", e);
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    e.to_string(),
+                ));
+            }
+        };
+
+        if let Err(e) = peer.verify(&self.context, &payload.network_id) {
+            tracing::warn!("Request from wrong peer: {:?}", e);
+            return Err(tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                e.to_string(),
+            ));
+        }
+
         match manager::register(
             self.context.clone(),
-            &content.network_id,
-            &content.address,
-            &content.public_key,
+            &payload.network_id,
+            &payload.address,
+            &payload.public_key,
+            payload.rollover_window,
+            &NetworkMetadata {
+                region: payload.region.clone(),
+                operator_group: payload.operator_group.clone(),
+                backup_capacity: payload.backup_capacity,
+            },
+            payload.timestamp,
+            &content.payload,
+            &content.signature,
+            &content.previous_key_signature,
         )
         .await
         {
@@ -48,10 +84,17 @@ impl Directory for DirectoryHandler {
         let content = request.into_inner();
 
         match manager::lookup_network(self.context.clone(), &content.network_id).await {
-            Ok((address, public_key)) => Ok(tonic::Response::new(LooukupNetworkResp {
-                address,
-                public_key,
-            })),
+            Ok((address, public_key, previous_key)) => {
+                let previous_key = previous_key.unwrap_or_default();
+
+                Ok(tonic::Response::new(LooukupNetworkResp {
+                    address,
+                    public_key,
+                    previous_public_key: previous_key.public_key,
+                    previous_key_replaced: previous_key.replaced,
+                    previous_key_expiration: previous_key.expiration,
+                }))
+            }
             Err(e) => {
                 tracing::warn!("Request failed: {:?}", e);
                 Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
@@ -85,8 +128,17 @@ impl Directory for DirectoryHandler {
     ) -> Result<tonic::Response<UpsertUserResp>, tonic::Status> {
         tracing::info!("New request: {:?}", request);
 
+        let peer = PeerCertificates::from_request(&request);
         let content = request.into_inner();
 
+        if let Err(e) = peer.verify(&self.context, &content.home_network_id) {
+            tracing::warn!("Request from wrong peer: {:?}", e);
+            return Err(tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                e.to_string(),
+            ));
+        }
+
         match manager::upsert_user(
             self.context.clone(),
             &content.user_id,
@@ -102,4 +154,123 @@ impl Directory for DirectoryHandler {
             }
         }
     }
+
+    async fn delete_user(
+        &self,
+        request: tonic::Request<DeleteUserReq>,
+    ) -> Result<tonic::Response<DeleteUserResp>, tonic::Status> {
+        tracing::info!("New request: {:?}", request);
+
+        let peer = PeerCertificates::from_request(&request);
+        let content = request.into_inner();
+
+        let payload = match delete_user_req::Payload::decode(content.payload.as_slice()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Failed to decode payload: {:?}", e);
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    e.to_string(),
+                ));
+            }
+        };
+
+        if let Err(e) = peer.verify(&self.context, &payload.home_network_id) {
+            tracing::warn!("Request from wrong peer: {:?}", e);
+            return Err(tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                e.to_string(),
+            ));
+        }
+
+        match manager::delete_user(
+            self.context.clone(),
+            &payload.user_id,
+            &payload.home_network_id,
+            payload.timestamp,
+            &content.payload,
+            &content.signature,
+        )
+        .await
+        {
+            Ok(()) => Ok(tonic::Response::new(DeleteUserResp {})),
+            Err(e) => {
+                tracing::warn!("Request failed: {:?}", e);
+                Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
+            }
+        }
+    }
+
+    async fn deregister_network(
+        &self,
+        request: tonic::Request<DeregisterNetworkReq>,
+    ) -> Result<tonic::Response<DeregisterNetworkResp>, tonic::Status> {
+        tracing::info!("New request: {:?}", request);
+
+        let peer = PeerCertificates::from_request(&request);
+        let content = request.into_inner();
+
+        let payload = match deregister_network_req::Payload::decode(content.payload.as_slice()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Failed to decode payload: {:?}", e);
+                return Err(tonic::Status::new(
+                    tonic::Code::InvalidArgument,
+                    e.to_string(),
+                ));
+            }
+        };
+
+        if let Err(e) = peer.verify(&self.context, &payload.network_id) {
+            tracing::warn!("Request from wrong peer: {:?}", e);
+            return Err(tonic::Status::new(
+                tonic::Code::Unauthenticated,
+                e.to_string(),
+            ));
+        }
+
+        match manager::deregister_network(
+            self.context.clone(),
+            &payload.network_id,
+            payload.timestamp,
+            &content.payload,
+            &content.signature,
+        )
+        .await
+        {
+            Ok(()) => Ok(tonic::Response::new(DeregisterNetworkResp {})),
+            Err(e) => {
+                tracing::warn!("Request failed: {:?}", e);
+                Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
+            }
+        }
+    }
+
+    async fn list_networks(
+        &self,
+        request: tonic::Request<ListNetworksReq>,
+    ) -> Result<tonic::Response<ListNetworksResp>, tonic::Status> {
+        tracing::info!("New request: {:?}", request);
+
+        match manager::list_networks(self.context.clone()).await {
+            Ok(networks) => Ok(tonic::Response::new(ListNetworksResp {
+                networks: networks
+                    .into_iter()
+                    .map(
+                        |(network_id, metadata, backup_count)| list_networks_resp::Network {
+                            network_id,
+                            region: metadata.region,
+                            operator_group: metadata.operator_group,
+                            backup_capacity: metadata.backup_capacity,
+                            backup_count,
+                        },
+                    )
+                    .collect(),
+            })),
+            Err(e) => {
+                tracing::warn!("Request failed: {:?}", e);
+                Err(tonic::Status::new(tonic::Code::Aborted, e.to_string()))
+            }
+        }
+    }
 }
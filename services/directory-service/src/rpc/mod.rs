@@ -1,5 +1,6 @@
 pub mod handler;
 pub mod server;
+pub mod tls;
 
 pub mod directory_service {
     tonic::include_proto!("dauth_directory");
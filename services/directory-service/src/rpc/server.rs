@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use tonic::transport::Server;
+use tonic::transport::{Server, ServerTlsConfig};
 
 use crate::data::context::DirectoryContext;
 use crate::rpc::directory_service::directory_server::DirectoryServer;
@@ -10,7 +10,20 @@ use crate::rpc::handler::DirectoryHandler;
 pub async fn start_server(context: Arc<DirectoryContext>) {
     tracing::info!("Hosting directory server on {}", context.host_address);
 
-    Server::builder()
+    // With mutual TLS, only holders of a certificate
+    // from the network CA can connect.
+    let mut server = Server::builder();
+    if let Some(tls) = &context.tls {
+        server = server
+            .tls_config(
+                ServerTlsConfig::new()
+                    .identity(tls.identity.clone())
+                    .client_ca_root(tls.ca_certificate.clone()),
+            )
+            .expect("Invalid TLS config");
+    }
+
+    server
         .add_service(DirectoryServer::new(DirectoryHandler {
             context: context.clone(),
         }))
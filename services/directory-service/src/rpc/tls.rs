@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use tonic::transport::Certificate;
+
+use crate::data::{context::DirectoryContext, error::DirectoryError};
+
+/// Certificates presented by the client of a request, kept
+/// to check the identity of the client once the request
+/// has been consumed.
+pub struct PeerCertificates(Option<Arc<Vec<Certificate>>>);
+
+impl PeerCertificates {
+    pub fn from_request<T>(request: &tonic::Request<T>) -> Self {
+        PeerCertificates(request.peer_certs())
+    }
+
+    /// Checks that the client holds a certificate for the network id.
+    /// Always passes without TLS.
+    pub fn verify(
+        &self,
+        context: &DirectoryContext,
+        network_id: &str,
+    ) -> Result<(), DirectoryError> {
+        if context.tls.is_none() {
+            return Ok(());
+        }
+
+        let certificate = self
+            .0
+            .as_ref()
+            .and_then(|certificates| certificates.first().cloned())
+            .ok_or_else(|| {
+                DirectoryError::PeerIdentityError("No client certificate presented".to_string())
+            })?;
+
+        // The certificate chain was already verified during the handshake
+        tls_identity::verify_certificate_name(certificate.get_ref(), network_id)
+            .map_err(DirectoryError::PeerIdentityError)
+    }
+}
@@ -1,19 +1,45 @@
 use std::{path::PathBuf, sync::Arc};
 
+use tonic::transport::{Certificate, Identity};
+
 use crate::data::{
-    self, config::DirectoryConfig, context::DirectoryContext, error::DirectoryError,
+    self,
+    config::{DirectoryConfig, TlsConfig},
+    context::{DirectoryContext, TlsContext},
+    error::DirectoryError,
 };
 use crate::database;
 
+/// Longest rollover window in seconds that networks may request
+/// for a previous key, unless configured otherwise.
+const DEFAULT_MAX_ROLLOVER_WINDOW: i64 = 7 * 24 * 60 * 60;
+
 pub async fn build_context(
     config: DirectoryConfig,
 ) -> Result<Arc<DirectoryContext>, DirectoryError> {
     Ok(Arc::new(data::context::DirectoryContext {
         host_address: config.host_address,
         database_pool: database::general::database_init(&config.database_path).await?,
+        tls: config.tls.map(build_tls).transpose()?,
+        max_rollover_window: config
+            .max_rollover_window
+            .unwrap_or(DEFAULT_MAX_ROLLOVER_WINDOW),
     }))
 }
 
+fn build_tls(config: TlsConfig) -> Result<TlsContext, DirectoryError> {
+    let read = |path: &str| {
+        std::fs::read(path).map_err(|e| {
+            DirectoryError::ConfigError(format!("Failed to read TLS file '{}': {}", path, e))
+        })
+    };
+
+    Ok(TlsContext {
+        identity: Identity::from_pem(read(&config.cert_path)?, read(&config.key_path)?),
+        ca_certificate: Certificate::from_pem(read(&config.ca_cert_path)?),
+    })
+}
+
 pub fn build_config(yaml_path: PathBuf) -> Result<DirectoryConfig, DirectoryError> {
     match std::fs::read_to_string(yaml_path) {
         Ok(yaml_string) => match serde_yaml::from_str(&yaml_string) {
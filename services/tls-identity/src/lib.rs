@@ -0,0 +1,35 @@
+/// Checks that the DER encoded certificate is valid for the network id,
+/// which networks and the directory hold as a DNS subject alternative name.
+/// The certificate chain must already be verified, as it is during the
+/// handshake with a client presenting a certificate.
+/// Returns the reason the certificate is not valid for the network id.
+pub fn verify_certificate_name(certificate: &[u8], network_id: &str) -> Result<(), String> {
+    let name = webpki::DNSNameRef::try_from_ascii_str(network_id)
+        .map_err(|e| format!("Invalid network id {}: {}", network_id, e))?;
+
+    webpki::EndEntityCert::from(certificate)
+        .and_then(|certificate| certificate.verify_is_valid_for_dns_name(name))
+        .map_err(|e| {
+            format!(
+                "Client certificate is not valid for network {}: {}",
+                network_id, e
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify_certificate_name;
+
+    #[test]
+    fn test_invalid_network_id() {
+        let error = verify_certificate_name(&[], "not a network id").unwrap_err();
+        assert!(error.starts_with("Invalid network id"));
+    }
+
+    #[test]
+    fn test_malformed_certificate() {
+        let error = verify_certificate_name(&[0; 32], "network-a").unwrap_err();
+        assert!(error.starts_with("Client certificate is not valid"));
+    }
+}